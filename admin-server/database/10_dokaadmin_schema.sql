@@ -37,7 +37,9 @@ CREATE TABLE dokaadmin.customer (
     full_name character varying(255),
     default_language character(3) NOT NULL,
     default_time_zone character varying(50) NOT NULL,
-    is_removable boolean NOT NULL DEFAULT false
+    is_removable boolean NOT NULL DEFAULT false,
+    storage_quota bigint NULL, -- Max total size of the uploaded files, in bytes, no limit when null
+    file_count_quota bigint NULL -- Max number of uploaded files, no limit when null
 );
 
 ALTER TABLE ONLY dokaadmin.customer
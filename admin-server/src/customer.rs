@@ -17,11 +17,12 @@ use common_config::properties::get_prop_value;
 use common_config::property_name::{KEY_MANAGER_HOSTNAME_PROPERTY, KEY_MANAGER_PORT_PROPERTY};
 use dkcrypto::dk_crypto::DkEncrypt;
 use dkdto::error_codes::{
-    CUSTOMER_NAME_ALREADY_TAKEN, CUSTOMER_NOT_REMOVABLE, INTERNAL_DATABASE_ERROR, INTERNAL_TECHNICAL_ERROR,
-    INVALID_PASSWORD, INVALID_TOKEN, USER_NAME_ALREADY_TAKEN,
+    CUSTOMER_NAME_ALREADY_TAKEN, CUSTOMER_NOT_FOUND, CUSTOMER_NOT_REMOVABLE, INTERNAL_DATABASE_ERROR,
+    INTERNAL_TECHNICAL_ERROR, INVALID_PASSWORD, INVALID_TOKEN, USER_NAME_ALREADY_TAKEN,
 };
 use dkdto::web_types::{
-    AddKeyRequest, CreateCustomerReply, CreateCustomerRequest, SimpleMessage, WebType, WebTypeBuilder,
+    AddKeyRequest, CreateCustomerReply, CreateCustomerRequest, CustomerQuotaReply, CustomerQuotaRequest,
+    CustomerUsageReply, ListOfCustomerUsageReply, SimpleMessage, WebType, WebTypeBuilder,
 };
 use doka_cli::async_request_client::KeyManagerClientAsync;
 use doka_cli::request_client::TokenType;
@@ -59,6 +60,10 @@ impl DbServerInfo {
             password: get_prop_value("db.password").map_err(tr_fwd!()).unwrap(), // Careful, it's not fs_db
         }
     }
+
+    pub fn connect_string(&self) -> String {
+        format!("postgresql://{}:{}@{}:{}/{}", self.db_user, self.password, self.host, self.port, self.db_name)
+    }
 }
 
 fn generate_cs_schema_script(customer_code: &str) -> String {
@@ -512,12 +517,312 @@ impl CustomerDelegate {
         WebType::from_item(StatusCode::OK.as_u16(), SimpleMessage { message: "Ok".to_string() })
     }
 
+    ///
+    /// 🔑 Quotas of the customer, used by the file server before storing a new file
+    ///
+    pub async fn customer_quota(mut self, customer_code: &str) -> WebType<CustomerQuotaReply> {
+        log_info!("🚀 Start customer_quota api, customer_code=[{}], follower=[{}]", customer_code, &self.follower);
+
+        // Check if the token is valid
+        if !self.security_token.is_valid() {
+            log_error!("💣 Invalid security token, token=[{:?}], follower=[{}]", &self.security_token, &self.follower);
+            return WebType::from_api_error(&INVALID_TOKEN);
+        }
+
+        self.follower.token_type = TokenType::Token(self.security_token.0.clone());
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let wt_quota = match self.search_customer_quota(&mut trans, customer_code).await {
+            Ok(Some(quota)) => WebType::from_item(StatusCode::OK.as_u16(), quota),
+            Ok(None) => {
+                log_warn!("⛔ Customer not found, customer_code=[{}], follower=[{}]", customer_code, &self.follower);
+                WebType::from_api_error(&CUSTOMER_NOT_FOUND)
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the quotas, e=[{}], follower=[{}]", e, &self.follower);
+                WebType::from_api_error(&INTERNAL_DATABASE_ERROR)
+            }
+        };
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        log_info!("🏁 End customer_quota, customer_code=[{}], follower=[{}]", customer_code, &self.follower);
+        wt_quota
+    }
+
+    ///
+    /// 🔑 Change the quotas of the customer, a None quota removes the limit
+    ///
+    pub async fn set_customer_quota(
+        mut self,
+        customer_code: &str,
+        quota_request: Json<CustomerQuotaRequest>,
+    ) -> WebType<CustomerQuotaReply> {
+        log_info!(
+            "🚀 Start set_customer_quota api, customer_code=[{}], storage_quota=[{:?}], file_count_quota=[{:?}], follower=[{}]",
+            customer_code,
+            quota_request.storage_quota,
+            quota_request.file_count_quota,
+            &self.follower
+        );
+
+        // Check if the token is valid
+        if !self.security_token.is_valid() {
+            log_error!("💣 Invalid security token, token=[{:?}], follower=[{}]", &self.security_token, &self.follower);
+            return WebType::from_api_error(&INVALID_TOKEN);
+        }
+
+        self.follower.token_type = TokenType::Token(self.security_token.0.clone());
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if self.search_customer(&mut trans, customer_code).await.is_err() {
+            log_warn!("⛔ Customer not found, customer_code=[{}], follower=[{}]", customer_code, &self.follower);
+            return WebType::from_api_error(&CUSTOMER_NOT_FOUND);
+        }
+
+        if self
+            .update_customer_quota_from_db(&mut trans, customer_code, &quota_request)
+            .await
+            .map_err(err_fwd!("💣 Cannot update the quotas, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        log_info!("😎 Updated the quotas with success, follower=[{}]", &self.follower);
+        log_info!("🏁 End set_customer_quota, customer_code=[{}], follower=[{}]", customer_code, &self.follower);
+
+        WebType::from_item(
+            StatusCode::OK.as_u16(),
+            CustomerQuotaReply {
+                customer_code: customer_code.to_string(),
+                storage_quota: quota_request.storage_quota,
+                file_count_quota: quota_request.file_count_quota,
+            },
+        )
+    }
+
+    ///
+    /// 🔑 Storage used by each customer, computed from the file references of its FS schema
+    ///
+    pub async fn customer_usage(mut self) -> WebType<ListOfCustomerUsageReply> {
+        log_info!("🚀 Start customer_usage api, follower=[{}]", &self.follower);
+
+        // Check if the token is valid
+        if !self.security_token.is_valid() {
+            log_error!("💣 Invalid security token, token=[{:?}], follower=[{}]", &self.security_token, &self.follower);
+            return WebType::from_api_error(&INVALID_TOKEN);
+        }
+
+        self.follower.token_type = TokenType::Token(self.security_token.0.clone());
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(customers) = self
+            .search_customers_with_quota(&mut trans)
+            .await
+            .map_err(err_fwd!("💣 Cannot read the customers, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        // The usage is read on the file server database
+        let Ok(mut fs_cnx) = SQLConnectionAsync::new(&DbServerInfo::for_fs().connect_string())
+            .await
+            .map_err(err_fwd!("💣 Connection issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let mut list_of_usages = Vec::with_capacity(customers.len());
+        for (customer_name, quota) in customers {
+            // Each customer has its own transaction, so a missing schema does not stop the others
+            let Ok(mut fs_trans) =
+                fs_cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+            else {
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            };
+
+            let usage = self.search_usage_from_fs_db(&mut fs_trans, &quota.customer_code).await;
+            let _ = fs_trans.commit().await;
+
+            let Ok((file_count, original_size, encrypted_size)) = usage.map_err(err_fwd!(
+                "⛔ Cannot compute the usage, customer_code=[{}], follower=[{}]",
+                &quota.customer_code,
+                &self.follower
+            )) else {
+                continue;
+            };
+
+            list_of_usages.push(CustomerUsageReply {
+                customer_code: quota.customer_code,
+                customer_name,
+                storage_quota: quota.storage_quota,
+                file_count_quota: quota.file_count_quota,
+                file_count,
+                original_size,
+                encrypted_size,
+            });
+        }
+
+        log_info!("🏁 End customer_usage, customer count=[{}], follower=[{}]", list_of_usages.len(), &self.follower);
+
+        WebType::from_item(StatusCode::OK.as_u16(), ListOfCustomerUsageReply { list_of_usages })
+    }
+
+    async fn search_customer_quota(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        customer_code: &str,
+    ) -> anyhow::Result<Option<CustomerQuotaReply>> {
+        let mut params = HashMap::new();
+        params.insert("p_customer_code".to_owned(), CellValue::from_raw_string(customer_code.to_string()));
+
+        let query = SQLQueryBlockAsync {
+            sql_query:
+                r"SELECT code, storage_quota, file_count_quota FROM dokaadmin.customer WHERE code = :p_customer_code"
+                    .to_string(),
+            start: 0,
+            length: None,
+            params,
+        };
+        let mut data_set = query.execute(trans).await.map_err(err_fwd!("Query failed"))?;
+
+        if !data_set.next() {
+            return Ok(None);
+        }
+        Ok(Some(Self::read_customer_quota(&data_set)?))
+    }
+
+    /// All the customers, with their name and quotas
+    async fn search_customers_with_quota(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+    ) -> anyhow::Result<Vec<(String, CustomerQuotaReply)>> {
+        let query = SQLQueryBlockAsync {
+            sql_query: r"SELECT code, full_name, storage_quota, file_count_quota FROM dokaadmin.customer ORDER BY code"
+                .to_string(),
+            start: 0,
+            length: None,
+            params: HashMap::new(),
+        };
+        let mut data_set = query.execute(trans).await.map_err(err_fwd!("Query failed"))?;
+
+        let mut customers = Vec::with_capacity(data_set.len());
+        while data_set.next() {
+            let customer_name = data_set.get_string("full_name").unwrap_or_default();
+            customers.push((customer_name, Self::read_customer_quota(&data_set)?));
+        }
+        Ok(customers)
+    }
+
+    fn read_customer_quota(data_set: &SQLDataSet) -> anyhow::Result<CustomerQuotaReply> {
+        Ok(CustomerQuotaReply {
+            customer_code: data_set.get_string("code").ok_or(anyhow!("Wrong column code"))?,
+            storage_quota: data_set.get_int("storage_quota").map(|quota| quota as u64),
+            file_count_quota: data_set.get_int("file_count_quota").map(|quota| quota as u64),
+        })
+    }
+
+    async fn update_customer_quota_from_db(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        customer_code: &str,
+        quota_request: &CustomerQuotaRequest,
+    ) -> anyhow::Result<()> {
+        let mut params = HashMap::new();
+        params.insert("p_customer_code".to_owned(), CellValue::from_raw_string(customer_code.to_string()));
+        params.insert("p_storage_quota".to_owned(), CellValue::Int(quota_request.storage_quota.map(|q| q as i64)));
+        params
+            .insert("p_file_count_quota".to_owned(), CellValue::Int(quota_request.file_count_quota.map(|q| q as i64)));
+
+        let query = SQLChangeAsync {
+            sql_query:
+                r"UPDATE dokaadmin.customer SET storage_quota = :p_storage_quota, file_count_quota = :p_file_count_quota
+                        WHERE code = :p_customer_code"
+                    .to_string(),
+            params,
+            sequence_name: "".to_string(),
+        };
+        query.update(trans).await.map_err(err_fwd!("Update of the quotas failed"))?;
+
+        Ok(())
+    }
+
+    /// Number of files, total original size and total encrypted size of the customer.
+    /// The previews are not uploaded files, but their parts are stored. The shared parts are counted once.
+    async fn search_usage_from_fs_db(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        customer_code: &str,
+    ) -> anyhow::Result<(u64, u64, u64)> {
+        let sql_query = format!(
+            r"SELECT COUNT(*) FILTER (WHERE p.id IS NULL) file_count,
+                    COALESCE(SUM(fr.original_file_size) FILTER (WHERE p.id IS NULL), 0)::int8 original_size,
+                    COALESCE(SUM(fr.encrypted_file_size) FILTER (WHERE fr.parts_reference_id IS NULL), 0)::int8 encrypted_size
+                FROM fs_{0}.file_reference fr
+                LEFT JOIN fs_{0}.preview p ON p.file_identifier = fr.file_ref",
+            customer_code
+        );
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params: HashMap::new() };
+        let mut data_set = query.execute(trans).await.map_err(err_fwd!("Query failed"))?;
+
+        if !data_set.next() {
+            return Ok((0, 0, 0));
+        }
+        Ok((
+            data_set.get_int("file_count").ok_or(anyhow!("Wrong column file_count"))? as u64,
+            data_set.get_int("original_size").ok_or(anyhow!("Wrong column original_size"))? as u64,
+            data_set.get_int("encrypted_size").ok_or(anyhow!("Wrong column encrypted_size"))? as u64,
+        ))
+    }
+
     async fn run_script(&self, dbi: DbServerInfo, batch_script: &str, title: &str) -> anyhow::Result<()> {
         // Open a transaction on the cs database
-        let connect_string =
-            format!("postgresql://{}:{}@{}:{}/{}", dbi.db_user, dbi.password, dbi.host, dbi.port, dbi.db_name);
-
-        let Ok(mut cnx) = SQLConnectionAsync::new(&connect_string)
+        let Ok(mut cnx) = SQLConnectionAsync::new(&dbi.connect_string())
             .await
             .map_err(err_fwd!("💣 Connection issue, follower=[{}]", &self.follower))
         else {
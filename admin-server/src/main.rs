@@ -4,7 +4,7 @@ use std::net::SocketAddr;
 use std::process::exit;
 
 use axum::extract::Path;
-use axum::routing::{delete, patch, post};
+use axum::routing::{delete, get, patch, post};
 use axum::{Json, Router};
 use log::*;
 
@@ -16,7 +16,10 @@ use commons_services::x_request_id::XRequestID;
 use common_config::conf_reader::{read_config, read_env};
 use common_config::properties::{get_prop_pg_connect_string, get_prop_value, set_prop_values};
 use common_config::property_name::{COMMON_EDIBLE_KEY_PROPERTY, LOG_CONFIG_FILE_PROPERTY, SERVER_PORT_PROPERTY};
-use dkdto::web_types::{CreateCustomerReply, CreateCustomerRequest, LoginReply, LoginRequest, SimpleMessage, WebType};
+use dkdto::web_types::{
+    CreateCustomerReply, CreateCustomerRequest, CustomerQuotaReply, CustomerQuotaRequest, ListOfCustomerUsageReply,
+    LoginReply, LoginRequest, SimpleMessage, WebType,
+};
 
 use crate::customer::CustomerDelegate;
 use crate::login::LoginDelegate;
@@ -99,6 +102,45 @@ pub async fn delete_integration_tests_customer(
     delegate.delete_integration_tests_customer().await
 }
 
+/// 🔑 Quotas of a customer
+/// **NORM
+///
+/// #[get("/customer/quota/<customer_code>")]
+pub async fn customer_quota(
+    security_token: SecurityToken,
+    x_request_id: XRequestID,
+    Path(customer_code): Path<String>,
+) -> WebType<CustomerQuotaReply> {
+    let delegate = CustomerDelegate::new(security_token, x_request_id);
+    delegate.customer_quota(&customer_code).await
+}
+
+/// 🔑 Change the quotas of a customer
+/// **NORM
+///
+/// #[post("/customer/quota/<customer_code>", format = "application/json", data = "<quota_request>")]
+pub async fn set_customer_quota(
+    security_token: SecurityToken,
+    x_request_id: XRequestID,
+    Path(customer_code): Path<String>,
+    quota_request: Json<CustomerQuotaRequest>,
+) -> WebType<CustomerQuotaReply> {
+    let delegate = CustomerDelegate::new(security_token, x_request_id);
+    delegate.set_customer_quota(&customer_code, quota_request).await
+}
+
+/// 🔑 Storage used by each customer, with its quotas
+/// **NORM
+///
+/// #[get("/customer/usage")]
+pub async fn customer_usage(
+    security_token: SecurityToken,
+    x_request_id: XRequestID,
+) -> WebType<ListOfCustomerUsageReply> {
+    let delegate = CustomerDelegate::new(security_token, x_request_id);
+    delegate.customer_usage().await
+}
+
 /// Accept parameters from the commande line
 /// * --doka-env [optional] : the path to the .doka-config.json file (or from the DOKA_ENV environment variable)
 /// * --cluster-profile : the name of the cluster profile
@@ -171,7 +213,9 @@ async fn main() {
         .route("/customer", post(create_customer))
         .route("/customer/:customer_code", delete(delete_customer))
         .route("/customer/integration_tests", delete(delete_integration_tests_customer))
-        .route("/customer/removable/:customer_code", patch(set_removable_flag_customer));
+        .route("/customer/removable/:customer_code", patch(set_removable_flag_customer))
+        .route("/customer/quota/:customer_code", get(customer_quota).post(set_customer_quota))
+        .route("/customer/usage", get(customer_usage));
 
     let app = Router::new().nest(&base_url, key_routes);
 
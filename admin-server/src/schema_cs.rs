@@ -44,23 +44,25 @@ CREATE INDEX item_name_btree_idx ON item USING btree (public.unaccent_lower((nam
 CREATE INDEX item_name_gin_idx ON item USING gin (public.unaccent_lower((name)::text) public.gin_trgm_ops);
 
 
--- preview definition
+-- item_file_version definition
+-- The successive files of an item, the item.file_ref is the current version.
+-- An item with a file but without version has only one implicit version, number 1
 
 -- Drop table
 
--- DROP TABLE preview;
+-- DROP TABLE item_file_version;
 
-CREATE TABLE preview (
+CREATE TABLE item_file_version (
 	id bigserial NOT NULL,
-	file_reference_id int8 NOT NULL,
-	file_identifier varchar(50) NOT NULL,
-	sort_order int2 NOT NULL,
-	CONSTRAINT prev_f_ident_and_f_ref_uk UNIQUE (file_identifier, file_reference_id),
-	CONSTRAINT prev_f_ref_and_sort_uk UNIQUE (file_identifier, sort_order),
-	CONSTRAINT prev_f_ref_uk UNIQUE (file_identifier),
-	CONSTRAINT preview_pk PRIMARY KEY (id)
+	item_id int8 NOT NULL,
+	version_number int4 NOT NULL,
+	file_ref varchar(50) NOT NULL,
+	created_gmt timestamp(0) NOT NULL,
+	CONSTRAINT item_file_version_pk PRIMARY KEY (id),
+	CONSTRAINT item_file_version_uk UNIQUE (item_id, version_number),
+	CONSTRAINT item_file_version_file_ref_uk UNIQUE (file_ref),
+	CONSTRAINT fk_item_file_version_item_id FOREIGN KEY (item_id) REFERENCES item(id)
 );
-CREATE INDEX preview_file_ref_id_idx ON preview USING btree (file_reference_id);
 
 
 -- tag_definition definition
@@ -75,6 +77,11 @@ CREATE TABLE tag_definition (
 	"type" varchar(25) NOT NULL,
 	string_tag_length int4 NULL,
 	default_value varchar(255) NULL,
+	required bool NOT NULL DEFAULT false,
+	allowed_values text NULL,
+	min_value varchar(255) NULL,
+	max_value varchar(255) NULL,
+	"pattern" varchar(1000) NULL,
 	CONSTRAINT length_limit CHECK (((string_tag_length >= 0) AND (string_tag_length <= 10000000))),
 	CONSTRAINT tag_name_uk UNIQUE (name),
 	CONSTRAINT tag_pk PRIMARY KEY (id)
@@ -97,13 +104,16 @@ CREATE TABLE tag_value (
 	value_date date NULL,
 	value_datetime timestamp(0) NULL,
 	value_boolean bool NULL,
+	value_link int8 NULL,
 	CONSTRAINT tag_value_pk PRIMARY KEY (id),
-	CONSTRAINT fk_tag_value_item_id FOREIGN KEY (item_id) REFERENCES item(id)
+	CONSTRAINT fk_tag_value_item_id FOREIGN KEY (item_id) REFERENCES item(id),
+	CONSTRAINT fk_tag_value_link FOREIGN KEY (value_link) REFERENCES item(id)
 );
 CREATE INDEX tag_value_date_idx ON tag_value USING btree (value_date);
 CREATE INDEX tag_value_datetime_idx ON tag_value USING btree (value_datetime);
 CREATE INDEX tag_value_double_idx ON tag_value USING btree (value_double);
 CREATE INDEX tag_value_integer_idx ON tag_value USING btree (value_integer);
+CREATE INDEX tag_value_link_idx ON tag_value USING btree (value_link);
 CREATE INDEX tag_value_str_like_gin_idx ON tag_value USING gin (public.unaccent_lower((value_string)::text) public.gin_trgm_ops);
 CREATE INDEX tag_value_str_sort_btree_idx ON tag_value USING btree (public.unaccent_lower((value_string)::text) COLLATE "C");
 
@@ -15,13 +15,25 @@ CREATE TABLE file_reference (
 	is_encrypted bool NOT NULL,
 	is_fulltext_parsed bool NULL,
 	is_preview_generated bool NULL,
+	parts_reference_id int8 NULL,
+	ref_count int4 NOT NULL DEFAULT 1,
+	created_gmt timestamp NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	processing_status varchar(20) NOT NULL DEFAULT 'received',
+	failure_reason varchar(500) NULL,
+	status_gmt timestamp NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	verified_gmt timestamp NULL,
+	integrity_issue varchar(500) NULL,
 	CONSTRAINT file_reference_pk PRIMARY KEY (id),
-	CONSTRAINT file_reference_uk UNIQUE (file_ref)
+	CONSTRAINT file_reference_uk UNIQUE (file_ref),
+	CONSTRAINT file_reference_parts_fk FOREIGN KEY (parts_reference_id) REFERENCES file_reference(id)
 );
+CREATE INDEX file_reference_checksum_idx ON file_reference USING btree (checksum);
 
+-- storage_id is the file the part was written for, the key of the part in the fs and s3 stores
 CREATE TABLE file_parts (
 	id bigserial NOT NULL,
 	file_reference_id int8 NOT NULL,
+	storage_id int8 NOT NULL,
 	part_number int4 NOT NULL,
 	part_data text NULL,
 	CONSTRAINT file_parts_pkey PRIMARY KEY (id),
@@ -43,14 +55,50 @@ CREATE UNIQUE INDEX file_uploads_customer_user_idx ON file_uploads (file_ref, pa
 CREATE INDEX file_uploads_file_ref_idx ON file_uploads (file_ref);
 CREATE INDEX file_uploads_start_time_idx ON file_uploads (start_time_gmt);
 
+CREATE TABLE file_upload_session (
+	file_ref varchar(50) NOT NULL,
+	user_id bigint NOT NULL,
+	item_info varchar(50) NOT NULL,
+	file_size int8 NULL,
+	block_count int4 NULL,
+	start_time_gmt timestamp NOT NULL,
+	CONSTRAINT file_upload_session_pk PRIMARY KEY (file_ref)
+);
+
 CREATE TABLE file_metadata (
 	id bigserial NOT NULL,
 	file_reference_id int8 NOT NULL,
-	meta_key varchar(50) NOT NULL,
-	value varchar(200) NULL,
+	meta_key varchar(256) NOT NULL,
+	value text NULL,
+	value_type varchar(10) NOT NULL DEFAULT 'text',
+	value_integer int8 NULL,
+	value_datetime timestamp NULL,
 	CONSTRAINT file_metadata_pkey PRIMARY KEY (id),
 	CONSTRAINT file_metadata_id_fk FOREIGN KEY (file_reference_id) REFERENCES file_reference(id)
 );
 CREATE UNIQUE INDEX ref_meta_udx ON file_metadata USING btree (file_reference_id, meta_key);
 
+-- Integrity check of all the stored files, end_gmt is null while the check is running
+CREATE TABLE scrub_run (
+	id bigserial NOT NULL,
+	start_gmt timestamp NOT NULL,
+	end_gmt timestamp NULL,
+	file_count int8 NOT NULL DEFAULT 0,
+	corrupted_count int8 NOT NULL DEFAULT 0,
+	CONSTRAINT scrub_run_pkey PRIMARY KEY (id)
+);
+
+-- file_identifier is the file_ref of the preview image, which is a file reference of its own
+CREATE TABLE preview (
+	id bigserial NOT NULL,
+	file_reference_id int8 NOT NULL,
+	file_identifier varchar(50) NOT NULL,
+	sort_order int2 NOT NULL,
+	CONSTRAINT prev_f_ref_and_sort_uk UNIQUE (file_reference_id, sort_order),
+	CONSTRAINT prev_f_ref_uk UNIQUE (file_identifier),
+	CONSTRAINT preview_pk PRIMARY KEY (id),
+	CONSTRAINT preview_file_reference_fk FOREIGN KEY (file_reference_id) REFERENCES file_reference(id)
+);
+CREATE INDEX preview_file_ref_id_idx ON preview USING btree (file_reference_id);
+
     "#;
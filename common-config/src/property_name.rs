@@ -11,7 +11,25 @@ pub const SESSION_MANAGER_PORT_PROPERTY: &str = "sm.port";
 pub const KEY_MANAGER_HOSTNAME_PROPERTY: &str = "km.host";
 pub const KEY_MANAGER_PORT_PROPERTY: &str = "km.port";
 
+pub const ADMIN_SERVER_HOSTNAME_PROPERTY: &str = "as.host";
+pub const ADMIN_SERVER_PORT_PROPERTY: &str = "as.port";
+
 pub const DOCUMENT_SERVER_HOSTNAME_PROPERTY: &str = "ds.host";
 pub const DOCUMENT_SERVER_PORT_PROPERTY: &str = "ds.port";
+pub const FILE_SERVER_HOSTNAME_PROPERTY: &str = "fs.host";
+pub const FILE_SERVER_PORT_PROPERTY: &str = "fs.port";
 pub const TIKA_SERVER_HOSTNAME_PROPERTY: &str = "tks.host";
-pub const TIKA_SERVER_PORT_PROPERTY: &str = "tks.port";
\ No newline at end of file
+pub const TIKA_SERVER_PORT_PROPERTY: &str = "tks.port";
+
+pub const BLOCK_STORE_PROPERTY: &str = "fs.block_store";
+pub const BLOCK_STORE_PATH_PROPERTY: &str = "fs.block_store.path";
+pub const S3_ENDPOINT_PROPERTY: &str = "fs.s3.endpoint";
+pub const S3_REGION_PROPERTY: &str = "fs.s3.region";
+pub const S3_BUCKET_PROPERTY: &str = "fs.s3.bucket";
+pub const S3_ACCESS_KEY_PROPERTY: &str = "fs.s3.access_key";
+pub const S3_SECRET_KEY_PROPERTY: &str = "fs.s3.secret_key";
+pub const PDF_RENDER_COMMAND_PROPERTY: &str = "fs.preview.pdf_command";
+pub const CLEANUP_RETENTION_DAYS_PROPERTY: &str = "fs.cleanup.retention_days";
+pub const CLEANUP_INTERVAL_HOURS_PROPERTY: &str = "fs.cleanup.interval_hours";
+pub const METADATA_TAGS_PROPERTY: &str = "fs.metadata.tags";
+pub const ENCRYPT_THREAD_COUNT_PROPERTY: &str = "fs.encrypt.thread_count";
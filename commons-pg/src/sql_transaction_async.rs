@@ -301,8 +301,8 @@ impl SQLChangeAsync {
         Ok(())
     }
 
-    /// Base routine for update, insert and delete
-    async fn change(&self, sql_transaction: &mut SQLTransactionAsync<'_>) -> anyhow::Result<()> {
+    /// Base routine for update, insert and delete, return the number of rows affected
+    async fn change(&self, sql_transaction: &mut SQLTransactionAsync<'_>) -> anyhow::Result<u64> {
         let null_str = "".to_owned();
         let (new_sql_string, v_params) = parse_query_async(self.sql_query.as_str(), &self.params, &null_str);
         let mut query_builder = sqlx::query(new_sql_string.as_str());
@@ -310,13 +310,13 @@ impl SQLChangeAsync {
         for param in v_params {
             query_builder = bind_cell_to_query(param, query_builder);
         }
-        let _ = query_builder.execute(&mut *sql_transaction.inner_transaction).await.map_err(err_fwd!(
+        let result = query_builder.execute(&mut *sql_transaction.inner_transaction).await.map_err(err_fwd!(
             "Query failed : {}, Params : {:?}",
             new_sql_string.as_str(),
             v_params_debug
         ))?;
 
-        Ok(())
+        Ok(result.rows_affected())
     }
 
     /// Return the id of the new row if success
@@ -333,18 +333,19 @@ impl SQLChangeAsync {
     }
 
     pub async fn insert_no_pk(&self, sql_transaction: &mut SQLTransactionAsync<'_>) -> anyhow::Result<()> {
-        let insert_info = self.change(sql_transaction).await?;
-        Ok(insert_info)
+        let _ = self.change(sql_transaction).await?;
+        Ok(())
     }
 
     pub async fn update(&self, sql_transaction: &mut SQLTransactionAsync<'_>) -> anyhow::Result<()> {
-        let update_info = self.change(sql_transaction).await?;
-        Ok(update_info)
+        let _ = self.change(sql_transaction).await?;
+        Ok(())
     }
 
-    pub async fn delete(&self, sql_transaction: &mut SQLTransactionAsync<'_>) -> anyhow::Result<()> {
-        let delete_info = self.change(sql_transaction).await?;
-        Ok(delete_info)
+    /// Return the number of deleted rows
+    pub async fn delete(&self, sql_transaction: &mut SQLTransactionAsync<'_>) -> anyhow::Result<u64> {
+        let delete_count = self.change(sql_transaction).await?;
+        Ok(delete_count)
     }
 }
 
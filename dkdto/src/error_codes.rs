@@ -54,6 +54,9 @@ pub static SESSION_LOGIN_DENIED: Lazy<ApiError<'static>> =
 // Tags
 pub static INCORRECT_DEFAULT_STRING_LENGTH: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Incorrect string length"));
+pub static INCORRECT_DEFAULT_LINK_VALUE: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Incorrect default link value"));
+#[deprecated(note = "The default value of a link tag is an item id, use INCORRECT_DEFAULT_LINK_VALUE")]
 pub static INCORRECT_DEFAULT_LINK_LENGTH: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Incorrect link length"));
 pub static INCORRECT_DEFAULT_BOOLEAN_VALUE: Lazy<ApiError<'static>> =
@@ -72,14 +75,40 @@ pub static INCORRECT_CHAR_TAG_NAME: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Wrong char in tag name"));
 pub static INCORRECT_LENGTH_TAG_NAME: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Tag name too long"));
+pub static TAG_NOT_FOUND: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::NOT_FOUND.as_u16(), "Tag not found"));
+pub static TAG_NAME_ALREADY_TAKEN: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::CONFLICT.as_u16(), "Tag name already taken"));
+pub static TAG_CONVERSION_FAILED: Lazy<ApiError<'static>> = Lazy::new(|| {
+    ApiError::borrowed(
+        StatusCode::CONFLICT.as_u16(),
+        "Some values of the tag cannot be converted, use a dry run to list them",
+    )
+});
+pub static INCORRECT_TAG_CONSTRAINT: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Incorrect tag constraint"));
+pub static TAG_VALUE_NOT_ALLOWED: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "The value is not one of the allowed values"));
+pub static TAG_VALUE_OUT_OF_RANGE: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "The value is out of the range of the tag"));
+pub static TAG_VALUE_PATTERN_MISMATCH: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "The value does not match the tag pattern"));
 
 // Items
 pub static MISSING_ITEM: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Missing item"));
+pub static ITEM_VERSION_NOT_FOUND: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::NOT_FOUND.as_u16(), "Version of the item not found"));
+pub static FILE_ALREADY_ATTACHED: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::CONFLICT.as_u16(), "The file is already attached to an item"));
 pub static BAD_TAG_FOR_ITEM: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Bad tag definition"));
 pub static MISSING_TAG_FOR_ITEM: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Missing or Incorrect tag definition"));
+pub static INCORRECT_LINK_TARGET: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "The linked item does not exist"));
+pub static MISSING_REQUIRED_TAG: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "A required tag is missing on the item"));
 
 // Customer
 pub static CUSTOMER_NAME_ALREADY_TAKEN: Lazy<ApiError<'static>> =
@@ -90,12 +119,31 @@ pub static USER_NAME_ALREADY_TAKEN: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::CONFLICT.as_u16(), "User name already taken"));
 pub static CUSTOMER_NOT_REMOVABLE: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::FORBIDDEN.as_u16(), "Customer not removable"));
+pub static CUSTOMER_NOT_FOUND: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::NOT_FOUND.as_u16(), "Customer not found"));
 
 // Upload
 pub static UPLOAD_WRONG_ITEM_INFO: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Item info is not a correct string"));
 pub static FILE_INFO_NOT_FOUND: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::NOT_FOUND.as_u16(), "Information about the file is not found"));
+pub static UPLOAD_SESSION_NOT_FOUND: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::NOT_FOUND.as_u16(), "The upload session is not found"));
+pub static UPLOAD_WRONG_BLOCK: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "The block number or size is not correct"));
+pub static UPLOAD_INCOMPLETE: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::CONFLICT.as_u16(), "Some blocks of the file are missing"));
+pub static PREVIEW_NOT_FOUND: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::NOT_FOUND.as_u16(), "No preview for the file"));
+pub static FILE_NOT_STORED: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::CONFLICT.as_u16(), "The file is not stored yet"));
+pub static SCRUB_NOT_FOUND: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::NOT_FOUND.as_u16(), "No integrity check of the files"));
+pub static SCRUB_ALREADY_RUNNING: Lazy<ApiError<'static>> = Lazy::new(|| {
+    ApiError::borrowed(StatusCode::CONFLICT.as_u16(), "The integrity check of the files is already running")
+});
+pub static QUOTA_EXCEEDED: Lazy<ApiError<'static>> =
+    Lazy::new(|| ApiError::borrowed(StatusCode::FORBIDDEN.as_u16(), "The storage quota of the customer is exceeded"));
 
 pub static HTTP_CLIENT_ERROR: Lazy<ApiError<'static>> =
     Lazy::new(|| ApiError::borrowed(StatusCode::BAD_REQUEST.as_u16(), "Http Client Error"));
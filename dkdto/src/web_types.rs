@@ -221,6 +221,37 @@ pub struct DeleteCustomerRequest {
     pub customer_code: String,
 }
 
+/// Quotas of a customer, there is no limit when a quota is None
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomerQuotaRequest {
+    pub storage_quota: Option<u64>, // in bytes
+    pub file_count_quota: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomerQuotaReply {
+    pub customer_code: String,
+    pub storage_quota: Option<u64>,
+    pub file_count_quota: Option<u64>,
+}
+
+/// Storage used by a customer, compared to its quotas
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomerUsageReply {
+    pub customer_code: String,
+    pub customer_name: String,
+    pub storage_quota: Option<u64>,
+    pub file_count_quota: Option<u64>,
+    pub file_count: u64,
+    pub original_size: u64,  // total size of the uploaded files
+    pub encrypted_size: u64, // total size of the stored parts, previews included
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListOfCustomerUsageReply {
+    pub list_of_usages: Vec<CustomerUsageReply>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DeleteCustomerReply {
     pub status: String,
@@ -250,7 +281,7 @@ pub enum EnumTagValue {
     Double(Option<f64>),
     SimpleDate(Option<String>),
     DateTime(Option<String>), // "1970-03-23T23:04:10.236Z"
-    Link(Option<String>),
+    Link(Option<i64>),        // id of the linked item
 }
 
 impl EnumTagValue {
@@ -262,7 +293,7 @@ impl EnumTagValue {
             EnumTagValue::Double(v) => v.clone().unwrap_or(0.0_f64).to_string(),
             EnumTagValue::SimpleDate(v) => v.clone().unwrap_or("".to_string()).to_string(),
             EnumTagValue::DateTime(v) => v.clone().unwrap_or("".to_string()).to_string(),
-            EnumTagValue::Link(v) => v.clone().unwrap_or("".to_string()).to_string(),
+            EnumTagValue::Link(v) => v.map(|id| id.to_string()).unwrap_or("".to_string()),
         }
     }
 
@@ -289,7 +320,10 @@ impl EnumTagValue {
                 Ok(_) => Ok(Self::DateTime(Some(tag_value.to_owned()))),
                 Err(e) => Err(format!("Bad datetime value: {}", e.to_string())),
             },
-            TAG_TYPE_LINK => Ok(Self::Link(Some(tag_value.to_owned()))),
+            TAG_TYPE_LINK => match tag_value.parse::<i64>() {
+                Ok(id) => Ok(Self::Link(Some(id))),
+                Err(e) => Err(format!("Bad link value: {}", e)),
+            },
             _ => Err(format!("Bad type: {}", tag_type)),
         }
     }
@@ -344,6 +378,10 @@ pub struct AddItemTagReply {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetItemReply {
     pub items: Vec<ItemElement>,
+    /// Number of items matching the search, whatever the page
+    pub total_count: Option<i64>,
+    /// Opaque cursor to get the page after this one, when there are more items
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -356,6 +394,71 @@ pub struct ItemElement {
     pub properties: Option<Vec<TagValueElement>>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddItemVersionRequest {
+    pub file_ref: String, // file reference of the new version
+}
+
+/// A file version of an item, the size and the checksum are only known by the file server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemVersionElement {
+    pub version_number: u32,
+    pub file_ref: String,
+    pub created: String,
+    pub is_current: bool,
+    pub file_size: Option<u64>,
+    pub checksum: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemVersionReply {
+    pub item_id: i64,
+    pub list_of_versions: Vec<ItemVersionElement>,
+}
+
+/// Changes of an item, the missing fields are not changed.
+/// A null file_ref detaches the file from the item, a file_ref attaches the file as the new version
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PatchItemRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some", skip_serializing_if = "Option::is_none")]
+    pub file_ref: Option<Option<String>>,
+}
+
+/// Keep the null value of a field as Some(None), a missing field stays None
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    serde::Deserialize::deserialize(deserializer).map(Some)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteItemReply {
+    pub item_id: i64,
+    /// Files of the item deleted from the file server
+    pub deleted_files: Vec<String>,
+    /// Files of the item the file server could not delete
+    pub kept_files: Vec<String>,
+}
+
+/// Tags to add and to remove on all the items matching the filter expression
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BulkItemTagRequest {
+    pub filters: String,
+    #[serde(default)]
+    pub add_tags: Vec<AddTagValue>,
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BulkItemTagReply {
+    pub item_count: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TagValueElement {
     pub tag_value_id: i64,
@@ -375,7 +478,7 @@ const TAG_TYPE_DATE: &str = "date";
 const TAG_TYPE_DATETIME: &str = "datetime";
 const TAG_TYPE_LINK: &str = "link";
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TagType {
     Text,
     Bool,
@@ -435,12 +538,34 @@ impl fmt::Display for TagType {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct AddTagRequest {
     pub name: String,
     pub tag_type: String, // string, bool, integer, double, date, datetime
 
     pub default_value: Option<String>,
+    #[serde(flatten)]
+    pub constraints: TagConstraints,
+}
+
+/// Rules on the values of a tag
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct TagConstraints {
+    /// The tag must be set on every new item
+    #[serde(default)]
+    pub required: bool,
+    /// The only accepted values, for text and int tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_values: Option<Vec<String>>,
+    /// Lowest accepted value, for int, decimal, date and datetime tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_value: Option<String>,
+    /// Highest accepted value, for int, decimal, date and datetime tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_value: Option<String>,
+    /// Regular expression the whole value must match, for text tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -459,6 +584,54 @@ pub struct TagElement {
     pub name: String,
     pub tag_type: String, // string, bool, integer, double, date, datetime
     pub default_value: Option<String>,
+    #[serde(default)]
+    pub string_tag_length: Option<i32>,
+    #[serde(flatten)]
+    pub constraints: TagConstraints,
+}
+
+/// Changes of a tag definition, the missing fields are not changed.
+/// A null default_value removes the default of the tag
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PatchTagRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_type: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some", skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub string_tag_length: Option<i32>,
+    /// Replace all the constraints of the tag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constraints: Option<TagConstraints>,
+    /// Report the changes without applying them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Give the default value to the items without a value for the tag
+    #[serde(default)]
+    pub apply_default: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PatchTagReply {
+    pub tag: TagElement,
+    pub dry_run: bool,
+    /// Values of the tag that cannot be converted to the new type or do not follow the new constraints
+    pub conversion_failures: Vec<TagValueConversionFailure>,
+    /// Number of items that received the default value
+    pub defaulted_item_count: u64,
+    /// Number of items still without a value for a required tag
+    #[serde(default)]
+    pub missing_value_item_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TagValueConversionFailure {
+    pub tag_value_id: i64,
+    pub item_id: i64,
+    pub value: String,
+    pub reason: String,
 }
 
 // Full text
@@ -480,6 +653,21 @@ pub struct DeleteFullTextRequest {
     pub file_ref: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FullTextSearchReply {
+    pub items: Vec<FullTextItemElement>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FullTextItemElement {
+    pub item_id: i64,
+    pub name: String,
+    pub file_ref: String,
+    pub created: String,
+    pub last_modified: Option<String>,
+    pub rank: f64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UploadReply {
     pub file_ref: String,
@@ -487,7 +675,143 @@ pub struct UploadReply {
     pub block_count: u32,
 }
 
-pub type DownloadReply = Result<(HeaderMap, Body), (StatusCode, String)>;
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateUploadSessionRequest {
+    pub item_info: String, // Is a non unique string to make link with the item element (ex : the file name)
+    pub file_size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadSessionReply {
+    pub file_ref: String,
+    pub block_size: u32,           // Size of each block, except the last one
+    pub block_count: Option<u32>,  // Number of blocks expected, when the file size is known
+    pub received_blocks: Vec<u32>, // Block numbers already stored
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadBlockReply {
+    pub file_ref: String,
+    pub block_num: u32,
+    pub size: usize,
+}
+
+/// Rows removed by the maintenance of the file server, for all the customers
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CleanupReply {
+    pub customer_count: u32,
+    pub upload_sessions: u64, // Upload sessions older than the retention
+    pub file_uploads: u64,    // Blocks of the uploads older than the retention
+    pub file_references: u64, // References of the failed or abandoned uploads, with their previews
+    pub file_parts: u64,
+    pub file_metadata: u64,
+}
+
+const PROCESSING_STATUS_RECEIVED: &str = "received";
+const PROCESSING_STATUS_ENCRYPTING: &str = "encrypting";
+const PROCESSING_STATUS_PARSING: &str = "parsing";
+const PROCESSING_STATUS_INDEXED: &str = "indexed";
+const PROCESSING_STATUS_STORED: &str = "stored";
+const PROCESSING_STATUS_FAILED: &str = "failed";
+
+/// Steps of the processing of an uploaded file, "indexed", "stored" and "failed" are final
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ProcessingStatus {
+    Received,
+    Encrypting,
+    Parsing,
+    Indexed,
+    /// Encrypted and available for download, but the parsing failed, the file can be reindexed
+    Stored,
+    Failed,
+}
+
+impl ProcessingStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ProcessingStatus::Received => PROCESSING_STATUS_RECEIVED,
+            ProcessingStatus::Encrypting => PROCESSING_STATUS_ENCRYPTING,
+            ProcessingStatus::Parsing => PROCESSING_STATUS_PARSING,
+            ProcessingStatus::Indexed => PROCESSING_STATUS_INDEXED,
+            ProcessingStatus::Stored => PROCESSING_STATUS_STORED,
+            ProcessingStatus::Failed => PROCESSING_STATUS_FAILED,
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, ProcessingStatus::Indexed | ProcessingStatus::Stored | ProcessingStatus::Failed)
+    }
+}
+
+impl FromStr for ProcessingStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            PROCESSING_STATUS_RECEIVED => Ok(ProcessingStatus::Received),
+            PROCESSING_STATUS_ENCRYPTING => Ok(ProcessingStatus::Encrypting),
+            PROCESSING_STATUS_PARSING => Ok(ProcessingStatus::Parsing),
+            PROCESSING_STATUS_INDEXED => Ok(ProcessingStatus::Indexed),
+            PROCESSING_STATUS_STORED => Ok(ProcessingStatus::Stored),
+            PROCESSING_STATUS_FAILED => Ok(ProcessingStatus::Failed),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for ProcessingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileStatusReply {
+    pub file_ref: String,
+    pub status: String, // received, encrypting, parsing, indexed, stored or failed
+    pub failure_reason: Option<String>,
+    pub status_date_time: DateTime<Utc>,
+}
+
+/// Status of the files after their reindexing
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReindexReply {
+    pub list_of_files: Vec<FileStatusReply>,
+}
+
+/// Result of the integrity check of the parts of a stored file
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileIntegrityReply {
+    pub file_ref: String,
+    pub is_valid: bool,
+    pub issues: Vec<String>, // Missing, duplicate or undecipherable parts, wrong checksum or sizes
+    pub verified_date_time: DateTime<Utc>,
+}
+
+/// Progress of the integrity check of all the stored files of the customer, with the corrupted files found
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScrubReply {
+    pub status: String, // running or done
+    pub start_date_time: DateTime<Utc>,
+    pub end_date_time: Option<DateTime<Utc>>,
+    pub file_count: u64,
+    pub corrupted_count: u64,
+    pub list_of_corrupted_files: Vec<FileIntegrityReply>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MetadataElement {
+    pub key: String, // Tika key, or "doka:..." for the normalized ones
+    pub value: EnumTagValue,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileMetadataReply {
+    pub file_ref: String,
+    pub list_of_metadata: Vec<MetadataElement>,
+}
+
+pub type DownloadReply = Result<(StatusCode, HeaderMap, Body), (StatusCode, String)>;
 // pub type DownloadReply = Custom<Content<Vec<u8>>>;
 // pub type DownloadReply = Vec<u8>; // TODO
 //
@@ -518,11 +842,32 @@ pub struct GetFileInfoReply {
     pub is_encrypted: bool,
     pub is_fulltext_parsed: Option<bool>,
     pub is_preview_generated: Option<bool>,
+    pub processing_status: String,
+    pub failure_reason: Option<String>,
+    pub created: String,
+}
+
+///
+/// Criteria of the file listing.
+/// sort_by is file_ref (default), size or date, sort_order is asc (default) or desc.
+/// The media type is a glob pattern, like "image/*"
+///
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct FileListQuery {
+    pub start_page: Option<u32>,
+    pub page_size: Option<u32>,
+    pub sort_by: Option<String>,
+    pub sort_order: Option<String>,
+    pub media_type: Option<String>,
+    pub is_encrypted: Option<bool>,
+    pub is_fulltext_parsed: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ListOfFileInfoReply {
     pub list_of_files: Vec<GetFileInfoReply>,
+    /// Number of files matching the criteria, whatever the page
+    pub total_count: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -539,6 +884,7 @@ pub struct UploadInfoReply {
     pub encrypted_count: i64,   // Number of encrypted parts
     pub uploaded_count: i64,    // Number of block simply loaded
     pub total_part: i64,        // Number of parts to be uploaded
+    pub processing_status: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -550,6 +896,8 @@ pub struct GetFileInfoShortReply {
     pub uploaded_count: i64,
     pub fulltext_indexed_count: i64,
     pub preview_generated_count: i64,
+    pub processing_status: String,
+    pub failure_reason: Option<String>,
 }
 
 /// Tika
@@ -1,8 +1,11 @@
-use crate::filter::filter_ast::{ComparisonOperator, FilterCondition, FilterExpressionAST};
+use crate::engine::sort::{parse_sort_keys, sort_signature, sort_value_to_cell, SearchCursor, SortDirection, SortKey};
+use crate::filter::filter_ast::{ComparisonOperator, FilterCondition, FilterExpressionAST, FilterValue};
+use crate::filter::filter_date::{parse_date_literal, DateAnchor, DateLiteral, DateUnit};
 use axum::async_trait;
+use chrono::{TimeZone, Utc};
 use commons_error::tr_fwd;
 use commons_error::*;
-use commons_pg::sql_transaction::SQLDataSet;
+use commons_pg::sql_transaction::{CellValue, SQLDataSet};
 use commons_pg::sql_transaction_async::{SQLConnectionAsync, SQLQueryBlockAsync};
 use commons_services::x_request_id::{Follower, XRequestID};
 use dkdto::web_types::TagType;
@@ -11,8 +14,15 @@ use once_cell::sync::Lazy;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::str::FromStr;
+use std::time::SystemTime;
 
 const EXTRA_TABLE_PREFIX: &str = "ot";
+const FULLTEXT_TABLE_PREFIX: &str = "ft";
+const SELECT_TABLE_PREFIX: &str = "st";
+const SORT_TABLE_PREFIX: &str = "so";
+
+/// Reserved attribute for the fulltext conditions, ex : #text ~ "invoice overdue"
+pub(crate) const TEXT_ATTRIBUTE: &str = "#text";
 
 static LEGAL_OPERATORS_BY_TAG_TYPE: Lazy<HashMap<TagType, Vec<ComparisonOperator>>> = Lazy::new(|| {
     let mut map = HashMap::new();
@@ -40,6 +50,20 @@ static LEGAL_OPERATORS_BY_TAG_TYPE: Lazy<HashMap<TagType, Vec<ComparisonOperator
         ],
     );
     map.insert(TagType::Text, vec![ComparisonOperator::EQ, ComparisonOperator::NEQ, ComparisonOperator::LIKE]);
+    map.insert(TagType::Link, vec![ComparisonOperator::EQ, ComparisonOperator::NEQ]);
+    for tag_type in [TagType::Date, TagType::DateTime] {
+        map.insert(
+            tag_type,
+            vec![
+                ComparisonOperator::EQ,
+                ComparisonOperator::NEQ,
+                ComparisonOperator::GT,
+                ComparisonOperator::GTE,
+                ComparisonOperator::LT,
+                ComparisonOperator::LTE,
+            ],
+        );
+    }
     map
 });
 
@@ -48,12 +72,106 @@ pub(crate) enum SearchSqlGenerationMode {
     Persisted,
 }
 
+/// Search query, the values of the filter are in the params, ex : td."name" = :p_0_value
+#[derive(Debug)]
+pub(crate) struct SearchSql {
+    pub(crate) sql_query: String,
+    /// The params in the order of their creation
+    pub(crate) params: Vec<(String, CellValue)>,
+    /// The tags returned with the items, in the order of the selection
+    pub(crate) selected_tags: Vec<SelectedTag>,
+    /// The columns of the sort, the item id comes after them to make the order stable
+    pub(crate) sort_columns: Vec<SortColumn>,
+    /// Signature of the sort, for the cursors of the pages
+    pub(crate) sort: String,
+    /// Count of all the items matching the filter, whatever the cursor
+    pub(crate) count_query: String,
+    pub(crate) count_params: Vec<(String, CellValue)>,
+}
+
+/// Tag returned with the items of the search, its value is in the columns {alias}_value_id, {alias}_tag_id
+/// and {alias}_value of the query, ex : st_0_value
+#[derive(Debug, Clone)]
+pub(crate) struct SelectedTag {
+    pub(crate) tag_name: String,
+    pub(crate) tag_type: TagType,
+    pub(crate) alias: String,
+}
+
+/// Column the search is sorted on, its value is read from the column [column_name] of the query to build the cursor
+#[derive(Debug, Clone)]
+pub(crate) struct SortColumn {
+    pub(crate) key: SortKey,
+    pub(crate) tag_type: TagType,
+    /// Sql expression of the sort, ex : i.created_gmt or so_0.value
+    expression: String,
+    /// ex : created_gmt or so_0_value
+    pub(crate) column_name: String,
+}
+
+impl SelectedTag {
+    pub(crate) fn value_id_column(&self) -> String {
+        format!("{}_value_id", self.alias)
+    }
+
+    pub(crate) fn tag_id_column(&self) -> String {
+        format!("{}_tag_id", self.alias)
+    }
+
+    pub(crate) fn value_column(&self) -> String {
+        format!("{}_value", self.alias)
+    }
+}
+
+impl SearchSql {
+    /// Params as expected by the SQLQueryBlockAsync
+    pub(crate) fn params_map(&self) -> HashMap<String, CellValue> {
+        self.params.iter().cloned().collect()
+    }
+
+    /// Params of the count query, only the ones of the filter
+    pub(crate) fn count_params_map(&self) -> HashMap<String, CellValue> {
+        self.count_params.iter().cloned().collect()
+    }
+}
+
+/// Accumulate the values of the search query.
+/// The names look like p_{n}_value, so a name is never the beginning of another one
+#[derive(Debug, Default)]
+struct SearchParams {
+    params: Vec<(String, CellValue)>,
+}
+
+impl SearchParams {
+    /// Keep the value and return its placeholder for the sql, ex : :p_3_value
+    fn bind(&mut self, value: CellValue) -> String {
+        let name = format!("p_{}_value", self.params.len());
+        let placeholder = format!(":{}", name);
+        self.params.push((name, value));
+        placeholder
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct TagDefinition {
     tag_names: String,
     tag_type: TagType,
 }
 
+/// Tell if the condition is on the document text instead of a tag
+fn is_fulltext_condition(filter_condition: &FilterCondition) -> bool {
+    filter_condition.attribute == TEXT_ATTRIBUTE
+}
+
+/// Name of the joined table for the condition, ex : ot_lastname_0 or ft_text_0
+fn table_alias(filter_condition: &FilterCondition, occurrence: u32) -> String {
+    if is_fulltext_condition(filter_condition) {
+        format!("{}_text_{}", FULLTEXT_TABLE_PREFIX, occurrence)
+    } else {
+        format!("{}_{}_{}", EXTRA_TABLE_PREFIX, &filter_condition.attribute, occurrence)
+    }
+}
+
 /// Extract all the filter conditions from the filter_expression AST
 fn extract_all_conditions(
     filter_expression_ast: &FilterExpressionAST,
@@ -108,7 +226,7 @@ pub(crate) fn build_query_filter(
                     panic!("No matching conditions"); // TODO ...
                 }
                 Some((index, fc)) => {
-                    let s = format!(" {}_{}_{}.value is not null ", EXTRA_TABLE_PREFIX, &fc.attribute, index);
+                    let s = format!(" {}.value is not null ", table_alias(fc, *index));
                     content.push_str(&s);
                 }
             }
@@ -137,6 +255,8 @@ pub(crate) enum GenerationError {
     TagTypeUnknown(String),
     TagSearchError(String),
     TagIncompatibleType(String),
+    FullTextSearchError(String),
+    CursorInvalid(String),
 }
 
 impl fmt::Display for GenerationError {
@@ -145,8 +265,10 @@ impl fmt::Display for GenerationError {
     }
 }
 
+use crate::fulltext::{matching_files_query, FullTextDelegate};
 use crate::tag::TagDelegate;
 use anyhow::Result;
+use commons_services::key_lib::fetch_customer_key;
 use commons_services::token_lib::SessionToken;
 
 #[derive(Debug)]
@@ -167,6 +289,50 @@ trait TagDefinitionInterface {
     async fn get_tag_definition(&self, tag_names: &[String], customer_code: &str) -> Result<Vec<TagDefinition>>;
 }
 
+#[derive(Debug)]
+pub(crate) struct FullTextQueryBuilder {
+    pub session_token: SessionToken,
+    pub follower: Follower,
+}
+
+impl FullTextQueryBuilder {
+    pub fn new(session_token: SessionToken, follower: Follower) -> Self {
+        Self { session_token, follower }
+    }
+}
+
+#[async_trait]
+pub(crate) trait FullTextQueryInterface {
+    /// Returns the list of ( <lang>, <hashed lexeme> ) of the text for the given customer
+    async fn get_lexeme_queries(&self, text: &str, customer_code: &str) -> Result<Vec<(String, String)>>;
+}
+
+#[async_trait]
+impl FullTextQueryInterface for FullTextQueryBuilder {
+    async fn get_lexeme_queries(&self, text: &str, customer_code: &str) -> Result<Vec<(String, String)>> {
+        let customer_key = fetch_customer_key(customer_code, &self.follower)
+            .await
+            .map_err(err_fwd!("Cannot get the customer key; follower={}", &self.follower))?;
+
+        let mut cnx = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("New DB connection failed; follower={}", &self.follower))?;
+
+        let mut trans = cnx.begin().await.map_err(err_fwd!("Transaction issue; follower={}", &self.follower))?;
+
+        let fulltext_delegate = FullTextDelegate::new(self.session_token.clone(), self.follower.x_request_id);
+
+        let lexeme_queries = fulltext_delegate
+            .build_lexeme_queries(&mut trans, text, customer_code, &customer_key)
+            .await
+            .map_err(err_fwd!("build_lexeme_queries failed; follower={}", &self.follower))?;
+
+        trans.commit().await?;
+
+        Ok(lexeme_queries)
+    }
+}
+
 #[async_trait]
 impl TagDefinitionInterface for TagDefinitionBuilder {
     async fn get_tag_definition(&self, tag_names: &[String], customer_code: &str) -> Result<Vec<TagDefinition>> {
@@ -197,8 +363,12 @@ impl TagDefinitionInterface for TagDefinitionBuilder {
     }
 }
 
-///
-fn build_tag_value_filter(filter_condition: &FilterCondition, tag_type: &TagType) -> Result<String, GenerationError> {
+/// Build the condition on the tag value, the value itself is bound in the params
+fn build_tag_value_filter(
+    filter_condition: &FilterCondition,
+    tag_type: &TagType,
+    params: &mut SearchParams,
+) -> Result<String, GenerationError> {
     let sql_op = match filter_condition.operator {
         ComparisonOperator::EQ => "=",
         ComparisonOperator::NEQ => "<>",
@@ -207,14 +377,27 @@ fn build_tag_value_filter(filter_condition: &FilterCondition, tag_type: &TagType
         ComparisonOperator::GTE => ">=",
         ComparisonOperator::LTE => "<=",
         ComparisonOperator::LIKE => "LIKE",
+        ComparisonOperator::CONTAINS => {
+            return Err(GenerationError::TagIncompatibleType(format!(
+                "Tag : {}, The fulltext operator is only allowed on {}",
+                &filter_condition.attribute, TEXT_ATTRIBUTE
+            )));
+        }
+    };
+
+    let incompatible_value = || {
+        GenerationError::TagIncompatibleType(format!(
+            "Tag : {}, Invalid value {} for tag type {:?}",
+            &filter_condition.attribute, &filter_condition.value, tag_type
+        ))
     };
 
     let tag_value_filter = match tag_type {
         TagType::Text => {
-            //unaccent_lower((tv.value_string)::text) LIKE unaccent_lower('ab%')
-            let value = &filter_condition.value.to_string();
-            dbg!(&value);
-            format!("unaccent_lower((tv.value_string)::text) {0} unaccent_lower('{1}')", &sql_op, value)
+            //unaccent_lower((tv.value_string)::text) LIKE unaccent_lower(:p_0_value)
+            let value = filter_condition.value.to_string();
+            let placeholder = params.bind(CellValue::from_raw_string(value));
+            format!("unaccent_lower((tv.value_string)::text) {0} unaccent_lower({1})", &sql_op, placeholder)
         }
         TagType::Bool => {
             // science = true
@@ -226,31 +409,112 @@ fn build_tag_value_filter(filter_condition: &FilterCondition, tag_type: &TagType
             }
         }
         TagType::Int => {
-            format!("tv.value_integer {0} {1}", &sql_op, &filter_condition.value)
+            let value = match &filter_condition.value {
+                FilterValue::ValueInt(i) => *i as i64,
+                FilterValue::ValueString(text) => text.trim().parse::<i64>().map_err(|_| incompatible_value())?,
+                _ => return Err(incompatible_value()),
+            };
+            format!("tv.value_integer {0} {1}", &sql_op, params.bind(CellValue::from_raw_int(value)))
         }
         TagType::Double => {
-            format!("tv.value_double {0} {1}", &sql_op, &filter_condition.value)
-        }
-        TagType::Date => {
-            todo!();
+            // The lexer has no decimal numbers, so 10.5 must be written "10.5"
+            let value = match &filter_condition.value {
+                FilterValue::ValueInt(i) => *i as f64,
+                FilterValue::ValueString(text) => text.trim().parse::<f64>().map_err(|_| incompatible_value())?,
+                _ => return Err(incompatible_value()),
+            };
+            format!("tv.value_double {0} {1}", &sql_op, params.bind(CellValue::Double(Some(value))))
         }
-        TagType::DateTime => {
-            todo!();
+        TagType::Date | TagType::DateTime => {
+            // tv.value_date >= CAST(CAST(NOW() AT TIME ZONE 'UTC' AS DATE) - INTERVAL '30 days' AS DATE)
+            let Some(date) = date_value(&filter_condition.value) else {
+                return Err(incompatible_value());
+            };
+            format!(
+                "tv.{0} {1} {2}",
+                tag_type.value_column_name(),
+                &sql_op,
+                build_date_expression(&date, tag_type, params)
+            )
         }
         TagType::Link => {
-            todo!();
+            // parent = :p_0_value
+            let FilterValue::ValueInt(linked_item_id) = &filter_condition.value else {
+                return Err(GenerationError::TagIncompatibleType(format!(
+                    "Tag : {}, The value {} must be an item id",
+                    &filter_condition.attribute, &filter_condition.value
+                )));
+            };
+            format!("tv.value_link {0} {1}", &sql_op, params.bind(CellValue::from_raw_int(*linked_item_id as i64)))
         }
     };
 
     Ok(tag_value_filter)
 }
 
+/// Read the date of a condition, the value can also be a text like "2024-01-31"
+fn date_value(value: &FilterValue) -> Option<DateLiteral> {
+    match value {
+        FilterValue::ValueDate(date) => Some(date.clone()),
+        FilterValue::ValueString(text) => parse_date_literal(text.trim()),
+        _ => None,
+    }
+}
+
+/// Sql expression of the date, the relative dates are computed from the UTC time of the database
+fn build_date_expression(date: &DateLiteral, tag_type: &TagType, params: &mut SearchParams) -> String {
+    match date {
+        DateLiteral::Date(d) => params.bind(CellValue::Date(Some(*d))),
+        DateLiteral::DateTime(dt) => {
+            let st: SystemTime = Utc.from_utc_datetime(dt).into();
+            params.bind(CellValue::SystemTime(Some(st)))
+        }
+        DateLiteral::Relative { anchor, shift, unit } => {
+            let base = match anchor {
+                DateAnchor::Today => "CAST(NOW() AT TIME ZONE 'UTC' AS DATE)",
+                DateAnchor::Now => "(NOW() AT TIME ZONE 'UTC')",
+            };
+            if *shift == 0 {
+                return base.to_string();
+            }
+            let unit = match unit {
+                DateUnit::Day => "days",
+                DateUnit::Week => "weeks",
+                DateUnit::Hour => "hours",
+            };
+            let sign = if *shift < 0 { "-" } else { "+" };
+            let expression = format!("{} {} INTERVAL '{} {}'", base, sign, shift.abs(), unit);
+            // date +/- interval gives a timestamp
+            if *tag_type == TagType::Date {
+                format!("CAST({} AS DATE)", expression)
+            } else {
+                expression
+            }
+        }
+    }
+}
+
+/// Verify the value of a condition on a date tag, a date tag cannot be compared with a time
+fn verify_date_value(filter_condition: &FilterCondition, tag_type: &TagType) -> Result<(), GenerationError> {
+    match date_value(&filter_condition.value) {
+        Some(date) if *tag_type == TagType::DateTime || date.is_date_only() => Ok(()),
+        Some(date) => Err(GenerationError::TagIncompatibleType(format!(
+            "Tag : {}, The value {} has a time part but the tag type is {:?}",
+            &filter_condition.attribute, date, tag_type
+        ))),
+        None => Err(GenerationError::TagIncompatibleType(format!(
+            "Tag : {}, Invalid date value {} for tag type {:?}",
+            &filter_condition.attribute, &filter_condition.value, tag_type
+        ))),
+    }
+}
+
 /// Verify if all the tags are defined, compare tags and definitions by looping on tags and finding the definition
 fn verify_filter_conditions(
     filter_conditions: &HashMap<String, (u32, FilterCondition)>,
     definitions: &Vec<TagDefinition>,
 ) -> Result<(), GenerationError> {
-    for (_, (_, filter_condition)) in filter_conditions.iter() {
+    for (_, (_, filter_condition)) in filter_conditions.iter().filter(|(_, (_, fc))| !is_fulltext_condition(fc)) {
         if let Some(definition) = definitions.iter().find(|def| &def.tag_names == &filter_condition.attribute) {
             /** TODO we must also check the value format depending on the tag type here */
             // Check if the operator is valid for the tag type
@@ -261,6 +525,9 @@ fn verify_filter_conditions(
                         &filter_condition.attribute, &filter_condition.operator, &definition.tag_type
                     )));
                 }
+                if definition.tag_type == TagType::Date || definition.tag_type == TagType::DateTime {
+                    verify_date_value(filter_condition, &definition.tag_type)?;
+                }
             } else {
                 return Err(GenerationError::TagIncompatibleType(format!(
                     "Tag : {}, No valid operators defined for tag type {:?}",
@@ -277,48 +544,158 @@ fn verify_filter_conditions(
     Ok(())
 }
 
-fn build_order_column(order_tags: &Vec<String>, map_of_tags_with_occurrence: &HashMap<String, Vec<String>>) -> Vec<String> {
-    order_tags
-        .iter()
-        .filter_map(|tag| map_of_tags_with_occurrence.get(tag))
-        .map(|occurrences| {
-            let mut coalesce_expr = occurrences.iter().map(|o| format!("{}.value", o)).collect::<Vec<_>>();
-            if coalesce_expr.len() == 1 {
-                coalesce_expr.pop().unwrap()
-            } else {
-                let mut expr = coalesce_expr.pop().unwrap();
-                while let Some(next) = coalesce_expr.pop() {
-                    expr = format!("COALESCE({}, {})", next, expr);
+/// Verify if the fulltext conditions are like #text ~ "some words"
+fn verify_fulltext_conditions(
+    filter_conditions: &HashMap<String, (u32, FilterCondition)>,
+) -> Result<(), GenerationError> {
+    for (_, (_, filter_condition)) in filter_conditions.iter().filter(|(_, (_, fc))| is_fulltext_condition(fc)) {
+        if filter_condition.operator != ComparisonOperator::CONTAINS {
+            return Err(GenerationError::TagIncompatibleType(format!(
+                "Invalid operator {:?} for {}, only ~ is allowed",
+                &filter_condition.operator, TEXT_ATTRIBUTE
+            )));
+        }
+        match &filter_condition.value {
+            FilterValue::ValueString(text) if !text.trim().is_empty() => {}
+            _ => {
+                return Err(GenerationError::TagIncompatibleType(format!(
+                    "The value for {} must be a non empty text",
+                    TEXT_ATTRIBUTE
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Columns of the sort, the built-in columns of the item are read from the item table,
+/// the tags get their own joins, independent of the filter conditions
+fn build_sort_columns(
+    sort_keys: &[SortKey],
+    definitions: &[TagDefinition],
+    list_of_query_tags: &mut Vec<String>,
+    params: &mut SearchParams,
+) -> Vec<SortColumn> {
+    let mut sort_columns = vec![];
+    for (index, key) in sort_keys.iter().enumerate() {
+        let sort_column = match key.builtin_column() {
+            Some(column) => {
+                let tag_type = if column == "name" { TagType::Text } else { TagType::DateTime };
+                SortColumn {
+                    key: key.clone(),
+                    tag_type,
+                    expression: format!("i.{}", column),
+                    column_name: column.to_string(),
+                }
+            }
+            None => {
+                // The tag definitions have been verified
+                let tag_type =
+                    definitions.iter().find(|def| def.tag_names == key.name).map(|def| &def.tag_type).unwrap();
+                let alias = format!("{}_{}", SORT_TABLE_PREFIX, index);
+                list_of_query_tags.push(build_query_select(&key.name, tag_type, &alias, params));
+                SortColumn {
+                    key: key.clone(),
+                    tag_type: tag_type.clone(),
+                    expression: format!("{}.value", &alias),
+                    column_name: format!("{}_value", &alias),
                 }
-                expr
             }
+        };
+        sort_columns.push(sort_column);
+    }
+    sort_columns
+}
+
+/// The nulls sort as the greatest value: NULLS LAST for ASC, NULLS FIRST for DESC, ex : i.created_gmt DESC NULLS FIRST
+fn build_order_column(sort_columns: &[SortColumn]) -> Vec<String> {
+    sort_columns
+        .iter()
+        .map(|column| match column.key.direction {
+            SortDirection::Asc => format!("{} ASC NULLS LAST", &column.expression),
+            SortDirection::Desc => format!("{} DESC NULLS FIRST", &column.expression),
         })
+        .chain(std::iter::once("i.id ASC".to_string()))
         .collect()
 }
 
-/// Not used for now
-///
-/// Allow to add some tag column in the search query
-fn build_tag_column_with_alias(
-    select_tags: &[&str],
-    map_of_tags_with_occurrence: &HashMap<String, Vec<String>>,
-) -> Vec<String> {
-    select_tags
+/// Condition for the items after the cursor in the order of the sort (keyset pagination), ex for (-created, id) :
+/// ( (i.created_gmt < :p_5_value) OR (i.created_gmt = :p_6_value AND i.id > :p_7_value) )
+/// A null value is greater than all the other values, like in the ORDER BY
+fn build_cursor_filter(
+    sort_columns: &[SortColumn],
+    sort: &str,
+    cursor: &SearchCursor,
+    params: &mut SearchParams,
+) -> Result<String, GenerationError> {
+    if cursor.sort != sort || cursor.values.len() != sort_columns.len() {
+        return Err(GenerationError::CursorInvalid(format!("The cursor is not for the sort [{}]", sort)));
+    }
+
+    let mut values = vec![];
+    for (column, value) in sort_columns.iter().zip(cursor.values.iter()) {
+        let cell = sort_value_to_cell(value, &column.tag_type)
+            .map_err(|e| GenerationError::CursorInvalid(e.to_string()))?
+            .map(|cell| params.bind(cell));
+        values.push(cell);
+    }
+    let item_id = params.bind(CellValue::from_raw_int(cursor.item_id));
+
+    let mut terms = vec![];
+    for (index, column) in sort_columns.iter().enumerate() {
+        let after = match (column.key.direction, &values[index]) {
+            (SortDirection::Asc, Some(value)) => format!("({0} > {1} OR {0} IS NULL)", &column.expression, value),
+            // Nothing is greater than null
+            (SortDirection::Asc, None) => continue,
+            (SortDirection::Desc, Some(value)) => format!("{} < {}", &column.expression, value),
+            (SortDirection::Desc, None) => format!("{} IS NOT NULL", &column.expression),
+        };
+        let mut conditions = build_cursor_equalities(&sort_columns[..index], &values[..index]);
+        conditions.push(after);
+        terms.push(format!("({})", conditions.join(" AND ")));
+    }
+    let mut conditions = build_cursor_equalities(sort_columns, &values);
+    conditions.push(format!("i.id > {}", item_id));
+    terms.push(format!("({})", conditions.join(" AND ")));
+
+    Ok(format!("( {} )", terms.join(" OR ")))
+}
+
+fn build_cursor_equalities(sort_columns: &[SortColumn], values: &[Option<String>]) -> Vec<String> {
+    sort_columns
         .iter()
-        .filter_map(|tag| map_of_tags_with_occurrence.get(&tag.to_string()).map(|occurrences| (tag, occurrences)))
-        .map(|(tag, occurrences)| {
-            if occurrences.len() == 1 {
-                format!("{}.value AS {}", occurrences[0], tag)
-            } else if occurrences.len() == 2 {
-                format!("COALESCE({}.value, {}.value) AS {}", occurrences[0], occurrences[1], tag)
-            } else {
-                // For 3 or more, nest COALESCE as requested
-                let mut expr = format!("COALESCE({}.value, {}.value)", occurrences[0], occurrences[1]);
-                for o in &occurrences[2..] {
-                    expr = format!("COALESCE({}, {}.value)", expr, o);
-                }
-                format!("{} AS {}", expr, tag)
-            }
+        .zip(values.iter())
+        .map(|(column, value)| match value {
+            Some(value) => format!("{} = {}", &column.expression, value),
+            None => format!("{} IS NULL", &column.expression),
+        })
+        .collect()
+}
+
+/// Tags to return with the items, without the blank and the duplicated names, in the order of the selection
+fn clean_select_tags(select_tags: &[&str]) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in select_tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Allow to add some tag column in the search query, the columns are named from the alias of the joined table
+/// since the tag names are not safe to use as sql identifiers, ex : st_0.value AS st_0_value
+fn build_tag_column_with_alias(selected_tags: &[SelectedTag]) -> Vec<String> {
+    selected_tags
+        .iter()
+        .map(|tag| {
+            format!(
+                "{0}.id AS {1}, {0}.tag_id AS {2}, {0}.value AS {3}",
+                &tag.alias,
+                tag.value_id_column(),
+                tag.tag_id_column(),
+                tag.value_column()
+            )
         })
         .collect()
 }
@@ -326,24 +703,35 @@ fn build_tag_column_with_alias(
 /// 🔑 Generate the SQL query from the filter AST
 ///    
 ///     REF_TAG : DOKA_SEARCH_SQL
-pub(crate) async fn generate_search_sql<T: TagDefinitionInterface>(
+pub(crate) async fn generate_search_sql<T: TagDefinitionInterface, F: FullTextQueryInterface>(
     filter_expression_ast: &FilterExpressionAST,
     tag_definition_builder: &T,
-    _select_tags: &[&str],
+    fulltext_query_builder: &F,
+    select_tags: &[&str],
     order_tags: &Vec<String>,
+    cursor: Option<&SearchCursor>,
     generation_mode: SearchSqlGenerationMode,
     customer_code: &str,
-) -> Result<String, GenerationError> {
+) -> Result<SearchSql, GenerationError> {
     // Get all the final nodes (leaves), for instance, == (lastname, "a%" )
     let filter_conditions = extract_all_conditions(&filter_expression_ast).map_err(tr_fwd!())?;
 
     dbg!(&filter_conditions);
 
-    // Extract all the tags name from each leaves
-    let mut tags: HashSet<_> =
-        filter_conditions.iter().map(|(_, (_, filter_condition))| filter_condition.attribute.clone()).collect();
+    // Extract all the tags name from each leaves, the fulltext conditions are not on tags
+    let mut tags: HashSet<_> = filter_conditions
+        .iter()
+        .filter(|(_, (_, filter_condition))| !is_fulltext_condition(filter_condition))
+        .map(|(_, (_, filter_condition))| filter_condition.attribute.clone())
+        .collect();
 
-    tags.extend(order_tags.iter().map(|s| s.to_string()));
+    // The built-in columns of the sort are not tags
+    let sort_keys = parse_sort_keys(order_tags);
+    let sort = sort_signature(&sort_keys);
+    tags.extend(sort_keys.iter().filter(|key| key.builtin_column().is_none()).map(|key| key.name.clone()));
+
+    let select_tags = clean_select_tags(select_tags);
+    tags.extend(select_tags.iter().cloned());
 
     dbg!(&tags);
 
@@ -375,26 +763,47 @@ pub(crate) async fn generate_search_sql<T: TagDefinitionInterface>(
         return Err(e);
     }
 
+    if let Err(e) = verify_fulltext_conditions(&filter_conditions) {
+        log_error!("Error while verifying fulltext conditions: {:?}", e);
+        return Err(e);
+    }
+
     let mut map_of_tags_with_occurrence: HashMap<String, Vec<String>> = HashMap::new();
 
+    // All the values of the conditions are sql params
+    let mut params = SearchParams::default();
+
     // Generate the {{tag_value_filter}} for all tags condition
     let mut list_of_query_tags: Vec<String> = vec![];
     for (_, (occurrence, fc)) in filter_conditions.iter() {
+        if is_fulltext_condition(fc) {
+            let text = fc.value.to_string();
+            let lexeme_queries =
+                fulltext_query_builder.get_lexeme_queries(&text, customer_code).await.map_err(|e| {
+                    log_error!("Error while building the fulltext queries: {:?}", e);
+                    GenerationError::FullTextSearchError("Error in fulltext search".to_string())
+                })?;
+
+            let query_fulltext = build_query_fulltext(&lexeme_queries, *occurrence, &mut params);
+            log_info!("query_fulltext: {}", &query_fulltext);
+            list_of_query_tags.push(query_fulltext);
+            continue;
+        }
+
         let tag_type = definitions.iter().find(|def| def.tag_names == fc.attribute).map(|def| &def.tag_type).unwrap();
 
-        let tag_value_filter = build_tag_value_filter(fc, tag_type).map_err(|e| {
+        let tag_value_filter = build_tag_value_filter(fc, tag_type, &mut params).map_err(|e| {
             log_error!("Error while building tag value filter: {:?}", e);
             e
         })?;
         log_debug!("tag_value_filter: {}", &tag_value_filter);
 
-        let query_tag =
-            build_query_tag(fc, tag_type, *occurrence, &tag_value_filter).map_err(tr_fwd!()).map_err(|e| {
-                log_error!("Error while building query filter: {:?}", e);
-                GenerationError::TagSearchError("Error in tag search".to_string())
-            })?;
+        let query_tag = build_query_tag(fc, tag_type, *occurrence, &tag_value_filter, &mut params).map_err(|e| {
+            log_error!("Error while building query filter: {:?}", e);
+            GenerationError::TagSearchError("Error in tag search".to_string())
+        })?;
 
-        let tag_occurrence = format!("ot_{}_{}", &fc.attribute, occurrence);
+        let tag_occurrence = table_alias(fc, *occurrence);
         dbg!(&tag_occurrence);
 
         // Add the tag_occurrence to a list associated with the tag name through a hash map
@@ -410,14 +819,41 @@ pub(crate) async fn generate_search_sql<T: TagDefinitionInterface>(
 
     dbg!(&query_filter);
 
-    // build the order columns
-    let order_columns = build_order_column(order_tags, &map_of_tags_with_occurrence).join(",\n    ");
+    // The count of the items only needs the joins and the params of the filter
+    let count_query = format!(
+        "SELECT COUNT(*) AS total_count\n FROM {{customer_schema}}.item i \n{}\n WHERE \n    {}\n",
+        list_of_query_tags.join("\n"),
+        query_filter.as_str()
+    );
+    let count_params = params.params.clone();
+
+    // Build the tag_columns, each selected tag has its own join, independent of the filter conditions
+    let mut selected_tags = vec![];
+    for (index, tag_name) in select_tags.into_iter().enumerate() {
+        let tag_type = definitions.iter().find(|def| def.tag_names == tag_name).map(|def| &def.tag_type).unwrap();
+        let alias = format!("{}_{}", SELECT_TABLE_PREFIX, index);
+        list_of_query_tags.push(build_query_select(&tag_name, tag_type, &alias, &mut params));
+        selected_tags.push(SelectedTag { tag_name, tag_type: tag_type.clone(), alias });
+    }
+    let mut tag_columns = build_tag_column_with_alias(&selected_tags);
+
+    // build the order columns, the values of the sorted tags are read for the cursor
+    let sort_columns = build_sort_columns(&sort_keys, &definitions, &mut list_of_query_tags, &mut params);
+    tag_columns.extend(
+        sort_columns
+            .iter()
+            .filter(|column| column.key.builtin_column().is_none())
+            .map(|column| format!("{} AS {}", &column.expression, &column.column_name)),
+    );
+    let tag_columns = tag_columns.join(",\n    ");
+    let order_columns = build_order_column(&sort_columns).join(",\n    ");
 
     dbg!(&order_columns);
 
-    // Build the tag_columns - There is no need to generate extra columns for the tags.
-    //let tag_columns = build_tag_column_with_alias(select_tags, &map_of_tags_with_occurrence).join(",\n    ");
-    //dbg!(&tag_columns);
+    let cursor_filter = match cursor {
+        Some(cursor) => Some(build_cursor_filter(&sort_columns, &sort, cursor, &mut params)?),
+        None => None,
+    };
 
     // TODO Super filter implementation
     if let SearchSqlGenerationMode::Persisted = generation_mode {
@@ -443,10 +879,12 @@ pub(crate) async fn generate_search_sql<T: TagDefinitionInterface>(
     last_modified_gmt"#,
     );
 
-    final_sql.push_str("\n    ");
-    // final_sql.push_str(&tag_columns);
+    if !tag_columns.is_empty() {
+        final_sql.push_str(",\n    ");
+        final_sql.push_str(&tag_columns);
+    }
 
-    // final_sql.push_str("\n");
+    final_sql.push_str("\n    ");
     final_sql.push_str(" FROM {customer_schema}.item i ");
 
     final_sql.push_str("\n");
@@ -458,6 +896,11 @@ pub(crate) async fn generate_search_sql<T: TagDefinitionInterface>(
     final_sql.push_str("\n    ");
     final_sql.push_str(query_filter.as_str());
 
+    if let Some(cursor_filter) = cursor_filter {
+        final_sql.push_str("\n    AND ");
+        final_sql.push_str(&cursor_filter);
+    }
+
     final_sql.push_str("\n");
     final_sql.push_str(" ORDER BY ");
 
@@ -465,8 +908,10 @@ pub(crate) async fn generate_search_sql<T: TagDefinitionInterface>(
     final_sql.push_str(order_columns.as_str());
     final_sql.push_str("\n");
 
-    let sql_query = final_sql.to_string().replace("{customer_schema}", format!("cs_{}", customer_code).as_str());
-    Ok(sql_query)
+    let customer_schema = format!("cs_{}", customer_code);
+    let sql_query = final_sql.replace("{customer_schema}", &customer_schema);
+    let count_query = count_query.replace("{customer_schema}", &customer_schema);
+    Ok(SearchSql { sql_query, params: params.params, selected_tags, sort_columns, sort, count_query, count_params })
 }
 
 /// tag_value_filter and tag_super_filter are side by side to avoid a blank line
@@ -476,7 +921,7 @@ const QUERY_FILTER_TEMPLATE: &str = r#"LEFT OUTER JOIN (
     FROM {customer_schema}.tag_definition td
     JOIN {customer_schema}.tag_value tv ON
         tv.tag_id = td.id
-        AND td."name" = '{{tag_name}}'
+        AND td."name" = {{tag_name_param}}
         {{tag_value_filter}}{{tag_super_filter}}
 ) ot_{{tag_name}}_{{occurence}} ON ot_{{tag_name}}_{{occurence}}.item_id = i.id"#;
 
@@ -485,9 +930,12 @@ fn build_query_tag(
     tag_type: &TagType,
     occurence: u32,
     tag_value_filter: &str,
+    params: &mut SearchParams,
 ) -> anyhow::Result<String> {
+    let tag_name_param = params.bind(CellValue::from_raw_string(filter_condition.attribute.clone()));
     let query_filter = QUERY_FILTER_TEMPLATE
         .replace("{{value_column_name}}", tag_type.value_column_name())
+        .replace("{{tag_name_param}}", &tag_name_param)
         .replace("{{tag_name}}", &filter_condition.attribute)
         .replace("{{tag_value_filter}}", &format!("AND {}", tag_value_filter))
         .replace("{{tag_super_filter}}", "") // Add super filter logic if needed
@@ -495,20 +943,69 @@ fn build_query_tag(
     Ok(query_filter)
 }
 
+/// All the values of the tag, the items without the tag get nulls
+const QUERY_SELECT_TEMPLATE: &str = r#"LEFT OUTER JOIN (
+    SELECT tv.id, tv.item_id, tv.tag_id, tv.{{value_column_name}} as value
+    FROM {customer_schema}.tag_definition td
+    JOIN {customer_schema}.tag_value tv ON
+        tv.tag_id = td.id
+        AND td."name" = {{tag_name_param}}
+) {{alias}} ON {{alias}}.item_id = i.id"#;
+
+fn build_query_select(tag_name: &str, tag_type: &TagType, alias: &str, params: &mut SearchParams) -> String {
+    let tag_name_param = params.bind(CellValue::from_raw_string(tag_name.to_string()));
+    QUERY_SELECT_TEMPLATE
+        .replace("{{value_column_name}}", tag_type.value_column_name())
+        .replace("{{tag_name_param}}", &tag_name_param)
+        .replace("{{alias}}", alias)
+}
+
+/// The files matching all the words in one of the languages, as for the fulltext search
+const QUERY_FULLTEXT_TEMPLATE: &str = r#"LEFT OUTER JOIN (
+    SELECT DISTINCT f.file_ref, TRUE as value
+    FROM ( {{matching_files}} ) f
+) ft_text_{{occurence}} ON ft_text_{{occurence}}.file_ref = i.file_ref"#;
+
+fn build_query_fulltext(lexeme_queries: &[(String, String)], occurence: u32, params: &mut SearchParams) -> String {
+    let matching_files = if lexeme_queries.is_empty() {
+        // Nothing can match, ex : no document or only stop words in the text
+        "SELECT d.file_ref FROM {customer_schema}.document d WHERE FALSE".to_string()
+    } else {
+        let values = lexeme_queries
+            .iter()
+            .map(|(lang, tsq)| {
+                let lang_param = params.bind(CellValue::from_raw_string(lang.clone()));
+                let tsq_param = params.bind(CellValue::from_raw_string(tsq.clone()));
+                format!("({}, {})", lang_param, tsq_param)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        matching_files_query("{customer_schema}", &values)
+    };
+
+    QUERY_FULLTEXT_TEMPLATE
+        .replace("{{matching_files}}", &matching_files)
+        .replace("{{occurence}}", &occurence.to_string())
+}
+
 #[cfg(test)]
 mod tests {
 
     // cargo test --color=always --bin document-server engine  [ -- --show-output]
 
     use crate::engine::generator::{
-        build_query_filter, extract_all_conditions, generate_search_sql, verify_filter_conditions, GenerationError,
-        SearchSqlGenerationMode, TagDefinition, TagDefinitionInterface,
+        build_query_filter, extract_all_conditions, generate_search_sql, verify_filter_conditions,
+        FullTextQueryInterface, GenerationError, SearchSql, SearchSqlGenerationMode, TagDefinition,
+        TagDefinitionInterface,
     };
+    use crate::engine::sort::SearchCursor;
     use crate::filter::analyse_expression;
     use crate::filter::filter_ast::{ComparisonOperator, FilterCondition, FilterExpressionAST, FilterValue};
     use crate::parser_log;
     use axum::async_trait;
+    use chrono::NaiveDate;
     use commons_error::*;
+    use commons_pg::sql_transaction::CellValue;
     use commons_services::x_request_id::XRequestID;
     use dkdto::web_types::TagType;
     use log::*;
@@ -552,15 +1049,29 @@ mod tests {
         }
     }
 
+    struct FullTextQueryBuilderMock {}
+
+    #[async_trait]
+    impl FullTextQueryInterface for FullTextQueryBuilderMock {
+        async fn get_lexeme_queries(&self, _text: &str, _customer_code: &str) -> anyhow::Result<Vec<(String, String)>> {
+            Ok(vec![
+                ("english".to_string(), "'TWmRmYyT6oIzVY7Jrg2qSw'".to_string()),
+                ("english".to_string(), "'hPDMnQ6C4ufSvDdqYIqKFA'".to_string()),
+                ("french".to_string(), "'l1nT3rc-bF3VxQ1Bfh2dZg'".to_string()),
+            ])
+        }
+    }
+
     /// Parse the SQL and return a list of base tables used in the query.
     ///
     /// Example:
     ///   SELECT ... FROM item i
     ///   JOIN tag_value tv ON ...
     /// returns ["item", "tag_value"]
-    pub fn validate_my_engine_query(sql: &str) -> Result<Vec<String>, String> {
+    pub fn validate_my_engine_query(search_sql: &SearchSql) -> Result<Vec<String>, String> {
         let dialect = PostgreSqlDialect {};
-        let r_statements = Parser::parse_sql(&dialect, sql).map_err(|e| e.to_string());
+        let sql = to_positional_sql(search_sql);
+        let r_statements = Parser::parse_sql(&dialect, &sql).map_err(|e| e.to_string());
 
         assert_eq!(false, r_statements.is_err());
 
@@ -580,6 +1091,26 @@ mod tests {
         Ok(list)
     }
 
+    /// Replace the :p_{n}_value params with $1, $2, ... like the SQLQueryBlockAsync does
+    fn to_positional_sql(search_sql: &SearchSql) -> String {
+        let mut sql = search_sql.sql_query.clone();
+        for (i, (name, _)) in search_sql.params.iter().enumerate() {
+            sql = sql.replace(&format!(":{}", name), &format!("${}", i + 1));
+        }
+        assert!(!sql.contains(":p_"), "Unbound param in {}", sql);
+        sql
+    }
+
+    /// Find the placeholder of the value in the query params
+    fn placeholder_of(search_sql: &SearchSql, value: &CellValue) -> String {
+        search_sql
+            .params
+            .iter()
+            .find(|(_, v)| format!("{:?}", v) == format!("{:?}", value))
+            .map(|(name, _)| format!(":{}", name))
+            .unwrap_or_else(|| panic!("No param for {:?} in {:?}", value, &search_sql.params))
+    }
+
     fn collect_tables_from_statement(stmt: &Statement, tables: &mut HashSet<String>) {
         match stmt {
             Statement::Query(q) => collect_tables_from_query(q, tables),
@@ -636,16 +1167,18 @@ mod tests {
         let query = generate_search_sql(
             &filter_expression_ast,
             &tag_definition_builder,
+            &FullTextQueryBuilderMock {},
             &vec!["country", "science", "is_open"],
             &vec!["country".to_string(), "science".to_string(), "is_open".to_string()],
+            None,
             SearchSqlGenerationMode::Live,
             "cs_123456",
         )
         .await;
-        let q = &query.unwrap();
+        let search_sql = &query.unwrap();
         // validate and assert table names
-        let _r = validate_my_engine_query(q);
-        log_info!("FINAL QUERY : {}", q);
+        let _r = validate_my_engine_query(search_sql);
+        log_info!("FINAL QUERY : {}", &search_sql.sql_query);
     }
 
     struct TagDefinitionBuilderMock2 {}
@@ -677,17 +1210,432 @@ mod tests {
         let query = generate_search_sql(
             &filter_expression_ast,
             &tag_definition_builder,
+            &FullTextQueryBuilderMock {},
             &vec![""],
             &vec!["lastname".to_string(), "postal_code".to_string()],
+            None,
             SearchSqlGenerationMode::Live,
             "cs_123456",
         )
         .await;
 
-        let q = &query.unwrap();
+        let search_sql = &query.unwrap();
 
         // validate and assert table names
-        let _r = validate_my_engine_query(q);
+        let _r = validate_my_engine_query(search_sql);
+    }
+
+    ///
+    /// Mix a fulltext condition with the tag conditions
+    ///
+    #[tokio::test]
+    pub async fn test_generate_search_sql_fulltext() {
+        init_logger();
+        let input = r#"#text ~ "invoice overdue" AND (lastname LIKE "%ab%" OR #text ~ "penalty")"#;
+        let filter_expression_ast = analyse_expression(input).unwrap();
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock2 {},
+            &FullTextQueryBuilderMock {},
+            &[],
+            &vec!["lastname".to_string()],
+            None,
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        let search_sql = &query.unwrap();
+        let q = &search_sql.sql_query;
+
+        let dialect = PostgreSqlDialect {};
+        let statements = Parser::parse_sql(&dialect, &to_positional_sql(search_sql)).unwrap();
+        let mut tables = HashSet::new();
+        for stmt in statements {
+            collect_tables_from_statement(&stmt, &mut tables);
+        }
+        let mut list: Vec<String> = tables.into_iter().collect();
+        list.sort();
+        assert_eq!(
+            list,
+            vec!["document".to_string(), "item".to_string(), "tag_definition".to_string(), "tag_value".to_string()]
+        );
+
+        assert!(q.contains("ft_text_0.value is not null"));
+        assert!(q.contains("ft_text_1.value is not null"));
+        assert!(q.contains("ot_lastname_0.value is not null"));
+        // Each lexeme is a row of the search, the file must match all the lexemes of the language
+        for lexeme in ["'TWmRmYyT6oIzVY7Jrg2qSw'", "'hPDMnQ6C4ufSvDdqYIqKFA'"] {
+            let tsq = CellValue::from_raw_string(lexeme.to_string());
+            assert!(q.contains(&placeholder_of(search_sql, &tsq)));
+        }
+        assert!(q.contains("HAVING COUNT(*) = MAX(m.lexeme_count)"));
+    }
+
+    #[tokio::test]
+    pub async fn test_generate_search_sql_fulltext_wrong_operator() {
+        init_logger();
+        let input = r#"#text == "invoice" AND lastname LIKE "%ab%""#;
+        let filter_expression_ast = analyse_expression(input).unwrap();
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock2 {},
+            &FullTextQueryBuilderMock {},
+            &[],
+            &vec![],
+            None,
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        assert!(matches!(query, Err(GenerationError::TagIncompatibleType(_))));
+    }
+
+    struct TagDefinitionBuilderMock3 {}
+    #[async_trait]
+    impl TagDefinitionInterface for TagDefinitionBuilderMock3 {
+        async fn get_tag_definition(
+            &self,
+            _tag_name: &[String],
+            _customer_code: &str,
+        ) -> anyhow::Result<Vec<TagDefinition>> {
+            Ok(vec![
+                TagDefinition { tag_names: "lastname".to_string(), tag_type: TagType::Text },
+                TagDefinition { tag_names: "birthdate".to_string(), tag_type: TagType::Date },
+                TagDefinition { tag_names: "sent_at".to_string(), tag_type: TagType::DateTime },
+                TagDefinition { tag_names: "parent".to_string(), tag_type: TagType::Link },
+            ])
+        }
+    }
+
+    ///
+    /// Absolute and relative dates on date and datetime tags
+    ///
+    #[tokio::test]
+    pub async fn test_generate_search_sql_dates() {
+        init_logger();
+        let input = r#"(birthdate >= 1980-01-31 AND birthdate < today-2w) OR (sent_at > 2024-01-31T10:30:00+02:00 AND sent_at <= now-12h) OR birthdate == "2000-02-29""#;
+        let filter_expression_ast = analyse_expression(input).unwrap();
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock3 {},
+            &FullTextQueryBuilderMock {},
+            &[],
+            &vec!["birthdate".to_string()],
+            None,
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        let search_sql = &query.unwrap();
+        let q = &search_sql.sql_query;
+        let _r = validate_my_engine_query(search_sql);
+
+        let date_1 = CellValue::Date(NaiveDate::from_ymd_opt(1980, 1, 31));
+        let date_2 = CellValue::Date(NaiveDate::from_ymd_opt(2000, 2, 29));
+        let datetime = CellValue::SystemTime(Some(
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(1706689800), // 2024-01-31T08:30:00Z
+        ));
+        assert!(q.contains(&format!("tv.value_date >= {}", placeholder_of(search_sql, &date_1))));
+        assert!(q.contains("tv.value_date < CAST(CAST(NOW() AT TIME ZONE 'UTC' AS DATE) - INTERVAL '2 weeks' AS DATE)"));
+        assert!(q.contains(&format!("tv.value_date = {}", placeholder_of(search_sql, &date_2))));
+        assert!(q.contains(&format!("tv.value_datetime > {}", placeholder_of(search_sql, &datetime))));
+        assert!(q.contains("tv.value_datetime <= (NOW() AT TIME ZONE 'UTC') - INTERVAL '12 hours'"));
+    }
+
+    #[tokio::test]
+    pub async fn test_generate_search_sql_dates_incompatible() {
+        init_logger();
+        // A date tag cannot be compared with a time, a date cannot be LIKE
+        for input in [
+            r#"birthdate > 2024-01-31T10:30:00Z AND lastname LIKE "%ab%""#,
+            r#"birthdate > now-2h AND lastname LIKE "%ab%""#,
+            r#"sent_at > "yesterday" AND lastname LIKE "%ab%""#,
+            r#"sent_at > 18 AND lastname LIKE "%ab%""#,
+            r#"sent_at LIKE "2024%" AND lastname LIKE "%ab%""#,
+        ] {
+            let filter_expression_ast = analyse_expression(input).unwrap();
+            let query = generate_search_sql(
+                &filter_expression_ast,
+                &TagDefinitionBuilderMock3 {},
+                &FullTextQueryBuilderMock {},
+                &[],
+                &vec![],
+                None,
+                SearchSqlGenerationMode::Live,
+                "cs_123456",
+            )
+            .await;
+
+            assert!(matches!(query, Err(GenerationError::TagIncompatibleType(_))), "{}", input);
+        }
+    }
+
+    ///
+    /// Items linked to another item
+    ///
+    #[tokio::test]
+    pub async fn test_generate_search_sql_link() {
+        init_logger();
+        let input = r#"(parent == 1234) AND (lastname LIKE "%ab%" OR parent != 99)"#;
+        let filter_expression_ast = analyse_expression(input).unwrap();
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock3 {},
+            &FullTextQueryBuilderMock {},
+            &[],
+            &vec!["parent".to_string()],
+            None,
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        let search_sql = &query.unwrap();
+        let q = &search_sql.sql_query;
+        let _r = validate_my_engine_query(search_sql);
+
+        assert!(q.contains("tv.value_link as value"));
+        assert!(q.contains(&format!("tv.value_link = {}", placeholder_of(search_sql, &CellValue::Int(Some(1234))))));
+        assert!(q.contains(&format!("tv.value_link <> {}", placeholder_of(search_sql, &CellValue::Int(Some(99))))));
+
+        // Only the item id is allowed
+        for input in [r#"parent == "1234" AND lastname LIKE "%ab%""#, r#"parent > 1234 AND lastname LIKE "%ab%""#] {
+            let filter_expression_ast = analyse_expression(input).unwrap();
+            let query = generate_search_sql(
+                &filter_expression_ast,
+                &TagDefinitionBuilderMock3 {},
+                &FullTextQueryBuilderMock {},
+                &[],
+                &vec![],
+                None,
+                SearchSqlGenerationMode::Live,
+                "cs_123456",
+            )
+            .await;
+
+            assert!(matches!(query, Err(GenerationError::TagIncompatibleType(_))), "{}", input);
+        }
+    }
+
+    ///
+    /// The quotes and backslashes of the values are kept as they are, in the params, never in the sql
+    ///
+    #[tokio::test]
+    pub async fn test_generate_search_sql_bound_values() {
+        init_logger();
+        let input = r#"lastname == "O'Brien\' OR 1=1 --" AND (postal_code == 30099 OR lastname LIKE "%\_%")"#;
+        let filter_expression_ast = analyse_expression(input).unwrap();
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock2 {},
+            &FullTextQueryBuilderMock {},
+            &[],
+            &vec!["lastname".to_string()],
+            None,
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        let search_sql = &query.unwrap();
+        let q = &search_sql.sql_query;
+        let _r = validate_my_engine_query(search_sql);
+
+        assert!(!q.contains("Brien"));
+        assert!(!q.contains("30099"));
+        assert!(!q.contains("'lastname'"));
+
+        let value_1 = CellValue::from_raw_string(r#"O'Brien\' OR 1=1 --"#.to_string());
+        let value_2 = CellValue::from_raw_string(r#"%\_%"#.to_string());
+        assert!(q.contains(&format!(
+            "unaccent_lower((tv.value_string)::text) = unaccent_lower({})",
+            placeholder_of(search_sql, &value_1)
+        )));
+        assert!(q.contains(&format!(
+            "unaccent_lower((tv.value_string)::text) LIKE unaccent_lower({})",
+            placeholder_of(search_sql, &value_2)
+        )));
+        assert!(q.contains(&format!("tv.value_integer = {}", placeholder_of(search_sql, &CellValue::Int(Some(30099))))));
+        assert!(q.contains(&format!(
+            "td.\"name\" = {}",
+            placeholder_of(search_sql, &CellValue::from_raw_string("postal_code".to_string()))
+        )));
+
+        // 3 conditions with a tag name and a value each, and the tag name of the sort
+        assert_eq!(7, search_sql.params.len());
+        assert_eq!(7, search_sql.params_map().len());
+        assert_eq!(6, search_sql.count_params.len());
+    }
+
+    ///
+    /// The selected tags get their own joins and columns, whatever the filter conditions
+    ///
+    #[tokio::test]
+    pub async fn test_generate_search_sql_select_tags() {
+        let input = r#"(lastname LIKE "%ab%")"#;
+        let filter_expression_ast = analyse_expression(input).unwrap();
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock2 {},
+            &FullTextQueryBuilderMock {},
+            &["postal_code", " ", "lastname", "postal_code"],
+            &vec!["lastname".to_string()],
+            None,
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        let search_sql = &query.unwrap();
+        let q = &search_sql.sql_query;
+        let _r = validate_my_engine_query(search_sql);
+
+        // The blank and the duplicated tags are ignored
+        let selected: Vec<(&str, &TagType)> =
+            search_sql.selected_tags.iter().map(|t| (t.tag_name.as_str(), &t.tag_type)).collect();
+        assert_eq!(vec![("postal_code", &TagType::Int), ("lastname", &TagType::Text)], selected);
+
+        assert!(q.contains("st_0.id AS st_0_value_id, st_0.tag_id AS st_0_tag_id, st_0.value AS st_0_value"));
+        assert!(q.contains("st_1.value AS st_1_value"));
+        assert!(q.contains("tv.value_integer as value"));
+        assert!(q.contains(") st_1 ON st_1.item_id = i.id"));
+        assert!(q.contains(&format!(
+            "td.\"name\" = {}",
+            placeholder_of(search_sql, &CellValue::from_raw_string("postal_code".to_string()))
+        )));
+    }
+
+    #[tokio::test]
+    pub async fn test_generate_search_sql_select_unknown_tag() {
+        let filter_expression_ast = analyse_expression(r#"(lastname LIKE "%ab%")"#).unwrap();
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock2 {},
+            &FullTextQueryBuilderMock {},
+            &["birthdate"],
+            &vec!["lastname".to_string()],
+            None,
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        assert!(matches!(query, Err(GenerationError::TagUnknown(tag)) if tag == "birthdate"));
+    }
+
+    ///
+    /// Sort on a built-in column and a tag, the item id makes the order stable
+    ///
+    #[tokio::test]
+    pub async fn test_generate_search_sql_sort() {
+        let filter_expression_ast = analyse_expression(r#"(postal_code == 30099)"#).unwrap();
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock2 {},
+            &FullTextQueryBuilderMock {},
+            &[],
+            &vec!["-created".to_string(), " lastname".to_string()],
+            None,
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        let search_sql = &query.unwrap();
+        let q = &search_sql.sql_query;
+        let _r = validate_my_engine_query(search_sql);
+
+        assert_eq!("-created,lastname", search_sql.sort);
+        assert!(q.contains("i.created_gmt DESC NULLS FIRST,\n    so_1.value ASC NULLS LAST,\n    i.id ASC"));
+        assert!(q.contains("so_1.value AS so_1_value"));
+
+        // The count only has the filter
+        assert!(!search_sql.count_query.contains("ORDER BY"));
+        assert!(!search_sql.count_query.contains("so_1"));
+        assert_eq!(2, search_sql.count_params.len());
+        let count_sql = SearchSql {
+            sql_query: search_sql.count_query.clone(),
+            params: search_sql.count_params.clone(),
+            selected_tags: vec![],
+            sort_columns: vec![],
+            sort: String::new(),
+            count_query: String::new(),
+            count_params: vec![],
+        };
+        let _r = validate_my_engine_query(&count_sql);
+    }
+
+    #[tokio::test]
+    pub async fn test_generate_search_sql_no_sort() {
+        let filter_expression_ast = analyse_expression(r#"(postal_code == 30099)"#).unwrap();
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock2 {},
+            &FullTextQueryBuilderMock {},
+            &[],
+            &vec![],
+            None,
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        let search_sql = &query.unwrap();
+        let _r = validate_my_engine_query(search_sql);
+        assert!(search_sql.sql_query.contains(" ORDER BY \n    i.id ASC"));
+    }
+
+    ///
+    /// The items after the cursor, a null value is greater than all the others
+    ///
+    #[tokio::test]
+    pub async fn test_generate_search_sql_cursor() {
+        let filter_expression_ast = analyse_expression(r#"(postal_code == 30099)"#).unwrap();
+        let cursor = SearchCursor {
+            sort: "-created,lastname".to_string(),
+            values: vec![serde_json::Value::from("2024-01-31T10:30:00+00:00"), serde_json::Value::Null],
+            item_id: 42,
+        };
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock2 {},
+            &FullTextQueryBuilderMock {},
+            &[],
+            &vec!["-created".to_string(), "lastname".to_string()],
+            Some(&cursor),
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+
+        let search_sql = &query.unwrap();
+        let q = &search_sql.sql_query;
+        let _r = validate_my_engine_query(search_sql);
+
+        let item_id = placeholder_of(search_sql, &CellValue::from_raw_int(42));
+        assert!(q.contains("AND ( (i.created_gmt < :p_"));
+        assert!(q.contains(&format!("AND so_1.value IS NULL AND i.id > {})", item_id)));
+        // Nothing is after a null value in an ascending order
+        assert!(!q.contains("so_1.value >"));
+        // The cursor does not change the count
+        assert_eq!(2, search_sql.count_params.len());
+
+        let wrong_sort = SearchCursor { sort: "created,lastname".to_string(), ..cursor };
+        let query = generate_search_sql(
+            &filter_expression_ast,
+            &TagDefinitionBuilderMock2 {},
+            &FullTextQueryBuilderMock {},
+            &[],
+            &vec!["-created".to_string(), "lastname".to_string()],
+            Some(&wrong_sort),
+            SearchSqlGenerationMode::Live,
+            "cs_123456",
+        )
+        .await;
+        assert!(matches!(query, Err(GenerationError::CursorInvalid(_))));
     }
 
     #[test]
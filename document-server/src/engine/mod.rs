@@ -1 +1,2 @@
 pub(crate) mod generator;
+pub(crate) mod sort;
@@ -0,0 +1,174 @@
+use anyhow::anyhow;
+use base64::Engine;
+use commons_pg::sql_transaction::{
+    date_time_to_iso, iso_to_datetime, iso_to_naivedate, naivedate_to_iso, CellValue, SQLDataSet,
+};
+use dkdto::web_types::TagType;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Columns of the item the search can be sorted on, they take precedence over the tags of the same name
+/// ( <sort name>, <column of the item> )
+const BUILTIN_SORT_COLUMNS: [(&str, &str); 3] =
+    [("name", "name"), ("created", "created_gmt"), ("last_modified", "last_modified_gmt")];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// Entry of the order_tags, a tag or a built-in column with its direction, ex : -created, +lastname, lastname
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SortKey {
+    pub(crate) name: String,
+    pub(crate) direction: SortDirection,
+}
+
+impl SortKey {
+    /// Read the sort key, the direction is ascending when there is no sign.
+    /// The "+" of an url query is decoded as a space, so the blanks around the name are ignored.
+    pub(crate) fn parse(order_tag: &str) -> Option<Self> {
+        let order_tag = order_tag.trim();
+        let (direction, name) = match order_tag.strip_prefix('-') {
+            Some(name) => (SortDirection::Desc, name),
+            None => (SortDirection::Asc, order_tag.strip_prefix('+').unwrap_or(order_tag)),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        Some(Self { name: name.to_string(), direction })
+    }
+
+    /// Column of the item when the key is a built-in column, ex : created_gmt
+    pub(crate) fn builtin_column(&self) -> Option<&'static str> {
+        BUILTIN_SORT_COLUMNS.iter().find(|(name, _)| *name == self.name).map(|(_, column)| *column)
+    }
+}
+
+impl std::fmt::Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.direction {
+            SortDirection::Asc => write!(f, "{}", &self.name),
+            SortDirection::Desc => write!(f, "-{}", &self.name),
+        }
+    }
+}
+
+/// Read the order_tags, without the blank and the duplicated keys
+pub(crate) fn parse_sort_keys(order_tags: &[String]) -> Vec<SortKey> {
+    let mut keys: Vec<SortKey> = vec![];
+    for key in order_tags.iter().filter_map(|order_tag| SortKey::parse(order_tag)) {
+        if !keys.iter().any(|k| k.name == key.name) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+/// Text form of the sort, the cursor is only valid for the sort it was made for, ex : -created,lastname
+pub(crate) fn sort_signature(sort_keys: &[SortKey]) -> String {
+    sort_keys.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(",")
+}
+
+/// Position of the last item of a page in the sorted search result, the next page starts after it
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct SearchCursor {
+    /// Signature of the sort
+    pub(crate) sort: String,
+    /// Values of the sort columns for the item, in the order of the sort
+    pub(crate) values: Vec<Value>,
+    pub(crate) item_id: i64,
+}
+
+impl SearchCursor {
+    /// Opaque form of the cursor, sent to the client
+    pub(crate) fn encode(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub(crate) fn decode(cursor: &str) -> anyhow::Result<Self> {
+        let json = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(cursor.trim())?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// Read the sort value of type [tag_type] in the column [column_name] of the current row
+pub(crate) fn read_sort_value(sql_result: &SQLDataSet, tag_type: &TagType, column_name: &str) -> Value {
+    match tag_type {
+        TagType::Text => sql_result.get_string(column_name).map(Value::from),
+        TagType::Bool => sql_result.get_bool(column_name).map(Value::from),
+        TagType::Int | TagType::Link => sql_result.get_int(column_name).map(Value::from),
+        TagType::Double => sql_result.get_double(column_name).map(Value::from),
+        TagType::Date => sql_result.get_naivedate(column_name).as_ref().map(naivedate_to_iso).map(Value::from),
+        TagType::DateTime => {
+            sql_result.get_timestamp_as_datetime(column_name).as_ref().map(date_time_to_iso).map(Value::from)
+        }
+    }
+    .unwrap_or(Value::Null)
+}
+
+/// Sql param for the sort value of the cursor, None when the value is null
+pub(crate) fn sort_value_to_cell(value: &Value, tag_type: &TagType) -> anyhow::Result<Option<CellValue>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    let wrong_value = || anyhow!("Wrong sort value in the cursor, value=[{}], type=[{:?}]", value, tag_type);
+    let cell = match tag_type {
+        TagType::Text => CellValue::from_raw_string(value.as_str().ok_or_else(wrong_value)?.to_string()),
+        TagType::Bool => CellValue::from_raw_bool(value.as_bool().ok_or_else(wrong_value)?),
+        TagType::Int | TagType::Link => CellValue::from_raw_int(value.as_i64().ok_or_else(wrong_value)?),
+        TagType::Double => CellValue::Double(Some(value.as_f64().ok_or_else(wrong_value)?)),
+        TagType::Date => {
+            let date = iso_to_naivedate(value.as_str().ok_or_else(wrong_value)?).map_err(|_| wrong_value())?;
+            CellValue::Date(Some(date))
+        }
+        TagType::DateTime => {
+            let datetime = iso_to_datetime(value.as_str().ok_or_else(wrong_value)?).map_err(|_| wrong_value())?;
+            CellValue::SystemTime(Some(datetime.into()))
+        }
+    };
+    Ok(Some(cell))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::engine::sort::{parse_sort_keys, sort_signature, SearchCursor, SortDirection, SortKey};
+
+    #[test]
+    fn parse_sort_keys_test() {
+        let order_tags: Vec<String> =
+            ["-created", "+name", " lastname", "", "-", "name"].iter().map(|s| s.to_string()).collect();
+        let keys = parse_sort_keys(&order_tags);
+
+        assert_eq!(
+            vec![
+                SortKey { name: "created".to_string(), direction: SortDirection::Desc },
+                SortKey { name: "name".to_string(), direction: SortDirection::Asc },
+                SortKey { name: "lastname".to_string(), direction: SortDirection::Asc },
+            ],
+            keys
+        );
+        assert_eq!("-created,name,lastname", sort_signature(&keys));
+        assert_eq!(Some("created_gmt"), keys[0].builtin_column());
+        assert_eq!(None, keys[2].builtin_column());
+    }
+
+    #[test]
+    fn search_cursor_test() {
+        let cursor = SearchCursor {
+            sort: "-created,lastname".to_string(),
+            values: vec![Value::from("2024-01-31T10:30:00Z"), Value::Null],
+            item_id: 42,
+        };
+        let encoded = cursor.encode();
+        assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(cursor, SearchCursor::decode(&encoded).unwrap());
+
+        assert!(SearchCursor::decode("not a cursor").is_err());
+    }
+}
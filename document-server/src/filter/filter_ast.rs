@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 use std::fmt;
 
+use crate::filter::filter_date::DateLiteral;
 use crate::filter::filter_lexer::FilterErrorCode::{
     AttributeExpected, ClosingExpected, LogicalOperatorExpected, OpeningExpected, OperatorExpected, ValueExpected,
 };
@@ -29,6 +30,7 @@ pub(crate) enum ComparisonOperator {
     LT,
     LTE,
     LIKE,
+    CONTAINS,
 }
 
 #[derive(Debug, Clone)]
@@ -36,6 +38,7 @@ pub(crate) enum FilterValue {
     ValueInt(i32),
     ValueString(String),
     ValueBool(bool),
+    ValueDate(DateLiteral),
 }
 
 impl fmt::Display for FilterValue {
@@ -50,6 +53,9 @@ impl fmt::Display for FilterValue {
             FilterValue::ValueBool(b) => {
                 write!(f, "{}", if *b { "TRUE" } else { "FALSE" })
             }
+            FilterValue::ValueDate(d) => {
+                write!(f, "{}", d)
+            }
         }
     }
 }
@@ -252,6 +258,7 @@ fn parse_condition(tokens: &[Token], index: &RefCell<usize>) -> Result<Box<Filte
                         Token::ValueInt(op) => FilterValue::ValueInt(op.clone().token),
                         Token::ValueString(op) => FilterValue::ValueString(op.clone().token),
                         Token::ValueBool(op) => FilterValue::ValueBool(op.clone().token),
+                        Token::ValueDate(op) => FilterValue::ValueDate(op.clone().token),
                         _ => {
                             warn!("Must be a token value"); // TODO NORM
                             return Err(FilterError { char_position: *index.borrow(), error_code: ValueExpected });
@@ -0,0 +1,172 @@
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DATETIME_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"];
+
+static RELATIVE_DATE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(today|now)(?:([+-])([0-9]{1,6})([dwh]))?$").unwrap());
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum DateAnchor {
+    Today,
+    Now,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum DateUnit {
+    Day,
+    Week,
+    Hour,
+}
+
+/// Date value of a filter condition, ex : 2024-01-31, 2024-01-31T10:00:00Z, today-30d
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum DateLiteral {
+    Date(NaiveDate),
+    /// Always in UTC, like the values stored in the tag_value table
+    DateTime(NaiveDateTime),
+    Relative {
+        anchor: DateAnchor,
+        shift: i32,
+        unit: DateUnit,
+    },
+}
+
+impl DateLiteral {
+    /// Tell if the literal can be compared to a simple date, without any time part
+    pub(crate) fn is_date_only(&self) -> bool {
+        match self {
+            DateLiteral::Date(_) => true,
+            DateLiteral::DateTime(_) => false,
+            DateLiteral::Relative { anchor, unit, .. } => {
+                *anchor == DateAnchor::Today && (*unit == DateUnit::Day || *unit == DateUnit::Week)
+            }
+        }
+    }
+}
+
+impl fmt::Display for DateLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateLiteral::Date(d) => write!(f, "{}", d.format(DATE_FORMAT)),
+            DateLiteral::DateTime(dt) => write!(f, "{}Z", dt.format("%Y-%m-%dT%H:%M:%S")),
+            DateLiteral::Relative { anchor, shift, unit } => {
+                let anchor = match anchor {
+                    DateAnchor::Today => "today",
+                    DateAnchor::Now => "now",
+                };
+                if *shift == 0 {
+                    return write!(f, "{}", anchor);
+                }
+                let unit = match unit {
+                    DateUnit::Day => "d",
+                    DateUnit::Week => "w",
+                    DateUnit::Hour => "h",
+                };
+                write!(f, "{}{:+}{}", anchor, shift, unit)
+            }
+        }
+    }
+}
+
+/// Parse an ISO-8601 date, datetime or a relative date like today-30d, now+2h
+pub(crate) fn parse_date_literal(value: &str) -> Option<DateLiteral> {
+    if let Some(captures) = RELATIVE_DATE_REGEX.captures(&value.to_lowercase()) {
+        let anchor = if &captures[1] == "today" { DateAnchor::Today } else { DateAnchor::Now };
+        let (shift, unit) = match (captures.get(2), captures.get(3), captures.get(4)) {
+            (Some(sign), Some(amount), Some(unit)) => {
+                let amount: i32 = amount.as_str().parse().ok()?;
+                let shift = if sign.as_str() == "-" { -amount } else { amount };
+                let unit = match unit.as_str() {
+                    "w" => DateUnit::Week,
+                    "h" => DateUnit::Hour,
+                    _ => DateUnit::Day,
+                };
+                (shift, unit)
+            }
+            _ => (0, DateUnit::Day),
+        };
+        return Some(DateLiteral::Relative { anchor, shift, unit });
+    }
+
+    if let Ok(d) = NaiveDate::parse_from_str(value, DATE_FORMAT) {
+        return Some(DateLiteral::Date(d));
+    }
+
+    // With a time zone, the datetime is converted to UTC
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(DateLiteral::DateTime(dt.with_timezone(&Utc).naive_utc()));
+    }
+
+    DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(DateLiteral::DateTime)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use crate::filter::filter_date::{parse_date_literal, DateAnchor, DateLiteral, DateUnit};
+
+    #[test]
+    fn parse_absolute_dates() {
+        assert_eq!(
+            Some(DateLiteral::Date(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap())),
+            parse_date_literal("2024-01-31")
+        );
+
+        let expected =
+            NaiveDateTime::parse_from_str("2024-01-31 08:30:00", "%Y-%m-%d %H:%M:%S").map(DateLiteral::DateTime).ok();
+        assert_eq!(expected, parse_date_literal("2024-01-31T08:30:00Z"));
+        assert_eq!(expected, parse_date_literal("2024-01-31T10:30:00+02:00"));
+        assert_eq!(expected, parse_date_literal("2024-01-31T08:30:00"));
+        assert_eq!(expected, parse_date_literal("2024-01-31T08:30"));
+
+        assert_eq!(None, parse_date_literal("2024-02-30"));
+        assert_eq!(None, parse_date_literal("31/01/2024"));
+        assert_eq!(None, parse_date_literal("2024"));
+    }
+
+    #[test]
+    fn parse_relative_dates() {
+        assert_eq!(
+            Some(DateLiteral::Relative { anchor: DateAnchor::Today, shift: -30, unit: DateUnit::Day }),
+            parse_date_literal("today-30d")
+        );
+        assert_eq!(
+            Some(DateLiteral::Relative { anchor: DateAnchor::Now, shift: 2, unit: DateUnit::Hour }),
+            parse_date_literal("NOW+2h")
+        );
+        assert_eq!(
+            Some(DateLiteral::Relative { anchor: DateAnchor::Today, shift: 0, unit: DateUnit::Day }),
+            parse_date_literal("today")
+        );
+
+        assert_eq!(None, parse_date_literal("today-30"));
+        assert_eq!(None, parse_date_literal("today-30y"));
+        assert_eq!(None, parse_date_literal("yesterday"));
+    }
+
+    #[test]
+    fn date_only_literals() {
+        assert!(parse_date_literal("2024-01-31").unwrap().is_date_only());
+        assert!(parse_date_literal("today-2w").unwrap().is_date_only());
+        assert!(!parse_date_literal("today-2h").unwrap().is_date_only());
+        assert!(!parse_date_literal("now").unwrap().is_date_only());
+        assert!(!parse_date_literal("2024-01-31T08:30:00Z").unwrap().is_date_only());
+    }
+
+    #[test]
+    fn display_literals() {
+        assert_eq!("today-30d", parse_date_literal("today-30d").unwrap().to_string());
+        assert_eq!("now+2h", parse_date_literal("now+2h").unwrap().to_string());
+        assert_eq!("today", parse_date_literal("today").unwrap().to_string());
+        assert_eq!("2024-01-31T08:30:00Z", parse_date_literal("2024-01-31T10:30:00+02:00").unwrap().to_string());
+    }
+}
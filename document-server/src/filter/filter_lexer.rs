@@ -1,9 +1,10 @@
 use std::cell::RefCell;
 use std::fmt;
 
+use crate::filter::filter_date::{parse_date_literal, DateLiteral};
 use crate::filter::filter_lexer::Token::{LogicalClose, LogicalOpen};
 use crate::filter::ComparisonOperator;
-use crate::filter::ComparisonOperator::{EQ, GT, GTE, LIKE, LT, LTE, NEQ};
+use crate::filter::ComparisonOperator::{CONTAINS, EQ, GT, GTE, LIKE, LT, LTE, NEQ};
 use commons_error::*;
 use log::{debug, error, info};
 use regex::Regex;
@@ -34,6 +35,7 @@ pub(crate) enum Token {
     ValueInt(PositionalToken<i32>),
     ValueString(PositionalToken<String>),
     ValueBool(PositionalToken<bool>),
+    ValueDate(PositionalToken<DateLiteral>),
     BinaryLogicalOperator(PositionalToken<LogicalOperator>),
     ConditionOpen(PositionalToken<()>),  // [
     ConditionClose(PositionalToken<()>), // ]
@@ -70,6 +72,7 @@ impl Token {
             Token::ValueInt(p) => p.position,
             Token::ValueString(p) => p.position,
             Token::ValueBool(p) => p.position,
+            Token::ValueDate(p) => p.position,
             Token::BinaryLogicalOperator(p) => p.position,
             Token::ConditionOpen(p) => p.position,
             Token::ConditionClose(p) => p.position,
@@ -85,6 +88,7 @@ impl Token {
             Token::ValueInt(p) => p.position = (p.position as i32 + nb) as usize,
             Token::ValueString(p) => p.position = (p.position as i32 + nb) as usize,
             Token::ValueBool(p) => p.position = (p.position as i32 + nb) as usize,
+            Token::ValueDate(p) => p.position = (p.position as i32 + nb) as usize,
             Token::BinaryLogicalOperator(p) => p.position = (p.position as i32 + nb) as usize,
             Token::ConditionOpen(p) => p.position = (p.position as i32 + nb) as usize,
             Token::ConditionClose(p) => p.position = (p.position as i32 + nb) as usize,
@@ -121,11 +125,13 @@ impl fmt::Display for Token {
                     ComparisonOperator::LT => "<",
                     ComparisonOperator::LTE => "<=",
                     ComparisonOperator::LIKE => "LIKE",
+                    ComparisonOperator::CONTAINS => "~",
                 }
             ),
             Token::ValueInt(pt) => write!(f, "{}", pt.token),
             Token::ValueString(pt) => write!(f, "\"{}\"", pt.token),
             Token::ValueBool(pt) => write!(f, "{}", pt.token),
+            Token::ValueDate(pt) => write!(f, "{}", pt.token),
             Token::BinaryLogicalOperator(pt) => write!(
                 f,
                 "{}",
@@ -249,7 +255,12 @@ const FOP_LTE_2: &str = "=<";
 const FOP_GT: &str = ">";
 const FOP_LT: &str = "<";
 const FOP_LIKE: &str = "LIKE";
-const LIST_OF_FOP: &[&str] = &[FOP_EQ, FOP_NEQ, FOP_GTE_1, FOP_GTE_2, FOP_LTE_1, FOP_LTE_2, FOP_GT, FOP_LT, FOP_LIKE];
+const FOP_CONTAINS: &str = "~";
+const LIST_OF_FOP: &[&str] =
+    &[FOP_EQ, FOP_NEQ, FOP_GTE_1, FOP_GTE_2, FOP_LTE_1, FOP_LTE_2, FOP_GT, FOP_LT, FOP_LIKE, FOP_CONTAINS];
+
+/// Reserved attributes (not tags) start with this char, ex : #text
+const PSEUDO_ATTRIBUTE_PREFIX: char = '#';
 
 #[macro_export]
 macro_rules! parser_log {
@@ -313,12 +324,13 @@ pub(crate) fn lex3_with_offset(input: &str, offset: usize) -> Result<Vec<Token>,
 // EXP ::= '(' ( EXP | COND ) ( LOP EXP | COND )* ')'
 // LOP ::= 'AND' | 'OR'
 // COND ::= ATTR FOP VALUE
-// VALUE ::= VALTXT | VALNUM | VALBOOL
-// ATTR ::= ( lettre | chiffre )*
-// FOP ::= '>=' | '>' | '<' | '<=' | '==' | 'LIKE'
+// VALUE ::= VALTXT | VALNUM | VALBOOL | VALDATE
+// ATTR ::= ( '#' )? ( lettre | chiffre )*
+// FOP ::= '>=' | '>' | '<' | '<=' | '==' | 'LIKE' | '~'
 // VALTXT ::= '"' ( unicode_char )* '"'
 // VALNUM ::= ( chiffre )+ ( '.' ( chiffre )+ )?
 // VALBOOL ::= 'TRUE' | 'FALSE'
+// VALDATE ::= yyyy-mm-dd | yyyy-mm-ddThh:mm(:ss)?(Z|+hh:mm)? | ( 'today' | 'now' ) ( ( '+' | '-' ) chiffre+ ( 'd' | 'w' | 'h' ) )?
 // lettre ::= 'a'-'z' | 'A'-'Z'
 // chiffre ::= '0'-'9'
 
@@ -519,7 +531,7 @@ fn condition_lexer_index(
                 // Here we are at a "condition" level
                 match expected_lexeme {
                     ConditionExpectedLexeme::Attribute => {
-                        if is_valid_char_attribute(c) {
+                        if is_valid_char_attribute(c) || (c == PSEUDO_ATTRIBUTE_PREFIX && attribute.is_empty()) {
                             attribute.push(c);
                         } else {
                             // check if c is the first char of the filter operator, return true if it is
@@ -767,6 +779,7 @@ fn create_fop(fop: &str, index: usize, offset: usize) -> Result<Token, FilterErr
             FOP_LT => Ok(Token::Operator(PositionalToken::new(LT, char_pos + offset))),
             FOP_LTE_1 | FOP_LTE_2 => Ok(Token::Operator(PositionalToken::new(LTE, char_pos + offset))),
             FOP_LIKE => Ok(Token::Operator(PositionalToken::new(LIKE, char_pos + offset))),
+            FOP_CONTAINS => Ok(Token::Operator(PositionalToken::new(CONTAINS, char_pos + offset))),
             _ => Err(FilterError {
                 char_position: char_pos + offset,
                 error_code: FilterErrorCode::UnknownFilterOperator,
@@ -823,12 +836,15 @@ fn append_value(value: &mut String, tokens: &mut Vec<Token>, index: usize, offse
     } else {
         match value.parse() {
             Ok(parsed) => Token::ValueInt(PositionalToken::new(parsed, index + offset - value.len())),
-            Err(_) => {
-                return Err(FilterError {
-                    char_position: index + offset - value.len(),
-                    error_code: FilterErrorCode::WrongNumericValue,
-                });
-            }
+            Err(_) => match parse_date_literal(value) {
+                Some(date) => Token::ValueDate(PositionalToken::new(date, index + offset - value.len())),
+                None => {
+                    return Err(FilterError {
+                        char_position: index + offset - value.len(),
+                        error_code: FilterErrorCode::WrongNumericValue,
+                    });
+                }
+            },
         }
     };
     tokens.push(lexeme);
@@ -840,6 +856,7 @@ fn append_value(value: &mut String, tokens: &mut Vec<Token>, index: usize, offse
 mod tests {
     //cargo test --color=always --bin document-server expression_filter_parser::tests   -- --show-output
 
+    use crate::filter::filter_date::parse_date_literal;
     use crate::filter::filter_lexer::{
         lex3, FilterError, FilterErrorCode, LogicalOperator, PositionalToken, Token, TokenSlice,
     };
@@ -927,6 +944,85 @@ mod tests {
         assert_eq!(expected, tokens);
     }
 
+    /// The #text pseudo-attribute with the fulltext operator
+    #[test]
+    pub fn lexer_fulltext_condition() {
+        init_logger();
+        let pos = vec![1, 2, 8, 11, 27, 29, 33, 40, 42];
+        let input = r#"(#text ~ "invoice overdue") AND amount > 10"#;
+        let tokens = lex3(input).unwrap();
+
+        let expected: Vec<Token> = vec![
+            Token::LogicalOpen(PositionalToken::new((), pos[0])),
+            Token::Attribute(PositionalToken::new("#text".to_string(), pos[1])),
+            Token::Operator(PositionalToken::new(ComparisonOperator::CONTAINS, pos[2])),
+            Token::ValueString(PositionalToken::new("invoice overdue".to_string(), pos[3])),
+            Token::LogicalClose(PositionalToken::new((), pos[4])),
+            Token::BinaryLogicalOperator(PositionalToken::new(LogicalOperator::AND, pos[5])),
+            Token::Attribute(PositionalToken::new("amount".to_string(), pos[6])),
+            Token::Operator(PositionalToken::new(ComparisonOperator::GT, pos[7])),
+            Token::ValueInt(PositionalToken::new(10, pos[8])),
+        ];
+
+        assert_eq!(expected, tokens);
+    }
+
+    /// Absolute and relative date values
+    #[test]
+    pub fn lexer_date_condition() {
+        init_logger();
+        let pos = vec![1, 2, 10, 13, 23, 25, 29, 37, 39];
+        let input = r#"(sent_at >= 2024-01-31) AND sent_at < today-30d"#;
+        let tokens = lex3(input).unwrap();
+
+        let expected: Vec<Token> = vec![
+            Token::LogicalOpen(PositionalToken::new((), pos[0])),
+            Token::Attribute(PositionalToken::new("sent_at".to_string(), pos[1])),
+            Token::Operator(PositionalToken::new(ComparisonOperator::GTE, pos[2])),
+            Token::ValueDate(PositionalToken::new(parse_date_literal("2024-01-31").unwrap(), pos[3])),
+            Token::LogicalClose(PositionalToken::new((), pos[4])),
+            Token::BinaryLogicalOperator(PositionalToken::new(LogicalOperator::AND, pos[5])),
+            Token::Attribute(PositionalToken::new("sent_at".to_string(), pos[6])),
+            Token::Operator(PositionalToken::new(ComparisonOperator::LT, pos[7])),
+            Token::ValueDate(PositionalToken::new(parse_date_literal("today-30d").unwrap(), pos[8])),
+        ];
+
+        assert_eq!(expected, tokens);
+    }
+
+    #[test]
+    pub fn lexer_wrong_date_fail() {
+        init_logger();
+        let input = r#"sent_at >= 2024-13-01"#;
+        match lex3(input) {
+            Ok(tokens) => {
+                let dummy: Vec<Token> = vec![];
+                assert_eq!(dummy, tokens);
+            }
+            Err(e) => {
+                assert_eq!(FilterErrorCode::WrongNumericValue, e.error_code);
+                assert_eq!(12, e.char_position);
+            }
+        }
+    }
+
+    /// The # is only allowed as the first char of the attribute
+    #[test]
+    pub fn lexer_misplaced_pseudo_attribute_fail() {
+        init_logger();
+        let input = r#"amo#unt > 10"#;
+        match lex3(input) {
+            Ok(tokens) => {
+                let dummy: Vec<Token> = vec![];
+                assert_eq!(dummy, tokens);
+            }
+            Err(e) => {
+                assert_eq!(FilterErrorCode::IncorrectAttributeChar, e.error_code);
+                assert_eq!(4, e.char_position);
+            }
+        }
+    }
+
     #[test]
     pub fn lexer_simple_and_extra() {
         init_logger();
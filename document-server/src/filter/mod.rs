@@ -12,6 +12,7 @@ use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 pub(crate) mod filter_ast;
+pub(crate) mod filter_date;
 pub(crate) mod filter_lexer;
 pub(crate) mod filter_normalizer;
 
@@ -42,6 +43,7 @@ pub(crate) fn to_sql_form(filter_expression: &FilterExpressionAST) -> Result<Str
                 ComparisonOperator::GTE => ">=",
                 ComparisonOperator::LTE => "<=",
                 ComparisonOperator::LIKE => "LIKE",
+                ComparisonOperator::CONTAINS => "~",
             };
 
             let s = format!("({} {} {})", attribute, sql_op, value);
@@ -410,6 +410,26 @@ pub fn encrypt_tsvector(tsvector: &str, customer_key: &str) -> anyhow::Result<St
     Ok(complete_phrase)
 }
 
+///
+/// Hash each lexeme of the tsvector of the search words, exactly as in encrypt_tsvector.
+/// Each hashed lexeme is a tsquery of its own, empty if the vector has no lexeme (ex: stop words only)
+///
+pub fn hash_lexemes(tsvector: &str, customer_key: &str) -> Vec<String> {
+    let (_, words_to_hash) = parse_vector(tsvector);
+
+    let mut orders: Vec<&u64> = words_to_hash.keys().collect();
+    orders.sort();
+
+    let mut hashed_words: Vec<String> = vec![];
+    for order in orders {
+        let hashed_word = format!("'{}'", DkEncrypt::hmac_word(&words_to_hash[order], customer_key));
+        if !hashed_words.contains(&hashed_word) {
+            hashed_words.push(hashed_word);
+        }
+    }
+    hashed_words
+}
+
 #[cfg(test)]
 mod file_server_test {
     use std::collections::HashMap;
@@ -417,7 +437,7 @@ mod file_server_test {
     use chrono::Utc;
 
     use crate::char_lib::has_not_printable_char;
-    use crate::ft_tokenizer::{encrypt_tsvector, FTTokenizer};
+    use crate::ft_tokenizer::{encrypt_tsvector, hash_lexemes, FTTokenizer};
 
     const KEY: &str = "fqYVyce-Nh0HwpPQ7ZGZLog5s7PBLnwFMAW2OMnNPUs";
 
@@ -737,6 +757,20 @@ mod file_server_test {
         assert_eq!(ANSWER, &phrase);
     }
 
+    #[test]
+    pub fn lexemes_hash() {
+        let tsv = "'ateli':2 'admiss':1";
+        let lexemes = hash_lexemes(tsv, KEY);
+        let encrypted_tsv = encrypt_tsvector(tsv, KEY).unwrap();
+
+        assert_eq!(2, lexemes.len());
+        for lexeme in &lexemes {
+            assert!(encrypted_tsv.contains(lexeme.as_str()));
+        }
+
+        assert!(hash_lexemes("", KEY).is_empty());
+    }
+
     #[test]
     pub fn simple_grapheme() {
         let my_str_1 = "denis 😎 papin\n";
@@ -6,7 +6,7 @@ use log::*;
 use serde::de::DeserializeOwned;
 
 use commons_error::*;
-use commons_pg::sql_transaction::CellValue;
+use commons_pg::sql_transaction::{date_time_to_iso, CellValue};
 use commons_pg::sql_transaction_async::{SQLChangeAsync, SQLConnectionAsync, SQLQueryBlockAsync, SQLTransactionAsync};
 use commons_services::key_lib::fetch_customer_key;
 use commons_services::session_lib::valid_sid_get_session;
@@ -18,14 +18,42 @@ use common_config::property_name::{TIKA_SERVER_HOSTNAME_PROPERTY, TIKA_SERVER_PO
 use dkcrypto::dk_crypto::CypherMode::CC20;
 use dkcrypto::dk_crypto::DkEncrypt;
 use dkdto::api_error::ApiError;
-use dkdto::error_codes::{INTERNAL_DATABASE_ERROR, INTERNAL_TECHNICAL_ERROR};
-use dkdto::web_types::{DeleteFullTextRequest, FullTextReply, FullTextRequest, SimpleMessage, WebType, WebTypeBuilder};
+use dkdto::error_codes::{INTERNAL_DATABASE_ERROR, INTERNAL_TECHNICAL_ERROR, INVALID_REQUEST};
+use dkdto::web_types::{
+    DeleteFullTextRequest, FullTextItemElement, FullTextReply, FullTextRequest, FullTextSearchReply, SimpleMessage,
+    WebType, WebTypeBuilder,
+};
 use doka_cli::async_request_client::TikaServerClientAsync;
 use doka_cli::request_client::TokenType;
 
-use crate::ft_tokenizer::{encrypt_tsvector, FTTokenizer};
+use crate::ft_tokenizer::{encrypt_tsvector, hash_lexemes, FTTokenizer};
 use crate::language::{lang_name_from_code_2, map_code};
 
+///
+/// Query of the files matching all the lexemes of a language, with their rank ( file_ref, rank ).
+/// A document row holds the tsvector of one part of the file only, so each lexeme is matched on its own
+/// and the file is kept when the lexemes are all found in its parts.
+/// [values] are the sql rows ( <lang>, <hashed lexeme> ) of the search
+///
+pub(crate) fn matching_files_query(customer_schema: &str, values: &str) -> String {
+    format!(
+        r"SELECT m.file_ref, AVG(m.rank) AS rank
+            FROM (
+                SELECT d.file_ref, q.lang, q.lexeme_count, MAX(ts_rank(d.tsv, CAST(q.tsq AS TSQUERY))) AS rank
+                FROM {0}.document d
+                INNER JOIN (
+                    SELECT v.lang, v.tsq, COUNT(*) OVER (PARTITION BY v.lang) AS lexeme_count
+                    FROM ( VALUES {1} ) AS v(lang, tsq)
+                ) q ON q.lang = d.lang
+                WHERE d.tsv @@ CAST(q.tsq AS TSQUERY)
+                GROUP BY d.file_ref, q.lang, q.tsq, q.lexeme_count
+            ) m
+            GROUP BY m.file_ref, m.lang
+            HAVING COUNT(*) = MAX(m.lexeme_count)",
+        customer_schema, values
+    )
+}
+
 pub(crate) struct FullTextDelegate {
     pub session_token: SessionToken,
     pub follower: Follower,
@@ -147,6 +175,195 @@ impl FullTextDelegate {
         WebType::from_item(StatusCode::OK.as_u16(), FullTextReply { part_count })
     }
 
+    /// 🌟 Find the items whose document contains all the words of the text, best ranked first
+    /// The words are hashed with the customer key, the same way as the indexed tsvectors
+    pub async fn fulltext_search(
+        mut self,
+        text: &str,
+        start_page: Option<u32>,
+        page_size: Option<u32>,
+    ) -> WebType<FullTextSearchReply> {
+        log_info!(
+            "🚀 Start fulltext_search api, start_page=[{:?}], page_size=[{:?}], follower=[{}]",
+            start_page,
+            page_size,
+            &self.follower
+        );
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+
+        if text.trim().is_empty() {
+            log_error!("💣 The search text is empty, follower=[{}]", &self.follower);
+            return WebType::from_api_error(&INVALID_REQUEST);
+        }
+
+        let customer_code = entry_session.customer_code.as_str();
+
+        // Get the crypto key
+        let Ok(customer_key) = fetch_customer_key(customer_code, &self.follower)
+            .await
+            .map_err(err_fwd!("💣 Cannot get the customer key, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_TECHNICAL_ERROR);
+        };
+
+        // Open Db connection
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        // The lexemes depend on the language, so we hash the lexemes of the text for each language of the documents
+        let Ok(lexeme_queries) = self
+            .build_lexeme_queries(&mut trans, text, customer_code, &customer_key)
+            .await
+            .map_err(err_fwd!("💣 Cannot build the search queries, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let items = if lexeme_queries.is_empty() {
+            vec![]
+        } else {
+            let Ok(items) = self
+                .search_documents(&mut trans, &lexeme_queries, start_page, page_size, customer_code)
+                .await
+                .map_err(err_fwd!("💣 Cannot search the documents, follower=[{}]", &self.follower))
+            else {
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            };
+            items
+        };
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        log_info!("😎 We found the items, item count=[{}], follower=[{}]", items.len(), &self.follower);
+        log_info!("🏁 End fulltext_search api, follower=[{}]", &self.follower);
+
+        WebType::from_item(StatusCode::OK.as_u16(), FullTextSearchReply { items })
+    }
+
+    /// Return the list of ( <lang>, <hashed lexeme> ) of the text for all the languages found in the documents
+    pub(crate) async fn build_lexeme_queries(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        text: &str,
+        customer_code: &str,
+        customer_key: &str,
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        let langs = self.search_document_langs(trans, customer_code).await.map_err(tr_fwd!())?;
+
+        let mut lexeme_queries = vec![];
+        for lang in langs {
+            let tsv = self.select_tsvector(trans, Some(&lang), text).await.map_err(err_fwd!(
+                "Cannot build the tsvector, lang=[{}], follower=[{}]",
+                &lang,
+                &self.follower
+            ))?;
+
+            // Stop words only give no lexeme, nothing to search in this language
+            for lexeme in hash_lexemes(&tsv, customer_key) {
+                lexeme_queries.push((lang.clone(), lexeme));
+            }
+        }
+
+        Ok(lexeme_queries)
+    }
+
+    /// Languages of the documents of the customer
+    async fn search_document_langs(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        customer_code: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        let sql_query = format!(r"SELECT DISTINCT lang FROM cs_{}.document", customer_code);
+        let sql_block = SQLQueryBlockAsync { sql_query, start: 0, length: None, params: HashMap::new() };
+
+        let mut data = sql_block.execute(trans).await.map_err(err_fwd!("Cannot read the document languages"))?;
+
+        let mut langs = vec![];
+        while data.next() {
+            let lang = data.get_string("lang").ok_or(anyhow::anyhow!("Wrong lang col"))?;
+            langs.push(lang);
+        }
+        Ok(langs)
+    }
+
+    /// Search the items linked to the files matching all the lexemes of one of the languages
+    async fn search_documents(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        lexeme_queries: &[(String, String)],
+        start_page: Option<u32>,
+        page_size: Option<u32>,
+        customer_code: &str,
+    ) -> anyhow::Result<Vec<FullTextItemElement>> {
+        let mut params = HashMap::new();
+        let mut values = vec![];
+        for (i, (lang, tsq)) in lexeme_queries.iter().enumerate() {
+            // The index is the prefix, so :p_1_lang cannot be mistaken for :p_10_lang
+            values.push(format!("(:p_{0}_lang, :p_{0}_tsq)", i));
+            params.insert(format!("p_{}_lang", i), CellValue::from_raw_string(lang.clone()));
+            params.insert(format!("p_{}_tsq", i), CellValue::from_raw_string(tsq.clone()));
+        }
+
+        let sql_query = format!(
+            r"SELECT i.id, i.name, i.file_ref, i.created_gmt, i.last_modified_gmt,
+                    CAST(MAX(f.rank) AS FLOAT8) as rank
+                FROM ( {1} ) f
+                INNER JOIN cs_{0}.item i ON i.file_ref = f.file_ref
+                GROUP BY i.id, i.name, i.file_ref, i.created_gmt, i.last_modified_gmt
+                ORDER BY rank DESC, i.id ",
+            customer_code,
+            matching_files_query(&format!("cs_{}", customer_code), &values.join(", "))
+        );
+
+        let query = SQLQueryBlockAsync {
+            sql_query,
+            start: start_page.unwrap_or(0) * page_size.unwrap_or(0),
+            length: page_size,
+            params,
+        };
+
+        let mut sql_result =
+            query.execute(trans).await.map_err(err_fwd!("Query failed, [{}]", &query.sql_query))?;
+
+        let mut items = vec![];
+        while sql_result.next() {
+            let item_id = sql_result.get_int("id").ok_or(anyhow::anyhow!("Wrong id"))?;
+            let name = sql_result.get_string("name").unwrap_or("".to_owned());
+            let file_ref = sql_result.get_string("file_ref").ok_or(anyhow::anyhow!("Wrong file_ref"))?;
+            let created_gmt =
+                sql_result.get_timestamp_as_datetime("created_gmt").ok_or(anyhow::anyhow!("Wrong created gmt"))?;
+            let last_modified =
+                sql_result.get_timestamp_as_datetime("last_modified_gmt").as_ref().map(date_time_to_iso);
+            let rank = sql_result.get_double("rank").unwrap_or(0.0);
+
+            items.push(FullTextItemElement {
+                item_id,
+                name,
+                file_ref,
+                created: date_time_to_iso(&created_gmt),
+                last_modified,
+                rank,
+            });
+        }
+
+        Ok(items)
+    }
+
     async fn indexing(
         &self,
         mut trans: &mut SQLTransactionAsync<'_>,
@@ -7,9 +7,11 @@ use anyhow::anyhow;
 use axum::http::StatusCode;
 use axum::Json;
 use chrono::{DateTime, Utc};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use serde::de::DeserializeOwned;
 
+use common_config::properties::get_prop_value;
+use common_config::property_name::{FILE_SERVER_HOSTNAME_PROPERTY, FILE_SERVER_PORT_PROPERTY};
 use commons_error::*;
 use commons_pg::sql_transaction::{
     date_time_to_iso, iso_to_datetime, iso_to_naivedate, naivedate_to_iso, CellValue, SQLDataSet,
@@ -21,21 +23,50 @@ use commons_services::try_or_return;
 use commons_services::x_request_id::{Follower, XRequestID};
 use dkdto::api_error::ApiError;
 use dkdto::error_codes::{
-    BAD_TAG_FOR_ITEM, INCORRECT_TAG_TYPE, INTERNAL_DATABASE_ERROR, MISSING_ITEM, MISSING_TAG_FOR_ITEM,
+    BAD_TAG_FOR_ITEM, INCORRECT_LINK_TARGET, INCORRECT_TAG_TYPE, INTERNAL_DATABASE_ERROR, INVALID_REQUEST,
+    MISSING_ITEM, MISSING_REQUIRED_TAG, MISSING_TAG_FOR_ITEM,
 };
 use dkdto::web_types::{
-    AddItemReply, AddItemRequest, AddItemTagReply, AddItemTagRequest, AddTagRequest, AddTagValue, ContextMessage,
-    EnumTagValue, GetItemReply, IntoWebTypeWithContext, ItemElement, SimpleMessage, TagType, TagValueElement, WebType,
-    WebTypeBuilder, WebTypeWithContext,
+    AddItemReply, AddItemRequest, AddItemTagReply, AddItemTagRequest, AddTagRequest, AddTagValue, BulkItemTagReply,
+    BulkItemTagRequest, ContextMessage, DeleteItemReply, EnumTagValue, GetItemReply, IntoWebTypeWithContext,
+    ItemElement, PatchItemRequest, SimpleMessage, TagConstraints, TagType, TagValueElement, WebType, WebTypeBuilder,
+    WebTypeWithContext,
 };
+use doka_cli::async_request_client::FileServerClientAsync;
 use doka_cli::request_client::TokenType;
 
-use crate::engine::generator::{generate_search_sql, GenerationError, SearchSqlGenerationMode, TagDefinitionBuilder};
+use crate::engine::generator::{
+    generate_search_sql, FullTextQueryBuilder, GenerationError, SearchSql, SearchSqlGenerationMode,
+    TagDefinitionBuilder,
+};
+use crate::engine::sort::{read_sort_value, SearchCursor};
 use crate::filter::filter_ast::FilterExpressionAST;
 use crate::filter::filter_lexer::FilterError;
 use crate::filter::{analyse_expression, to_sql_form};
+use crate::item_version::ItemVersionDelegate;
+use crate::tag::{check_tag_value, compile_tag_pattern};
 use crate::TagDelegate;
 
+/// Read the value of a tag of type [tag_type] from the column [column_name] of the current row
+pub(crate) fn read_enum_tag_value(sql_result: &SQLDataSet, tag_type: &TagType, column_name: &str) -> EnumTagValue {
+    match tag_type {
+        TagType::Text => EnumTagValue::Text(sql_result.get_string(column_name)),
+        TagType::Link => EnumTagValue::Link(sql_result.get_int(column_name)),
+        TagType::Bool => EnumTagValue::Boolean(sql_result.get_bool(column_name)),
+        TagType::Int => EnumTagValue::Integer(sql_result.get_int(column_name)),
+        TagType::Double => EnumTagValue::Double(sql_result.get_double(column_name)),
+        TagType::Date => {
+            // Simply get the naive date and change it to iso string, no need of "Date"
+            let value_naivedate = sql_result.get_naivedate(column_name);
+            EnumTagValue::SimpleDate(value_naivedate.as_ref().map(naivedate_to_iso))
+        }
+        TagType::DateTime => {
+            let value_datetime = sql_result.get_timestamp_as_datetime(column_name);
+            EnumTagValue::DateTime(value_datetime.as_ref().map(date_time_to_iso))
+        }
+    }
+}
+
 pub(crate) struct ItemDelegate {
     pub session_token: SessionToken,
     pub follower: Follower,
@@ -50,19 +81,25 @@ impl ItemDelegate {
     }
 
     ///
-    /// 🌟 Find all the items at page [start_page]
+    /// 🌟 Find all the items at page [start_page], or the page after the [cursor]
+    ///     The items come with the values of the [select_tags], or with all their tags when there is no selection
+    ///     The reply has the count of all the matching items and the cursor of the next page, if any
     ///
     pub async fn search_item(
         mut self,
         start_page: Option<u32>,
         page_size: Option<u32>,
         filter_expression: Option<String>,
-        order_tags: Option<Vec<String>>
+        order_tags: Option<Vec<String>>,
+        select_tags: Option<Vec<String>>,
+        cursor: Option<String>,
     ) -> WebTypeWithContext<GetItemReply> {
         log_info!(
-            "🚀 Start search_item api, start_page=[{:?}], page_size=[{:?}], follower=[{}]",
+            "🚀 Start search_item api, start_page=[{:?}], page_size=[{:?}], order_tags=[{:?}], select_tags=[{:?}], follower=[{}]",
             start_page,
             page_size,
+            &order_tags,
+            &select_tags,
             &self.follower
         );
 
@@ -73,6 +110,17 @@ impl ItemDelegate {
 
         log_info!("😎 We fetched the session, follower=[{}]", &self.follower);
 
+        let cursor = match cursor.as_deref().map(SearchCursor::decode).transpose() {
+            Ok(cursor) => cursor,
+            Err(e) => {
+                log_error!("💣 Wrong cursor, e=[{}], follower=[{}]", e, &self.follower);
+                let msg = SimpleMessage::from("Invalid cursor".to_string());
+                return WebType::from_simple(StatusCode::BAD_REQUEST.as_u16(), msg).into_with_context();
+            }
+        };
+        // The cursor replaces the page number
+        let start_page = if cursor.is_some() { None } else { start_page };
+
         let filter_expression_ast: Box<FilterExpressionAST> =
             try_or_return!(analyse_expression(filter_expression.as_deref().unwrap_or("()")), |e: FilterError| {
                 // Keep the column/char position in the context
@@ -83,7 +131,8 @@ impl ItemDelegate {
 
         // session_token: SessionToken, follower: Follower, x_request_id: XRequestID
         let tag_definition_builder = TagDefinitionBuilder::new(self.session_token.clone(), self.follower.clone());
-        let select_tags = &vec![];
+        let fulltext_query_builder = FullTextQueryBuilder::new(self.session_token.clone(), self.follower.clone());
+        let select_tags: Vec<&str> = select_tags.iter().flatten().map(String::as_str).collect();
         // let v_order_tags: Vec<&str> = order_tags
         //     .as_deref()                 // Option<&[String]>
         //     .unwrap_or(&[])             // &[]
@@ -93,12 +142,14 @@ impl ItemDelegate {
 
         // We use a tag definition interface,because we don't know which tags
         //      we want the definition for, because they are in the filter's conditions.
-        let sql_query = try_or_return!(
+        let search_sql = try_or_return!(
             generate_search_sql(
                 &filter_expression_ast,
                 &tag_definition_builder,
-                select_tags,
+                &fulltext_query_builder,
+                &select_tags,
                 & order_tags.unwrap_or(vec![]),
+                cursor.as_ref(),
                 SearchSqlGenerationMode::Live,
                 &entry_session.customer_code,
             )
@@ -110,7 +161,7 @@ impl ItemDelegate {
             }
         );
 
-        log_info!("sql = {}", &sql_query);
+        log_info!("sql = {}, params = {:?}", &search_sql.sql_query, &search_sql.params);
 
         let Ok(mut cnx) = SQLConnectionAsync::from_pool()
             .await
@@ -124,12 +175,28 @@ impl ItemDelegate {
             return WebType::from_api_error(&INTERNAL_DATABASE_ERROR).into_with_context();
         };
 
-        let Ok(items) = self.search_item_from_query(&mut trans, &sql_query, start_page, page_size).await else {
+        let Ok((items, next_cursor)) = self
+            .search_item_from_query(&mut trans, &search_sql, start_page, page_size, &entry_session.customer_code)
+            .await
+        else {
             log_error!("💣 Cannot find item by id, follower=[{}]", &self.follower);
             return WebType::from_api_error(&INTERNAL_DATABASE_ERROR).into_with_context();
         };
 
-        log_info!("😎 We found the items, item count=[{}], follower=[{}]", items.len(), &self.follower);
+        let Ok(total_count) = self
+            .count_items_from_query(&mut trans, &search_sql)
+            .await
+            .map_err(err_fwd!("💣 Cannot count the items, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR).into_with_context();
+        };
+
+        log_info!(
+            "😎 We found the items, item count=[{}], total count=[{}], follower=[{}]",
+            items.len(),
+            total_count,
+            &self.follower
+        );
 
         if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
             return WebType::from_api_error(&INTERNAL_DATABASE_ERROR).into_with_context();
@@ -137,7 +204,10 @@ impl ItemDelegate {
 
         log_info!("🏁 End search_item, follower=[{}]", &self.follower);
 
-        WebTypeWithContext::from_item(StatusCode::OK.as_u16(), GetItemReply { items })
+        WebTypeWithContext::from_item(
+            StatusCode::OK.as_u16(),
+            GetItemReply { items, total_count: Some(total_count), next_cursor },
+        )
     }
 
     /// Deprecated - replace it with search_item
@@ -189,31 +259,40 @@ impl ItemDelegate {
 
         log_info!("🏁 End get_all_item, follower=[{}]", &self.follower);
 
-        WebType::from_item(StatusCode::OK.as_u16(), GetItemReply { items })
+        WebType::from_item(StatusCode::OK.as_u16(), GetItemReply { items, total_count: None, next_cursor: None })
     }
 
     /// Search items from the standard engine sql query REF_TAG: DOKA_ENGINE
+    /// The properties are the selected tags of the query, or all the tags of the items when there is no selection
+    /// Return the items of the page and the cursor of the next page, if there are more items
     async fn search_item_from_query(
         &self,
         mut trans: &mut SQLTransactionAsync<'_>,
-        sql_query: &str,
+        search_sql: &SearchSql,
         start_page: Option<u32>,
         page_size: Option<u32>,
-    ) -> anyhow::Result<Vec<ItemElement>> {
-        let params = HashMap::new();
-
+        customer_code: &str,
+    ) -> anyhow::Result<(Vec<ItemElement>, Option<String>)> {
+        // One more item tells if there is a next page
         let query = SQLQueryBlockAsync {
-            sql_query: sql_query.to_string(),
+            sql_query: search_sql.sql_query.clone(),
             start: start_page.unwrap_or(0) * page_size.unwrap_or(0),
-            length: page_size,
-            params,
+            length: page_size.map(|size| size + 1),
+            params: search_sql.params_map(),
         };
 
         let mut sql_result: SQLDataSet =
             query.execute(&mut trans).await.map_err(err_fwd!("Query failed, [{}]", &query.sql_query))?;
 
         let mut items = vec![];
+        let mut last_cursor: Option<SearchCursor> = None;
+        let mut next_cursor: Option<String> = None;
         while sql_result.next() {
+            if page_size.is_some_and(|size| items.len() >= size as usize) {
+                next_cursor = last_cursor.take().map(|cursor| cursor.encode());
+                break;
+            }
+
             let id: i64 = sql_result.get_int("id").ok_or(anyhow!("Wring id"))?;
             let name: String = sql_result.get_string("name").unwrap_or("".to_owned());
             let o_file_ref: Option<String> = sql_result.get_string("file_ref"); // .unwrap_or("".to_owned());
@@ -226,15 +305,21 @@ impl ItemDelegate {
             let last_modified_gmt =
                 sql_result.get_timestamp_as_datetime("last_modified_gmt").as_ref().map(|x| date_time_to_iso(x));
 
-            let lastname: String = sql_result.get_string("lastname").unwrap_or("".to_owned());
-
-            let tv = TagValueElement {
-                tag_value_id: 0,
-                item_id: 0,
-                tag_id: 0,
-                tag_name: "lastname".to_string(),
-                value: EnumTagValue::Text(Some(lastname.to_string())),
-            };
+            // The items without a value for a selected tag do not get the property
+            let mut props = vec![];
+            for selected_tag in &search_sql.selected_tags {
+                let Some(tag_value_id) = sql_result.get_int(&selected_tag.value_id_column()) else {
+                    continue;
+                };
+                let tag_id = sql_result.get_int(&selected_tag.tag_id_column()).ok_or(anyhow!("Wrong tag_id"))?;
+                props.push(TagValueElement {
+                    tag_value_id,
+                    item_id: id,
+                    tag_id,
+                    tag_name: selected_tag.tag_name.clone(),
+                    value: read_enum_tag_value(&sql_result, &selected_tag.tag_type, &selected_tag.value_column()),
+                });
+            }
 
             let item = ItemElement {
                 item_id: id,
@@ -242,13 +327,52 @@ impl ItemDelegate {
                 file_ref: o_file_ref,
                 created: date_time_to_iso(&created_gmt),
                 last_modified: last_modified_gmt,
-                properties: Some(vec![tv]), // fill it with the extra fields
+                properties: Some(props),
             };
 
             let _ = &items.push(item);
+
+            last_cursor = Some(SearchCursor {
+                sort: search_sql.sort.clone(),
+                values: search_sql
+                    .sort_columns
+                    .iter()
+                    .map(|column| read_sort_value(&sql_result, &column.tag_type, &column.column_name))
+                    .collect(),
+                item_id: id,
+            });
         }
 
-        Ok(items)
+        if search_sql.selected_tags.is_empty() && !items.is_empty() {
+            let item_ids: Vec<i64> = items.iter().map(|item| item.item_id).collect();
+            let mut props_by_item =
+                self.find_items_properties(trans, &item_ids, customer_code).await.map_err(tr_fwd!())?;
+            for item in items.iter_mut() {
+                item.properties = Some(props_by_item.remove(&item.item_id).unwrap_or_default());
+            }
+        }
+
+        Ok((items, next_cursor))
+    }
+
+    /// Count all the items matching the filter of the search query, whatever the page
+    async fn count_items_from_query(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        search_sql: &SearchSql,
+    ) -> anyhow::Result<i64> {
+        let query = SQLQueryBlockAsync {
+            sql_query: search_sql.count_query.clone(),
+            start: 0,
+            length: None,
+            params: search_sql.count_params_map(),
+        };
+
+        let mut sql_result = query.execute(trans).await.map_err(err_fwd!("Query failed, [{}]", &query.sql_query))?;
+        if !sql_result.next() {
+            return Err(anyhow!("No count for the search"));
+        }
+        sql_result.get_int("total_count").ok_or(anyhow!("Wrong total_count"))
     }
 
     /// ! Deprecated - user search_with_filter instead
@@ -317,6 +441,74 @@ impl ItemDelegate {
         Ok(items)
     }
 
+    /// Search the items having a link tag on the item [linked_item_id]
+    async fn search_item_by_link(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        linked_item_id: i64,
+        start_page: Option<u32>,
+        page_size: Option<u32>,
+        customer_code: &str,
+    ) -> anyhow::Result<Vec<ItemElement>> {
+        let mut params = HashMap::new();
+        params.insert("p_linked_item_id".to_owned(), CellValue::from_raw_int(linked_item_id));
+
+        let sql_query = format!(
+            r"SELECT DISTINCT i.id, i.name, i.file_ref, i.created_gmt, i.last_modified_gmt
+                    FROM cs_{0}.item i
+                    INNER JOIN cs_{0}.tag_value tv ON tv.item_id = i.id
+                    WHERE tv.value_link = :p_linked_item_id
+                    ORDER BY i.name, i.id ",
+            customer_code
+        );
+
+        let query = SQLQueryBlockAsync {
+            sql_query,
+            start: start_page.unwrap_or(0) * page_size.unwrap_or(0),
+            length: page_size,
+            params,
+        };
+
+        let mut sql_result: SQLDataSet =
+            query.execute(trans).await.map_err(err_fwd!("Query failed, [{}]", &query.sql_query))?;
+
+        let mut items = vec![];
+        while sql_result.next() {
+            let id: i64 = sql_result.get_int("id").ok_or(anyhow!("Wrong id"))?;
+            let name: String = sql_result.get_string("name").unwrap_or("".to_owned());
+            let o_file_ref: Option<String> = sql_result.get_string("file_ref");
+            let created_gmt = sql_result
+                .get_timestamp_as_datetime("created_gmt")
+                .ok_or(anyhow::anyhow!("Wrong created gmt"))
+                .map_err(tr_fwd!())?;
+
+            // Optional
+            let last_modified_gmt =
+                sql_result.get_timestamp_as_datetime("last_modified_gmt").as_ref().map(date_time_to_iso);
+
+            items.push(ItemElement {
+                item_id: id,
+                name,
+                file_ref: o_file_ref,
+                created: date_time_to_iso(&created_gmt),
+                last_modified: last_modified_gmt,
+                properties: None,
+            });
+        }
+
+        // The tags of all the items of the page in one query
+        if !items.is_empty() {
+            let item_ids: Vec<i64> = items.iter().map(|item| item.item_id).collect();
+            let mut props_by_item =
+                self.find_items_properties(trans, &item_ids, customer_code).await.map_err(tr_fwd!())?;
+            for item in items.iter_mut() {
+                item.properties = Some(props_by_item.remove(&item.item_id).unwrap_or_default());
+            }
+        }
+
+        Ok(items)
+    }
+
     ///
     ///
     ///
@@ -326,20 +518,41 @@ impl ItemDelegate {
         item_id: i64,
         customer_code: &str,
     ) -> anyhow::Result<Vec<TagValueElement>> {
-        let mut props = vec![];
+        let mut props_by_item =
+            self.find_items_properties(trans, &[item_id], customer_code).await.map_err(tr_fwd!())?;
+        Ok(props_by_item.remove(&item_id).unwrap_or_default())
+    }
 
-        let p_item_id = CellValue::from_raw_int(item_id);
+    ///
+    /// Find the tags of all the items [item_ids] in a single query
+    ///
+    async fn find_items_properties(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_ids: &[i64],
+        customer_code: &str,
+    ) -> anyhow::Result<HashMap<i64, Vec<TagValueElement>>> {
+        let mut props_by_item: HashMap<i64, Vec<TagValueElement>> = HashMap::new();
 
+        // The names look like p_{n}_item, so a name is never the beginning of another one
         let mut params = HashMap::new();
-        params.insert("p_item_id".to_owned(), p_item_id);
+        let mut placeholders = vec![];
+        for (index, item_id) in item_ids.iter().enumerate() {
+            let name = format!("p_{}_item", index);
+            placeholders.push(format!(":{}", &name));
+            params.insert(name, CellValue::from_raw_int(*item_id));
+        }
 
         let sql_query = format!(
             r"SELECT td.name, td.type, tv.id, tv.tag_id, tv.item_id, tv.value_string, tv.value_integer, tv.value_double,
-                tv.value_date, tv.value_datetime, tv.value_boolean
+                tv.value_date, tv.value_datetime, tv.value_boolean, tv.value_link
                 FROM cs_{}.tag_value tv
                 INNER JOIN cs_{}.tag_definition td ON td.id = tv.tag_id
-                WHERE tv.item_id = :p_item_id ",
-            customer_code, customer_code
+                WHERE tv.item_id IN ({})
+                ORDER BY tv.item_id, tv.id ",
+            customer_code,
+            customer_code,
+            placeholders.join(", ")
         );
 
         let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
@@ -361,46 +574,13 @@ impl ItemDelegate {
                 }
             };
 
-            let value = match tt {
-                TagType::Text => {
-                    let value_string = sql_result.get_string("value_string");
-                    EnumTagValue::Text(value_string)
-                }
-                TagType::Link => {
-                    let value_string = sql_result.get_string("value_string");
-                    EnumTagValue::Link(value_string)
-                }
-                TagType::Bool => {
-                    let value_boolean = sql_result.get_bool("value_boolean");
-                    EnumTagValue::Boolean(value_boolean)
-                }
-                TagType::Int => {
-                    let value_integer = sql_result.get_int("value_integer");
-                    EnumTagValue::Integer(value_integer)
-                }
-                TagType::Double => {
-                    let value_double = sql_result.get_double("value_double");
-                    EnumTagValue::Double(value_double)
-                }
-                TagType::Date => {
-                    // Changed to simply get the naive date and change it to iso string, no need of "Date"
-                    let value_naivedate = sql_result.get_naivedate("value_date");
-                    //let value_date = sql_result.get_naivedate_as_date("value_date");
-                    let opt_iso_d_str = value_naivedate.as_ref().map(|x| naivedate_to_iso(x));
-                    EnumTagValue::SimpleDate(opt_iso_d_str)
-                }
-                TagType::DateTime => {
-                    let value_datetime = sql_result.get_timestamp_as_datetime("value_datetime");
-                    let opt_iso_dt_str = value_datetime.as_ref().map(|x| date_time_to_iso(x));
-                    EnumTagValue::DateTime(opt_iso_dt_str)
-                }
-            };
+            let value = read_enum_tag_value(&sql_result, &tt, tt.value_column_name());
 
             let tv = TagValueElement { tag_value_id, item_id: row_item_id, tag_id, tag_name, value };
-            let _ = &props.push(tv);
+            props_by_item.entry(row_item_id).or_default().push(tv);
         }
 
-        Ok(props)
+        Ok(props_by_item)
     }
 
     ///
@@ -455,7 +635,75 @@ impl ItemDelegate {
         }
 
         log_info!("🏁 End get_item, follower=[{}]", &self.follower);
-        WebType::from_item(StatusCode::OK.as_u16(), GetItemReply { items })
+        WebType::from_item(StatusCode::OK.as_u16(), GetItemReply { items, total_count: None, next_cursor: None })
+    }
+
+    ///
+    /// 🌟 Find the items with a link tag pointing to the item [item_id]
+    ///
+    pub async fn get_linked_items(
+        mut self,
+        item_id: i64,
+        start_page: Option<u32>,
+        page_size: Option<u32>,
+    ) -> WebType<GetItemReply> {
+        log_info!(
+            "🚀 Start get_linked_items api, item_id=[{}], start_page=[{:?}], page_size=[{:?}], follower=[{}]",
+            item_id,
+            start_page,
+            page_size,
+            &self.follower
+        );
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+
+        self.follower.token_type = TokenType::Sid(self.session_token.0.clone());
+
+        log_info!("😎 We fetched the session, follower=[{}]", &self.follower);
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        match self.is_item_existing(&mut trans, item_id, &entry_session.customer_code).await {
+            Ok(true) => {}
+            Ok(false) => {
+                log_error!("💣 Missing item=[{:?}], follower=[{}]", item_id, &self.follower);
+                return WebType::from_api_error(&MISSING_ITEM);
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the item, message=[{}], follower=[{}]", e.to_string(), &self.follower);
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
+        }
+
+        let Ok(items) = self
+            .search_item_by_link(&mut trans, item_id, start_page, page_size, &entry_session.customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot search the linked items, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        log_info!("😎 We found the linked items, item count=[{}], follower=[{}]", items.len(), &self.follower);
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        log_info!("🏁 End get_linked_items, follower=[{}]", &self.follower);
+        WebType::from_item(StatusCode::OK.as_u16(), GetItemReply { items, total_count: None, next_cursor: None })
     }
 
     ///
@@ -516,6 +764,11 @@ impl ItemDelegate {
             log_info!("😎 We deleted the tag, tag_name=[{}], follower=[{}]", &tag_name, &self.follower);
         }
 
+        // A required tag cannot be removed
+        if let Err(e) = self.check_required_tags(&mut trans, item_id, customer_code).await {
+            return WebType::from_api_error(e);
+        }
+
         if trans.commit().await.map_err(err_fwd!("💣 Commit failed")).is_err() {
             return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
         }
@@ -644,10 +897,20 @@ impl ItemDelegate {
     }
 
     ///
-    /// 🌟 Create an item
+    /// 🌟 Update the tags on all the items of the file [file_ref]
+    ///     Used from the file-server, to set the metadata of the file as tags
     ///
-    pub async fn add_item(mut self, add_item_request: Json<AddItemRequest>) -> WebType<AddItemReply> {
-        log_info!("🚀 Start add_item api, add_item_request=[{:?}], follower=[{}]", &add_item_request, &self.follower);
+    pub async fn update_file_item_tags(
+        mut self,
+        file_ref: &str,
+        add_item_tag_request: Json<AddItemTagRequest>,
+    ) -> WebType<AddItemTagReply> {
+        log_info!(
+            "🚀 Start update_file_item_tags api, file_ref=[{}], add_item_tag_request=[{:?}], follower=[{}]",
+            file_ref,
+            &add_item_tag_request,
+            &self.follower
+        );
 
         let entry_session = try_or_return!(
             valid_sid_get_session(&self.session_token, &mut self.follower).await,
@@ -656,13 +919,6 @@ impl ItemDelegate {
 
         let customer_code = entry_session.customer_code.as_str();
 
-        log_info!(
-            "😎 We read the session information, customer_code=[{}], follower=[{}]",
-            customer_code,
-            &self.follower
-        );
-
-        // Open Db connection
         let Ok(mut cnx) = SQLConnectionAsync::from_pool()
             .await
             .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
@@ -675,94 +931,584 @@ impl ItemDelegate {
             return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
         };
 
-        let o_file_ref = add_item_request.file_ref.clone();
-        let Ok(item_id) = self
-            .create_item(&mut trans, &add_item_request.name, customer_code, o_file_ref)
+        let Ok(item_ids) = self
+            .search_item_ids_by_file_ref(&mut trans, file_ref, customer_code)
             .await
-            .map_err(err_fwd!("💣 Cannot create the item, follower=[{}]", &self.follower))
+            .map_err(err_fwd!("💣 Cannot find the items of the file, follower=[{}]", &self.follower))
         else {
             return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
         };
 
-        log_info!("😎 We created the item, item_id=[{}], follower=[{}]", item_id, &self.follower);
-
-        // | Insert all the properties
-        if let Some(properties) = &add_item_request.properties {
-            if let Err(e) = self.update_tags_on_item(&mut trans, item_id, customer_code, properties).await {
+        for item_id in &item_ids {
+            if let Err(e) =
+                self.update_tags_on_item(&mut trans, *item_id, customer_code, &add_item_tag_request.properties).await
+            {
                 return WebType::from_api_error(e);
             }
         }
 
-        log_info!("😎 We added all the properties to the item, item_id=[{}], follower=[{}]", item_id, &self.follower);
-
-        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed")).is_err() {
             return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
         }
 
-        let now = SystemTime::now();
-        let created: DateTime<Utc> = now.clone().into();
-        let last_modified: DateTime<Utc> = now.clone().into();
-
-        log_info!("🏁 End add_item, follower=[{}]", &self.follower);
+        log_info!(
+            "😎 Updated the tags of the items of the file, file_ref=[{}], item count=[{}], follower=[{}]",
+            file_ref,
+            item_ids.len(),
+            &self.follower
+        );
+        log_info!("🏁 End update_file_item_tags, follower=[{}]", &self.follower);
 
-        WebType::from_item(
-            StatusCode::OK.as_u16(),
-            AddItemReply {
-                item_id,
-                name: add_item_request.name.clone(),
-                created: date_time_to_iso(&created),
-                last_modified: Some(date_time_to_iso(&last_modified)),
-            },
-        )
+        WebType::from_item(StatusCode::OK.as_u16(), AddItemTagReply { status: "Ok".to_string() })
     }
 
-    /// Add tags on an item
-    async fn update_tags_on_item(
-        &self,
-        mut trans: &mut SQLTransactionAsync<'_>,
-        item_id: i64,
-        customer_code: &str,
-        properties: &Vec<AddTagValue>,
-    ) -> Result<(), &ApiError<'static>> {
-        for tag in properties {
-            // Check / Define the property
-            let tag_id = match (tag.tag_id, &tag.tag_name) {
-                (None, None) => {
-                    // Impossible case, return an error.
-                    log_error!("💣 A tag must have a tag_id or a tag_name, follower=[{}]", &self.follower);
-                    return Err(&INTERNAL_DATABASE_ERROR);
-                }
-                (Some(tag_id), None) => {
-                    // Tag id only, verify if the tag exists, return the tag_id
-                    // Any case with a tag_name provided, check / create, return the tag_id
-                    let Ok(tid) =
-                        self.check_tag_id_validity(&mut trans, tag_id, tag, customer_code).await.map_err(err_fwd!(
-                            "💣 The definition of the new tag failed, tag name=[{:?}], follower=[{}]",
-                            tag,
-                            &self.follower
-                        ))
-                    else {
-                        //let message = format!("tag_id: [{}]", &tag_id);
-                        return Err(&MISSING_TAG_FOR_ITEM);
-                    };
-                    log_info!("😎 The tag is already in the system, tag id=[{}], follower=[{}]", tid, &self.follower);
-                    tid
-                }
-                (_, Some(_tag_name)) => {
-                    // Any case with a tag_name provided, check / create , return the tag_id
-                    let Ok(tid) = self.define_tag_if_needed(&mut trans, tag, customer_code).await.map_err(err_fwd!(
-                        "💣 The definition of the new tag failed, tag name=[{:?}], follower=[{}]",
-                        tag,
-                        &self.follower
-                    )) else {
-                        //let message = format!("tag_name: [{}]", &tag_name);
-                        return Err(&BAD_TAG_FOR_ITEM);
+    ///
+    /// 🌟 Add and remove the tags on all the items matching the filter expression, in one transaction
+    ///
+    pub async fn bulk_item_tags(
+        mut self,
+        bulk_item_tag_request: Json<BulkItemTagRequest>,
+    ) -> WebTypeWithContext<BulkItemTagReply> {
+        log_info!(
+            "🚀 Start bulk_item_tags api, bulk_item_tag_request=[{:?}], follower=[{}]",
+            &bulk_item_tag_request,
+            &self.follower
+        );
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error_ctx()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+
+        let filter_expression_ast: Box<FilterExpressionAST> =
+            try_or_return!(analyse_expression(&bulk_item_tag_request.filters), |e: FilterError| {
+                let c = ContextMessage { message: e.human_error_message(), context: vec![e.char_position.to_string()] };
+                WebTypeWithContext::from_simple(StatusCode::BAD_REQUEST.as_u16(), c)
+            });
+
+        let tag_definition_builder = TagDefinitionBuilder::new(self.session_token.clone(), self.follower.clone());
+        let fulltext_query_builder = FullTextQueryBuilder::new(self.session_token.clone(), self.follower.clone());
+
+        let search_sql = try_or_return!(
+            generate_search_sql(
+                &filter_expression_ast,
+                &tag_definition_builder,
+                &fulltext_query_builder,
+                &[],
+                &vec![],
+                None,
+                SearchSqlGenerationMode::Live,
+                customer_code,
+            )
+            .await,
+            |e: GenerationError| {
+                log_error!("💣 Fail to generate sql search query, [{:?}],follower=[{}]", e, &self.follower);
+                let msg = SimpleMessage::from(e.to_string());
+                WebType::from_simple(StatusCode::BAD_REQUEST.as_u16(), msg).into_with_context()
+            }
+        );
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR).into_with_context();
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR).into_with_context();
+        };
+
+        let Ok(item_ids) = self
+            .search_item_ids_from_query(&mut trans, &search_sql)
+            .await
+            .map_err(err_fwd!("💣 Cannot find the items of the filter, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR).into_with_context();
+        };
+
+        for item_id in &item_ids {
+            if !bulk_item_tag_request.add_tags.is_empty() {
+                if let Err(e) =
+                    self.update_tags_on_item(&mut trans, *item_id, customer_code, &bulk_item_tag_request.add_tags).await
+                {
+                    return WebType::from_api_error(e).into_with_context();
+                }
+            }
+            for tag_name in &bulk_item_tag_request.remove_tags {
+                if let Err(e) = self.delete_item_tag_value(&mut trans, *item_id, tag_name, customer_code).await {
+                    log_error!("💣 Delete item tag value error, error=[{:?}], follower=[{}]", e, &self.follower);
+                    return WebType::from_api_error(&INTERNAL_DATABASE_ERROR).into_with_context();
+                }
+            }
+            if !bulk_item_tag_request.remove_tags.is_empty() {
+                if let Err(e) = self.check_required_tags(&mut trans, *item_id, customer_code).await {
+                    return WebType::from_api_error(e).into_with_context();
+                }
+            }
+        }
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR).into_with_context();
+        }
+
+        log_info!("😎 Updated the tags of the items, item count=[{}], follower=[{}]", item_ids.len(), &self.follower);
+        log_info!("🏁 End bulk_item_tags, follower=[{}]", &self.follower);
+
+        WebTypeWithContext::from_item(StatusCode::OK.as_u16(), BulkItemTagReply { item_count: item_ids.len() as u64 })
+    }
+
+    /// Ids of all the items matching the search query, whatever the page
+    async fn search_item_ids_from_query(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        search_sql: &SearchSql,
+    ) -> anyhow::Result<Vec<i64>> {
+        let query = SQLQueryBlockAsync {
+            sql_query: search_sql.sql_query.clone(),
+            start: 0,
+            length: None,
+            params: search_sql.params_map(),
+        };
+
+        let mut dataset = query.execute(trans).await.map_err(err_fwd!("Query failed, [{}]", &query.sql_query))?;
+
+        let mut item_ids = vec![];
+        while dataset.next() {
+            item_ids.push(dataset.get_int("id").ok_or(anyhow!("Wrong id"))?);
+        }
+        Ok(item_ids)
+    }
+
+    /// Ids of the items attached to the file [file_ref]
+    async fn search_item_ids_by_file_ref(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        file_ref: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<Vec<i64>> {
+        let sql_query = format!(r"SELECT id FROM cs_{}.item WHERE file_ref = :p_file_ref ORDER BY id", customer_code);
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+
+        let mut dataset = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, [{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        let mut item_ids = vec![];
+        while dataset.next() {
+            item_ids.push(dataset.get_int("id").ok_or(anyhow!("Wrong id"))?);
+        }
+        Ok(item_ids)
+    }
+
+    ///
+    /// 🌟 Create an item
+    ///
+    pub async fn add_item(mut self, add_item_request: Json<AddItemRequest>) -> WebType<AddItemReply> {
+        log_info!("🚀 Start add_item api, add_item_request=[{:?}], follower=[{}]", &add_item_request, &self.follower);
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+
+        let customer_code = entry_session.customer_code.as_str();
+
+        log_info!(
+            "😎 We read the session information, customer_code=[{}], follower=[{}]",
+            customer_code,
+            &self.follower
+        );
+
+        // Open Db connection
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let o_file_ref = add_item_request.file_ref.clone();
+        let Ok(item_id) = self
+            .create_item(&mut trans, &add_item_request.name, customer_code, o_file_ref)
+            .await
+            .map_err(err_fwd!("💣 Cannot create the item, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        log_info!("😎 We created the item, item_id=[{}], follower=[{}]", item_id, &self.follower);
+
+        // | Insert all the properties, every required tag must be set
+        let no_properties = vec![];
+        let properties = add_item_request.properties.as_ref().unwrap_or(&no_properties);
+        if let Err(e) = self.update_tags_on_item(&mut trans, item_id, customer_code, properties).await {
+            return WebType::from_api_error(e);
+        }
+
+        log_info!("😎 We added all the properties to the item, item_id=[{}], follower=[{}]", item_id, &self.follower);
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        let now = SystemTime::now();
+        let created: DateTime<Utc> = now.clone().into();
+        let last_modified: DateTime<Utc> = now.clone().into();
+
+        log_info!("🏁 End add_item, follower=[{}]", &self.follower);
+
+        WebType::from_item(
+            StatusCode::OK.as_u16(),
+            AddItemReply {
+                item_id,
+                name: add_item_request.name.clone(),
+                created: date_time_to_iso(&created),
+                last_modified: Some(date_time_to_iso(&last_modified)),
+            },
+        )
+    }
+
+    ///
+    /// 🌟 Delete an item with its tags, its versions and the links of the other items to it.
+    ///     The item cannot be deleted while it is the value of a required link tag of another item.
+    ///     With [delete_file], the files of the item are removed from the full text index and from the file server
+    ///
+    pub async fn delete_item(mut self, item_id: i64, delete_file: bool) -> WebType<DeleteItemReply> {
+        log_info!(
+            "🚀 Start delete_item api, item_id=[{}], delete_file=[{}], follower=[{}]",
+            item_id,
+            delete_file,
+            &self.follower
+        );
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        match self.is_item_existing(&mut trans, item_id, customer_code).await {
+            Ok(true) => {}
+            Ok(false) => {
+                log_error!("💣 Missing item=[{}], follower=[{}]", item_id, &self.follower);
+                return WebType::from_api_error(&MISSING_ITEM);
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the item, e=[{}], follower=[{}]", e, &self.follower);
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
+        }
+
+        let Ok(file_refs) = self
+            .search_item_file_refs(&mut trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot read the files of the item, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(linking_item_ids) = self
+            .search_linking_items(&mut trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot read the items linked to the item, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if delete_file
+            && self
+                .delete_item_documents(&mut trans, item_id, customer_code)
+                .await
+                .map_err(err_fwd!("💣 Cannot delete the full text documents, follower=[{}]", &self.follower))
+                .is_err()
+        {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        if self
+            .remove_item(&mut trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot delete the item, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        // The items linked to the deleted item lose their link, it must not be a required tag
+        for linking_item_id in linking_item_ids {
+            if let Err(api_error) = self.check_required_tags(&mut trans, linking_item_id, customer_code).await {
+                log_error!(
+                    "💣 The item is the required link of another item, item_id=[{}], linking item_id=[{}], follower=[{}]",
+                    item_id,
+                    linking_item_id,
+                    &self.follower
+                );
+                return WebType::from_api_error(api_error);
+            }
+        }
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        log_info!("😎 We deleted the item, item_id=[{}], follower=[{}]", item_id, &self.follower);
+
+        // The item is gone, a file the file server cannot delete is only reported
+        let mut deleted_files = vec![];
+        let mut kept_files = vec![];
+        if delete_file {
+            for file_ref in file_refs {
+                if self.delete_server_file(&file_ref).await {
+                    deleted_files.push(file_ref);
+                } else {
+                    kept_files.push(file_ref);
+                }
+            }
+        }
+
+        log_info!(
+            "🏁 End delete_item, deleted files=[{:?}], kept files=[{:?}], follower=[{}]",
+            &deleted_files,
+            &kept_files,
+            &self.follower
+        );
+        WebType::from_item(StatusCode::OK.as_u16(), DeleteItemReply { item_id, deleted_files, kept_files })
+    }
+
+    /// Delete the file [file_ref] on the file server, return true when it's done
+    async fn delete_server_file(&self, file_ref: &str) -> bool {
+        let file_server_client = match Self::find_file_server_client() {
+            Ok(client) => client,
+            Err(e) => {
+                log_error!("💣 Cannot build the file server client, e=[{}], follower=[{}]", e, &self.follower);
+                return false;
+            }
+        };
+
+        match file_server_client.delete_file(file_ref, &self.session_token.0).await {
+            Ok(_) => {
+                log_info!("😎 We deleted the file, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+                true
+            }
+            Err(e) => {
+                log_warn!(
+                    "⛔ The file server did not delete the file, file_ref=[{}], e=[{:?}], follower=[{}]",
+                    file_ref,
+                    e,
+                    &self.follower
+                );
+                false
+            }
+        }
+    }
+
+    pub(crate) fn find_file_server_client() -> anyhow::Result<FileServerClientAsync> {
+        let file_server_host = get_prop_value(FILE_SERVER_HOSTNAME_PROPERTY)?;
+        let file_server_port = get_prop_value(FILE_SERVER_PORT_PROPERTY)?.parse::<u16>()?;
+        Ok(FileServerClientAsync::new(&file_server_host, file_server_port))
+    }
+
+    ///
+    /// 🌟 Rename an item, attach or detach its file.
+    ///     An attached file becomes the new version of the item
+    ///
+    pub async fn patch_item(
+        mut self,
+        item_id: i64,
+        patch_item_request: Json<PatchItemRequest>,
+    ) -> WebType<GetItemReply> {
+        log_info!(
+            "🚀 Start patch_item api, item_id=[{}], patch_item_request=[{:?}], follower=[{}]",
+            item_id,
+            &patch_item_request,
+            &self.follower
+        );
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+
+        if patch_item_request.name.as_ref().is_some_and(|name| name.trim().is_empty()) {
+            log_error!("💣 The name of the item cannot be empty, follower=[{}]", &self.follower);
+            return WebType::from_api_error(&INVALID_REQUEST);
+        }
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(items) = self
+            .search_item_by_id(&mut trans, Some(item_id), None, None, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot search item by id, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Some(item) = items.first() else {
+            log_error!("💣 Missing item=[{}], follower=[{}]", item_id, &self.follower);
+            return WebType::from_api_error(&MISSING_ITEM);
+        };
+
+        if let Some(name) = &patch_item_request.name {
+            if self
+                .rename_item(&mut trans, item_id, name, customer_code)
+                .await
+                .map_err(err_fwd!("💣 Cannot rename the item, follower=[{}]", &self.follower))
+                .is_err()
+            {
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
+        }
+
+        match &patch_item_request.file_ref {
+            None => {}
+            // An item without file has nothing to detach
+            Some(None) if item.file_ref.is_none() => {}
+            Some(None) => {
+                let version_delegate =
+                    ItemVersionDelegate { session_token: self.session_token.clone(), follower: self.follower.clone() };
+                if let Err(e) = version_delegate.detach_file(&mut trans, item_id, customer_code).await {
+                    return WebType::from_api_error(e);
+                }
+            }
+            // The current file stays as it is
+            Some(Some(file_ref)) if item.file_ref.as_ref() == Some(file_ref) => {}
+            Some(Some(file_ref)) => {
+                let version_delegate =
+                    ItemVersionDelegate { session_token: self.session_token.clone(), follower: self.follower.clone() };
+                if let Err(e) = version_delegate.attach_file(&mut trans, item_id, file_ref, customer_code).await {
+                    return WebType::from_api_error(e);
+                }
+            }
+        }
+
+        let Ok(items) = self
+            .search_item_by_id(&mut trans, Some(item_id), None, None, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot search item by id, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        log_info!("🏁 End patch_item, follower=[{}]", &self.follower);
+        WebType::from_item(StatusCode::OK.as_u16(), GetItemReply { items, total_count: None, next_cursor: None })
+    }
+
+    /// Add tags on an item
+    async fn update_tags_on_item(
+        &self,
+        mut trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+        properties: &Vec<AddTagValue>,
+    ) -> Result<(), &ApiError<'static>> {
+        for tag in properties {
+            // Check / Define the property
+            let tag_id = match (tag.tag_id, &tag.tag_name) {
+                (None, None) => {
+                    // Impossible case, return an error.
+                    log_error!("💣 A tag must have a tag_id or a tag_name, follower=[{}]", &self.follower);
+                    return Err(&INTERNAL_DATABASE_ERROR);
+                }
+                (Some(tag_id), None) => {
+                    // Tag id only, verify if the tag exists, return the tag_id
+                    // Any case with a tag_name provided, check / create, return the tag_id
+                    let Ok(tid) =
+                        self.check_tag_id_validity(&mut trans, tag_id, tag, customer_code).await.map_err(err_fwd!(
+                            "💣 The definition of the new tag failed, tag name=[{:?}], follower=[{}]",
+                            tag,
+                            &self.follower
+                        ))
+                    else {
+                        //let message = format!("tag_id: [{}]", &tag_id);
+                        return Err(&MISSING_TAG_FOR_ITEM);
+                    };
+                    log_info!("😎 The tag is already in the system, tag id=[{}], follower=[{}]", tid, &self.follower);
+                    tid
+                }
+                (_, Some(_tag_name)) => {
+                    // Any case with a tag_name provided, check / create , return the tag_id
+                    let Ok(tid) = self.define_tag_if_needed(&mut trans, tag, customer_code).await.map_err(err_fwd!(
+                        "💣 The definition of the new tag failed, tag name=[{:?}], follower=[{}]",
+                        tag,
+                        &self.follower
+                    )) else {
+                        //let message = format!("tag_name: [{}]", &tag_name);
+                        return Err(&BAD_TAG_FOR_ITEM);
                     };
                     log_info!("The tag is in the system, tag id=[{:?}], follower=[{}]", tid, &self.follower);
                     tid
                 }
             };
 
+            // The value must follow the constraints of the tag
+            self.check_tag_value_constraints(trans, tag_id, &tag.value, customer_code).await?;
+
+            // A link must point to an existing item
+            if let EnumTagValue::Link(Some(linked_item_id)) = tag.value {
+                match self.is_item_existing(trans, linked_item_id, customer_code).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        log_error!(
+                            "💣 The linked item does not exist, linked item id=[{}], follower=[{}]",
+                            linked_item_id,
+                            &self.follower
+                        );
+                        return Err(&INCORRECT_LINK_TARGET);
+                    }
+                    Err(e) => {
+                        log_error!(
+                            "💣 Error while reading the linked item, linked item id=[{}], message=[{}], follower=[{}]",
+                            linked_item_id,
+                            e.to_string(),
+                            &self.follower
+                        );
+                        return Err(&INTERNAL_DATABASE_ERROR);
+                    }
+                }
+            }
+
             // Verify if the tag exists on the item
             match self.is_tags_on_item(&mut trans, item_id, tag_id, customer_code).await {
                 Ok((o_tag_value_id, o_tag_type)) => {
@@ -849,11 +1595,114 @@ impl ItemDelegate {
                 }
             }
         }
+
+        self.check_required_tags(trans, item_id, customer_code).await
+    }
+
+    /// Check the value against the constraints of the tag definition
+    async fn check_tag_value_constraints(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        tag_id: i64,
+        value: &EnumTagValue,
+        customer_code: &str,
+    ) -> Result<(), &'static ApiError<'static>> {
+        let tag_delegate = TagDelegate::new(self.session_token.clone(), self.follower.x_request_id);
+
+        let Ok(tags) = tag_delegate
+            .search_tag_by_id(trans, Some(tag_id), None, None, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot find the tag by id, tag_id=[{}], follower=[{}]", tag_id, &self.follower))
+        else {
+            return Err(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Some(tag_definition) = tags.first() else {
+            return Err(&MISSING_TAG_FOR_ITEM);
+        };
+
+        let Ok(tag_type) = TagType::from_str(tag_definition.tag_type.to_lowercase().as_str()) else {
+            return Err(&INCORRECT_TAG_TYPE);
+        };
+
+        let pattern = compile_tag_pattern(&tag_definition.constraints)?;
+        check_tag_value(&tag_type, &tag_definition.constraints, pattern.as_ref(), value).inspect_err(|e| {
+            log_error!(
+                "💣 The value does not follow the tag constraints, tag_id=[{}], value=[{:?}], message=[{}], follower=[{}]",
+                tag_id,
+                value,
+                e.message,
+                &self.follower
+            );
+        })
+    }
+
+    /// Every required tag must have a value on the item
+    async fn check_required_tags(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> Result<(), &'static ApiError<'static>> {
+        let Ok(missing_tags) = self
+            .search_missing_required_tags(trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot find the required tags, follower=[{}]", &self.follower))
+        else {
+            return Err(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if !missing_tags.is_empty() {
+            log_error!(
+                "💣 Some required tags are missing, item_id=[{}], tag names=[{}], follower=[{}]",
+                item_id,
+                missing_tags.join(", "),
+                &self.follower
+            );
+            return Err(&MISSING_REQUIRED_TAG);
+        }
+
         Ok(())
     }
 
+    /// Names of the required tags without a value on the item, an empty value counts as no value
+    async fn search_missing_required_tags(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        let sql_query = format!(
+            r"SELECT td.name FROM cs_{0}.tag_definition td
+                WHERE td.required
+                AND NOT EXISTS ( SELECT 1 FROM cs_{0}.tag_value tv WHERE tv.tag_id = td.id AND tv.item_id = :p_item_id
+                    AND num_nonnulls(tv.value_boolean, tv.value_string, tv.value_integer, tv.value_double,
+                        tv.value_date, tv.value_datetime, tv.value_link) > 0 )
+                ORDER BY td.name",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+
+        let mut dataset = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, [{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        let mut tag_names = vec![];
+        while dataset.next() {
+            tag_names.push(dataset.get_string("name").ok_or(anyhow!("Wrong name"))?);
+        }
+
+        Ok(tag_names)
+    }
+
     ///
-    async fn change_item_tag_value(
+    pub(crate) async fn change_item_tag_value(
         &self,
         mut trans: &mut SQLTransactionAsync<'_>,
         tag: &AddTagValue,
@@ -868,7 +1717,8 @@ impl ItemDelegate {
                                                 value_integer = :p_value_integer,
                                                 value_double = :p_value_double,
                                                 value_date = :p_val_date,
-                                                value_datetime = :p_value_datetime
+                                                value_datetime = :p_value_datetime,
+                                                value_link = :p_value_link
                                             WHERE id = :p_tag_value_id
                                                  ",
             customer_code
@@ -917,6 +1767,168 @@ impl ItemDelegate {
         Ok(())
     }
 
+    /// Find if the item exists
+    pub(crate) async fn is_item_existing(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<bool> {
+        let sql_query = format!(r"SELECT 1 FROM cs_{}.item WHERE id = :p_item_id", customer_code);
+
+        let mut params = HashMap::new();
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+
+        let dataset = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, [{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        Ok(dataset.len() > 0)
+    }
+
+    /// Current and previous files of the item
+    async fn search_item_file_refs(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        let sql_query = format!(
+            r"SELECT file_ref FROM cs_{0}.item WHERE id = :p_item_id AND file_ref IS NOT NULL
+            UNION
+            SELECT file_ref FROM cs_{0}.item_file_version WHERE item_id = :p_item_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+        let mut dataset = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, [{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        let mut file_refs = vec![];
+        while dataset.next() {
+            file_refs.push(dataset.get_string("file_ref").ok_or(anyhow!("Wrong file_ref"))?);
+        }
+        Ok(file_refs)
+    }
+
+    /// Ids of the other items with a link tag value to the item
+    async fn search_linking_items(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<Vec<i64>> {
+        let sql_query = format!(
+            r"SELECT DISTINCT item_id FROM cs_{}.tag_value WHERE value_link = :p_item_id AND item_id <> :p_item_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+        let mut dataset = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, [{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        let mut item_ids = vec![];
+        while dataset.next() {
+            item_ids.push(dataset.get_int("item_id").ok_or(anyhow!("Wrong item_id"))?);
+        }
+        Ok(item_ids)
+    }
+
+    /// Full text documents of the current and previous files of the item
+    async fn delete_item_documents(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r"DELETE FROM cs_{0}.document WHERE file_ref IN (
+                SELECT file_ref FROM cs_{0}.item WHERE id = :p_item_id
+                UNION
+                SELECT file_ref FROM cs_{0}.item_file_version WHERE item_id = :p_item_id )",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let query = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        let _ = query.delete(trans).await.map_err(err_fwd!(
+            "💣 Query failed, [{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+        Ok(())
+    }
+
+    /// Delete the item, its tag values, the link tag values of the other items to it and its versions
+    async fn remove_item(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let sql_deletes = [
+            format!(
+                r"DELETE FROM cs_{}.tag_value WHERE item_id = :p_item_id OR value_link = :p_item_id",
+                customer_code
+            ),
+            format!(r"DELETE FROM cs_{}.item_file_version WHERE item_id = :p_item_id", customer_code),
+            format!(r"DELETE FROM cs_{}.item WHERE id = :p_item_id", customer_code),
+        ];
+
+        for sql_query in sql_deletes {
+            let mut params = HashMap::new();
+            params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+            let query = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+            let _ = query.delete(trans).await.map_err(err_fwd!(
+                "💣 Query failed, [{}], follower=[{}]",
+                &query.sql_query,
+                &self.follower
+            ))?;
+        }
+        Ok(())
+    }
+
+    async fn rename_item(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        item_name: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r"UPDATE cs_{}.item SET name = :p_name, last_modified_gmt = :p_last_modified WHERE id = :p_item_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_name".to_string(), CellValue::from_raw_str(item_name));
+        params.insert("p_last_modified".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+        Ok(())
+    }
+
     /// find if the tag is already assigned to the item
     async fn is_tags_on_item(
         &self,
@@ -1044,6 +2056,7 @@ impl ItemDelegate {
                     name: tag_name.clone(),
                     tag_type: Self::enum_tag_value_to_tag_type(&prop),
                     default_value: None,
+                    constraints: TagConstraints::default(),
                 };
 
                 if let Err(err) = tag_delegate.check_input_values(&add_tag_request) {
@@ -1094,8 +2107,8 @@ impl ItemDelegate {
         // FIXME BUG: we named the variable :p_val_date because otherwise it conflict with :p_value_datetime
         //              the replacement expression should be ":variable:" to avoid this case
         let sql_query = format!(
-            r"INSERT INTO cs_{}.tag_value (tag_id, item_id, value_boolean, value_string, value_integer, value_double, value_date, value_datetime)
-                 VALUES (:p_tag_id, :p_item_id, :p_value_boolean, :p_value_string, :p_value_integer, :p_value_double, :p_val_date, :p_value_datetime) ",
+            r"INSERT INTO cs_{}.tag_value (tag_id, item_id, value_boolean, value_string, value_integer, value_double, value_date, value_datetime, value_link)
+                 VALUES (:p_tag_id, :p_item_id, :p_value_boolean, :p_value_string, :p_value_integer, :p_value_double, :p_val_date, :p_value_datetime, :p_value_link) ",
             customer_code
         );
 
@@ -1118,7 +2131,7 @@ impl ItemDelegate {
         Ok(())
     }
 
-    fn build_params_for_insert_and_update(
+    pub(crate) fn build_params_for_insert_and_update(
         &self,
         tag: &AddTagValue,
         mut params: HashMap<String, CellValue>,
@@ -1129,6 +2142,7 @@ impl ItemDelegate {
         params.insert("p_value_double".to_string(), CellValue::Double(None));
         params.insert("p_val_date".to_string(), CellValue::Date(None));
         params.insert("p_value_datetime".to_string(), CellValue::SystemTime(None));
+        params.insert("p_value_link".to_string(), CellValue::Int(None));
 
         match &tag.value {
             EnumTagValue::Text(tv) => {
@@ -1172,7 +2186,7 @@ impl ItemDelegate {
                 params.insert("p_value_datetime".to_string(), CellValue::SystemTime(opt_st));
             }
             EnumTagValue::Link(tv) => {
-                params.insert("p_value_string".to_string(), CellValue::String(tv.clone()));
+                params.insert("p_value_link".to_string(), CellValue::Int(*tv));
             }
         }
         params
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use anyhow::anyhow;
+use axum::http::StatusCode;
+use axum::Json;
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+
+use commons_error::*;
+use commons_pg::sql_transaction::{date_time_to_iso, CellValue};
+use commons_pg::sql_transaction_async::{SQLChangeAsync, SQLConnectionAsync, SQLQueryBlockAsync, SQLTransactionAsync};
+use commons_services::session_lib::valid_sid_get_session;
+use commons_services::token_lib::SessionToken;
+use commons_services::try_or_return;
+use commons_services::x_request_id::{Follower, XRequestID};
+use dkdto::api_error::ApiError;
+use dkdto::error_codes::{FILE_ALREADY_ATTACHED, INTERNAL_DATABASE_ERROR, ITEM_VERSION_NOT_FOUND, MISSING_ITEM};
+use dkdto::web_types::{AddItemVersionRequest, ItemVersionElement, ItemVersionReply, WebType, WebTypeBuilder};
+use doka_cli::request_client::TokenType;
+
+use crate::item::ItemDelegate;
+
+///
+/// Versions of the file of an item.
+/// The item.file_ref is the current version, the item_file_version table keeps all the versions.
+/// An item created with a file has no version row until a second version is added,
+/// its file is the implicit version number 1
+///
+pub(crate) struct ItemVersionDelegate {
+    pub session_token: SessionToken,
+    pub follower: Follower,
+}
+
+impl ItemVersionDelegate {
+    pub fn new(session_token: SessionToken, x_request_id: XRequestID) -> Self {
+        Self {
+            session_token,
+            follower: Follower { x_request_id: x_request_id.new_if_null(), token_type: TokenType::None },
+        }
+    }
+
+    ///
+    /// 🌟 All the file versions of the item [item_id], ordered by version number
+    ///
+    pub async fn get_item_versions(mut self, item_id: i64) -> WebType<ItemVersionReply> {
+        log_info!("🚀 Start get_item_versions api, item_id=[{}], follower=[{}]", item_id, &self.follower);
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if let Err(e) = self.check_item(&mut trans, item_id, customer_code).await {
+            return WebType::from_api_error(e);
+        }
+
+        let Ok(mut list_of_versions) = self
+            .search_item_versions(&mut trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot read the versions, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        self.add_file_details(&mut list_of_versions).await;
+
+        log_info!(
+            "🏁 End get_item_versions, version count=[{}], follower=[{}]",
+            list_of_versions.len(),
+            &self.follower
+        );
+        WebType::from_item(StatusCode::OK.as_u16(), ItemVersionReply { item_id, list_of_versions })
+    }
+
+    ///
+    /// 🌟 Attach an uploaded file as the new current version of the item [item_id]
+    ///
+    pub async fn add_item_version(
+        mut self,
+        item_id: i64,
+        add_item_version_request: Json<AddItemVersionRequest>,
+    ) -> WebType<ItemVersionReply> {
+        log_info!(
+            "🚀 Start add_item_version api, item_id=[{}], file_ref=[{}], follower=[{}]",
+            item_id,
+            &add_item_version_request.file_ref,
+            &self.follower
+        );
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+        let file_ref = add_item_version_request.file_ref.as_str();
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if let Err(e) = self.check_item(&mut trans, item_id, customer_code).await {
+            return WebType::from_api_error(e);
+        }
+
+        if let Err(e) = self.attach_file(&mut trans, item_id, file_ref, customer_code).await {
+            return WebType::from_api_error(e);
+        }
+
+        let Ok(mut list_of_versions) = self
+            .search_item_versions(&mut trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot read the versions, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        self.add_file_details(&mut list_of_versions).await;
+
+        log_info!(
+            "😎 Added the version, item_id=[{}], file_ref=[{}], follower=[{}]",
+            item_id,
+            file_ref,
+            &self.follower
+        );
+        log_info!("🏁 End add_item_version, follower=[{}]", &self.follower);
+        WebType::from_item(StatusCode::OK.as_u16(), ItemVersionReply { item_id, list_of_versions })
+    }
+
+    ///
+    /// 🌟 Make the version [version_number] the current file of the item [item_id].
+    /// The versions are kept as they are, only the current one changes
+    ///
+    pub async fn restore_item_version(mut self, item_id: i64, version_number: u32) -> WebType<ItemVersionReply> {
+        log_info!(
+            "🚀 Start restore_item_version api, item_id=[{}], version_number=[{}], follower=[{}]",
+            item_id,
+            version_number,
+            &self.follower
+        );
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if let Err(e) = self.check_item(&mut trans, item_id, customer_code).await {
+            return WebType::from_api_error(e);
+        }
+
+        let Ok(versions) = self
+            .search_item_versions(&mut trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot read the versions, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Some(version) = versions.iter().find(|version| version.version_number == version_number) else {
+            log_warn!("⛔ Version not found, version_number=[{}], follower=[{}]", version_number, &self.follower);
+            return WebType::from_api_error(&ITEM_VERSION_NOT_FOUND);
+        };
+
+        if !version.is_current
+            && self
+                .update_item_file_ref(&mut trans, item_id, &version.file_ref, customer_code)
+                .await
+                .map_err(err_fwd!("💣 Cannot change the file of the item, follower=[{}]", &self.follower))
+                .is_err()
+        {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        let Ok(mut list_of_versions) = self
+            .search_item_versions(&mut trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot read the versions, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        self.add_file_details(&mut list_of_versions).await;
+
+        log_info!("🏁 End restore_item_version, follower=[{}]", &self.follower);
+        WebType::from_item(StatusCode::OK.as_u16(), ItemVersionReply { item_id, list_of_versions })
+    }
+
+    /// Make the uploaded file [file_ref] the new current version of the item [item_id]
+    pub(crate) async fn attach_file(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        file_ref: &str,
+        customer_code: &str,
+    ) -> Result<(), &'static ApiError<'static>> {
+        match self.is_file_attached(trans, file_ref, customer_code).await {
+            Ok(false) => {}
+            Ok(true) => {
+                log_warn!("⛔ The file is already attached, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+                return Err(&*FILE_ALREADY_ATTACHED);
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the file attachments, e=[{}], follower=[{}]", e, &self.follower);
+                return Err(&*INTERNAL_DATABASE_ERROR);
+            }
+        }
+
+        // The current file of the item becomes the version 1, if it has no version yet
+        if self
+            .insert_first_version(trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot record the first version, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return Err(&*INTERNAL_DATABASE_ERROR);
+        }
+
+        if self
+            .insert_next_version(trans, item_id, file_ref, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot record the new version, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return Err(&*INTERNAL_DATABASE_ERROR);
+        }
+
+        if self
+            .update_item_file_ref(trans, item_id, file_ref, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot change the file of the item, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return Err(&*INTERNAL_DATABASE_ERROR);
+        }
+        Ok(())
+    }
+
+    /// Remove the current file of the item [item_id], the file stays in the versions of the item
+    pub(crate) async fn detach_file(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> Result<(), &'static ApiError<'static>> {
+        // The current file of the item becomes the version 1, if it has no version yet
+        if self
+            .insert_first_version(trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot record the first version, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return Err(&*INTERNAL_DATABASE_ERROR);
+        }
+
+        if self
+            .clear_item_file_ref(trans, item_id, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot detach the file of the item, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return Err(&*INTERNAL_DATABASE_ERROR);
+        }
+        Ok(())
+    }
+
+    async fn check_item(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> Result<(), &'static ApiError<'static>> {
+        let sql_query = format!(r"SELECT 1 FROM cs_{}.item WHERE id = :p_item_id", customer_code);
+
+        let mut params = HashMap::new();
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+        match query.execute(trans).await {
+            Ok(dataset) if dataset.len() > 0 => Ok(()),
+            Ok(_) => {
+                log_error!("💣 Missing item=[{}], follower=[{}]", item_id, &self.follower);
+                Err(&*MISSING_ITEM)
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the item, e=[{}], follower=[{}]", e, &self.follower);
+                Err(&*INTERNAL_DATABASE_ERROR)
+            }
+        }
+    }
+
+    /// The versions of the item, with the implicit version 1 when the item has a file but no version row.
+    /// The size and the checksum of the files are added by [Self::add_file_details]
+    async fn search_item_versions(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<Vec<ItemVersionElement>> {
+        let sql_query = format!(
+            r"SELECT v.version_number, v.file_ref, v.created_gmt, v.file_ref = i.file_ref AS is_current
+                FROM cs_{0}.item_file_version v
+                INNER JOIN cs_{0}.item i ON i.id = v.item_id
+                WHERE v.item_id = :p_item_id
+            UNION ALL
+            SELECT 1, i.file_ref, i.created_gmt, true
+                FROM cs_{0}.item i
+                WHERE i.id = :p_item_id AND i.file_ref IS NOT NULL
+                AND NOT EXISTS (SELECT 1 FROM cs_{0}.item_file_version v WHERE v.item_id = i.id)
+            ORDER BY version_number",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+        let mut dataset = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, [{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        let mut versions = Vec::with_capacity(dataset.len());
+        while dataset.next() {
+            let created = dataset.get_timestamp_as_datetime("created_gmt").ok_or(anyhow!("Wrong created_gmt"))?;
+            versions.push(ItemVersionElement {
+                version_number: dataset.get_int_32("version_number").ok_or(anyhow!("Wrong version_number"))? as u32,
+                file_ref: dataset.get_string("file_ref").ok_or(anyhow!("Wrong file_ref"))?,
+                created: date_time_to_iso(&created),
+                is_current: dataset.get_bool("is_current").unwrap_or(false),
+                file_size: None,
+                checksum: None,
+            });
+        }
+        Ok(versions)
+    }
+
+    /// The size and the checksum of the files of the versions, kept by the file server.
+    /// A file the file server cannot give is only listed without them
+    async fn add_file_details(&self, versions: &mut [ItemVersionElement]) {
+        let file_server_client = match ItemDelegate::find_file_server_client() {
+            Ok(client) => client,
+            Err(e) => {
+                log_error!("💣 Cannot build the file server client, e=[{}], follower=[{}]", e, &self.follower);
+                return;
+            }
+        };
+
+        for version in versions.iter_mut() {
+            match file_server_client.info(&version.file_ref, &self.session_token.0).await {
+                Ok(info) => {
+                    version.file_size = info.original_file_size.map(|size| size as u64);
+                    version.checksum = info.checksum;
+                }
+                Err(e) => {
+                    log_warn!(
+                        "⛔ No information about the file of the version, file_ref=[{}], e=[{:?}], follower=[{}]",
+                        &version.file_ref,
+                        e,
+                        &self.follower
+                    );
+                }
+            }
+        }
+    }
+
+    /// The file is the current file or a version of an item
+    async fn is_file_attached(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        file_ref: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<bool> {
+        let sql_query = format!(
+            r"SELECT 1 FROM cs_{0}.item WHERE file_ref = :p_file_ref
+            UNION ALL
+            SELECT 1 FROM cs_{0}.item_file_version WHERE file_ref = :p_file_ref",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+        let dataset = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, [{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        Ok(dataset.len() > 0)
+    }
+
+    async fn insert_first_version(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r"INSERT INTO cs_{0}.item_file_version (item_id, version_number, file_ref, created_gmt)
+                SELECT i.id, 1, i.file_ref, i.created_gmt
+                FROM cs_{0}.item i
+                WHERE i.id = :p_item_id AND i.file_ref IS NOT NULL
+                AND NOT EXISTS (SELECT 1 FROM cs_{0}.item_file_version v WHERE v.item_id = i.id)",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let sql_insert = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_insert.insert_no_pk(trans).await.map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
+        Ok(())
+    }
+
+    async fn insert_next_version(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        file_ref: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r"INSERT INTO cs_{0}.item_file_version (item_id, version_number, file_ref, created_gmt)
+                SELECT :p_item_id, COALESCE(MAX(v.version_number), 0) + 1, :p_file_ref, :p_created
+                FROM cs_{0}.item_file_version v
+                WHERE v.item_id = :p_item_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+        params.insert("p_created".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
+
+        let sql_insert = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_insert.insert_no_pk(trans).await.map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
+        Ok(())
+    }
+
+    async fn clear_item_file_ref(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r"UPDATE cs_{}.item SET file_ref = NULL, last_modified_gmt = :p_last_modified WHERE id = :p_item_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_last_modified".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+        Ok(())
+    }
+
+    async fn update_item_file_ref(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        item_id: i64,
+        file_ref: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r"UPDATE cs_{}.item SET file_ref = :p_file_ref, last_modified_gmt = :p_last_modified WHERE id = :p_item_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+        params.insert("p_last_modified".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
+        params.insert("p_item_id".to_string(), CellValue::from_raw_int(item_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+        Ok(())
+    }
+
+    fn web_type_error<T>() -> impl Fn(&ApiError<'static>) -> WebType<T>
+    where
+        T: DeserializeOwned,
+    {
+        |e| {
+            log_error!("💣 Error after try {:?}", e);
+            WebType::from_api_error(e)
+        }
+    }
+}
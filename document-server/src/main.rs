@@ -2,7 +2,7 @@ use std::net::SocketAddr;
 use std::process::exit;
 
 use axum::extract::{Path, Query};
-use axum::routing::{delete, get, post};
+use axum::routing::{delete, get, patch, post};
 use axum::{Json, Router};
 use log::{error, info};
 use serde_derive::{Deserialize, Serialize};
@@ -16,13 +16,15 @@ use common_config::conf_reader::{read_config, read_env};
 use common_config::properties::{get_prop_pg_connect_string, get_prop_value, set_prop_values};
 use common_config::property_name::{COMMON_EDIBLE_KEY_PROPERTY, LOG_CONFIG_FILE_PROPERTY, SERVER_PORT_PROPERTY};
 use dkdto::web_types::{
-    AddItemReply, AddItemRequest, AddItemTagReply, AddItemTagRequest, AddTagReply, AddTagRequest,
-    DeleteFullTextRequest, FullTextReply, FullTextRequest, GetItemReply, GetTagReply, SimpleMessage, WebType,
-    WebTypeBuilder, WebTypeWithContext,
+    AddItemReply, AddItemRequest, AddItemTagReply, AddItemTagRequest, AddItemVersionRequest, AddTagReply,
+    AddTagRequest, BulkItemTagReply, BulkItemTagRequest, DeleteFullTextRequest, DeleteItemReply, FullTextReply,
+    FullTextRequest, FullTextSearchReply, GetItemReply, GetTagReply, ItemVersionReply, PatchItemRequest, PatchTagReply,
+    PatchTagRequest, SimpleMessage, WebType, WebTypeBuilder, WebTypeWithContext,
 };
 
 use crate::fulltext::FullTextDelegate;
 use crate::item::ItemDelegate;
+use crate::item_version::ItemVersionDelegate;
 use crate::tag::TagDelegate;
 
 mod char_lib;
@@ -31,6 +33,7 @@ mod filter;
 mod ft_tokenizer;
 mod fulltext;
 mod item;
+mod item_version;
 mod language;
 mod tag;
 
@@ -56,21 +59,29 @@ pub struct SearchQuery {
     pub start_page: Option<u32>,
     pub page_size: Option<u32>,
     pub filters: Option<String>,
-    pub order_tags: Option<Vec<String>>,
+    /// Tags or item columns (name, created, last_modified) to sort on, separated by commas,
+    /// with a "-" for a descending order, ex : -created,lastname
+    pub order_tags: Option<String>,
+    /// Tags returned with the items, separated by commas, ex : lastname,postal_code
+    pub select_tags: Option<String>,
+    /// Cursor of the page to get, from the previous reply, it replaces the start_page
+    pub cursor: Option<String>,
 }
 
 ///
-/// 🌟 Find all the items at page [start_page]
+/// 🌟 Find all the items at page [start_page], or after the [cursor]
 /// **NORM
 ///
-/// #[get("/search?<start_page>&<page_size>&<filters>")]
+/// #[get("/search?<start_page>&<page_size>&<filters>&<order_tags>&<select_tags>&<cursor>")]
 pub async fn search_item(
     Query(page): Query<SearchQuery>,
     session_token: SessionToken,
 ) -> WebTypeWithContext<GetItemReply> {
     let delegate = ItemDelegate::new(session_token, XRequestID::from_value(None));
+    let order_tags = page.order_tags.map(|tags| tags.split(',').map(str::to_string).collect());
+    let select_tags = page.select_tags.map(|tags| tags.split(',').map(str::to_string).collect());
 
-    delegate.search_item(page.start_page, page.page_size, page.filters, page.order_tags).await
+    delegate.search_item(page.start_page, page.page_size, page.filters, order_tags, select_tags, page.cursor).await
 }
 
 ///
@@ -83,6 +94,20 @@ pub(crate) async fn get_item(Path(item_id): Path<i64>, session_token: SessionTok
     delegate.get_item(item_id).await
 }
 
+///
+/// 🌟 Find the items with a link tag pointing to the item [item_id]
+/// **NORM
+///
+/// #[get("/item/<item_id>/backlinks?<start_page>&<page_size>")]
+pub(crate) async fn get_linked_items(
+    Path(item_id): Path<i64>,
+    Query(page): Query<PageQuery>,
+    session_token: SessionToken,
+) -> WebType<GetItemReply> {
+    let delegate = ItemDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.get_linked_items(item_id, page.start_page, page.page_size).await
+}
+
 ///
 /// 🌟 Create an item and all its tags
 ///     A tag can be existing or not
@@ -117,6 +142,119 @@ pub(crate) async fn update_item_tag(
     delegate.update_item_tag(item_id, add_item_tag_request).await
 }
 
+///
+/// 🌟 Update tags on all the items of a file
+/// Used from file-server, to set the metadata of the file as tags
+///
+/// ```
+/// #[post(
+///     "/file_tags/<file_ref>",
+///     format = "application/json",
+///     data = "<add_item_tag_request>"
+/// )]
+/// ```
+pub(crate) async fn update_file_item_tags(
+    session_token: SessionToken,
+    Path(file_ref): Path<String>,
+    add_item_tag_request: Json<AddItemTagRequest>,
+) -> WebType<AddItemTagReply> {
+    let delegate = ItemDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.update_file_item_tags(&file_ref, add_item_tag_request).await
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeleteItemQuery {
+    /// Also delete the files of the item, on the file server and in the full text index
+    pub delete_file: Option<bool>,
+}
+
+///
+/// 🌟 Delete an item, with its tags and its versions
+///
+/// #[delete("/item/<item_id>?<delete_file>")]
+pub(crate) async fn delete_item(
+    session_token: SessionToken,
+    Path(item_id): Path<i64>,
+    Query(query): Query<DeleteItemQuery>,
+) -> WebType<DeleteItemReply> {
+    let delegate = ItemDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.delete_item(item_id, query.delete_file.unwrap_or(false)).await
+}
+
+///
+/// 🌟 Rename an item, attach or detach its file
+///
+/// ```
+/// #[patch(
+///     "/item/<item_id>",
+///     format = "application/json",
+///     data = "<patch_item_request>"
+/// )]
+/// ```
+pub(crate) async fn patch_item(
+    session_token: SessionToken,
+    Path(item_id): Path<i64>,
+    patch_item_request: Json<PatchItemRequest>,
+) -> WebType<GetItemReply> {
+    let delegate = ItemDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.patch_item(item_id, patch_item_request).await
+}
+
+///
+/// 🌟 Add and remove tags on all the items matching the filters
+///
+/// #[post("/search/tags", format = "application/json", data = "<bulk_item_tag_request>")]
+pub(crate) async fn bulk_item_tags(
+    session_token: SessionToken,
+    bulk_item_tag_request: Json<BulkItemTagRequest>,
+) -> WebTypeWithContext<BulkItemTagReply> {
+    let delegate = ItemDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.bulk_item_tags(bulk_item_tag_request).await
+}
+
+///
+/// 🌟 Get all the file versions of an item
+///
+/// #[get("/item/<item_id>/versions")]
+pub(crate) async fn get_item_versions(
+    session_token: SessionToken,
+    Path(item_id): Path<i64>,
+) -> WebType<ItemVersionReply> {
+    let delegate = ItemVersionDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.get_item_versions(item_id).await
+}
+
+///
+/// 🌟 Attach an uploaded file as the new current version of an item
+///
+/// ```
+/// #[post(
+///     "/item/<item_id>/versions",
+///     format = "application/json",
+///     data = "<add_item_version_request>"
+/// )]
+/// ```
+pub(crate) async fn add_item_version(
+    session_token: SessionToken,
+    Path(item_id): Path<i64>,
+    add_item_version_request: Json<AddItemVersionRequest>,
+) -> WebType<ItemVersionReply> {
+    let delegate = ItemVersionDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.add_item_version(item_id, add_item_version_request).await
+}
+
+///
+/// 🌟 Make a previous version the current file of an item
+///
+/// #[post("/item/<item_id>/versions/<version_number>/restore")]
+pub(crate) async fn restore_item_version(
+    session_token: SessionToken,
+    Path((item_id, version_number)): Path<(i64, u32)>,
+) -> WebType<ItemVersionReply> {
+    let delegate = ItemVersionDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.restore_item_version(item_id, version_number).await
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DeleteTagsQuery {
     pub names: Vec<String>,
@@ -160,6 +298,20 @@ pub(crate) async fn delete_tag(session_token: SessionToken, Path(tag_id): Path<i
     delegate.delete_tag(tag_id).await
 }
 
+///
+/// 🌟 Change a tag definition, and convert its values to the new type
+/// **NORM
+///
+/// #[patch("/tag/<tag_id>", format = "application/json", data = "<patch_tag_request>")]
+pub(crate) async fn update_tag(
+    session_token: SessionToken,
+    Path(tag_id): Path<i64>,
+    patch_tag_request: Json<PatchTagRequest>,
+) -> WebType<PatchTagReply> {
+    let delegate = TagDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.update_tag(tag_id, patch_tag_request).await
+}
+
 ///
 /// 🌟 Create a new tag
 /// **NORM
@@ -211,6 +363,26 @@ pub(crate) async fn delete_text_indexing(
     delegate.delete_text_indexing(delete_text_request).await
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct FullTextSearchQuery {
+    pub text: String,
+    pub start_page: Option<u32>,
+    pub page_size: Option<u32>,
+}
+
+///
+/// 🌟 Find the items whose document contains all the words of the text
+/// **NORM
+///
+/// #[get("/fulltext_search?<text>&<start_page>&<page_size>")]
+pub(crate) async fn fulltext_search(
+    Query(search): Query<FullTextSearchQuery>,
+    session_token: SessionToken,
+) -> WebType<FullTextSearchReply> {
+    let delegate = FullTextDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.fulltext_search(&search.text, search.start_page, search.page_size).await
+}
+
 #[tokio::main]
 async fn main() {
     const PROGRAM_NAME: &str = "Document Server";
@@ -279,15 +451,25 @@ async fn main() {
     let key_routes = Router::new()
         .route("/item", get(get_all_item))
         .route("/search", get(search_item))
+        .route("/search/tags", post(bulk_item_tags))
         .route("/item/:item_id", get(get_item))
+        .route("/item/:item_id", delete(delete_item))
+        .route("/item/:item_id", patch(patch_item))
+        .route("/item/:item_id/backlinks", get(get_linked_items))
         .route("/item", post(add_item))
         .route("/item/:item_id/tags", post(update_item_tag))
         .route("/item/:item_id/tags", delete(delete_item_tag))
+        .route("/item/:item_id/versions", get(get_item_versions))
+        .route("/item/:item_id/versions", post(add_item_version))
+        .route("/item/:item_id/versions/:version_number/restore", post(restore_item_version))
+        .route("/file_tags/:file_ref", post(update_file_item_tags))
         .route("/tag", get(get_all_tag))
         .route("/tag", post(add_tag))
         .route("/tag/:tag_id", delete(delete_tag))
+        .route("/tag/:tag_id", patch(update_tag))
         .route("/fulltext_indexing", post(fulltext_indexing))
-        .route("/delete_text_indexing", post(delete_text_indexing));
+        .route("/delete_text_indexing", post(delete_text_indexing))
+        .route("/fulltext_search", get(fulltext_search));
 
     let app = Router::new().nest(&base_url, key_routes);
 
@@ -2,7 +2,9 @@ use anyhow::anyhow;
 use axum::http::StatusCode;
 use axum::Json;
 use log::{debug, error, info};
+use regex::Regex;
 use serde::de::DeserializeOwned;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::str::FromStr;
 
@@ -17,15 +19,21 @@ use dkdto::api_error::ApiError;
 use dkdto::error_codes::{
     INCORRECT_CHAR_TAG_NAME, INCORRECT_DEFAULT_BOOLEAN_VALUE, INCORRECT_DEFAULT_DATETIME_VALUE,
     INCORRECT_DEFAULT_DATE_VALUE, INCORRECT_DEFAULT_DOUBLE_VALUE, INCORRECT_DEFAULT_INTEGER_VALUE,
-    INCORRECT_DEFAULT_LINK_LENGTH, INCORRECT_DEFAULT_STRING_LENGTH, INCORRECT_LENGTH_TAG_NAME, INCORRECT_TAG_TYPE,
-    INTERNAL_DATABASE_ERROR, STILL_IN_USE,
+    INCORRECT_DEFAULT_LINK_VALUE, INCORRECT_DEFAULT_STRING_LENGTH, INCORRECT_LENGTH_TAG_NAME, INCORRECT_TAG_CONSTRAINT,
+    INCORRECT_TAG_TYPE, INTERNAL_DATABASE_ERROR, MISSING_REQUIRED_TAG, STILL_IN_USE, TAG_CONVERSION_FAILED,
+    TAG_NAME_ALREADY_TAKEN, TAG_NOT_FOUND, TAG_VALUE_NOT_ALLOWED, TAG_VALUE_OUT_OF_RANGE, TAG_VALUE_PATTERN_MISMATCH,
 };
 use dkdto::web_types::{
-    AddTagReply, AddTagRequest, GetTagReply, SimpleMessage, TagElement, TagType, WebType, WebTypeBuilder,
+    AddTagReply, AddTagRequest, AddTagValue, EnumTagValue, GetTagReply, PatchTagReply, PatchTagRequest, SimpleMessage,
+    TagConstraints, TagElement, TagType, TagValueConversionFailure, WebType, WebTypeBuilder,
 };
 use doka_cli::request_client::TokenType;
 
 use crate::char_lib::has_not_printable_char;
+use crate::item::{read_enum_tag_value, ItemDelegate};
+
+/// Size of the value_string column of the tag values
+const MAX_STRING_LENGTH: usize = 2000;
 
 pub(crate) struct TagDelegate {
     pub session_token: SessionToken,
@@ -92,8 +100,25 @@ impl TagDelegate {
         let name: String = sql_result.get_string("name").ok_or(anyhow!("Wrong name"))?;
         let tag_type = sql_result.get_string("type").ok_or(anyhow!("Wrong tag_type"))?;
         let default_value = sql_result.get_string("default_value"); // optional
+        let string_tag_length = sql_result.get_int_32("string_tag_length"); // optional
+        let constraints = Self::map_current_row_to_constraints(sql_result)?;
+
+        Ok(TagElement { tag_id: id, name, tag_type, default_value, string_tag_length, constraints })
+    }
+
+    #[inline]
+    fn map_current_row_to_constraints(sql_result: &SQLDataSet) -> anyhow::Result<TagConstraints> {
+        let required = sql_result.get_bool("required").unwrap_or(false);
+        // The allowed values are stored as a json array
+        let allowed_values = match sql_result.get_string("allowed_values") {
+            Some(json) => Some(serde_json::from_str(&json).map_err(tr_fwd!())?),
+            None => None,
+        };
+        let min_value = sql_result.get_string("min_value");
+        let max_value = sql_result.get_string("max_value");
+        let pattern = sql_result.get_string("pattern");
 
-        Ok(TagElement { tag_id: id, name, tag_type, default_value })
+        Ok(TagConstraints { required, allowed_values, min_value, max_value, pattern })
     }
 
     /// Search items by id
@@ -112,7 +137,7 @@ impl TagDelegate {
         params.insert("p_tag_id".to_owned(), p_tag_id);
 
         let sql_query = format!(
-            r"SELECT id, name, type, string_tag_length, default_value
+            r"SELECT id, name, type, string_tag_length, default_value, required, allowed_values, min_value, max_value, pattern
                                     FROM cs_{}.tag_definition
                                     WHERE ( id = :p_tag_id OR :p_tag_id IS NULL )
                                     ORDER BY name ",
@@ -167,7 +192,7 @@ impl TagDelegate {
         };
 
         let sql_query = format!(
-            r#"SELECT id, name, type, string_tag_length, default_value
+            r#"SELECT id, name, type, string_tag_length, default_value, required, allowed_values, min_value, max_value, pattern
                    FROM cs_{0}.tag_definition
                    WHERE name IN {1}
                    ORDER BY name"#,
@@ -212,7 +237,7 @@ impl TagDelegate {
         params.insert("p_tag_name".to_owned(), p_tag_name);
 
         let sql_query = format!(
-            r"SELECT id, name, type, string_tag_length, default_value
+            r"SELECT id, name, type, string_tag_length, default_value, required, allowed_values, min_value, max_value, pattern
                                     FROM cs_{}.tag_definition
                                     WHERE ( name = :p_tag_name )
                                     ORDER BY name ",
@@ -228,16 +253,11 @@ impl TagDelegate {
         ))?;
 
         if sql_result.next() {
-            let id: i64 = sql_result.get_int("id").ok_or(anyhow!("Wrong id"))?;
-            let name: String = sql_result.get_string("name").ok_or(anyhow!("Wrong name"))?;
-            let tag_type = sql_result.get_string("type").ok_or(anyhow!("Wrong tag_type"))?;
-            // optional
-            // let string_tag_length = sql_result.get_int_32("string_tag_length");
-            let default_value = sql_result.get_string("default_value");
+            let tag = Self::map_current_row_to_tag(&sql_result)?;
 
-            log_debug!("Found tag, tag id=[{}], tag_name=[{}], follower=[{}]", id, &name, &self.follower);
+            log_debug!("Found tag, tag id=[{}], tag_name=[{}], follower=[{}]", tag.tag_id, &tag.name, &self.follower);
 
-            Ok(TagElement { tag_id: id, name, tag_type, default_value })
+            Ok(tag)
         } else {
             log_error!("💣 Cannot find the tag, tag_name=[{}], follower=[{}]", tag_name, &self.follower);
             Err(anyhow!("Cannot find tag, tag_name=[{}]", tag_name))
@@ -342,6 +362,442 @@ impl TagDelegate {
         Ok(())
     }
 
+    ///
+    /// 🌟 Change a tag definition : its name, its type, its default value or its string length
+    ///     The values of the tag are converted to the new type, the change is refused if one of them cannot be.
+    ///     A dry run reports the values which cannot be converted, without changing anything
+    ///
+    pub async fn update_tag(mut self, tag_id: i64, patch_tag_request: Json<PatchTagRequest>) -> WebType<PatchTagReply> {
+        log_info!("🚀 Start update_tag api, tag_id=[{}], follower=[{}]", tag_id, &self.follower);
+
+        // Check if the token is valid
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+
+        self.follower.token_type = TokenType::Sid(self.session_token.0.clone());
+
+        let customer_code = entry_session.customer_code.as_str();
+
+        log_info!("😎 We found the session, customer code=[{}], follower=[{}]", customer_code, &self.follower);
+
+        // Open Db connection
+        let Ok(mut cnx) = SQLConnectionAsync::from_pool()
+            .await
+            .map_err(err_fwd!("💣 New Db connection failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(mut trans) = cnx.begin().await.map_err(err_fwd!("💣 Transaction issue, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok(tags) = self
+            .search_tag_by_id(&mut trans, Some(tag_id), None, None, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot find the tag by id, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Some(current_tag) = tags.into_iter().next() else {
+            log_error!("💣 The tag does not exist, tag_id=[{}], follower=[{}]", tag_id, &self.follower);
+            return WebType::from_api_error(&TAG_NOT_FOUND);
+        };
+
+        // The new definition is made of the requested changes and of the current values
+        let new_definition = AddTagRequest {
+            name: patch_tag_request.name.clone().unwrap_or(current_tag.name.clone()),
+            tag_type: patch_tag_request.tag_type.clone().unwrap_or(current_tag.tag_type.clone()).to_lowercase(),
+            default_value: patch_tag_request.default_value.clone().unwrap_or(current_tag.default_value.clone()),
+            constraints: patch_tag_request.constraints.clone().unwrap_or(current_tag.constraints.clone()),
+        };
+        let string_tag_length = patch_tag_request.string_tag_length.or(current_tag.string_tag_length);
+
+        if let Err(e) = self
+            .check_input_values(&new_definition)
+            .and_then(|_| Self::check_string_tag_length(&new_definition, string_tag_length))
+        {
+            log_error!("💣 Tag definition is not correct, err message=[{}], follower=[{}]", e.message, &self.follower);
+            return WebType::from_api_error(e);
+        }
+
+        if new_definition.name != current_tag.name {
+            let Ok(same_name_tags) = self
+                .search_tags_by_names(&mut trans, std::slice::from_ref(&new_definition.name), customer_code)
+                .await
+                .map_err(err_fwd!("💣 Cannot find the tag by name, follower=[{}]", &self.follower))
+            else {
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            };
+
+            if !same_name_tags.is_empty() {
+                log_error!(
+                    "💣 The tag name is already taken, name=[{}], follower=[{}]",
+                    &new_definition.name,
+                    &self.follower
+                );
+                return WebType::from_api_error(&TAG_NAME_ALREADY_TAKEN);
+            }
+        }
+
+        if let Err(e) = self.check_default_link(&mut trans, &new_definition, customer_code).await {
+            return WebType::from_api_error(e);
+        }
+
+        let (Ok(current_type), Ok(new_type)) = (
+            TagType::from_str(current_tag.tag_type.to_lowercase().as_str()),
+            TagType::from_str(new_definition.tag_type.as_str()),
+        ) else {
+            return WebType::from_api_error(&INCORRECT_TAG_TYPE);
+        };
+
+        // Convert the values of the tag
+        let Ok((converted_values, conversion_failures)) = self
+            .convert_tag_values(&mut trans, tag_id, &current_type, &new_definition, string_tag_length, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot convert the values of the tag, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if !conversion_failures.is_empty() && !patch_tag_request.dry_run {
+            log_error!(
+                "💣 Some values of the tag cannot be converted, tag_id=[{}], failure count=[{}], follower=[{}]",
+                tag_id,
+                conversion_failures.len(),
+                &self.follower
+            );
+            return WebType::from_api_error(&TAG_CONVERSION_FAILED);
+        }
+
+        log_info!(
+            "😎 The values of the tag are converted, converted count=[{}], failure count=[{}], follower=[{}]",
+            converted_values.len(),
+            conversion_failures.len(),
+            &self.follower
+        );
+
+        let item_delegate = ItemDelegate { session_token: self.session_token.clone(), follower: self.follower.clone() };
+        for (tag_value_id, value) in converted_values {
+            let tag = AddTagValue { tag_id: Some(tag_id), tag_name: Some(new_definition.name.clone()), value };
+            if item_delegate
+                .change_item_tag_value(&mut trans, &tag, tag_value_id, customer_code)
+                .await
+                .map_err(err_fwd!(
+                    "💣 Cannot change the tag value, tag_value_id=[{}], follower=[{}]",
+                    tag_value_id,
+                    &self.follower
+                ))
+                .is_err()
+            {
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
+        }
+
+        if self
+            .update_tag_definition(&mut trans, tag_id, &new_definition, string_tag_length, customer_code)
+            .await
+            .map_err(err_fwd!(
+                "💣 Cannot change the tag definition, tag_id=[{}], follower=[{}]",
+                tag_id,
+                &self.follower
+            ))
+            .is_err()
+        {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        let defaulted_item_count = match (&new_definition.default_value, patch_tag_request.apply_default) {
+            (Some(default_value), true) => {
+                let Ok(item_count) = self
+                    .apply_default_value(&mut trans, tag_id, &new_type, default_value, customer_code)
+                    .await
+                    .map_err(err_fwd!(
+                        "💣 Cannot apply the default value, tag_id=[{}], follower=[{}]",
+                        tag_id,
+                        &self.follower
+                    ))
+                else {
+                    return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+                };
+                item_count
+            }
+            _ => 0,
+        };
+
+        // A required tag must have a value on all the items
+        let missing_value_item_count = if new_definition.constraints.required {
+            let Ok(item_count) = self
+                .count_items_without_value(&mut trans, tag_id, customer_code)
+                .await
+                .map_err(err_fwd!("💣 Cannot count the items without value, follower=[{}]", &self.follower))
+            else {
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            };
+            item_count
+        } else {
+            0
+        };
+
+        if missing_value_item_count > 0 && !patch_tag_request.dry_run {
+            log_error!(
+                "💣 Some items have no value for the required tag, tag_id=[{}], item count=[{}], follower=[{}]",
+                tag_id,
+                missing_value_item_count,
+                &self.follower
+            );
+            return WebType::from_api_error(&MISSING_REQUIRED_TAG);
+        }
+
+        let Some(tag) = self
+            .search_tag_by_id(&mut trans, Some(tag_id), None, None, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot find the tag by id, follower=[{}]", &self.follower))
+            .ok()
+            .and_then(|tags| tags.into_iter().next())
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        if patch_tag_request.dry_run {
+            trans.rollback().await;
+            log_info!(
+                "😎 Dry run, the changes of the tag are not applied, tag_id=[{}], follower=[{}]",
+                tag_id,
+                &self.follower
+            );
+        } else if trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower)).is_err() {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
+        log_info!("🏁 End update_tag api, follower=[{}]", &self.follower);
+
+        WebType::from_item(
+            StatusCode::OK.as_u16(),
+            PatchTagReply {
+                tag,
+                dry_run: patch_tag_request.dry_run,
+                conversion_failures,
+                defaulted_item_count,
+                missing_value_item_count,
+            },
+        )
+    }
+
+    /// Read all the values of the tag, convert them to the new type and check them against the new constraints.
+    /// Return the converted values by tag value id, none if the type does not change, and the values in failure
+    async fn convert_tag_values(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        tag_id: i64,
+        current_type: &TagType,
+        new_definition: &AddTagRequest,
+        string_tag_length: Option<i32>,
+        customer_code: &str,
+    ) -> anyhow::Result<(Vec<(i64, EnumTagValue)>, Vec<TagValueConversionFailure>)> {
+        let new_type = &TagType::from_str(new_definition.tag_type.as_str())
+            .map_err(|_| anyhow!("Wrong tag type, tag_type=[{}]", &new_definition.tag_type))?;
+        let column_name = current_type.value_column_name();
+        let sql_query = format!(
+            r"SELECT id, item_id, {1}
+                    FROM cs_{0}.tag_value
+                    WHERE tag_id = :p_tag_id
+                    ORDER BY id",
+            customer_code, column_name
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_tag_id".to_owned(), CellValue::from_raw_int(tag_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+
+        let mut sql_result: SQLDataSet = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, sql=[{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        let pattern = compile_tag_pattern(&new_definition.constraints)
+            .map_err(|_| anyhow!("Wrong pattern, pattern=[{:?}]", &new_definition.constraints.pattern))?;
+        let item_delegate = ItemDelegate { session_token: self.session_token.clone(), follower: self.follower.clone() };
+        let mut converted_values = vec![];
+        let mut conversion_failures = vec![];
+        while sql_result.next() {
+            let tag_value_id: i64 = sql_result.get_int("id").ok_or(anyhow!("Wrong id"))?;
+            let item_id: i64 = sql_result.get_int("item_id").ok_or(anyhow!("Wrong item_id"))?;
+            let value = read_enum_tag_value(&sql_result, current_type, column_name);
+
+            let r_converted = match convert_tag_value(&value, new_type, string_tag_length) {
+                // A new link must point to an existing item
+                Ok(EnumTagValue::Link(Some(link_id))) if current_type != new_type => {
+                    if item_delegate.is_item_existing(trans, link_id, customer_code).await? {
+                        Ok(EnumTagValue::Link(Some(link_id)))
+                    } else {
+                        Err(format!("The linked item does not exist: {}", link_id))
+                    }
+                }
+                r => r,
+            };
+
+            let r_converted = r_converted.and_then(|converted| {
+                match check_tag_value(new_type, &new_definition.constraints, pattern.as_ref(), &converted) {
+                    Ok(()) => Ok(converted),
+                    Err(e) => Err(e.message.to_string()),
+                }
+            });
+
+            match r_converted {
+                Ok(converted) => {
+                    if current_type != new_type {
+                        converted_values.push((tag_value_id, converted));
+                    }
+                }
+                Err(reason) => {
+                    log_debug!(
+                        "The tag value cannot be converted, tag_value_id=[{}], reason=[{}], follower=[{}]",
+                        tag_value_id,
+                        &reason,
+                        &self.follower
+                    );
+                    conversion_failures.push(TagValueConversionFailure {
+                        tag_value_id,
+                        item_id,
+                        value: value.to_string(),
+                        reason,
+                    });
+                }
+            }
+        }
+
+        Ok((converted_values, conversion_failures))
+    }
+
+    async fn update_tag_definition(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        tag_id: i64,
+        tag_definition: &AddTagRequest,
+        string_tag_length: Option<i32>,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r"UPDATE cs_{}.tag_definition
+                SET name = :p_name, type = :p_type, default_value = :p_default_value,
+                    string_tag_length = :p_string_tag_length, required = :p_required,
+                    allowed_values = :p_allowed_values, min_value = :p_min_value, max_value = :p_max_value,
+                    pattern = :p_pattern
+                WHERE id = :p_tag_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_tag_id".to_string(), CellValue::from_raw_int(tag_id));
+        params.insert("p_name".to_string(), CellValue::from_raw_string(tag_definition.name.clone()));
+        params.insert("p_type".to_string(), CellValue::from_raw_string(tag_definition.tag_type.clone()));
+        params.insert("p_default_value".to_string(), CellValue::from_opt_str(tag_definition.default_value.as_deref()));
+        params.insert("p_string_tag_length".to_string(), CellValue::Int32(string_tag_length));
+        Self::add_constraint_params(&tag_definition.constraints, &mut params)?;
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+
+        sql_update.update(trans).await.map_err(err_fwd!("💣 Tag update failed, follower=[{}]", &self.follower))?;
+
+        Ok(())
+    }
+
+    /// Give the default value to all the items without a value for the tag.
+    /// Return the number of items changed
+    async fn apply_default_value(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        tag_id: i64,
+        tag_type: &TagType,
+        default_value: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<u64> {
+        let value = EnumTagValue::from_string(default_value, tag_type.as_str()).map_err(|e| anyhow!(e))?;
+
+        let sql_query = format!(
+            r"SELECT COUNT(*) AS item_count FROM cs_{0}.item i
+                WHERE NOT EXISTS ( SELECT 1 FROM cs_{0}.tag_value tv WHERE tv.tag_id = :p_tag_id AND tv.item_id = i.id )",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_tag_id".to_owned(), CellValue::from_raw_int(tag_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+
+        let mut sql_result: SQLDataSet = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, sql=[{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        let item_count = if sql_result.next() { sql_result.get_int("item_count").unwrap_or(0) as u64 } else { 0 };
+
+        if item_count == 0 {
+            return Ok(0);
+        }
+
+        let sql_query = format!(
+            r"INSERT INTO cs_{0}.tag_value (tag_id, item_id, value_boolean, value_string, value_integer, value_double, value_date, value_datetime, value_link)
+                SELECT :p_tag_id, i.id, :p_value_boolean, :p_value_string, :p_value_integer, :p_value_double, :p_val_date, :p_value_datetime, :p_value_link
+                FROM cs_{0}.item i
+                WHERE NOT EXISTS ( SELECT 1 FROM cs_{0}.tag_value tv WHERE tv.tag_id = :p_tag_id AND tv.item_id = i.id )",
+            customer_code
+        );
+
+        let item_delegate = ItemDelegate { session_token: self.session_token.clone(), follower: self.follower.clone() };
+        let tag = AddTagValue { tag_id: Some(tag_id), tag_name: None, value };
+
+        let mut params = HashMap::new();
+        params.insert("p_tag_id".to_string(), CellValue::from_raw_int(tag_id));
+        params = item_delegate.build_params_for_insert_and_update(&tag, params);
+
+        let sql_insert = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+
+        sql_insert
+            .insert_no_pk(trans)
+            .await
+            .map_err(err_fwd!("💣 Cannot insert the default values, follower=[{}]", &self.follower))?;
+
+        Ok(item_count)
+    }
+
+    /// Number of items without a value for the tag, an empty value counts as no value
+    async fn count_items_without_value(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        tag_id: i64,
+        customer_code: &str,
+    ) -> anyhow::Result<u64> {
+        let sql_query = format!(
+            r"SELECT COUNT(*) AS item_count FROM cs_{0}.item i
+                WHERE NOT EXISTS ( SELECT 1 FROM cs_{0}.tag_value tv WHERE tv.tag_id = :p_tag_id AND tv.item_id = i.id
+                    AND num_nonnulls(tv.value_boolean, tv.value_string, tv.value_integer, tv.value_double,
+                        tv.value_date, tv.value_datetime, tv.value_link) > 0 )",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_tag_id".to_owned(), CellValue::from_raw_int(tag_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+
+        let mut sql_result: SQLDataSet = query.execute(trans).await.map_err(err_fwd!(
+            "Query failed, sql=[{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+
+        Ok(if sql_result.next() { sql_result.get_int("item_count").unwrap_or(0) as u64 } else { 0 })
+    }
+
     ///
     /// 🌟 Create a new tag
     ///
@@ -394,6 +850,10 @@ impl TagDelegate {
             return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
         };
 
+        if let Err(e) = self.check_default_link(&mut trans, &add_tag_request, customer_code).await {
+            return WebType::from_api_error(e);
+        }
+
         let Ok(tag_id) = self
             .insert_tag_definition(&mut trans, &add_tag_request, customer_code)
             .await
@@ -419,8 +879,10 @@ impl TagDelegate {
         customer_code: &str,
     ) -> anyhow::Result<i64> {
         let sql_query = format!(
-            r"INSERT INTO cs_{}.tag_definition(name, string_tag_length, default_value, type)
-	            VALUES (:p_name, :p_string_tag_length , :p_default_value, :p_type)",
+            r"INSERT INTO cs_{}.tag_definition(name, string_tag_length, default_value, type,
+                    required, allowed_values, min_value, max_value, pattern)
+	            VALUES (:p_name, :p_string_tag_length , :p_default_value, :p_type,
+	                :p_required, :p_allowed_values, :p_min_value, :p_max_value, :p_pattern)",
             customer_code
         );
 
@@ -433,6 +895,7 @@ impl TagDelegate {
         params.insert("p_type".to_string(), CellValue::from_raw_string(add_tag_request.tag_type.clone()));
         params.insert("p_string_tag_length".to_string(), length);
         params.insert("p_default_value".to_string(), default_value);
+        Self::add_constraint_params(&add_tag_request.constraints, &mut params)?;
 
         let sql_insert = SQLChangeAsync { sql_query, params, sequence_name };
 
@@ -444,6 +907,22 @@ impl TagDelegate {
         Ok(tag_id)
     }
 
+    fn add_constraint_params(
+        constraints: &TagConstraints,
+        params: &mut HashMap<String, CellValue>,
+    ) -> anyhow::Result<()> {
+        let allowed_values = match &constraints.allowed_values {
+            Some(values) => Some(serde_json::to_string(values).map_err(tr_fwd!())?),
+            None => None,
+        };
+        params.insert("p_required".to_string(), CellValue::Bool(Some(constraints.required)));
+        params.insert("p_allowed_values".to_string(), CellValue::String(allowed_values));
+        params.insert("p_min_value".to_string(), CellValue::from_opt_str(constraints.min_value.as_deref()));
+        params.insert("p_max_value".to_string(), CellValue::from_opt_str(constraints.max_value.as_deref()));
+        params.insert("p_pattern".to_string(), CellValue::from_opt_str(constraints.pattern.as_deref()));
+        Ok(())
+    }
+
     ///
     /// Return a None if the tag definition is correct
     ///
@@ -470,7 +949,6 @@ impl TagDelegate {
         match tag_type {
             TagType::Text => {
                 // The string_length between 0 and 10_000_000
-                const MAX_STRING_LENGTH: usize = 2000;
                 if let Some(default_string) = &add_tag_request.default_value {
                     if default_string.len() > MAX_STRING_LENGTH as usize {
                         return Err(&INCORRECT_DEFAULT_STRING_LENGTH);
@@ -478,11 +956,10 @@ impl TagDelegate {
                 }
             }
             TagType::Link => {
-                // A Link is like a string
-                const MAX_LINK_LENGTH: usize = 400;
-                if let Some(default_string) = &add_tag_request.default_value {
-                    if default_string.len() > MAX_LINK_LENGTH as usize {
-                        return Err(&INCORRECT_DEFAULT_LINK_LENGTH);
+                // A Link is the id of another item
+                if let Some(v) = &add_tag_request.default_value {
+                    if v.parse::<i64>().is_err() {
+                        return Err(&INCORRECT_DEFAULT_LINK_VALUE);
                     }
                 }
             }
@@ -525,6 +1002,108 @@ impl TagDelegate {
             }
         };
 
+        Self::check_tag_constraints(&tag_type, &add_tag_request.constraints)?;
+
+        // The default value must follow the constraints too
+        if let Some(default_value) = &add_tag_request.default_value {
+            let value =
+                EnumTagValue::from_string(default_value, tag_type.as_str()).map_err(|_| &*INCORRECT_TAG_TYPE)?;
+            let pattern = compile_tag_pattern(&add_tag_request.constraints)?;
+            check_tag_value(&tag_type, &add_tag_request.constraints, pattern.as_ref(), &value)?;
+        }
+
+        Ok(())
+    }
+
+    /// The default value of a link tag must be the id of an existing item
+    async fn check_default_link(
+        &self,
+        trans: &mut SQLTransactionAsync<'_>,
+        tag_definition: &AddTagRequest,
+        customer_code: &str,
+    ) -> Result<(), &'static ApiError<'static>> {
+        let (Ok(TagType::Link), Some(default_value)) =
+            (TagType::from_str(tag_definition.tag_type.to_lowercase().as_str()), &tag_definition.default_value)
+        else {
+            return Ok(());
+        };
+
+        let Ok(linked_item_id) = default_value.parse::<i64>() else {
+            return Err(&INCORRECT_DEFAULT_LINK_VALUE);
+        };
+
+        let item_delegate = ItemDelegate { session_token: self.session_token.clone(), follower: self.follower.clone() };
+        match item_delegate.is_item_existing(trans, linked_item_id, customer_code).await {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                log_error!(
+                    "💣 The default value links to a missing item, linked item id=[{}], follower=[{}]",
+                    linked_item_id,
+                    &self.follower
+                );
+                Err(&INCORRECT_DEFAULT_LINK_VALUE)
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the linked item, e=[{}], follower=[{}]", e, &self.follower);
+                Err(&INTERNAL_DATABASE_ERROR)
+            }
+        }
+    }
+
+    /// Each constraint applies to some types of tag only, and its values must be of the type of the tag
+    fn check_tag_constraints(
+        tag_type: &TagType,
+        constraints: &TagConstraints,
+    ) -> Result<(), &'static ApiError<'static>> {
+        if let Some(allowed_values) = &constraints.allowed_values {
+            if !matches!(tag_type, TagType::Text | TagType::Int) || allowed_values.is_empty() {
+                return Err(&INCORRECT_TAG_CONSTRAINT);
+            }
+            if allowed_values.iter().any(|v| EnumTagValue::from_string(v, tag_type.as_str()).is_err()) {
+                return Err(&INCORRECT_TAG_CONSTRAINT);
+            }
+        }
+
+        for bound in [&constraints.min_value, &constraints.max_value].into_iter().flatten() {
+            if compare_tag_values(tag_type, bound, bound).is_none() {
+                return Err(&INCORRECT_TAG_CONSTRAINT);
+            }
+        }
+
+        if let (Some(min_value), Some(max_value)) = (&constraints.min_value, &constraints.max_value) {
+            if compare_tag_values(tag_type, min_value, max_value) == Some(Ordering::Greater) {
+                return Err(&INCORRECT_TAG_CONSTRAINT);
+            }
+        }
+
+        if constraints.pattern.is_some() && (*tag_type != TagType::Text || compile_tag_pattern(constraints).is_err()) {
+            return Err(&INCORRECT_TAG_CONSTRAINT);
+        }
+
+        Ok(())
+    }
+
+    /// The string length of a text tag fits in the value column and is enough for the default value
+    fn check_string_tag_length(
+        tag_definition: &AddTagRequest,
+        string_tag_length: Option<i32>,
+    ) -> Result<(), &'static ApiError<'static>> {
+        let Some(length) = string_tag_length else {
+            return Ok(());
+        };
+
+        if length <= 0 || length as usize > MAX_STRING_LENGTH {
+            return Err(&INCORRECT_DEFAULT_STRING_LENGTH);
+        }
+
+        if let (Ok(TagType::Text), Some(default_string)) =
+            (TagType::from_str(tag_definition.tag_type.as_str()), &tag_definition.default_value)
+        {
+            if default_string.chars().count() > length as usize {
+                return Err(&INCORRECT_DEFAULT_STRING_LENGTH);
+            }
+        }
+
         Ok(())
     }
 
@@ -539,11 +1118,129 @@ impl TagDelegate {
     }
 }
 
+/// Convert a tag value to the type [new_type], a text value must not be longer than [string_tag_length]
+fn convert_tag_value(
+    value: &EnumTagValue,
+    new_type: &TagType,
+    string_tag_length: Option<i32>,
+) -> Result<EnumTagValue, String> {
+    let opt_text = match value {
+        EnumTagValue::Text(v) | EnumTagValue::SimpleDate(v) | EnumTagValue::DateTime(v) => v.clone(),
+        EnumTagValue::Boolean(v) => v.map(|b| b.to_string()),
+        EnumTagValue::Integer(v) | EnumTagValue::Link(v) => v.map(|i| i.to_string()),
+        EnumTagValue::Double(v) => v.map(|d| d.to_string()),
+    };
+
+    let Some(text) = opt_text else {
+        return Ok(empty_tag_value(new_type));
+    };
+
+    let text = match (value, new_type) {
+        // A date becomes the start of the day, a datetime keeps its day
+        (EnumTagValue::SimpleDate(_), TagType::DateTime) => format!("{}T00:00:00Z", text),
+        (EnumTagValue::DateTime(_), TagType::Date) => text.chars().take(10).collect(),
+        _ => text,
+    };
+
+    if let (TagType::Text, Some(length)) = (new_type, string_tag_length) {
+        if text.chars().count() > length as usize {
+            return Err(format!("The value is longer than {} chars", length));
+        }
+    }
+
+    EnumTagValue::from_string(&text, new_type.as_str())
+}
+
+/// Compile the pattern of the constraints once, to check many values with it. The pattern must match the whole value
+pub(crate) fn compile_tag_pattern(constraints: &TagConstraints) -> Result<Option<Regex>, &'static ApiError<'static>> {
+    match &constraints.pattern {
+        None => Ok(None),
+        Some(pattern) => Regex::new(&format!("^(?:{})$", pattern)).map(Some).map_err(|_| &*INCORRECT_TAG_CONSTRAINT),
+    }
+}
+
+/// Check a tag value against the constraints of its tag, an empty value is accepted unless the tag is required.
+/// The [pattern] is the one of the constraints, compiled with [compile_tag_pattern]
+pub(crate) fn check_tag_value(
+    tag_type: &TagType,
+    constraints: &TagConstraints,
+    pattern: Option<&Regex>,
+    value: &EnumTagValue,
+) -> Result<(), &'static ApiError<'static>> {
+    let text = match value {
+        EnumTagValue::Text(None)
+        | EnumTagValue::Boolean(None)
+        | EnumTagValue::Integer(None)
+        | EnumTagValue::Double(None)
+        | EnumTagValue::SimpleDate(None)
+        | EnumTagValue::DateTime(None)
+        | EnumTagValue::Link(None) => {
+            return if constraints.required { Err(&MISSING_REQUIRED_TAG) } else { Ok(()) };
+        }
+        _ => value.to_string(),
+    };
+
+    if let Some(allowed_values) = &constraints.allowed_values {
+        let is_allowed = allowed_values
+            .iter()
+            .any(|allowed| compare_tag_values(tag_type, allowed, &text) == Some(Ordering::Equal) || *allowed == text);
+        if !is_allowed {
+            return Err(&TAG_VALUE_NOT_ALLOWED);
+        }
+    }
+
+    if let Some(min_value) = &constraints.min_value {
+        if compare_tag_values(tag_type, &text, min_value) == Some(Ordering::Less) {
+            return Err(&TAG_VALUE_OUT_OF_RANGE);
+        }
+    }
+
+    if let Some(max_value) = &constraints.max_value {
+        if compare_tag_values(tag_type, &text, max_value) == Some(Ordering::Greater) {
+            return Err(&TAG_VALUE_OUT_OF_RANGE);
+        }
+    }
+
+    if let Some(pattern) = pattern {
+        if !pattern.is_match(&text) {
+            return Err(&TAG_VALUE_PATTERN_MISMATCH);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare two values in the string form of the [tag_type], None if the type has no order or a value is incorrect
+fn compare_tag_values(tag_type: &TagType, left: &str, right: &str) -> Option<Ordering> {
+    match tag_type {
+        TagType::Int => Some(left.parse::<i64>().ok()?.cmp(&right.parse::<i64>().ok()?)),
+        TagType::Double => left.parse::<f64>().ok()?.partial_cmp(&right.parse::<f64>().ok()?),
+        TagType::Date => Some(iso_to_naivedate(left).ok()?.cmp(&iso_to_naivedate(right).ok()?)),
+        TagType::DateTime => Some(iso_to_datetime(left).ok()?.cmp(&iso_to_datetime(right).ok()?)),
+        TagType::Text | TagType::Bool | TagType::Link => None,
+    }
+}
+
+fn empty_tag_value(tag_type: &TagType) -> EnumTagValue {
+    match tag_type {
+        TagType::Text => EnumTagValue::Text(None),
+        TagType::Bool => EnumTagValue::Boolean(None),
+        TagType::Int => EnumTagValue::Integer(None),
+        TagType::Double => EnumTagValue::Double(None),
+        TagType::Date => EnumTagValue::SimpleDate(None),
+        TagType::DateTime => EnumTagValue::DateTime(None),
+        TagType::Link => EnumTagValue::Link(None),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use chrono::{DateTime, Datelike, Timelike, Utc};
 
     use commons_pg::sql_transaction::{iso_to_datetime, iso_to_naivedate};
+    use dkdto::web_types::{EnumTagValue, TagConstraints, TagType};
+
+    use super::{check_tag_value, compile_tag_pattern, convert_tag_value, TagDelegate};
 
     #[test]
     fn is_valid_datetime_test() {
@@ -607,6 +1304,99 @@ mod test {
         }
     }
 
+    #[test]
+    fn convert_tag_value_to_new_type_test() {
+        let v = convert_tag_value(&EnumTagValue::Text(Some("42".to_string())), &TagType::Int, None).unwrap();
+        assert_eq!(Some(42), if let EnumTagValue::Integer(i) = v { i } else { None });
+
+        let v = convert_tag_value(&EnumTagValue::Text(Some("1977-04-22".to_string())), &TagType::Date, None).unwrap();
+        assert_eq!("1977-04-22", v.to_string());
+
+        let v = convert_tag_value(&EnumTagValue::SimpleDate(Some("1977-04-22".to_string())), &TagType::DateTime, None)
+            .unwrap();
+        assert_eq!("1977-04-22T00:00:00Z", v.to_string());
+
+        let v =
+            convert_tag_value(&EnumTagValue::DateTime(Some("1977-04-22T06:12:04Z".to_string())), &TagType::Date, None)
+                .unwrap();
+        assert_eq!("1977-04-22", v.to_string());
+
+        let v = convert_tag_value(&EnumTagValue::Double(Some(3.0)), &TagType::Int, None).unwrap();
+        assert_eq!("3", v.to_string());
+
+        let v = convert_tag_value(&EnumTagValue::Integer(Some(12)), &TagType::Text, Some(10)).unwrap();
+        assert_eq!("12", v.to_string());
+
+        let v = convert_tag_value(&EnumTagValue::Text(None), &TagType::Bool, None).unwrap();
+        assert!(matches!(v, EnumTagValue::Boolean(None)));
+    }
+
+    #[test]
+    fn convert_tag_value_failure_test() {
+        assert!(convert_tag_value(&EnumTagValue::Text(Some("abc".to_string())), &TagType::Int, None).is_err());
+        assert!(convert_tag_value(&EnumTagValue::Text(Some("22/04/1977".to_string())), &TagType::Date, None).is_err());
+        assert!(convert_tag_value(&EnumTagValue::Double(Some(3.5)), &TagType::Int, None).is_err());
+        assert!(convert_tag_value(&EnumTagValue::Integer(Some(1)), &TagType::Bool, None).is_err());
+        assert!(convert_tag_value(&EnumTagValue::Text(Some("too long".to_string())), &TagType::Text, Some(3)).is_err());
+    }
+
+    #[test]
+    fn check_tag_value_test() {
+        let constraints =
+            TagConstraints { allowed_values: Some(vec!["red".to_string(), "blue".to_string()]), ..Default::default() };
+        assert!(
+            check_tag_value(&TagType::Text, &constraints, None, &EnumTagValue::Text(Some("red".to_string()))).is_ok()
+        );
+        assert!(check_tag_value(&TagType::Text, &constraints, None, &EnumTagValue::Text(Some("green".to_string())))
+            .is_err());
+        assert!(check_tag_value(&TagType::Text, &constraints, None, &EnumTagValue::Text(None)).is_ok());
+
+        let constraints = TagConstraints { required: true, ..Default::default() };
+        assert!(check_tag_value(&TagType::Bool, &constraints, None, &EnumTagValue::Boolean(Some(false))).is_ok());
+        assert!(check_tag_value(&TagType::Bool, &constraints, None, &EnumTagValue::Boolean(None)).is_err());
+
+        let constraints = TagConstraints {
+            min_value: Some("10".to_string()),
+            max_value: Some("20".to_string()),
+            ..Default::default()
+        };
+        assert!(check_tag_value(&TagType::Int, &constraints, None, &EnumTagValue::Integer(Some(10))).is_ok());
+        assert!(check_tag_value(&TagType::Int, &constraints, None, &EnumTagValue::Integer(Some(21))).is_err());
+        assert!(check_tag_value(&TagType::Int, &constraints, None, &EnumTagValue::Integer(Some(9))).is_err());
+
+        let constraints = TagConstraints { min_value: Some("2000-01-01".to_string()), ..Default::default() };
+        let value = EnumTagValue::SimpleDate(Some("1999-12-31".to_string()));
+        assert!(check_tag_value(&TagType::Date, &constraints, None, &value).is_err());
+
+        let constraints = TagConstraints { pattern: Some("[A-Z]{2}-[0-9]+".to_string()), ..Default::default() };
+        let pattern = compile_tag_pattern(&constraints).unwrap();
+        let value = EnumTagValue::Text(Some("AB-123".to_string()));
+        assert!(check_tag_value(&TagType::Text, &constraints, pattern.as_ref(), &value).is_ok());
+        let value = EnumTagValue::Text(Some("xAB-123".to_string()));
+        assert!(check_tag_value(&TagType::Text, &constraints, pattern.as_ref(), &value).is_err());
+    }
+
+    #[test]
+    fn check_tag_constraints_test() {
+        let constraints = TagConstraints { pattern: Some("[a-z".to_string()), ..Default::default() };
+        assert!(TagDelegate::check_tag_constraints(&TagType::Text, &constraints).is_err());
+
+        let constraints = TagConstraints { pattern: Some("[a-z]+".to_string()), ..Default::default() };
+        assert!(TagDelegate::check_tag_constraints(&TagType::Text, &constraints).is_ok());
+        assert!(TagDelegate::check_tag_constraints(&TagType::Int, &constraints).is_err());
+
+        let constraints = TagConstraints {
+            min_value: Some("5.5".to_string()),
+            max_value: Some("1.0".to_string()),
+            ..Default::default()
+        };
+        assert!(TagDelegate::check_tag_constraints(&TagType::Double, &constraints).is_err());
+
+        let constraints = TagConstraints { allowed_values: Some(vec!["one".to_string()]), ..Default::default() };
+        assert!(TagDelegate::check_tag_constraints(&TagType::Int, &constraints).is_err());
+        assert!(TagDelegate::check_tag_constraints(&TagType::Bool, &constraints).is_err());
+    }
+
     #[test]
     fn convert_datetime_to_iso8601_string() {
         let dt = Utc::now();
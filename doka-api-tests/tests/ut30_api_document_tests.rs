@@ -1,7 +1,15 @@
 mod test_lib;
 
-const TEST_TO_RUN: &[&str] =
-    &["t10_create_document", "t20_create_document_with_props", "t30_add_props", "t40_modify_tags"];
+const TEST_TO_RUN: &[&str] = &[
+    "t10_create_document",
+    "t20_create_document_with_props",
+    "t30_add_props",
+    "t40_modify_tags",
+    "t50_link_items",
+    "t60_patch_and_delete_items",
+    "t70_migrate_tag_type",
+    "t80_tag_constraints",
+];
 
 #[cfg(test)]
 mod api_document_tests {
@@ -9,7 +17,10 @@ mod api_document_tests {
     use rand::Rng;
 
     use dkdto::api_error::ApiError;
-    use dkdto::web_types::{AddItemRequest, AddItemTagRequest, AddTagValue, EnumTagValue, GetItemReply};
+    use dkdto::web_types::{
+        AddItemRequest, AddItemTagRequest, AddTagRequest, AddTagValue, BulkItemTagRequest, EnumTagValue, GetItemReply,
+        PatchItemRequest, PatchTagRequest, TagConstraints,
+    };
     use doka_cli::request_client::{AdminServerClient, DocumentServerClient};
 
     use crate::test_lib::{get_login_request, Lookup};
@@ -212,6 +223,282 @@ mod api_document_tests {
         Ok(())
     }
 
+    ///
+    /// Link an item to another one and find it back
+    ///
+    #[test]
+    fn t50_link_items() -> Result<(), ApiError<'static>> {
+        let lookup = Lookup::new("t50_link_items", TEST_TO_RUN); // auto dropping
+        let props = lookup.props();
+
+        let admin_server = AdminServerClient::new("localhost", 30060);
+        let login_request = get_login_request(&props);
+        let login_reply = admin_server.login(&login_request)?;
+
+        let document_server = DocumentServerClient::new("localhost", 30070);
+
+        let request = AddItemRequest { name: "A garage".to_string(), file_ref: None, properties: None };
+        let parent_reply = document_server.create_item(&request, &login_reply.session_id)?;
+
+        // Create an item linked to the first one
+        let link_tag = generate_random_tag();
+        let p1 = AddTagValue {
+            tag_id: None,
+            tag_name: Some(link_tag.to_owned()),
+            value: EnumTagValue::Link(Some(parent_reply.item_id)),
+        };
+        let request = AddItemRequest { name: "A truck".to_string(), file_ref: None, properties: Some(vec![p1]) };
+        let child_reply = document_server.create_item(&request, &login_reply.session_id)?;
+
+        let get_item_reply = document_server.get_item(child_reply.item_id, &login_reply.session_id)?;
+        let prop_value_1 = read_property(&get_item_reply, 0)?;
+        assert_eq!(parent_reply.item_id.to_string(), prop_value_1);
+
+        // What links to the first item
+        let linked_reply = document_server.get_linked_items(parent_reply.item_id, &login_reply.session_id)?;
+        assert_eq!(1, linked_reply.items.len());
+        assert_eq!(child_reply.item_id, linked_reply.items.get(0).unwrap().item_id);
+
+        // A link to a missing item is refused
+        let p1 = AddTagValue { tag_id: None, tag_name: Some(link_tag.to_owned()), value: EnumTagValue::Link(Some(-1)) };
+        let add_item_tag_request = AddItemTagRequest { properties: vec![p1] };
+        let r = document_server.update_item_tag(child_reply.item_id, &add_item_tag_request, &login_reply.session_id);
+        assert!(r.is_err());
+
+        // The default value of a link tag must be an existing item too
+        let request = AddTagRequest {
+            name: generate_random_tag(),
+            tag_type: "link".to_string(),
+            default_value: Some("-1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(400, document_server.create_tag(&request, &login_reply.session_id).unwrap_err().http_error_code);
+
+        lookup.close();
+        Ok(())
+    }
+
+    ///
+    /// Rename items, tag them by filter, then delete them
+    ///
+    #[test]
+    fn t60_patch_and_delete_items() -> Result<(), ApiError<'static>> {
+        let lookup = Lookup::new("t60_patch_and_delete_items", TEST_TO_RUN); // auto dropping
+        let props = lookup.props();
+
+        let admin_server = AdminServerClient::new("localhost", 30060);
+        let login_request = get_login_request(&props);
+        let login_reply = admin_server.login(&login_request)?;
+
+        let document_server = DocumentServerClient::new("localhost", 30070);
+
+        let kind_tag = generate_random_tag();
+        let link_tag = generate_random_tag();
+        let p1 = AddTagValue {
+            tag_id: None,
+            tag_name: Some(kind_tag.to_owned()),
+            value: EnumTagValue::Text(Some("vehicle".to_owned())),
+        };
+        let request = AddItemRequest { name: "A truck".to_string(), file_ref: None, properties: Some(vec![p1]) };
+        let truck_reply = document_server.create_item(&request, &login_reply.session_id)?;
+
+        let p1 = AddTagValue {
+            tag_id: None,
+            tag_name: Some(kind_tag.to_owned()),
+            value: EnumTagValue::Text(Some("vehicle".to_owned())),
+        };
+        let p2 = AddTagValue {
+            tag_id: None,
+            tag_name: Some(link_tag.to_owned()),
+            value: EnumTagValue::Link(Some(truck_reply.item_id)),
+        };
+        let request = AddItemRequest { name: "A trailer".to_string(), file_ref: None, properties: Some(vec![p1, p2]) };
+        let trailer_reply = document_server.create_item(&request, &login_reply.session_id)?;
+
+        // Rename
+        let request = PatchItemRequest { name: Some("A red truck".to_string()), ..Default::default() };
+        let patch_reply = document_server.patch_item(truck_reply.item_id, &request, &login_reply.session_id)?;
+        assert_eq!("A red truck", &patch_reply.items.first().unwrap().name);
+
+        // Tag all the vehicles
+        let color_tag = generate_random_tag();
+        let request = BulkItemTagRequest {
+            filters: format!(r#"({} == "vehicle")"#, &kind_tag),
+            add_tags: vec![AddTagValue {
+                tag_id: None,
+                tag_name: Some(color_tag.to_owned()),
+                value: EnumTagValue::Text(Some("red".to_owned())),
+            }],
+            remove_tags: vec![kind_tag.to_owned()],
+        };
+        let bulk_reply = document_server.bulk_item_tags(&request, &login_reply.session_id)?;
+        assert_eq!(2, bulk_reply.item_count);
+
+        let get_item_reply = document_server.get_item(truck_reply.item_id, &login_reply.session_id)?;
+        assert_eq!("red", read_property(&get_item_reply, 0)?);
+
+        // The trailer loses its link to the deleted truck
+        let delete_reply = document_server.delete_item(truck_reply.item_id, false, &login_reply.session_id)?;
+        assert!(delete_reply.deleted_files.is_empty());
+        assert!(document_server.get_item(truck_reply.item_id, &login_reply.session_id).is_err());
+
+        let get_item_reply = document_server.get_item(trailer_reply.item_id, &login_reply.session_id)?;
+        let trailer_props = get_item_reply.items.first().unwrap().properties.as_ref().unwrap();
+        assert!(trailer_props.iter().all(|prop| prop.tag_name != link_tag));
+
+        let _ = document_server.delete_item(trailer_reply.item_id, false, &login_reply.session_id)?;
+
+        lookup.close();
+        Ok(())
+    }
+
+    ///
+    /// Change the type of a tag in use, after a dry run
+    ///
+    #[test]
+    fn t70_migrate_tag_type() -> Result<(), ApiError<'static>> {
+        let lookup = Lookup::new("t70_migrate_tag_type", TEST_TO_RUN); // auto dropping
+        let props = lookup.props();
+
+        let admin_server = AdminServerClient::new("localhost", 30060);
+        let login_request = get_login_request(&props);
+        let login_reply = admin_server.login(&login_request)?;
+
+        let document_server = DocumentServerClient::new("localhost", 30070);
+
+        let count_tag = generate_random_tag();
+        let p1 = AddTagValue {
+            tag_id: None,
+            tag_name: Some(count_tag.to_owned()),
+            value: EnumTagValue::Text(Some("12".to_owned())),
+        };
+        let request = AddItemRequest { name: "A truck".to_string(), file_ref: None, properties: Some(vec![p1]) };
+        let truck_reply = document_server.create_item(&request, &login_reply.session_id)?;
+
+        let p1 = AddTagValue {
+            tag_id: None,
+            tag_name: Some(count_tag.to_owned()),
+            value: EnumTagValue::Text(Some("many".to_owned())),
+        };
+        let request = AddItemRequest { name: "A trailer".to_string(), file_ref: None, properties: Some(vec![p1]) };
+        let trailer_reply = document_server.create_item(&request, &login_reply.session_id)?;
+
+        let get_item_reply = document_server.get_item(truck_reply.item_id, &login_reply.session_id)?;
+        let tag_id = get_item_reply.items.first().unwrap().properties.as_ref().unwrap().first().unwrap().tag_id;
+
+        // The dry run reports the value of the trailer
+        let request = PatchTagRequest { tag_type: Some("int".to_string()), dry_run: true, ..Default::default() };
+        let patch_reply = document_server.update_tag(tag_id, &request, &login_reply.session_id)?;
+        assert_eq!(1, patch_reply.conversion_failures.len());
+        assert_eq!(trailer_reply.item_id, patch_reply.conversion_failures.first().unwrap().item_id);
+
+        let request = PatchTagRequest { tag_type: Some("int".to_string()), ..Default::default() };
+        assert!(document_server.update_tag(tag_id, &request, &login_reply.session_id).is_err());
+
+        // Once the trailer is gone, the conversion succeeds
+        let _ = document_server.delete_item(trailer_reply.item_id, false, &login_reply.session_id)?;
+        let patch_reply = document_server.update_tag(tag_id, &request, &login_reply.session_id)?;
+        assert_eq!("int", &patch_reply.tag.tag_type);
+
+        let get_item_reply = document_server.get_item(truck_reply.item_id, &login_reply.session_id)?;
+        let prop = get_item_reply.items.first().unwrap().properties.as_ref().unwrap().first().unwrap();
+        assert!(matches!(prop.value, EnumTagValue::Integer(Some(12))));
+
+        let _ = document_server.delete_item(truck_reply.item_id, false, &login_reply.session_id)?;
+
+        lookup.close();
+        Ok(())
+    }
+
+    ///
+    /// Refuse the values out of the allowed list, and the items without a required tag
+    ///
+    #[test]
+    fn t80_tag_constraints() -> Result<(), ApiError<'static>> {
+        let lookup = Lookup::new("t80_tag_constraints", TEST_TO_RUN); // auto dropping
+        let props = lookup.props();
+
+        let admin_server = AdminServerClient::new("localhost", 30060);
+        let login_request = get_login_request(&props);
+        let login_reply = admin_server.login(&login_request)?;
+
+        let document_server = DocumentServerClient::new("localhost", 30070);
+
+        let color_tag = generate_random_tag();
+        let request = AddTagRequest {
+            name: color_tag.to_owned(),
+            tag_type: "text".to_string(),
+            default_value: None,
+            constraints: TagConstraints {
+                allowed_values: Some(vec!["red".to_string(), "blue".to_string()]),
+                ..Default::default()
+            },
+        };
+        let tag_reply = document_server.create_tag(&request, &login_reply.session_id)?;
+
+        let p1 = AddTagValue {
+            tag_id: None,
+            tag_name: Some(color_tag.to_owned()),
+            value: EnumTagValue::Text(Some("green".to_owned())),
+        };
+        let request = AddItemRequest { name: "A truck".to_string(), file_ref: None, properties: Some(vec![p1]) };
+        assert!(document_server.create_item(&request, &login_reply.session_id).is_err());
+
+        let p1 = AddTagValue {
+            tag_id: None,
+            tag_name: Some(color_tag.to_owned()),
+            value: EnumTagValue::Text(Some("red".to_owned())),
+        };
+        let request = AddItemRequest { name: "A truck".to_string(), file_ref: None, properties: Some(vec![p1]) };
+        let truck_reply = document_server.create_item(&request, &login_reply.session_id)?;
+
+        let request = AddItemRequest { name: "A trailer".to_string(), file_ref: None, properties: None };
+        let trailer_reply = document_server.create_item(&request, &login_reply.session_id)?;
+
+        // The tag cannot become required while an item has no color
+        let constraints = TagConstraints {
+            required: true,
+            allowed_values: Some(vec!["red".to_string(), "blue".to_string()]),
+            ..Default::default()
+        };
+        let request = PatchTagRequest { constraints: Some(constraints.clone()), ..Default::default() };
+        assert!(document_server.update_tag(tag_reply.tag_id, &request, &login_reply.session_id).is_err());
+
+        let request = PatchTagRequest { constraints: Some(constraints.clone()), dry_run: true, ..Default::default() };
+        let patch_reply = document_server.update_tag(tag_reply.tag_id, &request, &login_reply.session_id)?;
+        assert!(patch_reply.missing_value_item_count >= 1);
+
+        // The items without a color get the default one
+        let request = PatchTagRequest {
+            constraints: Some(constraints),
+            default_value: Some(Some("blue".to_string())),
+            apply_default: true,
+            ..Default::default()
+        };
+        let patch_reply = document_server.update_tag(tag_reply.tag_id, &request, &login_reply.session_id)?;
+        assert_eq!(0, patch_reply.missing_value_item_count);
+
+        // Once the tag is required, an item without a color is refused
+        let request = AddItemRequest { name: "A trailer".to_string(), file_ref: None, properties: None };
+        assert!(document_server.create_item(&request, &login_reply.session_id).is_err());
+
+        let p1 = AddTagValue { tag_id: None, tag_name: Some(color_tag.to_owned()), value: EnumTagValue::Text(None) };
+        let request = AddItemRequest { name: "A trailer".to_string(), file_ref: None, properties: Some(vec![p1]) };
+        assert!(document_server.create_item(&request, &login_reply.session_id).is_err());
+
+        // The color cannot be removed from the truck
+        let tag_names = [color_tag.to_owned()];
+        assert!(document_server.delete_item_tag(truck_reply.item_id, &tag_names, &login_reply.session_id).is_err());
+
+        let request = PatchTagRequest { constraints: Some(TagConstraints::default()), ..Default::default() };
+        let _ = document_server.update_tag(tag_reply.tag_id, &request, &login_reply.session_id)?;
+        let _ = document_server.delete_item(truck_reply.item_id, false, &login_reply.session_id)?;
+        let _ = document_server.delete_item(trailer_reply.item_id, false, &login_reply.session_id)?;
+
+        lookup.close();
+        Ok(())
+    }
+
     fn read_property(get_item_reply: &GetItemReply, prop_order: usize) -> anyhow::Result<String> {
         let item = get_item_reply.items.get(0).ok_or(anyhow!("No item found"))?;
         Ok(item
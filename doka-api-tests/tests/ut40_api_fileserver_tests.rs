@@ -1,6 +1,12 @@
 mod test_lib;
 
-const TEST_TO_RUN: &[&str] = &["t10_upload_file", "t20_upload_download_file", "t30_upload_download_big_file"];
+const TEST_TO_RUN: &[&str] = &[
+    "t10_upload_file",
+    "t20_upload_download_file",
+    "t30_upload_download_big_file",
+    "t40_item_versions",
+    "t50_delete_shared_file",
+];
 
 /// cargo test t30_upload_download_big_file -- --nocapture
 
@@ -8,7 +14,8 @@ const TEST_TO_RUN: &[&str] = &["t10_upload_file", "t20_upload_download_file", "t
 mod api_fileserver_tests {
     use core::time::Duration;
     use dkdto::api_error::ApiError;
-    use doka_cli::request_client::{AdminServerClient, FileServerClient};
+    use dkdto::web_types::{AddItemRequest, AddItemVersionRequest, PatchItemRequest};
+    use doka_cli::request_client::{AdminServerClient, DocumentServerClient, FileServerClient};
     use std::thread;
 
     use crate::test_lib::{get_login_request, Lookup};
@@ -49,12 +56,36 @@ mod api_fileserver_tests {
 
         eprintln!("Loading is finished");
 
+        // The processing must have reached its final step
+        let status_reply = file_server.status(&upload_reply.file_ref, &login_reply.session_id)?;
+        eprintln!("Status reply [{:?}]", &status_reply);
+        assert_eq!("indexed", status_reply.status);
+        assert!(status_reply.failure_reason.is_none());
+
         // Get the information of the file
         let info_reply = file_server.info(&upload_reply.file_ref, &login_reply.session_id)?;
 
         eprintln!("Info reply [{:?}]", &info_reply);
         assert_eq!("image/jpeg", info_reply.media_type.unwrap());
 
+        // The metadata found by Tika, with the normalized ones
+        let metadata_reply = file_server.metadata(&upload_reply.file_ref, &login_reply.session_id)?;
+        eprintln!("Metadata reply [{:?}]", &metadata_reply);
+        assert!(metadata_reply.list_of_metadata.iter().any(|metadata| metadata.key == "Content-Type"));
+        assert!(metadata_reply.list_of_metadata.iter().any(|metadata| metadata.key == "doka:width"));
+
+        // The stored parts match the checksum and the sizes of the file
+        let verify_reply = file_server.verify(&upload_reply.file_ref, &login_reply.session_id)?;
+        eprintln!("Verify reply [{:?}]", &verify_reply);
+        assert!(verify_reply.is_valid);
+        assert!(verify_reply.issues.is_empty());
+
+        // Parse and index the stored file again
+        let reindex_reply = file_server.reindex(&upload_reply.file_ref, &login_reply.session_id)?;
+        eprintln!("Reindex reply [{:?}]", &reindex_reply);
+        assert_eq!(1, reindex_reply.list_of_files.len());
+        assert_eq!("indexed", reindex_reply.list_of_files[0].status);
+
         lookup.close();
         Ok(())
     }
@@ -167,4 +198,129 @@ mod api_fileserver_tests {
         lookup.close();
         Ok(())
     }
+
+    #[test]
+    fn t40_item_versions() -> Result<(), ApiError<'static>> {
+        let lookup = Lookup::new("t40_item_versions", TEST_TO_RUN); // auto dropping
+        let props = lookup.props();
+
+        // Login
+        let admin_server = AdminServerClient::new("localhost", 30060);
+        let login_request = get_login_request(&props);
+        let login_reply = admin_server.login(&login_request)?;
+
+        // Upload the 2 files
+        let file_server = FileServerClient::new("localhost", 30080);
+        let document_server = DocumentServerClient::new("localhost", 30070);
+
+        let file_name = format!(r"{}/111-Bright_Snow.jpg", &props.get("file.path").unwrap());
+        let file_content = std::fs::read(file_name).unwrap();
+        let first_reply = file_server.upload("bright_snow", &file_content, &login_reply.session_id)?;
+        let second_reply = file_server.upload("bright_snow", &file_content, &login_reply.session_id)?;
+
+        for upload_reply in [&first_reply, &second_reply] {
+            wait_until_file_processing_complete(
+                &file_server,
+                &upload_reply.file_ref,
+                &login_reply.session_id,
+                upload_reply.block_count,
+            );
+        }
+
+        // The item is created with the first file, the second one is a new version
+        let request = AddItemRequest {
+            name: "A snow".to_string(),
+            file_ref: Some(first_reply.file_ref.clone()),
+            properties: None,
+        };
+        let item_reply = document_server.create_item(&request, &login_reply.session_id)?;
+
+        // The detached file stays as the version 1 of the item
+        let request = PatchItemRequest { name: None, file_ref: Some(None) };
+        let _ = document_server.patch_item(item_reply.item_id, &request, &login_reply.session_id)?;
+        let detached_reply = document_server.item_versions(item_reply.item_id, &login_reply.session_id)?;
+        assert_eq!(1, detached_reply.list_of_versions.len());
+        assert_eq!(first_reply.file_ref, detached_reply.list_of_versions[0].file_ref);
+        assert!(!detached_reply.list_of_versions[0].is_current);
+
+        let version_request = AddItemVersionRequest { file_ref: second_reply.file_ref.clone() };
+        let version_reply =
+            document_server.add_item_version(item_reply.item_id, &version_request, &login_reply.session_id)?;
+        eprintln!("Version reply [{:?}]", &version_reply);
+        assert_eq!(2, version_reply.list_of_versions.len());
+        assert!(version_reply.list_of_versions[1].is_current);
+        assert!(version_reply.list_of_versions.iter().all(|version| version.file_size == Some(8890555)));
+        assert!(version_reply.list_of_versions.iter().all(|version| version.checksum.is_some()));
+
+        // The same file cannot be attached twice
+        let already_attached =
+            document_server.add_item_version(item_reply.item_id, &version_request, &login_reply.session_id);
+        assert_eq!(409, already_attached.unwrap_err().http_error_code);
+
+        // The file server adds the size and the checksum of the files
+        let versions_reply = file_server.item_versions(item_reply.item_id, &login_reply.session_id)?;
+        eprintln!("Versions reply [{:?}]", &versions_reply);
+        assert!(versions_reply.list_of_versions.iter().all(|version| version.file_size == Some(8890555)));
+        assert_eq!(versions_reply.list_of_versions[0].checksum, versions_reply.list_of_versions[1].checksum);
+
+        // Back to the first version
+        let restore_reply = document_server.restore_item_version(item_reply.item_id, 1, &login_reply.session_id)?;
+        assert!(restore_reply.list_of_versions[0].is_current);
+        assert!(!restore_reply.list_of_versions[1].is_current);
+
+        let download_reply = file_server.download_version(item_reply.item_id, 2, &login_reply.session_id)?;
+        assert_eq!(8890555, download_reply.data.len());
+
+        lookup.close();
+        Ok(())
+    }
+
+    #[test]
+    fn t50_delete_shared_file() -> Result<(), ApiError<'static>> {
+        let lookup = Lookup::new("t50_delete_shared_file", TEST_TO_RUN); // auto dropping
+        let props = lookup.props();
+
+        // Login
+        let admin_server = AdminServerClient::new("localhost", 30060);
+        let login_request = get_login_request(&props);
+        let login_reply = admin_server.login(&login_request)?;
+
+        // The second file shares the parts of the first one, once it's stored
+        let file_server = FileServerClient::new("localhost", 30080);
+
+        let file_name = format!(r"{}/111-Bright_Snow.jpg", &props.get("file.path").unwrap());
+        let file_content = std::fs::read(file_name).unwrap();
+        let first_reply = file_server.upload("bright_snow", &file_content, &login_reply.session_id)?;
+        wait_until_file_processing_complete(
+            &file_server,
+            &first_reply.file_ref,
+            &login_reply.session_id,
+            first_reply.block_count,
+        );
+        let second_reply = file_server.upload("bright_snow", &file_content, &login_reply.session_id)?;
+        wait_until_file_processing_complete(
+            &file_server,
+            &second_reply.file_ref,
+            &login_reply.session_id,
+            second_reply.block_count,
+        );
+
+        // The parts go to the second file
+        let _ = file_server.delete_file(&first_reply.file_ref, &login_reply.session_id)?;
+        let deleted_again = file_server.delete_file(&first_reply.file_ref, &login_reply.session_id);
+        assert_eq!(404, deleted_again.unwrap_err().http_error_code);
+
+        let download_reply = file_server.download(&second_reply.file_ref, &login_reply.session_id)?;
+        assert_eq!(file_content, download_reply.data);
+        let verify_reply = file_server.verify(&second_reply.file_ref, &login_reply.session_id)?;
+        assert!(verify_reply.is_valid);
+
+        // The last reference removes the parts
+        let _ = file_server.delete_file(&second_reply.file_ref, &login_reply.session_id)?;
+        let deleted_again = file_server.delete_file(&second_reply.file_ref, &login_reply.session_id);
+        assert_eq!(404, deleted_again.unwrap_err().http_error_code);
+
+        lookup.close();
+        Ok(())
+    }
 }
@@ -1,6 +1,6 @@
 mod test_lib;
 
-const TEST_TO_RUN: &[&str] = &["t10_upload_mass_file"];
+const TEST_TO_RUN: &[&str] = &["t10_upload_mass_file", "t20_upload_throughput"];
 
 #[cfg(test)]
 mod api_fileserver_load_tests {
@@ -11,9 +11,13 @@ mod api_fileserver_load_tests {
     use dkdto::api_error::ApiError;
     use dkdto::web_types::UploadReply;
     use doka_cli::request_client::{AdminServerClient, FileServerClient};
+    use rand::Rng;
     use std::thread;
+    use std::time::Instant;
 
     const NB_PARTS: u32 = 37;
+    const FILE_NAME: &str = "1111-38M.pdf";
+    const NB_COMPARED_FILES: usize = 4;
 
     #[test]
     fn t10_upload_mass_file() -> Result<(), ApiError<'static>> {
@@ -28,13 +32,17 @@ mod api_fileserver_load_tests {
         let login_reply = admin_server.login(&login_request)?;
         eprintln!("login_reply {:?}", &login_reply);
 
-        let num_threads = 20; // Changer le nombre de threads selon vos besoins
+        let num_threads: u64 = 20; // Changer le nombre de threads selon vos besoins
 
-        let handles: Vec<_> = (0..num_threads)
-            .map(|_| {
-                let local_file_path = file_path.clone();
+        // Each file is different, so none is stored as a copy of another one
+        let contents: Vec<Vec<u8>> = (0..num_threads).map(|_| unique_content(file_path)).collect();
+
+        let start = Instant::now();
+        let handles: Vec<_> = contents
+            .into_iter()
+            .map(|content| {
                 let local_session_id = login_reply.session_id.clone();
-                thread::spawn(move || send_a_files(&local_file_path, &local_session_id).unwrap())
+                thread::spawn(move || send_a_file(content, &local_session_id).unwrap())
             })
             .collect();
 
@@ -52,6 +60,7 @@ mod api_fileserver_load_tests {
         // thread::sleep(duration);
         // Use the routine below to know when the processing is finished
         wait_until_loading_complete(&file_server, &login_reply.session_id);
+        print_throughput(num_threads * file_size(file_path), start.elapsed());
 
         for file_ref in new_file_refs {
             // Get the information of the file
@@ -64,17 +73,105 @@ mod api_fileserver_load_tests {
         Ok(())
     }
 
-    fn send_a_files(file_path: &str, session_id: &str) -> Result<UploadReply, ApiError<'static>> {
-        const FILE_NAME: &str = "1111-38M.pdf";
+    /// Time the upload and the processing of the same number of files, one after the other then all together.
+    /// The file server processes the files of parallel uploads at the same time, so it must be faster
+    #[test]
+    fn t20_upload_throughput() -> Result<(), ApiError<'static>> {
+        let lookup = Lookup::new("t20_upload_throughput", TEST_TO_RUN); // auto dropping
+        let props = lookup.props();
+
+        let file_path = props.get("file.path").unwrap();
+
+        // Login
+        let admin_server = AdminServerClient::new("localhost", 30060);
+        let login_request = get_login_request(&props);
+        let login_reply = admin_server.login(&login_request)?;
+
+        let file_server = FileServerClient::new("localhost", 30080);
+        let byte_count = NB_COMPARED_FILES as u64 * file_size(file_path);
+
+        // Each file is different, so none is stored as a copy of another one
+        let serial_contents: Vec<Vec<u8>> = (0..NB_COMPARED_FILES).map(|_| unique_content(file_path)).collect();
+        let parallel_contents: Vec<Vec<u8>> = (0..NB_COMPARED_FILES).map(|_| unique_content(file_path)).collect();
+
+        // One file after the other
+        let start = Instant::now();
+        let mut file_refs = vec![];
+        for content in serial_contents {
+            let upload_reply = send_a_file(content, &login_reply.session_id)?;
+            assert_eq!(NB_PARTS, upload_reply.block_count);
+            wait_until_loading_complete(&file_server, &login_reply.session_id);
+            file_refs.push(upload_reply.file_ref);
+        }
+        let serial_elapsed = start.elapsed();
+        eprint!("Serial uploads : ");
+        print_throughput(byte_count, serial_elapsed);
+
+        // All the files together
+        let start = Instant::now();
+        let handles: Vec<_> = parallel_contents
+            .into_iter()
+            .map(|content| {
+                let local_session_id = login_reply.session_id.clone();
+                thread::spawn(move || send_a_file(content, &local_session_id).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            let upload_reply = handle.join().unwrap();
+            assert_eq!(NB_PARTS, upload_reply.block_count);
+            file_refs.push(upload_reply.file_ref);
+        }
+        wait_until_loading_complete(&file_server, &login_reply.session_id);
+        let parallel_elapsed = start.elapsed();
+        eprint!("Parallel uploads : ");
+        print_throughput(byte_count, parallel_elapsed);
+
+        for file_ref in &file_refs {
+            let stats_reply = file_server.stats(file_ref, &login_reply.session_id)?;
+            assert_eq!(NB_PARTS as i64, stats_reply.encrypted_count);
+        }
+
+        assert!(
+            parallel_elapsed < serial_elapsed,
+            "The parallel uploads [{:?}] are not faster than the serial ones [{:?}]",
+            parallel_elapsed,
+            serial_elapsed
+        );
+
+        lookup.close();
+        Ok(())
+    }
+
+    fn print_throughput(byte_count: u64, elapsed: Duration) {
+        let mega_bytes = byte_count as f64 / 1_000_000.0;
+        eprintln!(
+            "Uploaded and processed [{:.0}] MB in [{:.1}] s, throughput [{:.2}] MB/s",
+            mega_bytes,
+            elapsed.as_secs_f64(),
+            mega_bytes / elapsed.as_secs_f64()
+        );
+    }
+
+    fn file_size(file_path: &str) -> u64 {
+        std::fs::metadata(format!(r"{}/{}", file_path, FILE_NAME)).unwrap().len()
+    }
 
+    /// The content of the test file with a random comment after its end, so it's never the same file twice
+    fn unique_content(file_path: &str) -> Vec<u8> {
+        let file_name = format!(r"{}/{}", &file_path, FILE_NAME);
+        let mut file_content = std::fs::read(file_name).unwrap();
+        let marker: u64 = rand::thread_rng().gen();
+        file_content.extend_from_slice(format!("\n% {:016x}\n", marker).as_bytes());
+        file_content
+    }
+
+    fn send_a_file(file_content: Vec<u8>, session_id: &str) -> Result<UploadReply, ApiError<'static>> {
         // Upload the document
         let file_server = FileServerClient::new("localhost", 30080);
 
         // encode in base64 url the file name
         let encoded_file_name = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(FILE_NAME);
 
-        let file_name = format!(r"{}/{}", &file_path, FILE_NAME);
-        let file_content = std::fs::read(file_name).unwrap();
         let upload_reply = file_server.upload(&encoded_file_name, &file_content, &session_id)?;
         Ok(upload_reply)
     }
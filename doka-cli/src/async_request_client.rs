@@ -13,14 +13,16 @@ use url::Url;
 use dkdto::error_codes::{HTTP_CLIENT_ERROR, INTERNAL_TECHNICAL_ERROR, URL_PARSING_ERROR};
 use dkdto::web_types::{
     AddItemReply, AddItemRequest, AddItemTagReply, AddItemTagRequest, AddKeyReply, AddKeyRequest, AddTagReply,
-    AddTagRequest, CustomerKeyReply, DeleteFullTextRequest, FullTextReply, FullTextRequest, GetFileInfoReply,
-    GetFileInfoShortReply, GetItemReply, GetTagReply, ListOfFileInfoReply, ListOfUploadInfoReply, MediaBytes,
-    OpenSessionReply, OpenSessionRequest, SessionReply, SimpleMessage, TikaMeta, TikaParsing, UploadReply, WebResponse,
-    WebTypeBuilder,
+    AddTagRequest, BulkItemTagReply, BulkItemTagRequest, CustomerKeyReply, CustomerQuotaReply, DeleteFullTextRequest,
+    DeleteItemReply, FileIntegrityReply, FileListQuery, FileMetadataReply, FileStatusReply, FullTextReply,
+    FullTextRequest, GetFileInfoReply, GetFileInfoShortReply, GetItemReply, GetTagReply, ItemVersionReply,
+    ListOfFileInfoReply, ListOfUploadInfoReply, MediaBytes, OpenSessionReply, OpenSessionRequest, PatchItemRequest,
+    PatchTagReply, PatchTagRequest, ScrubReply, SessionReply, SimpleMessage, TikaMeta, TikaParsing, UploadReply,
+    WebResponse, WebTypeBuilder,
 };
 
 use crate::request_client::TokenType::{Sid, Token};
-use crate::request_client::{CustomHeaders, TokenType};
+use crate::request_client::{file_list_query_string, CustomHeaders, TokenType};
 
 const TIMEOUT: Duration = Duration::from_secs(60 * 60);
 // const MAX_HTTP_RETRY: u32 = 5;
@@ -54,6 +56,25 @@ impl KeyManagerClientAsync {
     }
 }
 
+///
+/// Admin Server
+///
+pub struct AdminServerClientAsync {
+    server: WebServerAsync,
+}
+
+impl AdminServerClientAsync {
+    pub fn new(server_name: &str, port: u16) -> Self {
+        Self { server: WebServerAsync::new(server_name, port, "admin-server") }
+    }
+
+    pub async fn customer_quota(&self, customer_code: &str, token: &str) -> WebResponse<CustomerQuotaReply> {
+        // http://localhost:{{PORT}}/admin-server/customer/quota/f1248fab
+        let url = self.server.build_url_with_refcode("customer/quota", customer_code);
+        self.server.get_data_retry(&url, &Token(token.to_string())).await
+    }
+}
+
 #[derive(Clone)]
 pub struct SessionManagerClientAsync {
     server: WebServerAsync,
@@ -122,6 +143,55 @@ impl DocumentServerClientAsync {
         reply
     }
 
+    ///
+    /// Items with a link tag pointing to the item
+    ///
+    pub async fn get_linked_items(&self, item_id: i64, sid: &str) -> WebResponse<GetItemReply> {
+        // http://{}:{}/document-server/item/<item_id>/backlinks
+        let end_point = format!("item/{0}/backlinks", item_id);
+        let url = self.server.build_url(&end_point);
+        self.server.get_data_retry(&url, &Sid(sid.to_string())).await
+    }
+
+    ///
+    /// File versions of the item
+    ///
+    pub async fn item_versions(&self, item_id: i64, sid: &str) -> WebResponse<ItemVersionReply> {
+        // http://{}:{}/document-server/item/<item_id>/versions
+        let end_point = format!("item/{0}/versions", item_id);
+        let url = self.server.build_url(&end_point);
+        self.server.get_data_retry(&url, &Sid(sid.to_string())).await
+    }
+
+    ///
+    /// Delete the item, with its file when [delete_file] is set
+    ///
+    pub async fn delete_item(&self, item_id: i64, delete_file: bool, sid: &str) -> WebResponse<DeleteItemReply> {
+        // http://{}:{}/document-server/item/<item_id>?delete_file=<delete_file>
+        let end_point = format!("item/{0}?delete_file={1}", item_id, delete_file);
+        let url = self.server.build_url(&end_point);
+        self.server.delete_data_retry(&url, &Sid(sid.to_owned())).await
+    }
+
+    ///
+    /// Rename the item, attach or detach its file
+    ///
+    pub async fn patch_item(&self, item_id: i64, request: &PatchItemRequest, sid: &str) -> WebResponse<GetItemReply> {
+        // http://{}:{}/document-server/item/<item_id>
+        let url = self.server.build_url_with_refcode("item", item_id);
+        self.server.patch_json_data_retry(&url, request, &Sid(sid.to_string())).await
+    }
+
+    ///
+    /// Add and remove tags on all the items matching the filters
+    ///
+    pub async fn bulk_item_tags(&self, request: &BulkItemTagRequest, sid: &str) -> WebResponse<BulkItemTagReply> {
+        // http://{}:{}/document-server/search/tags
+        let url = self.server.build_url("search/tags");
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, request, &headers).await
+    }
+
     ///
     ///
     ///
@@ -141,6 +211,21 @@ impl DocumentServerClientAsync {
         self.server.post_data_retry(&url, request, &headers).await
     }
 
+    ///
+    /// Update the tags of all the items of the file [file_ref]
+    ///
+    pub async fn update_file_item_tags(
+        &self,
+        file_ref: &str,
+        request: &AddItemTagRequest,
+        sid: &str,
+    ) -> WebResponse<AddItemTagReply> {
+        // http://{}:{}/document-server/file_tags/<file_ref>
+        let url = self.server.build_url_with_refcode("file_tags", file_ref);
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, request, &headers).await
+    }
+
     ///
     /// TODO perform URL escaping
     ///
@@ -189,6 +274,15 @@ impl DocumentServerClientAsync {
         self.server.delete_for_url(tag_id, "tag", &Sid(sid.to_owned())).await
     }
 
+    ///
+    /// Change a tag definition, or only report the values which cannot be converted with a dry run
+    ///
+    pub async fn update_tag(&self, tag_id: i64, request: &PatchTagRequest, sid: &str) -> WebResponse<PatchTagReply> {
+        // http://{}:{}/document-server/tag/<tag_id>
+        let url = self.server.build_url_with_refcode("tag", tag_id);
+        self.server.patch_json_data_retry(&url, request, &Sid(sid.to_string())).await
+    }
+
     ///
     ///
     ///
@@ -264,16 +358,48 @@ impl FileServerClientAsync {
         self.server.get_data_retry(&url, &Sid(sid.to_string())).await
     }
 
+    pub async fn status(&self, file_ref: &str, sid: &str) -> WebResponse<FileStatusReply> {
+        let url = self.server.build_url_with_refcode("status", file_ref);
+        self.server.get_data_retry(&url, &Sid(sid.to_string())).await
+    }
+
+    pub async fn metadata(&self, file_ref: &str, sid: &str) -> WebResponse<FileMetadataReply> {
+        let url = self.server.build_url_with_refcode("metadata", file_ref);
+        self.server.get_data_retry(&url, &Sid(sid.to_string())).await
+    }
+
+    pub async fn delete_file(&self, file_ref: &str, sid: &str) -> WebResponse<SimpleMessage> {
+        // http://localhost:{{PORT}}/file-server/file/<file_ref>
+        self.server.delete_for_url(file_ref, "file", &Sid(sid.to_owned())).await
+    }
+
+    pub async fn verify(&self, file_ref: &str, sid: &str) -> WebResponse<FileIntegrityReply> {
+        let url = self.server.build_url_with_refcode("verify", file_ref);
+        self.server.get_data_retry(&url, &Sid(sid.to_string())).await
+    }
+
+    pub async fn start_scrub(&self, sid: &str) -> WebResponse<ScrubReply> {
+        let url = self.server.build_url("scrub");
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, &(), &headers).await
+    }
+
+    pub async fn scrub_report(&self, sid: &str) -> WebResponse<ScrubReply> {
+        let url = self.server.build_url("scrub");
+        self.server.get_data_retry(&url, &Sid(sid.to_string())).await
+    }
+
     pub async fn loading(&self, sid: &str) -> WebResponse<ListOfUploadInfoReply> {
         // let url = format!("http://{}:{}/file-server/loading/{}", &self.server.server_name, self.server.port);
         let url = self.server.build_url("loading");
         self.server.get_data_retry(&url, &Sid(sid.to_string())).await
     }
 
-    pub async fn list(&self, pattern: &str, sid: &str) -> WebResponse<ListOfFileInfoReply> {
-        // let url = format!("http://{}:{}/file-server/stats/{}", &self.server.server_name, self.server.port);
-        let url = self.server.build_url_with_refcode("list", &pattern);
-        // let url = self.server.build_url("stats/1ABH234");
+    pub async fn list(&self, pattern: &str, list_query: &FileListQuery, sid: &str) -> WebResponse<ListOfFileInfoReply> {
+        // http://localhost:{{PORT}}/file-server/list/<pattern>?start_page=0&page_size=20&sort_by=size
+        // The ? of the glob pattern must not start the query string
+        let pattern = utf8_percent_encode(pattern, NON_ALPHANUMERIC);
+        let url = self.server.build_url(&format!("list/{}?{}", pattern, file_list_query_string(list_query)));
         self.server.get_data_retry(&url, &Sid(sid.to_string())).await
     }
 }
@@ -425,6 +551,29 @@ impl WebServerAsync {
         Self::send_request_builder(request_builder).await
     }
 
+    async fn patch_json_data_retry<U: Serialize, V: DeserializeOwned>(
+        &self,
+        url: &str,
+        request: &U,
+        token: &TokenType,
+    ) -> WebResponse<V> {
+        self.patch_json_data(url, request, token)
+            .await
+            .unwrap_or_else(|_| WebResponse::from_api_error(&HTTP_CLIENT_ERROR))
+    }
+
+    async fn patch_json_data<U: Serialize, V: de::DeserializeOwned>(
+        &self,
+        url: &str,
+        request: &U,
+        token: &TokenType,
+    ) -> anyhow::Result<WebResponse<V>> {
+        let client = Client::new();
+        let url = Url::parse(url)?;
+        let request_builder = client.patch(url).timeout(TIMEOUT).json(request);
+        Self::send_request_builder(Self::add_header(request_builder, token)).await
+    }
+
     /// Generic routine to post a binary content
     async fn post_bytes<V: de::DeserializeOwned>(
         &self,
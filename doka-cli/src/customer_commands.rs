@@ -2,7 +2,7 @@ use anyhow::anyhow;
 
 use commons_error::*;
 use common_config::properties::get_prop_value;
-use dkdto::web_types::CreateCustomerRequest;
+use dkdto::web_types::{CreateCustomerRequest, CustomerQuotaRequest};
 use doka_cli::request_client::AdminServerClient;
 
 use crate::token_commands::read_security_token;
@@ -76,3 +76,65 @@ pub(crate) fn delete_customer(customer_code: &str) -> anyhow::Result<()> {
         Err(e) => Err(anyhow!("{}", e.message)),
     }
 }
+
+/// Set the storage quotas of a customer, a None quota removes the limit
+pub(crate) fn set_customer_quota(
+    customer_code: &str,
+    storage_quota: Option<u64>,
+    file_count_quota: Option<u64>,
+) -> anyhow::Result<()> {
+    println!("👶 Set the quotas of a customer...");
+
+    let server_host = get_prop_value("server.host")?;
+    let admin_server_port: u16 = get_prop_value("as.port")?.parse()?;
+    println!("Admin server port : {}", admin_server_port);
+    let client = AdminServerClient::new(&server_host, admin_server_port);
+
+    let token = read_security_token()?;
+    let wr_reply =
+        client.set_customer_quota(customer_code, &CustomerQuotaRequest { storage_quota, file_count_quota }, &token);
+
+    match wr_reply {
+        Ok(reply) => {
+            println!(
+                "😎 Quotas successfully set, customer code : [{}], storage quota : [{:?}], file count quota : [{:?}]",
+                &reply.customer_code, reply.storage_quota, reply.file_count_quota
+            );
+            Ok(())
+        }
+        Err(e) => Err(anyhow!("{}", e.message)),
+    }
+}
+
+/// Display the storage used by each customer
+pub(crate) fn customer_usage() -> anyhow::Result<()> {
+    println!("👶 Get the storage usage of the customers...");
+
+    let server_host = get_prop_value("server.host")?;
+    let admin_server_port: u16 = get_prop_value("as.port")?.parse()?;
+    println!("Admin server port : {}", admin_server_port);
+    let client = AdminServerClient::new(&server_host, admin_server_port);
+
+    let token = read_security_token()?;
+    let wr_reply = client.customer_usage(&token);
+
+    match wr_reply {
+        Ok(reply) => {
+            println!("code\tname\tfiles\tfile quota\tsize\tencrypted size\tsize quota");
+            for usage in reply.list_of_usages {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    &usage.customer_code,
+                    &usage.customer_name,
+                    usage.file_count,
+                    usage.file_count_quota.map(|q| q.to_string()).unwrap_or("-".to_string()),
+                    usage.original_size,
+                    usage.encrypted_size,
+                    usage.storage_quota.map(|q| q.to_string()).unwrap_or("-".to_string()),
+                );
+            }
+            Ok(())
+        }
+        Err(e) => Err(anyhow!("{}", e.message)),
+    }
+}
@@ -1,16 +1,27 @@
+use std::cmp::min;
+use std::collections::HashSet;
 use std::fs::File;
-use std::io::{BufReader, Cursor, Read};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::path::Path;
 
 use anyhow::anyhow;
 
 use common_config::properties::get_prop_value;
+use dkdto::web_types::{CreateUploadSessionRequest, FileListQuery};
 use doka_cli::request_client::FileServerClient;
 
 use crate::session_commands::read_session_id;
 
+/// Above this size, the file is sent block by block in an upload session
+const LARGE_FILE_SIZE: u64 = 10 * 1_048_576;
+/// Number of files read at once when all the pages are listed
+const LIST_PAGE_SIZE: u32 = 100;
+
+///
+/// Upload the file at the path.
+/// A large file, or an interrupted upload to resume (o_file_ref), is sent block by block.
 ///
-pub(crate) fn file_upload(item_info: &str, path :&str) -> anyhow::Result<()> {
+pub(crate) fn file_upload(item_info: &str, path :&str, o_file_ref: Option<&str>) -> anyhow::Result<()> {
     println!("👶 Uploading the file...");
 
     let server_host = get_prop_value("server.host")?;
@@ -20,6 +31,11 @@ pub(crate) fn file_upload(item_info: &str, path :&str) -> anyhow::Result<()> {
 
     let sid = read_session_id()?;
 
+    let file_size = std::fs::metadata(Path::new(&path))?.len();
+    if o_file_ref.is_some() || file_size > LARGE_FILE_SIZE {
+        return file_upload_by_blocks(&client, &sid, item_info, path, file_size, o_file_ref);
+    }
+
     let file = File::open(Path::new(&path))?;
     let mut buf_reader = BufReader::new(file);
     let mut binary : Vec<u8> = vec![];
@@ -37,6 +53,52 @@ pub(crate) fn file_upload(item_info: &str, path :&str) -> anyhow::Result<()> {
     }
 }
 
+///
+/// Send the file block by block in an upload session, the blocks already received by the server are skipped
+///
+fn file_upload_by_blocks(client: &FileServerClient, sid: &str, item_info: &str, path: &str,
+                         file_size: u64, o_file_ref: Option<&str>) -> anyhow::Result<()> {
+    let wr_session = match o_file_ref {
+        Some(file_ref) => client.upload_session_status(file_ref, sid),
+        None => {
+            let request = CreateUploadSessionRequest { item_info: item_info.to_string(), file_size: Some(file_size) };
+            client.create_upload_session(&request, sid)
+        }
+    };
+    let session = wr_session.map_err(|e| anyhow!("{}", e.message))?;
+    println!("Upload session, reference : {} (use it with -fr to resume the upload)", session.file_ref);
+
+    let block_size = session.block_size as u64;
+    let block_count = file_size.div_ceil(block_size) as u32;
+    let received_blocks: HashSet<u32> = session.received_blocks.into_iter().collect();
+
+    let mut file = File::open(Path::new(&path))?;
+    let mut buffer = vec![0u8; block_size as usize];
+    for block_num in 0..block_count {
+        if received_blocks.contains(&block_num) {
+            continue;
+        }
+        let offset = block_num as u64 * block_size;
+        let length = min(block_size, file_size - offset) as usize;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buffer[..length])?;
+
+        client.upload_block(&session.file_ref, block_num, &buffer[..length], sid)
+            .map_err(|e| anyhow!("Block {} not sent, {}", block_num, e.message))?;
+        println!("Block sent : {}/{}", block_num + 1, block_count);
+    }
+
+    match client.finalize_upload_session(&session.file_ref, sid) {
+        Ok(reply) => {
+            println!("😎 File successfully uploaded, reference : {}, number of blocks : {} ", reply.file_ref, reply.block_count);
+            Ok(())
+        }
+        Err(e) => {
+            Err(anyhow!("{}", e.message))
+        }
+    }
+}
+
 ///
 /// Download the content behind the reference into the file at the path
 ///
@@ -101,7 +163,7 @@ pub(crate) fn file_info(file_ref: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub(crate) fn file_list(pattern: &str) -> anyhow::Result<()> {
+pub(crate) fn file_list(pattern: &str, mut list_query: FileListQuery, all_pages: bool) -> anyhow::Result<()> {
     println!("👶 Getting the file information...");
 
     let server_host = get_prop_value("server.host")?;
@@ -110,16 +172,31 @@ pub(crate) fn file_list(pattern: &str) -> anyhow::Result<()> {
     let client = FileServerClient::new(&server_host, file_server_port);
     let sid = read_session_id()?;
 
-    let wr_reply = client.list(&pattern, &sid);
+    if all_pages {
+        list_query.start_page = Some(list_query.start_page.unwrap_or(0));
+        list_query.page_size = Some(list_query.page_size.unwrap_or(LIST_PAGE_SIZE));
+    }
 
-    match wr_reply {
-        Ok(reply) => {
-            let s = serde_json::to_string_pretty(&reply).unwrap();
-            println!("{}", &s);
-        }
-        Err(e) => {
-            println!("Status Code: {}", e.message);
+    loop {
+        let reply = match client.list(pattern, &list_query, &sid) {
+            Ok(reply) => reply,
+            Err(e) => {
+                println!("Status Code: {}", e.message);
+                return Ok(());
+            }
+        };
+
+        let s = serde_json::to_string_pretty(&reply.list_of_files).unwrap();
+        println!("{}", &s);
+
+        let first = list_query.start_page.unwrap_or(0) as i64 * list_query.page_size.unwrap_or(0) as i64;
+        let last = first + reply.list_of_files.len() as i64;
+        println!("Files {} to {} of {}", min(first + 1, last), last, reply.total_count);
+
+        if !all_pages || reply.list_of_files.is_empty() || last >= reply.total_count {
+            break;
         }
+        list_query.start_page = list_query.start_page.map(|start_page| start_page + 1);
     }
     Ok(())
 }
@@ -138,15 +215,163 @@ pub(crate) fn file_loading() -> anyhow::Result<()> {
 
     match wr_reply {
         Ok(reply) => {
-            println!("ref.\tinfo\tsession\tstart\tcount\tencrypted");
+            println!("ref.\tinfo\tsession\tstart\tcount\tencrypted\tstatus");
             for upload in reply.list_of_upload_info {
-                println!("{}\t{}\t{}\t{}\t{}\t{}",
+                println!("{}\t{}\t{}\t{}\t{}\t{}\t{}",
                     &upload.file_reference,
                     &upload.item_info,
                     &upload.session_number,
                     &upload.start_date_time,
                     &upload.uploaded_count,
-                    &upload.encrypted_count);
+                    &upload.encrypted_count,
+                    &upload.processing_status);
+            }
+        }
+        Err(e) => {
+            println!("Status Code: {}", e.message);
+        }
+    }
+    Ok(())
+}
+
+/// Fetch the processing status of a file, with the reason of its failure
+pub(crate) fn file_status(file_ref: &str) -> anyhow::Result<()> {
+    println!("👶 Getting the processing status...");
+
+    let server_host = get_prop_value("server.host")?;
+    let file_server_port: u16 = get_prop_value("fs.port")?.parse()?;
+    println!("File server port: {}", file_server_port);
+    let client = FileServerClient::new(&server_host, file_server_port);
+    let sid = read_session_id()?;
+
+    let wr_reply = client.status(file_ref, &sid);
+
+    match wr_reply {
+        Ok(reply) => {
+            println!("Status: {} ({})", &reply.status, &reply.status_date_time);
+            if let Some(failure_reason) = &reply.failure_reason {
+                println!("Failure reason: {}", failure_reason);
+            }
+        }
+        Err(e) => {
+            println!("Status Code: {}", e.message);
+        }
+    }
+    Ok(())
+}
+
+///
+/// Parse and index again the file, or the files matching the pattern whose parsing failed
+///
+pub(crate) fn file_reindex(o_file_ref: Option<&str>, o_pattern: Option<&str>) -> anyhow::Result<()> {
+    println!("👶 Reindexing the files...");
+
+    let server_host = get_prop_value("server.host")?;
+    let file_server_port: u16 = get_prop_value("fs.port")?.parse()?;
+    println!("File server port: {}", file_server_port);
+    let client = FileServerClient::new(&server_host, file_server_port);
+    let sid = read_session_id()?;
+
+    let wr_reply = match (o_file_ref, o_pattern) {
+        (Some(file_ref), _) => client.reindex(file_ref, &sid),
+        (None, Some(pattern)) => client.reindex_files(pattern, &sid),
+        (None, None) => return Err(anyhow!("💣 A file reference or a pattern is required")),
+    };
+
+    match wr_reply {
+        Ok(reply) => {
+            println!("ref.\tstatus\tfailure reason");
+            for file in reply.list_of_files {
+                println!("{}\t{}\t{}", &file.file_ref, &file.status, file.failure_reason.unwrap_or_default());
+            }
+        }
+        Err(e) => {
+            println!("Status Code: {}", e.message);
+        }
+    }
+    Ok(())
+}
+
+/// Fetch the metadata of a file
+pub(crate) fn file_metadata(file_ref: &str) -> anyhow::Result<()> {
+    println!("👶 Getting the metadata...");
+
+    let server_host = get_prop_value("server.host")?;
+    let file_server_port: u16 = get_prop_value("fs.port")?.parse()?;
+    println!("File server port: {}", file_server_port);
+    let client = FileServerClient::new(&server_host, file_server_port);
+    let sid = read_session_id()?;
+
+    let wr_reply = client.metadata(file_ref, &sid);
+
+    match wr_reply {
+        Ok(reply) => {
+            println!("key\tvalue");
+            for metadata in reply.list_of_metadata {
+                println!("{}\t{}", &metadata.key, metadata.value.to_string());
+            }
+        }
+        Err(e) => {
+            println!("Status Code: {}", e.message);
+        }
+    }
+    Ok(())
+}
+
+/// Check the stored parts of a file
+pub(crate) fn file_verify(file_ref: &str) -> anyhow::Result<()> {
+    println!("👶 Verifying the file...");
+
+    let server_host = get_prop_value("server.host")?;
+    let file_server_port: u16 = get_prop_value("fs.port")?.parse()?;
+    println!("File server port: {}", file_server_port);
+    let client = FileServerClient::new(&server_host, file_server_port);
+    let sid = read_session_id()?;
+
+    let wr_reply = client.verify(file_ref, &sid);
+
+    match wr_reply {
+        Ok(reply) => {
+            println!("File reference: {}", &reply.file_ref);
+            println!("Valid: {}", reply.is_valid);
+            for issue in reply.issues {
+                println!("\t{}", issue);
+            }
+        }
+        Err(e) => {
+            println!("Status Code: {}", e.message);
+        }
+    }
+    Ok(())
+}
+
+///
+/// Start the integrity check of all the stored files, or display the report of the last one
+///
+pub(crate) fn file_scrub(report: bool) -> anyhow::Result<()> {
+    println!("👶 Checking the integrity of the files...");
+
+    let server_host = get_prop_value("server.host")?;
+    let file_server_port: u16 = get_prop_value("fs.port")?.parse()?;
+    println!("File server port: {}", file_server_port);
+    let client = FileServerClient::new(&server_host, file_server_port);
+    let sid = read_session_id()?;
+
+    let wr_reply = if report { client.scrub_report(&sid) } else { client.start_scrub(&sid) };
+
+    match wr_reply {
+        Ok(reply) => {
+            println!("Status: {}", &reply.status);
+            println!("Started: {}", reply.start_date_time);
+            if let Some(end_date_time) = reply.end_date_time {
+                println!("Ended: {}", end_date_time);
+            }
+            println!("Checked files: {}, corrupted files: {}", reply.file_count, reply.corrupted_count);
+            if !reply.list_of_corrupted_files.is_empty() {
+                println!("ref.\tverified\tissues");
+                for file in reply.list_of_corrupted_files {
+                    println!("{}\t{}\t{}", &file.file_ref, file.verified_date_time, file.issues.join(", "));
+                }
             }
         }
         Err(e) => {
@@ -154,4 +379,4 @@ pub(crate) fn file_loading() -> anyhow::Result<()> {
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
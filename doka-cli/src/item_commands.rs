@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Cursor, Read};
 use std::path::Path;
 
 use anyhow::anyhow;
@@ -7,7 +7,9 @@ use regex::Regex;
 
 use commons_error::*;
 use common_config::properties::get_prop_value;
-use dkdto::web_types::{AddItemRequest, AddItemTagRequest, AddTagValue, EnumTagValue, GetItemReply};
+use dkdto::web_types::{
+    AddItemRequest, AddItemTagRequest, AddItemVersionRequest, AddTagValue, EnumTagValue, GetItemReply, ItemVersionReply,
+};
 use doka_cli::request_client::{DocumentServerClient, FileServerClient};
 
 use crate::item_commands::DisplayFormat::{INLINE, JSON};
@@ -292,6 +294,131 @@ pub fn item_tag_delete(id: &str, o_delete_props: Option<&str>) -> anyhow::Result
     }
 }
 
+///
+/// Upload the file [path] and make it the new current version of the item
+///
+pub(crate) fn item_version_add(id: &str, path: &str) -> anyhow::Result<()> {
+    println!("👶 Adding a version to the item...");
+
+    let item_id: i64 = id.parse()?;
+    let sid = read_session_id()?;
+    let server_host = get_prop_value("server.host")?;
+    let document_server_port: u16 = get_prop_value("ds.port")?.parse()?;
+    let file_server_port: u16 = get_prop_value("fs.port")?.parse()?;
+
+    println!("Uploading the file...");
+    let file = File::open(Path::new(path))?;
+    let mut buf_reader = BufReader::new(file);
+    let mut binary: Vec<u8> = vec![];
+    let _n = buf_reader.read_to_end(&mut binary)?;
+    let file_server_client = FileServerClient::new(&server_host, file_server_port);
+    let upload_reply = file_server_client.upload(id, &binary, &sid).map_err(|e| {
+        eprintln!("File upload failed, {}", &e.message);
+        anyhow!("{}", e.message)
+    })?;
+    println!("New file reference: {}", &upload_reply.file_ref);
+
+    let document_server_client = DocumentServerClient::new(&server_host, document_server_port);
+    let add_item_version_request = AddItemVersionRequest { file_ref: upload_reply.file_ref };
+
+    match document_server_client.add_item_version(item_id, &add_item_version_request, &sid) {
+        Ok(reply) => {
+            println!("😎 Version successfully added, for item id : {} ", item_id);
+            show_versions(&reply);
+            Ok(())
+        }
+        Err(e) => Err(anyhow!("{} - {}", e.http_error_code, e.message)),
+    }
+}
+
+///
+/// List the file versions of the item
+///
+pub(crate) fn item_versions(id: &str) -> anyhow::Result<()> {
+    println!("👶 Getting the item versions...");
+
+    let item_id: i64 = id.parse()?;
+    let sid = read_session_id()?;
+    let server_host = get_prop_value("server.host")?;
+    let file_server_port: u16 = get_prop_value("fs.port")?.parse()?;
+    let client = FileServerClient::new(&server_host, file_server_port);
+
+    match client.item_versions(item_id, &sid) {
+        Ok(reply) => {
+            println!("😎 Versions successfully found, count : {} ", reply.list_of_versions.len());
+            show_versions(&reply);
+            Ok(())
+        }
+        Err(e) => Err(anyhow!("{} - {}", e.http_error_code, e.message)),
+    }
+}
+
+///
+/// Make a previous version the current file of the item
+///
+pub(crate) fn item_version_restore(id: &str, version: &str) -> anyhow::Result<()> {
+    println!("👶 Restoring the item version...");
+
+    let item_id: i64 = id.parse()?;
+    let version_number: u32 = version.parse()?;
+    let sid = read_session_id()?;
+    let server_host = get_prop_value("server.host")?;
+    let document_server_port: u16 = get_prop_value("ds.port")?.parse()?;
+    let client = DocumentServerClient::new(&server_host, document_server_port);
+
+    match client.restore_item_version(item_id, version_number, &sid) {
+        Ok(reply) => {
+            println!("😎 Version {} is now the current one, for item id : {} ", version_number, item_id);
+            show_versions(&reply);
+            Ok(())
+        }
+        Err(e) => Err(anyhow!("{} - {}", e.http_error_code, e.message)),
+    }
+}
+
+///
+/// Download the file of a version of the item into [path]
+///
+pub(crate) fn item_version_download(id: &str, version: &str, path: &str) -> anyhow::Result<()> {
+    println!("👶 Downloading the item version...");
+
+    let item_id: i64 = id.parse()?;
+    let version_number: u32 = version.parse()?;
+    let sid = read_session_id()?;
+    let server_host = get_prop_value("server.host")?;
+    let file_server_port: u16 = get_prop_value("fs.port")?.parse()?;
+    let client = FileServerClient::new(&server_host, file_server_port);
+
+    let reply = client
+        .download_version(item_id, version_number, &sid)
+        .map_err(|e| anyhow!("{} - {}", e.http_error_code, e.message))?;
+
+    let size = reply.data.len();
+    let mut file = File::create(path)?;
+    let mut content = Cursor::new(reply.data);
+    std::io::copy(&mut content, &mut file)?;
+    println!("Document stored at: {}", path);
+    println!("Document type: {}", reply.media_type);
+    println!("Document size: {}", size);
+    Ok(())
+}
+
+fn show_versions(reply: &ItemVersionReply) {
+    for version in &reply.list_of_versions {
+        let current = if version.is_current { "*" } else { " " };
+        let file_size = version.file_size.map(|size| size.to_string()).unwrap_or("-".to_string());
+        println!(
+            "{}version:{}\tfile_ref:{}\tcreated:{}\tsize:{}\tchecksum:{}",
+            current,
+            version.version_number,
+            version.file_ref,
+            version.created,
+            file_size,
+            version.checksum.as_deref().unwrap_or("-")
+        );
+    }
+}
+
 fn build_properties_from_string(o_props: Option<&str>) -> anyhow::Result<Vec<AddTagValue>> {
     let properties = if let Some(props_str) = o_props {
         let re = Regex::new(r"\((.*?)\)").unwrap();
@@ -7,11 +7,18 @@ use anyhow::anyhow;
 use commons_error::*;
 use common_config::conf_reader::{read_config, read_config_from_path, read_env};
 use common_config::properties::{get_prop_value, set_prop_values};
+use dkdto::web_types::FileListQuery;
 
 use crate::command_options::{display_commands, load_commands, parse_args, Command, Params};
-use crate::customer_commands::{create_customer, delete_customer, disable_customer};
-use crate::file_commands::{file_download, file_info, file_list, file_loading, file_upload};
-use crate::item_commands::{create_item, get_item, item_tag_delete, item_tag_update, search_item};
+use crate::customer_commands::{create_customer, customer_usage, delete_customer, disable_customer, set_customer_quota};
+use crate::file_commands::{
+    file_download, file_info, file_list, file_loading, file_metadata, file_reindex, file_scrub, file_status,
+    file_upload, file_verify,
+};
+use crate::item_commands::{
+    create_item, get_item, item_tag_delete, item_tag_update, item_version_add, item_version_download,
+    item_version_restore, item_versions, search_item,
+};
 use crate::session_commands::session_login;
 use crate::token_commands::{get_target_file, token_generate};
 
@@ -27,10 +34,12 @@ const LOGIN_SESSION_FAILED: u16 = 30;
 const DELETE_CUSTOMER_FAILED: u16 = 40;
 const DISABLE_CUSTOMER_FAILED: u16 = 50;
 const CREATE_CUSTOMER_FAILED: u16 = 60;
+const CUSTOMER_QUOTA_FAILED: u16 = 70;
 const GENERATE_TOKEN_FAILED: u16 = 80;
 const CREATE_ITEM_FAILED: u16 = 90;
 const GET_ITEM_FAILED: u16 = 100;
 const PROP_ITEM_FAILED: u16 = 101;
+const ITEM_VERSION_FAILED: u16 = 102;
 const FILE_UPLOAD_FAILED: u16 = 110;
 const FILE_DOWNLOAD_FAILED: u16 = 120;
 const SUCCESS: u16 = 0;
@@ -126,6 +135,26 @@ fn dispatch(params: &Params, commands: &[Command]) -> u16 {
             let err = delete_customer(&customer_code);
             success_or_err(err, DELETE_CUSTOMER_FAILED)
         }
+        ("customer", "quota") => {
+            let Ok((customer_code, o_storage_quota, o_file_count_quota)) =
+                (|| -> anyhow::Result<(String, Option<u64>, Option<u64>)> {
+                    Ok((
+                        extract_mandatory_option(&params.options, "-cc")?,
+                        extract_option(&params.options, "-s")?.map(|s| s.parse()).transpose()?,
+                        extract_option(&params.options, "-fc")?.map(|s| s.parse()).transpose()?,
+                    ))
+                })()
+                .map_err(eprint_fwd!("Error"))
+            else {
+                return PARAMETER_ERROR;
+            };
+            let err = set_customer_quota(&customer_code, o_storage_quota, o_file_count_quota);
+            success_or_err(err, CUSTOMER_QUOTA_FAILED)
+        }
+        ("customer", "usage") => {
+            let err = customer_usage();
+            success_or_err(err, CUSTOMER_QUOTA_FAILED)
+        }
         ("session", "login") => {
             let Ok((user_name, user_password)) = (|| -> anyhow::Result<(String, String)> {
                 Ok((
@@ -195,17 +224,69 @@ fn dispatch(params: &Params, commands: &[Command]) -> u16 {
             };
             success_or_err(err, PROP_ITEM_FAILED)
         }
-        ("file", "upload") => {
-            let Ok((item_info, path)) = (|| -> anyhow::Result<(String, String)> {
+        ("item", "version-add") => {
+            let Ok((id, path)) = (|| -> anyhow::Result<(String, String)> {
                 Ok((
-                    extract_mandatory_option(&params.options, "-ii")?,
+                    extract_mandatory_option(&params.options, "-id")?,
                     extract_mandatory_option(&params.options, "-pt")?,
                 ))
             })()
             .map_err(eprint_fwd!("Error")) else {
                 return PARAMETER_ERROR;
             };
-            let err = file_upload(&item_info, &path);
+            let err = item_version_add(&id, &path);
+            success_or_err(err, ITEM_VERSION_FAILED)
+        }
+        ("item", "versions") => {
+            let Ok(id) =
+                extract_mandatory_option(&params.options, "-id").map_err(eprint_fwd!("Error"))
+            else {
+                return PARAMETER_ERROR;
+            };
+            let err = item_versions(&id);
+            success_or_err(err, ITEM_VERSION_FAILED)
+        }
+        ("item", "version-restore") => {
+            let Ok((id, version)) = (|| -> anyhow::Result<(String, String)> {
+                Ok((
+                    extract_mandatory_option(&params.options, "-id")?,
+                    extract_mandatory_option(&params.options, "-v")?,
+                ))
+            })()
+            .map_err(eprint_fwd!("Error")) else {
+                return PARAMETER_ERROR;
+            };
+            let err = item_version_restore(&id, &version);
+            success_or_err(err, ITEM_VERSION_FAILED)
+        }
+        ("item", "version-download") => {
+            let Ok((id, version, path)) = (|| -> anyhow::Result<(String, String, String)> {
+                Ok((
+                    extract_mandatory_option(&params.options, "-id")?,
+                    extract_mandatory_option(&params.options, "-v")?,
+                    extract_mandatory_option(&params.options, "-pt")?,
+                ))
+            })()
+            .map_err(eprint_fwd!("Error")) else {
+                return PARAMETER_ERROR;
+            };
+            let err = item_version_download(&id, &version, &path);
+            success_or_err(err, ITEM_VERSION_FAILED)
+        }
+        ("file", "upload") => {
+            let Ok((item_info, path, o_file_ref)) =
+                (|| -> anyhow::Result<(String, String, Option<String>)> {
+                    Ok((
+                        extract_mandatory_option(&params.options, "-ii")?,
+                        extract_mandatory_option(&params.options, "-pt")?,
+                        extract_option(&params.options, "-fr")?,
+                    ))
+                })()
+                .map_err(eprint_fwd!("Error"))
+            else {
+                return PARAMETER_ERROR;
+            };
+            let err = file_upload(&item_info, &path, o_file_ref.as_deref());
             success_or_err(err, FILE_UPLOAD_FAILED)
         }
         ("file", "download") => {
@@ -232,19 +313,74 @@ fn dispatch(params: &Params, commands: &[Command]) -> u16 {
             success_or_err(err, FILE_DOWNLOAD_FAILED)
         }
         ("file", "list") => {
-            let Ok(pattern) = (|| -> anyhow::Result<String> {
-                Ok(extract_mandatory_option(&params.options, "-m")?)
+            let Ok((pattern, list_query)) = (|| -> anyhow::Result<(String, FileListQuery)> {
+                let list_query = FileListQuery {
+                    start_page: extract_option(&params.options, "-sp")?.map(|v| v.parse()).transpose()?,
+                    page_size: extract_option(&params.options, "-ps")?.map(|v| v.parse()).transpose()?,
+                    sort_by: extract_option(&params.options, "-sb")?,
+                    sort_order: extract_option(&params.options, "-so")?,
+                    media_type: extract_option(&params.options, "-mt")?,
+                    is_encrypted: extract_option(&params.options, "-e")?.map(|v| v.parse()).transpose()?,
+                    is_fulltext_parsed: extract_option(&params.options, "-fp")?.map(|v| v.parse()).transpose()?,
+                };
+                Ok((extract_mandatory_option(&params.options, "-m")?, list_query))
             })()
             .map_err(eprint_fwd!("Error")) else {
                 return PARAMETER_ERROR;
             };
-            let err = file_list(&pattern);
+            let all_pages = params.options.contains_key("-a") || params.options.contains_key("--all");
+            let err = file_list(&pattern, list_query, all_pages);
             success_or_err(err, FILE_DOWNLOAD_FAILED)
         }
         ("file", "loading") => {
             let err = file_loading();
             success_or_err(err, FILE_DOWNLOAD_FAILED)
         }
+        ("file", "reindex") => {
+            let Ok((o_file_ref, o_pattern)) = (|| -> anyhow::Result<(Option<String>, Option<String>)> {
+                Ok((
+                    extract_option(&params.options, "-fr")?,
+                    extract_option(&params.options, "-m")?,
+                ))
+            })()
+            .map_err(eprint_fwd!("Error")) else {
+                return PARAMETER_ERROR;
+            };
+            let err = file_reindex(o_file_ref.as_deref(), o_pattern.as_deref());
+            success_or_err(err, FILE_DOWNLOAD_FAILED)
+        }
+        ("file", "status") => {
+            let Ok(file_ref) =
+                extract_mandatory_option(&params.options, "-fr").map_err(eprint_fwd!("Error"))
+            else {
+                return PARAMETER_ERROR;
+            };
+            let err = file_status(&file_ref);
+            success_or_err(err, FILE_DOWNLOAD_FAILED)
+        }
+        ("file", "metadata") => {
+            let Ok(file_ref) =
+                extract_mandatory_option(&params.options, "-fr").map_err(eprint_fwd!("Error"))
+            else {
+                return PARAMETER_ERROR;
+            };
+            let err = file_metadata(&file_ref);
+            success_or_err(err, FILE_DOWNLOAD_FAILED)
+        }
+        ("file", "verify") => {
+            let Ok(file_ref) =
+                extract_mandatory_option(&params.options, "-fr").map_err(eprint_fwd!("Error"))
+            else {
+                return PARAMETER_ERROR;
+            };
+            let err = file_verify(&file_ref);
+            success_or_err(err, FILE_DOWNLOAD_FAILED)
+        }
+        ("file", "scrub") => {
+            let report = params.options.contains_key("-r") || params.options.contains_key("--report");
+            let err = file_scrub(report);
+            success_or_err(err, FILE_DOWNLOAD_FAILED)
+        }
         (_, _) => SUCCESS,
     }
 }
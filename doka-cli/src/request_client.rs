@@ -15,11 +15,15 @@ use commons_error::*;
 use dkdto::api_error::ApiError;
 use dkdto::error_codes::HTTP_CLIENT_ERROR;
 use dkdto::web_types::{
-    AddItemReply, AddItemRequest, AddItemTagReply, AddItemTagRequest, AddKeyReply, AddKeyRequest, AddTagReply,
-    AddTagRequest, CreateCustomerReply, CreateCustomerRequest, CustomerKeyReply, DeleteFullTextRequest, FullTextReply,
-    FullTextRequest, GetFileInfoReply, GetFileInfoShortReply, GetItemReply, GetTagReply, ListOfFileInfoReply,
-    ListOfUploadInfoReply, LoginReply, LoginRequest, MediaBytes, OpenSessionReply, OpenSessionRequest, SessionReply,
-    SimpleMessage, TikaMeta, TikaParsing, UploadReply, WebResponse, WebTypeBuilder,
+    AddItemReply, AddItemRequest, AddItemTagReply, AddItemTagRequest, AddItemVersionRequest, AddKeyReply,
+    AddKeyRequest, AddTagReply, AddTagRequest, BulkItemTagReply, BulkItemTagRequest, CreateCustomerReply,
+    CreateCustomerRequest, CreateUploadSessionRequest, CustomerKeyReply, CustomerQuotaReply, CustomerQuotaRequest,
+    DeleteFullTextRequest, DeleteItemReply, FileIntegrityReply, FileListQuery, FileMetadataReply, FileStatusReply,
+    FullTextReply, FullTextRequest, GetFileInfoReply, GetFileInfoShortReply, GetItemReply, GetTagReply,
+    ItemVersionReply, ListOfCustomerUsageReply, ListOfFileInfoReply, ListOfUploadInfoReply, LoginReply, LoginRequest,
+    MediaBytes, OpenSessionReply, OpenSessionRequest, PatchItemRequest, PatchTagReply, PatchTagRequest, ReindexReply,
+    ScrubReply, SessionReply, SimpleMessage, TikaMeta, TikaParsing, UploadBlockReply, UploadReply, UploadSessionReply,
+    WebResponse, WebTypeBuilder,
 };
 
 /// TODO This file should be in Dkdto, so we could reuse it without the doka-cli module  
@@ -237,6 +241,29 @@ impl WebServer {
         self.retry(put_bytes)
     }
 
+    /// Generic routine to put a binary content, with a security token
+    fn put_bytes_with_token<V: de::DeserializeOwned>(
+        &self,
+        url: &str,
+        request: Vec<u8>,
+        token: &TokenType,
+    ) -> anyhow::Result<WebResponse<V>> {
+        let request_builder = reqwest::blocking::Client::new().put(Url::parse(url)?).timeout(TIMEOUT);
+        let request_builder_2 = Self::add_header(request_builder, token);
+        Self::send_request_builder(request_builder_2.body(request))
+    }
+
+    fn put_bytes_with_token_retry<V: de::DeserializeOwned>(
+        &self,
+        url: &str,
+        request: &[u8],
+        token: &TokenType,
+    ) -> WebResponse<V> {
+        let put_bytes =
+            || -> anyhow::Result<WebResponse<V>> { self.put_bytes_with_token(url, request.to_vec(), token) };
+        self.retry(put_bytes).unwrap_or_else(|_| WebResponse::from_api_error(&HTTP_CLIENT_ERROR))
+    }
+
     ///
     /// Patch
     ///
@@ -251,6 +278,26 @@ impl WebServer {
         self.retry(patch_data).unwrap_or_else(|_| WebResponse::from_api_error(&HTTP_CLIENT_ERROR))
     }
 
+    fn patch_json_data<U: Serialize, V: de::DeserializeOwned>(
+        &self,
+        url: &str,
+        request: &U,
+        token: &TokenType,
+    ) -> anyhow::Result<WebResponse<V>> {
+        let request_builder = reqwest::blocking::Client::new().patch(Url::parse(url)?).timeout(TIMEOUT).json(request);
+        Self::send_request_builder(Self::add_header(request_builder, token))
+    }
+
+    fn patch_json_data_retry<U: Serialize, V: de::DeserializeOwned>(
+        &self,
+        url: &str,
+        request: &U,
+        token: &TokenType,
+    ) -> WebResponse<V> {
+        let patch_data = || -> anyhow::Result<WebResponse<V>> { self.patch_json_data(url, request, token) };
+        self.retry(patch_data).unwrap_or_else(|_| WebResponse::from_api_error(&HTTP_CLIENT_ERROR))
+    }
+
     ///
     /// Delete
     ///
@@ -401,6 +448,24 @@ impl AdminServerClient {
         self.server.delete_for_url(customer_code, "customer", &Token(token.to_owned()))
     }
 
+    pub fn set_customer_quota(
+        &self,
+        customer_code: &str,
+        request: &CustomerQuotaRequest,
+        token: &str,
+    ) -> WebResponse<CustomerQuotaReply> {
+        let url = self
+            .server
+            .build_url_with_refcode("customer/quota", utf8_percent_encode(customer_code, NON_ALPHANUMERIC).to_string());
+        let headers = CustomHeaders { token_type: Token(token.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, request, &headers)
+    }
+
+    pub fn customer_usage(&self, token: &str) -> WebResponse<ListOfCustomerUsageReply> {
+        let url = self.server.build_url("customer/usage");
+        self.server.get_data_retry(&url, &Token(token.to_owned()))
+    }
+
     pub fn login(&self, request: &LoginRequest) -> WebResponse<LoginReply> {
         // let url = format!("http://{}:{}/admin-server/login", &self.server.server_name, self.server.port);
         let url = self.server.build_url("login");
@@ -443,6 +508,16 @@ impl DocumentServerClient {
         reply
     }
 
+    ///
+    /// Items with a link tag pointing to the item
+    ///
+    pub fn get_linked_items(&self, item_id: i64, sid: &str) -> WebResponse<GetItemReply> {
+        // http://{}:{}/document-server/item/<item_id>/backlinks
+        let end_point = format!("item/{0}/backlinks", item_id);
+        let url = self.server.build_url(&end_point);
+        self.server.get_data_retry(&url, &Sid(sid.to_string()))
+    }
+
     ///
     ///
     ///
@@ -472,6 +547,72 @@ impl DocumentServerClient {
         self.server.delete_data_retry(&url, &Sid(sid.to_owned()))
     }
 
+    ///
+    /// File versions of the item
+    ///
+    pub fn item_versions(&self, item_id: i64, sid: &str) -> WebResponse<ItemVersionReply> {
+        // http://{}:{}/document-server/item/<item_id>/versions
+        let end_point = format!("item/{0}/versions", item_id);
+        let url = self.server.build_url(&end_point);
+        self.server.get_data_retry(&url, &Sid(sid.to_string()))
+    }
+
+    ///
+    /// Attach an uploaded file as the new current version of the item
+    ///
+    pub fn add_item_version(
+        &self,
+        item_id: i64,
+        request: &AddItemVersionRequest,
+        sid: &str,
+    ) -> WebResponse<ItemVersionReply> {
+        // http://{}:{}/document-server/item/<item_id>/versions
+        let end_point = format!("item/{0}/versions", item_id);
+        let url = self.server.build_url(&end_point);
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, request, &headers)
+    }
+
+    ///
+    /// Make a previous version the current file of the item
+    ///
+    pub fn restore_item_version(&self, item_id: i64, version_number: u32, sid: &str) -> WebResponse<ItemVersionReply> {
+        // http://{}:{}/document-server/item/<item_id>/versions/<version_number>/restore
+        let end_point = format!("item/{0}/versions/{1}/restore", item_id, version_number);
+        let url = self.server.build_url(&end_point);
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, &(), &headers)
+    }
+
+    ///
+    /// Delete the item, with its file when [delete_file] is set
+    ///
+    pub fn delete_item(&self, item_id: i64, delete_file: bool, sid: &str) -> WebResponse<DeleteItemReply> {
+        // http://{}:{}/document-server/item/<item_id>?delete_file=<delete_file>
+        let end_point = format!("item/{0}?delete_file={1}", item_id, delete_file);
+        let url = self.server.build_url(&end_point);
+        self.server.delete_data_retry(&url, &Sid(sid.to_owned()))
+    }
+
+    ///
+    /// Rename the item, attach or detach its file
+    ///
+    pub fn patch_item(&self, item_id: i64, request: &PatchItemRequest, sid: &str) -> WebResponse<GetItemReply> {
+        // http://{}:{}/document-server/item/<item_id>
+        let url = self.server.build_url_with_refcode("item", item_id);
+        self.server.patch_json_data_retry(&url, request, &Sid(sid.to_string()))
+    }
+
+    ///
+    /// Add and remove tags on all the items matching the filters
+    ///
+    pub fn bulk_item_tags(&self, request: &BulkItemTagRequest, sid: &str) -> WebResponse<BulkItemTagReply> {
+        // http://{}:{}/document-server/search/tags
+        let url = self.server.build_url("search/tags");
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, request, &headers)
+    }
+
     ///
     /// TODO might be merged with get_item
     ///
@@ -510,6 +651,15 @@ impl DocumentServerClient {
         self.server.delete_for_url(tag_id, "tag", &Sid(sid.to_owned()))
     }
 
+    ///
+    /// Change a tag definition, or only report the values which cannot be converted with a dry run
+    ///
+    pub fn update_tag(&self, tag_id: i64, request: &PatchTagRequest, sid: &str) -> WebResponse<PatchTagReply> {
+        // http://{}:{}/document-server/tag/<tag_id>
+        let url = self.server.build_url_with_refcode("tag", tag_id);
+        self.server.patch_json_data_retry(&url, request, &Sid(sid.to_string()))
+    }
+
     ///
     ///
     ///
@@ -606,6 +756,39 @@ impl FileServerClient {
         self.server.post_bytes_retry(&url, request, &Sid(sid.to_string()))
     }
 
+    pub fn create_upload_session(
+        &self,
+        request: &CreateUploadSessionRequest,
+        sid: &str,
+    ) -> WebResponse<UploadSessionReply> {
+        let url = self.server.build_url("upload_session");
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, request, &headers)
+    }
+
+    pub fn upload_session_status(&self, file_ref: &str, sid: &str) -> WebResponse<UploadSessionReply> {
+        let url = self.server.build_url_with_refcode("upload_session", file_ref);
+        self.server.get_data_retry(&url, &Sid(sid.to_string()))
+    }
+
+    pub fn upload_block(
+        &self,
+        file_ref: &str,
+        block_num: u32,
+        block: &[u8],
+        sid: &str,
+    ) -> WebResponse<UploadBlockReply> {
+        // http://localhost:{{PORT}}/file-server/upload_session/47cef2c4-188d-43ed-895d-fe29440633da/block/0
+        let url = self.server.build_url_with_refcode("upload_session", format!("{}/block/{}", file_ref, block_num));
+        self.server.put_bytes_with_token_retry(&url, block, &Sid(sid.to_string()))
+    }
+
+    pub fn finalize_upload_session(&self, file_ref: &str, sid: &str) -> WebResponse<UploadReply> {
+        let url = self.server.build_url_with_refcode("upload_session", format!("{}/finalize", file_ref));
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, &(), &headers)
+    }
+
     pub fn download(&self, file_reference: &str, sid: &str) -> WebResponse<MediaBytes> /*WebResponse<( String, bytes::Bytes, StatusCode )>*/
     {
         // http://localhost:{{PORT}}/file-server/download/47cef2c4-188d-43ed-895d-fe29440633da
@@ -613,6 +796,23 @@ impl FileServerClient {
         self.server.get_binary_data_retry(&url, &Sid(sid.to_string()))
     }
 
+    pub fn item_versions(&self, item_id: i64, sid: &str) -> WebResponse<ItemVersionReply> {
+        // http://localhost:{{PORT}}/file-server/versions/<item_id>
+        let url = self.server.build_url_with_refcode("versions", item_id);
+        self.server.get_data_retry(&url, &Sid(sid.to_string()))
+    }
+
+    pub fn download_version(&self, item_id: i64, version_number: u32, sid: &str) -> WebResponse<MediaBytes> {
+        // http://localhost:{{PORT}}/file-server/versions/<item_id>/<version_number>/download
+        let url = self.server.build_url(&format!("versions/{}/{}/download", item_id, version_number));
+        self.server.get_binary_data_retry(&url, &Sid(sid.to_string()))
+    }
+
+    pub fn delete_file(&self, file_ref: &str, sid: &str) -> WebResponse<SimpleMessage> {
+        // http://localhost:{{PORT}}/file-server/file/<file_ref>
+        self.server.delete_for_url(file_ref, "file", &Sid(sid.to_owned()))
+    }
+
     pub fn info(&self, file_ref: &str, sid: &str) -> WebResponse<GetFileInfoReply> {
         // let url = format!("http://{}:{}/file-server/info/{}", &self.server.server_name, self.server.port);
         let url = self.server.build_url_with_refcode("info", &file_ref);
@@ -627,20 +827,78 @@ impl FileServerClient {
         self.server.get_data_retry(&url, &Sid(sid.to_string()))
     }
 
+    pub fn status(&self, file_ref: &str, sid: &str) -> WebResponse<FileStatusReply> {
+        let url = self.server.build_url_with_refcode("status", file_ref);
+        self.server.get_data_retry(&url, &Sid(sid.to_string()))
+    }
+
+    pub fn metadata(&self, file_ref: &str, sid: &str) -> WebResponse<FileMetadataReply> {
+        let url = self.server.build_url_with_refcode("metadata", file_ref);
+        self.server.get_data_retry(&url, &Sid(sid.to_string()))
+    }
+
+    pub fn verify(&self, file_ref: &str, sid: &str) -> WebResponse<FileIntegrityReply> {
+        let url = self.server.build_url_with_refcode("verify", file_ref);
+        self.server.get_data_retry(&url, &Sid(sid.to_string()))
+    }
+
+    pub fn start_scrub(&self, sid: &str) -> WebResponse<ScrubReply> {
+        let url = self.server.build_url("scrub");
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, &(), &headers)
+    }
+
+    pub fn scrub_report(&self, sid: &str) -> WebResponse<ScrubReply> {
+        let url = self.server.build_url("scrub");
+        self.server.get_data_retry(&url, &Sid(sid.to_string()))
+    }
+
+    pub fn reindex(&self, file_ref: &str, sid: &str) -> WebResponse<ReindexReply> {
+        let url = self.server.build_url_with_refcode("reindex", file_ref);
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, &(), &headers)
+    }
+
+    pub fn reindex_files(&self, pattern: &str, sid: &str) -> WebResponse<ReindexReply> {
+        // http://localhost:{{PORT}}/file-server/reindex?pattern=<pattern>
+        let url = self.server.build_url(&format!("reindex?pattern={}", pattern));
+        let headers = CustomHeaders { token_type: Sid(sid.to_string()), x_request_id: None, cek: None };
+        self.server.post_data_retry(&url, &(), &headers)
+    }
+
     pub fn loading(&self, sid: &str) -> WebResponse<ListOfUploadInfoReply> {
         // let url = format!("http://{}:{}/file-server/loading/{}", &self.server.server_name, self.server.port);
         let url = self.server.build_url("loading");
         self.server.get_data_retry(&url, &Sid(sid.to_string()))
     }
 
-    pub fn list(&self, pattern: &str, sid: &str) -> WebResponse<ListOfFileInfoReply> {
-        // let url = format!("http://{}:{}/file-server/stats/{}", &self.server.server_name, self.server.port);
-        let url = self.server.build_url_with_refcode("list", &pattern);
-        // let url = self.server.build_url("stats/1ABH234");
+    pub fn list(&self, pattern: &str, list_query: &FileListQuery, sid: &str) -> WebResponse<ListOfFileInfoReply> {
+        // http://localhost:{{PORT}}/file-server/list/<pattern>?start_page=0&page_size=20&sort_by=size
+        // The ? of the glob pattern must not start the query string
+        let pattern = utf8_percent_encode(pattern, NON_ALPHANUMERIC);
+        let url = self.server.build_url(&format!("list/{}?{}", pattern, file_list_query_string(list_query)));
         self.server.get_data_retry(&url, &Sid(sid.to_string()))
     }
 }
 
+/// Query string of the file listing, only the defined criteria are sent
+pub(crate) fn file_list_query_string(list_query: &FileListQuery) -> String {
+    let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+    let mut append = |name: &str, value: Option<String>| {
+        if let Some(value) = value {
+            serializer.append_pair(name, &value);
+        }
+    };
+    append("start_page", list_query.start_page.map(|v| v.to_string()));
+    append("page_size", list_query.page_size.map(|v| v.to_string()));
+    append("sort_by", list_query.sort_by.clone());
+    append("sort_order", list_query.sort_order.clone());
+    append("media_type", list_query.media_type.clone());
+    append("is_encrypted", list_query.is_encrypted.map(|v| v.to_string()));
+    append("is_fulltext_parsed", list_query.is_fulltext_parsed.map(|v| v.to_string()));
+    serializer.finish()
+}
+
 #[cfg(test)]
 mod test {
     use url::Url;
@@ -37,6 +37,7 @@ pub(crate) struct UploadWatchInfoHarbor {
     pub file_name: String,
     pub file_ref: String,
     pub percent_of_completion: u32,
+    pub processing_status: String,
 }
 
 #[derive(Clone)]
@@ -112,6 +113,7 @@ impl UploadWatcherComponent {
                 file_name: loading.item_info.to_string(),
                 file_ref: loading.file_reference.to_string(),
                 percent_of_completion: (loading.encrypted_count as u32 * 100 / loading.total_part as u32) as u32,
+                processing_status: loading.processing_status.to_string(),
             };
             uploading_files.push(harbor_data);
         }
@@ -128,6 +128,8 @@ fn generate_file_server_app_properties(config: &Config, ports: &Ports) -> anyhow
         .replace("{SM_PORT}", &ports.session_manager.to_string())
         .replace("{DS_HOST}", "localhost")
         .replace("{DS_PORT}", &ports.document_server.to_string())
+        .replace("{AS_HOST}", "localhost")
+        .replace("{AS_PORT}", &ports.admin_server.to_string())
         .replace("{TKS_HOST}", "localhost") // TKS is for TIKA Server
         .replace("{TKS_PORT}", &ports.tika_server.to_string())
     };
@@ -128,6 +128,9 @@ sm.port={SM_PORT}
 #Document Server
 ds.host={DS_HOST}
 ds.port={DS_PORT}
+#Admin Server
+as.host={AS_HOST}
+as.port={AS_PORT}
 #tika
 tks.host={TKS_HOST}
 tks.port={TKS_PORT}
@@ -0,0 +1,576 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use anyhow::anyhow;
+use axum::http::StatusCode;
+use log::*;
+
+use common_config::properties::get_prop_value;
+use common_config::property_name::{CLEANUP_INTERVAL_HOURS_PROPERTY, CLEANUP_RETENTION_DAYS_PROPERTY};
+use commons_error::*;
+use commons_pg::sql_transaction::CellValue;
+use commons_pg::sql_transaction_async::{SQLChangeAsync, SQLConnectionAsync, SQLQueryBlockAsync, SQLTransactionAsync};
+use commons_services::token_lib::SecurityToken;
+use commons_services::x_request_id::{Follower, XRequestID};
+use dkdto::error_codes::{INTERNAL_DATABASE_ERROR, INVALID_TOKEN};
+use dkdto::web_types::{CleanupReply, WebType, WebTypeBuilder};
+use doka_cli::request_client::TokenType;
+use doka_cli::request_client::TokenType::Token;
+
+use crate::block_store::{block_store, delete_parts, hand_over_part_rows};
+
+const DEFAULT_RETENTION_DAYS: u64 = 4;
+const DEFAULT_INTERVAL_HOURS: u64 = 24;
+
+/// File reference created before the retention limit, with what still uses it
+struct OrphanFile {
+    id: i64,
+    file_ref: String,
+    is_encrypted: bool,
+    is_failed: bool,
+    has_uploads: bool,
+    has_session: bool,
+}
+
+impl OrphanFile {
+    /// Either its processing failed, or it was never processed and nothing is left to process it
+    fn is_orphan(&self) -> bool {
+        self.is_failed || (!self.is_encrypted && !self.has_uploads && !self.has_session)
+    }
+}
+
+/// Counts of the rows removed with a file reference
+#[derive(Debug, Default)]
+pub(crate) struct RemovedFile {
+    /// The file reference and the ones of its previews
+    pub file_count: u64,
+    pub part_count: u64,
+    pub metadata_count: u64,
+}
+
+///
+/// What happens to the parts of a file reference being removed.
+/// The [ref_count] of a file owning its parts counts the file itself and all the files sharing them
+///
+#[derive(Debug, PartialEq)]
+enum PartsRelease {
+    /// The parts belong to the file [owner_id], count one less reference on them
+    Detach(i64),
+    /// Nobody else uses the parts, they are removed
+    Delete,
+    /// Other files use the parts, one of them becomes their owner
+    HandOver,
+}
+
+fn parts_release(parts_reference_id: Option<i64>, ref_count: i32) -> PartsRelease {
+    match parts_reference_id {
+        Some(owner_id) => PartsRelease::Detach(owner_id),
+        None if ref_count <= 1 => PartsRelease::Delete,
+        None => PartsRelease::HandOver,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CleanupDelegate {
+    pub security_token: SecurityToken,
+    pub follower: Follower,
+}
+
+impl CleanupDelegate {
+    pub fn new(security_token: SecurityToken, x_request_id: XRequestID) -> Self {
+        Self {
+            security_token,
+            follower: Follower { x_request_id: x_request_id.new_if_null(), token_type: TokenType::None },
+        }
+    }
+
+    ///
+    /// 🌟 Remove the stale uploads and the files whose upload failed, for all the customers
+    ///
+    pub async fn cleanup(&mut self) -> WebType<CleanupReply> {
+        log_info!("🚀 Start cleanup api, follower=[{}]", &self.follower);
+
+        if !self.security_token.is_valid() {
+            log_error!("💣 Invalid security token, token=[{:?}], follower=[{}]", &self.security_token, &self.follower);
+            return WebType::from_api_error(&INVALID_TOKEN);
+        }
+
+        self.follower.token_type = Token(self.security_token.0.clone());
+
+        let Ok(reply) =
+            run_cleanup(&self.follower).await.map_err(err_fwd!("💣 Cleanup failed, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        log_info!("🏁 End cleanup api, follower=[{}]", &self.follower);
+        WebType::from_item(StatusCode::OK.as_u16(), reply)
+    }
+}
+
+///
+/// Run the cleanup in the background every "fs.cleanup.interval_hours", starting now
+///
+pub(crate) fn start_cleanup_scheduler() {
+    let interval_hours = read_u64_prop(CLEANUP_INTERVAL_HOURS_PROPERTY, DEFAULT_INTERVAL_HOURS);
+    log_info!("Cleanup scheduled every [{}] hours", interval_hours);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_hours.max(1) * 3600));
+        loop {
+            interval.tick().await;
+            let follower =
+                Follower { x_request_id: XRequestID::from_value(None).new_if_null(), token_type: TokenType::None };
+            let _ =
+                run_cleanup(&follower).await.map_err(err_fwd!("💣 Scheduled cleanup failed, follower=[{}]", &follower));
+        }
+    });
+}
+
+fn read_u64_prop(prop_name: &str, default_value: u64) -> u64 {
+    get_prop_value(prop_name).ok().and_then(|value| value.parse::<u64>().ok()).unwrap_or(default_value)
+}
+
+///
+/// For each customer, remove
+///  - the upload sessions and the uploaded blocks older than "fs.cleanup.retention_days"
+///  - the file references of the failed uploads, with their parts, metadata and previews
+///  - the file references never processed, once their uploaded blocks are removed
+///
+async fn run_cleanup(follower: &Follower) -> anyhow::Result<CleanupReply> {
+    let retention_days = read_u64_prop(CLEANUP_RETENTION_DAYS_PROPERTY, DEFAULT_RETENTION_DAYS);
+    let limit_time = SystemTime::now() - Duration::from_secs(retention_days * 24 * 3600);
+    log_info!("Start the cleanup, retention days=[{}], follower=[{}]", retention_days, follower);
+
+    let customer_codes = search_customer_codes().await.map_err(tr_fwd!())?;
+
+    let mut reply = CleanupReply::default();
+    for customer_code in &customer_codes {
+        // A customer in error must not prevent the cleanup of the others
+        match cleanup_customer(customer_code, limit_time, &mut reply, follower).await {
+            Ok(_) => reply.customer_count += 1,
+            Err(e) => {
+                log_error!("💣 Cleanup failed, customer_code=[{}], e=[{}], follower=[{}]", customer_code, e, follower)
+            }
+        }
+    }
+
+    log_info!(
+        "😎 Cleanup done, customers=[{}], upload sessions=[{}], file uploads=[{}], file references=[{}], \
+        file parts=[{}], file metadata=[{}], follower=[{}]",
+        reply.customer_count,
+        reply.upload_sessions,
+        reply.file_uploads,
+        reply.file_references,
+        reply.file_parts,
+        reply.file_metadata,
+        follower
+    );
+    Ok(reply)
+}
+
+/// The customer codes, from the fs_<customer_code> schemas of the database
+async fn search_customer_codes() -> anyhow::Result<Vec<String>> {
+    let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+    let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+    let sql_query = r"SELECT substr(schema_name, 4) customer_code
+                FROM information_schema.schemata
+                WHERE schema_name LIKE 'fs\_%'
+                ORDER BY schema_name"
+        .to_string();
+
+    let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params: HashMap::new() };
+    let mut dataset = query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed"))?;
+    trans.commit().await.map_err(err_fwd!("💣 Commit failed"))?;
+
+    let mut customer_codes = Vec::with_capacity(dataset.len());
+    while dataset.next() {
+        customer_codes.push(dataset.get_string("customer_code").ok_or(anyhow!("Wrong customer_code col"))?);
+    }
+    Ok(customer_codes)
+}
+
+async fn cleanup_customer(
+    customer_code: &str,
+    limit_time: SystemTime,
+    reply: &mut CleanupReply,
+    follower: &Follower,
+) -> anyhow::Result<()> {
+    // The sessions first, so their file references become orphans
+    let sql_delete =
+        format!("DELETE FROM fs_{}.file_upload_session WHERE start_time_gmt < :p_limit_time", customer_code);
+    reply.upload_sessions += delete_older_than(sql_delete, limit_time).await.map_err(tr_fwd!())?;
+
+    // Use the file_uploads_start_time_idx index
+    let sql_delete = format!("DELETE FROM fs_{}.file_uploads WHERE start_time_gmt < :p_limit_time", customer_code);
+    reply.file_uploads += delete_older_than(sql_delete, limit_time).await.map_err(tr_fwd!())?;
+
+    let orphan_files = search_orphan_files(customer_code, limit_time).await.map_err(tr_fwd!())?;
+    for orphan_file in orphan_files {
+        log_info!(
+            "Remove the orphan file, customer_code=[{}], file_ref=[{}], follower=[{}]",
+            customer_code,
+            &orphan_file.file_ref,
+            follower
+        );
+        // The file is removed the same way as a deleted file, with its previews
+        let removed_file = remove_file(customer_code, orphan_file.id).await.map_err(tr_fwd!())?;
+        reply.file_parts += removed_file.part_count;
+        reply.file_metadata += removed_file.metadata_count;
+        reply.file_references += removed_file.file_count;
+    }
+    Ok(())
+}
+
+/// Return the number of deleted rows
+async fn delete_older_than(sql_query: String, limit_time: SystemTime) -> anyhow::Result<u64> {
+    let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+    let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+    let mut params = HashMap::new();
+    params.insert("p_limit_time".to_string(), CellValue::from_raw_systemtime(limit_time));
+
+    let query = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+    let delete_count = query.delete(&mut trans).await.map_err(err_fwd!("💣 Query failed, [{}]", &query.sql_query))?;
+    trans.commit().await.map_err(err_fwd!("💣 Commit failed"))?;
+
+    Ok(delete_count)
+}
+
+///
+/// File references created before the [limit_time] and
+///  - either never processed, with no more uploaded blocks nor upload session
+///  - or whose processing failed
+///
+/// The query only reads the candidates, see [OrphanFile::is_orphan]
+///
+async fn search_orphan_files(customer_code: &str, limit_time: SystemTime) -> anyhow::Result<Vec<OrphanFile>> {
+    let sql_query = format!(
+        r"SELECT fr.id, fr.file_ref, fr.is_encrypted,
+                fr.processing_status = 'failed' AS is_failed,
+                EXISTS (SELECT 1 FROM fs_{0}.file_uploads fu WHERE fu.file_ref = fr.file_ref) AS has_uploads,
+                EXISTS (SELECT 1 FROM fs_{0}.file_upload_session us WHERE us.file_ref = fr.file_ref) AS has_session
+            FROM fs_{0}.file_reference fr
+            WHERE
+                fr.created_gmt < :p_limit_time AND
+                ( fr.is_encrypted = false OR fr.processing_status = 'failed' )
+            ORDER BY fr.id",
+        customer_code
+    );
+
+    let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+    let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+    let mut params = HashMap::new();
+    params.insert("p_limit_time".to_string(), CellValue::from_raw_systemtime(limit_time));
+
+    let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+    let mut dataset = query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed"))?;
+    trans.commit().await.map_err(err_fwd!("💣 Commit failed"))?;
+
+    let mut orphan_files = Vec::with_capacity(dataset.len());
+    while dataset.next() {
+        let candidate = OrphanFile {
+            id: dataset.get_int("id").ok_or(anyhow!("Wrong id col"))?,
+            file_ref: dataset.get_string("file_ref").ok_or(anyhow!("Wrong file_ref col"))?,
+            is_encrypted: dataset.get_bool("is_encrypted").ok_or(anyhow!("Wrong is_encrypted col"))?,
+            is_failed: dataset.get_bool("is_failed").ok_or(anyhow!("Wrong is_failed col"))?,
+            has_uploads: dataset.get_bool("has_uploads").ok_or(anyhow!("Wrong has_uploads col"))?,
+            has_session: dataset.get_bool("has_session").ok_or(anyhow!("Wrong has_session col"))?,
+        };
+        if candidate.is_orphan() {
+            orphan_files.push(candidate);
+        }
+    }
+    Ok(orphan_files)
+}
+
+///
+/// Remove the file reference [file_id] with its metadata and its previews, in one transaction.
+/// The previews are file references of their own, removed after the file which links them
+///
+pub(crate) async fn remove_file(customer_code: &str, file_id: i64) -> anyhow::Result<RemovedFile> {
+    let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+    let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+    // The parts of the file are released first, the previews handed over with them stay with the new owner
+    let mut part_keys = release_file_parts(&mut trans, customer_code, file_id).await.map_err(tr_fwd!())?;
+    let preview_ids = search_preview_ids(&mut trans, customer_code, file_id).await.map_err(tr_fwd!())?;
+
+    for preview_id in &preview_ids {
+        part_keys.extend(release_file_parts(&mut trans, customer_code, *preview_id).await.map_err(tr_fwd!())?);
+    }
+
+    let mut removed_file = RemovedFile::default();
+    for id in std::iter::once(file_id).chain(preview_ids) {
+        removed_file.metadata_count += delete_file_reference(&mut trans, customer_code, id).await.map_err(tr_fwd!())?;
+        removed_file.file_count += 1;
+    }
+
+    trans.commit().await.map_err(err_fwd!("💣 Commit failed"))?;
+
+    removed_file.part_count = part_keys.len() as u64;
+    purge_parts(customer_code, &part_keys).await;
+
+    Ok(removed_file)
+}
+
+/// Remove the stored data of the released parts, once their release is committed.
+/// The file references are gone, the data of a failed removal is only left behind
+pub(crate) async fn purge_parts(customer_code: &str, part_keys: &[(i64, u32)]) {
+    if part_keys.is_empty() {
+        return;
+    }
+    if let Err(e) = async { block_store()?.purge_parts(customer_code, part_keys).await }.await {
+        log_error!(
+            "💣 Cannot remove the stored parts, customer_code=[{}], part count=[{}], e=[{}]",
+            customer_code,
+            part_keys.len(),
+            e
+        );
+    }
+}
+
+/// Ids of the file references of the previews of the file [file_id]
+async fn search_preview_ids(
+    trans: &mut SQLTransactionAsync<'_>,
+    customer_code: &str,
+    file_id: i64,
+) -> anyhow::Result<Vec<i64>> {
+    let sql_query = format!(
+        r"SELECT pr.id
+            FROM fs_{0}.preview p
+            INNER JOIN fs_{0}.file_reference pr ON pr.file_ref = p.file_identifier
+            WHERE p.file_reference_id = :p_file_id",
+        customer_code
+    );
+
+    let mut params = HashMap::new();
+    params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+    let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+    let mut dataset = query.execute(trans).await.map_err(err_fwd!("💣 Query failed"))?;
+
+    let mut preview_ids = Vec::with_capacity(dataset.len());
+    while dataset.next() {
+        preview_ids.push(dataset.get_int("id").ok_or(anyhow!("Wrong id col"))?);
+    }
+    Ok(preview_ids)
+}
+
+///
+/// Give up the parts of the file reference [file_id] :
+///  - the parts of another file count one less reference
+///  - the parts nobody else uses are removed from the block store
+///  - the parts other files use are handed over to the first of them, which becomes their owner
+///
+/// Return the keys of the parts removed, whose stored data is left to [purge_parts] after the commit
+///
+pub(crate) async fn release_file_parts(
+    trans: &mut SQLTransactionAsync<'_>,
+    customer_code: &str,
+    file_id: i64,
+) -> anyhow::Result<Vec<(i64, u32)>> {
+    // Lock the row until the end of the transaction, the query block appends its OFFSET after the sub select
+    let sql_query = format!(
+        r"SELECT fr.parts_reference_id, fr.ref_count
+            FROM (SELECT parts_reference_id, ref_count FROM fs_{}.file_reference WHERE id = :p_file_id FOR UPDATE) fr",
+        customer_code
+    );
+
+    let mut params = HashMap::new();
+    params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+    let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+    let mut dataset = query.execute(trans).await.map_err(err_fwd!("💣 Query failed"))?;
+    if !dataset.next() {
+        return Err(anyhow!("Unknown file reference, file_id=[{}]", file_id));
+    }
+    let parts_reference_id = dataset.get_int("parts_reference_id");
+    let ref_count = dataset.get_int_32("ref_count").ok_or(anyhow!("Wrong ref_count col"))?;
+
+    match parts_release(parts_reference_id, ref_count) {
+        PartsRelease::Detach(owner_id) => {
+            let sql_update = format!(
+                "UPDATE fs_{}.file_reference SET ref_count = ref_count - 1 WHERE id = :p_file_id",
+                customer_code
+            );
+            update_by_file_id(trans, sql_update, owner_id).await?;
+
+            let sql_update = format!(
+                "UPDATE fs_{}.file_reference SET parts_reference_id = NULL, checksum = NULL WHERE id = :p_file_id",
+                customer_code
+            );
+            update_by_file_id(trans, sql_update, file_id).await?;
+            Ok(vec![])
+        }
+        PartsRelease::Delete => delete_parts(trans, customer_code, file_id).await.map_err(tr_fwd!()),
+        PartsRelease::HandOver => match search_first_sharer(trans, customer_code, file_id).await? {
+            Some(sharer_id) => {
+                hand_over_parts(trans, customer_code, file_id, sharer_id, ref_count).await?;
+                Ok(vec![])
+            }
+            // The count is wrong, nobody else uses the parts
+            None => delete_parts(trans, customer_code, file_id).await.map_err(tr_fwd!()),
+        },
+    }
+}
+
+/// The oldest file reference using the parts of the file [file_id]
+async fn search_first_sharer(
+    trans: &mut SQLTransactionAsync<'_>,
+    customer_code: &str,
+    file_id: i64,
+) -> anyhow::Result<Option<i64>> {
+    let sql_query =
+        format!("SELECT id FROM fs_{}.file_reference WHERE parts_reference_id = :p_file_id ORDER BY id", customer_code);
+
+    let mut params = HashMap::new();
+    params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+    let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+    let mut dataset = query.execute(trans).await.map_err(err_fwd!("💣 Query failed"))?;
+    if !dataset.next() {
+        return Ok(None);
+    }
+    Ok(Some(dataset.get_int("id").ok_or(anyhow!("Wrong id col"))?))
+}
+
+/// The parts of the file [owner_id] become the parts of the file [sharer_id], with all their other references.
+/// Only the database changes, the stored parts stay where they are
+async fn hand_over_parts(
+    trans: &mut SQLTransactionAsync<'_>,
+    customer_code: &str,
+    owner_id: i64,
+    sharer_id: i64,
+    ref_count: i32,
+) -> anyhow::Result<()> {
+    hand_over_part_rows(trans, customer_code, owner_id, sharer_id).await.map_err(tr_fwd!())?;
+
+    let sql_query = format!(
+        r"UPDATE fs_{}.file_reference
+            SET parts_reference_id = NULL,
+                ref_count = :p_ref_count
+            WHERE id = :p_sharer_id",
+        customer_code
+    );
+
+    let mut params = HashMap::new();
+    params.insert("p_ref_count".to_string(), CellValue::from_raw_int_32(ref_count - 1));
+    params.insert("p_sharer_id".to_string(), CellValue::from_raw_int(sharer_id));
+
+    let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+    sql_update.update(trans).await.map_err(err_fwd!("💣 Query failed, [{}]", &sql_update.sql_query))?;
+
+    let sql_query = format!(
+        r"UPDATE fs_{}.file_reference
+            SET parts_reference_id = :p_sharer_id
+            WHERE parts_reference_id = :p_owner_id",
+        customer_code
+    );
+
+    let mut params = HashMap::new();
+    params.insert("p_sharer_id".to_string(), CellValue::from_raw_int(sharer_id));
+    params.insert("p_owner_id".to_string(), CellValue::from_raw_int(owner_id));
+
+    let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+    sql_update.update(trans).await.map_err(err_fwd!("💣 Query failed, [{}]", &sql_update.sql_query))?;
+
+    // The sharers show the preview of the owner of the parts, it goes with the parts
+    let sql_query = format!(
+        r"UPDATE fs_{}.preview
+            SET file_reference_id = :p_sharer_id
+            WHERE file_reference_id = :p_owner_id",
+        customer_code
+    );
+
+    let mut params = HashMap::new();
+    params.insert("p_sharer_id".to_string(), CellValue::from_raw_int(sharer_id));
+    params.insert("p_owner_id".to_string(), CellValue::from_raw_int(owner_id));
+
+    let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+    sql_update.update(trans).await.map_err(err_fwd!("💣 Query failed, [{}]", &sql_update.sql_query))?;
+
+    let sql_update = format!("UPDATE fs_{}.file_reference SET ref_count = 1 WHERE id = :p_file_id", customer_code);
+    update_by_file_id(trans, sql_update, owner_id).await
+}
+
+/// Delete the file reference and its metadata, return the number of metadata deleted
+async fn delete_file_reference(
+    trans: &mut SQLTransactionAsync<'_>,
+    customer_code: &str,
+    file_id: i64,
+) -> anyhow::Result<u64> {
+    let sql_delete = format!("DELETE FROM fs_{}.file_metadata WHERE file_reference_id = :p_file_id", customer_code);
+    let metadata_count = delete_by_file_id(trans, sql_delete, file_id).await?;
+
+    let sql_delete = format!("DELETE FROM fs_{}.preview WHERE file_reference_id = :p_file_id", customer_code);
+    let _ = delete_by_file_id(trans, sql_delete, file_id).await?;
+
+    let sql_delete = format!("DELETE FROM fs_{}.file_reference WHERE id = :p_file_id", customer_code);
+    let _ = delete_by_file_id(trans, sql_delete, file_id).await?;
+
+    Ok(metadata_count)
+}
+
+async fn delete_by_file_id(
+    trans: &mut SQLTransactionAsync<'_>,
+    sql_query: String,
+    file_id: i64,
+) -> anyhow::Result<u64> {
+    let mut params = HashMap::new();
+    params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+    let query = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+    query.delete(trans).await.map_err(err_fwd!("💣 Query failed, [{}]", &query.sql_query))
+}
+
+async fn update_by_file_id(trans: &mut SQLTransactionAsync<'_>, sql_query: String, file_id: i64) -> anyhow::Result<()> {
+    let mut params = HashMap::new();
+    params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+    let query = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+    query.update(trans).await.map_err(err_fwd!("💣 Query failed, [{}]", &query.sql_query))
+}
+
+#[cfg(test)]
+mod cleanup_tests {
+    use crate::cleanup_delegate::{parts_release, OrphanFile, PartsRelease};
+
+    fn candidate(is_encrypted: bool, is_failed: bool, has_uploads: bool, has_session: bool) -> OrphanFile {
+        OrphanFile { id: 1, file_ref: "f1248fab".to_string(), is_encrypted, is_failed, has_uploads, has_session }
+    }
+
+    #[test]
+    fn is_orphan_test() {
+        // Never processed and nothing left to process it
+        assert!(candidate(false, false, false, false).is_orphan());
+
+        // The upload is still going on
+        assert!(!candidate(false, false, true, false).is_orphan());
+        assert!(!candidate(false, false, false, true).is_orphan());
+        assert!(!candidate(false, false, true, true).is_orphan());
+
+        // A stored file is kept
+        assert!(!candidate(true, false, false, false).is_orphan());
+
+        // A failed processing, whatever is left
+        assert!(candidate(true, true, false, false).is_orphan());
+        assert!(candidate(false, true, true, true).is_orphan());
+    }
+
+    #[test]
+    fn parts_release_test() {
+        // A file using the parts of another one
+        assert_eq!(parts_release(Some(12), 1), PartsRelease::Detach(12));
+        assert_eq!(parts_release(Some(12), 3), PartsRelease::Detach(12));
+
+        // The last reference on its own parts
+        assert_eq!(parts_release(None, 1), PartsRelease::Delete);
+        assert_eq!(parts_release(None, 0), PartsRelease::Delete);
+
+        // Its parts are used by other files
+        assert_eq!(parts_release(None, 2), PartsRelease::HandOver);
+    }
+}
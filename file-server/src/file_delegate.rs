@@ -1,7 +1,6 @@
 use std::cmp::min;
 use std::collections::HashMap;
-use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::pin::pin;
 use std::time::{Duration, SystemTime};
 
 use anyhow::anyhow;
@@ -10,18 +9,19 @@ use axum::extract::Multipart;
 use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
 use base64::Engine;
 use bytes::Bytes;
+use chrono::DateTime;
 
-use futures::stream::Stream;
-use futures::{future, TryFutureExt};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use futures::{Future, TryFutureExt};
 use log::*;
 use mime::Mime;
 use rs_uuid::iso::uuid_v4;
 use serde::de::DeserializeOwned;
-use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
 use tokio::task;
 
 use commons_error::*;
-use commons_pg::sql_transaction::{CellValue, SQLDataSet};
+use commons_pg::sql_transaction::{date_time_to_iso, CellValue, SQLDataSet};
 use commons_pg::sql_transaction_async::{SQLChangeAsync, SQLConnectionAsync, SQLQueryBlockAsync};
 use commons_services::key_lib::fetch_customer_key;
 use commons_services::session_lib::valid_sid_get_session;
@@ -30,85 +30,127 @@ use commons_services::try_or_return;
 use commons_services::x_request_id::{Follower, XRequestID};
 use common_config::properties::get_prop_value;
 use common_config::property_name::{
-    DOCUMENT_SERVER_HOSTNAME_PROPERTY, DOCUMENT_SERVER_PORT_PROPERTY, TIKA_SERVER_HOSTNAME_PROPERTY,
-    TIKA_SERVER_PORT_PROPERTY,
+    DOCUMENT_SERVER_HOSTNAME_PROPERTY, DOCUMENT_SERVER_PORT_PROPERTY, ENCRYPT_THREAD_COUNT_PROPERTY,
+    METADATA_TAGS_PROPERTY, TIKA_SERVER_HOSTNAME_PROPERTY, TIKA_SERVER_PORT_PROPERTY,
 };
 use dkcrypto::dk_crypto::CypherMode::CC20;
 use dkcrypto::dk_crypto::DkEncrypt;
 use dkdto::api_error::ApiError;
-use dkdto::error_codes::{FILE_INFO_NOT_FOUND, INTERNAL_DATABASE_ERROR, INTERNAL_TECHNICAL_ERROR};
+use dkdto::error_codes::{
+    FILE_INFO_NOT_FOUND, INTERNAL_DATABASE_ERROR, INTERNAL_TECHNICAL_ERROR, INVALID_REQUEST, ITEM_VERSION_NOT_FOUND,
+    PREVIEW_NOT_FOUND, QUOTA_EXCEEDED, UPLOAD_INCOMPLETE, UPLOAD_SESSION_NOT_FOUND, UPLOAD_WRONG_BLOCK,
+    UPLOAD_WRONG_ITEM_INFO,
+};
 use dkdto::web_types::{
-    DownloadReply, EntrySession, GetFileInfoReply, GetFileInfoShortReply, ListOfFileInfoReply, ListOfUploadInfoReply,
-    UploadInfoReply, UploadReply, WebType, WebTypeBuilder,
+    AddItemTagRequest, CreateUploadSessionRequest, DownloadReply, EntrySession, EnumTagValue, FileListQuery,
+    FileMetadataReply, FileStatusReply, GetFileInfoReply, GetFileInfoShortReply, ItemVersionReply, ListOfFileInfoReply,
+    ListOfUploadInfoReply, MetadataElement, ProcessingStatus, ReindexReply, SimpleMessage, TagType, UploadBlockReply,
+    UploadInfoReply, UploadReply, UploadSessionReply, WebType, WebTypeBuilder,
 };
 use doka_cli::async_request_client::{DocumentServerClientAsync, TikaServerClientAsync};
 use doka_cli::request_client::TokenType;
 
+use crate::block_store::block_store;
+use crate::cleanup_delegate::{purge_parts, release_file_parts, remove_file};
+use crate::metadata::{extract_metadata, parse_tag_projection, project_on_tags, FileMetadata, TIKA_CONTENT_META};
+use crate::preview::{find_preview_renderer, PREVIEW_MEDIA_TYPE};
+use crate::quota::check_quota;
+
 // use tokio::stream;
 
-const TIKA_CONTENT_META: &str = "X-TIKA:content";
 const CONTENT_TYPE_META: &str = "Content-Type";
-
-pub type IndexedParts = HashMap<u32, Vec<u8>>;
-
-/// ---
-
-// Une structure pour encapsuler le Stream personnalisé
-struct PartsStream {
-    parts: Vec<Vec<u8>>,   // Les différentes parties en mémoire
-    current_index: usize,  // L'index de la partie actuellement traitée
-    current_offset: usize, // Offset actuel dans la partie en cours
+/// Size of the failure_reason column
+const FAILURE_REASON_MAX_LENGTH: usize = 500;
+/// Media type of the files the parsing failed for
+const UNKNOWN_MEDIA_TYPE: &str = "application/octet-stream";
+/// Max number of metadata inserted by a single statement
+const METADATA_BATCH_SIZE: usize = 200;
+/// Max length of the metadata keys
+const META_KEY_MAX_LENGTH: usize = 256;
+
+/// Number of blocks encrypted at the same time during the processing of an upload, read from "fs.encrypt.thread_count".
+/// All the cpus but one by default, the last one is left to serve the requests
+fn encrypt_thread_count() -> usize {
+    let default_count = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).saturating_sub(1);
+    thread_count_or_default(get_prop_value(ENCRYPT_THREAD_COUNT_PROPERTY).ok().as_deref(), default_count)
 }
 
-impl PartsStream {
-    pub fn new(parts: IndexedParts) -> Self {
-        // Trier les parties par clé et les convertir en un Vec
-        let mut sorted_parts: Vec<_> = parts.into_iter().collect();
-        sorted_parts.sort_by_key(|&(k, _)| k);
-
-        // On récupère juste les Vec<u8> triés
-        let sorted_data = sorted_parts.into_iter().map(|(_, data)| data).collect();
+fn thread_count_or_default(value: Option<&str>, default_count: usize) -> usize {
+    value.and_then(|v| v.trim().parse::<usize>().ok()).unwrap_or(default_count).max(1)
+}
 
-        PartsStream { parts: sorted_data, current_index: 0, current_offset: 0 }
-    }
+/// Stored file to parse again
+struct FileToReindex {
+    id: i64,
+    file_ref: String,
+    parts_id: i64,
+    total_part: u32,
 }
 
-// Implémentation du Stream pour PartsStream
-impl Stream for PartsStream {
-    type Item = Result<Bytes, std::io::Error>;
+/// Information of the file reference needed to start a download
+struct FileHeader {
+    media_type: String,
+    file_size: Option<i64>,
+    is_encrypted: bool,
+    /// Id of the file reference holding the parts, another one when the parts are shared
+    parts_id: i64,
+}
 
-    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        // Accéder aux champs via `Pin` pour obtenir un accès mutable
-        let self_mut = self.as_mut().get_mut();
+/// Upload session of a file sent block by block
+struct UploadSession {
+    file_id: i64,
+    item_info: String,
+    file_size: Option<i64>,
+    block_count: Option<u32>,
+}
 
-        // Si on a parcouru toutes les parties, on termine le Stream
-        if self_mut.current_index >= self_mut.parts.len() {
-            return Poll::Ready(None);
-        }
+/// Encrypted parts of a preview written in the block store, still to be linked to its file
+struct StoredPreview<'a> {
+    preview_id: i64,
+    preview_ref: &'a str,
+    checksum: String,
+    original_file_size: u64,
+    encrypted_file_size: u64,
+    block_count: u32,
+}
 
-        // Accéder à la partie en cours
-        let current_part = &self_mut.parts[self_mut.current_index];
-        let remaining = &current_part[self_mut.current_offset..];
+/// Inclusive range of bytes in the clear content of a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
 
-        // Lire un chunk (ici, on lit tout le reste de la partie)
-        let chunk_size = remaining.len();
-        let chunk = Bytes::copy_from_slice(remaining);
+impl ByteRange {
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
 
-        // Mettre à jour l'offset
-        self_mut.current_offset += chunk_size;
+    fn first_part(&self) -> u32 {
+        (self.start / FileDelegate::BLOCK_SIZE as u64) as u32
+    }
 
-        // Si nous avons fini cette partie, passer à la suivante
-        if self_mut.current_offset >= current_part.len() {
-            self_mut.current_index += 1;
-            self_mut.current_offset = 0;
-        }
+    fn last_part(&self) -> u32 {
+        (self.end / FileDelegate::BLOCK_SIZE as u64) as u32
+    }
 
-        // Retourner le chunk sous forme de Poll
-        Poll::Ready(Some(Ok(chunk)))
+    /// Keep only the bytes of the clear part [part_number] that belong to the range
+    fn slice_part(&self, part_number: u32, clear_part: Vec<u8>) -> Bytes {
+        let part_start = part_number as u64 * FileDelegate::BLOCK_SIZE as u64;
+        let part_len = clear_part.len() as u64;
+        let from = min(self.start.saturating_sub(part_start), part_len);
+        let to = min((self.end + 1).saturating_sub(part_start), part_len);
+        Bytes::from(clear_part).slice(from as usize..to as usize)
     }
 }
 
-/// ---
+/// What the Range header asks for
+#[derive(Debug, PartialEq)]
+enum RangeRequest {
+    Full,
+    Partial(ByteRange),
+    Unsatisfiable,
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct FileDelegate {
@@ -118,6 +160,14 @@ pub(crate) struct FileDelegate {
 
 impl FileDelegate {
     const BLOCK_SIZE: usize = 1_048_576;
+    /// Number of encrypted parts read from the database at once during a download
+    const DOWNLOAD_WINDOW_SIZE: u32 = 8;
+    /// Number of parts decrypted ahead of the one being sent during a download
+    const DECRYPT_AHEAD: usize = 4;
+    /// Number of encrypted parts stored at once during the processing of an upload
+    const ENCRYPT_GROUP_SIZE: usize = 8;
+    /// Number of blocks read at once from the file_uploads table
+    const UPLOAD_WINDOW_SIZE: u32 = 8;
 
     pub fn new(session_token: SessionToken, x_request_id: XRequestID) -> Self {
         Self {
@@ -132,7 +182,7 @@ impl FileDelegate {
         file_ref: &str,
         file_data: &mut Multipart,
         entry_session: &EntrySession,
-    ) -> anyhow::Result<(usize, u32)> {
+    ) -> anyhow::Result<(usize, u32, String)> {
         // Create parts
         log_info!("Start creating clear parts in the database, follower=[{}]", &self.follower);
 
@@ -141,6 +191,8 @@ impl FileDelegate {
 
         let mut total_size: usize = 0;
         let mut block_num: u32 = 0;
+        // Checksum of the clear content, to find an identical file already stored
+        let mut hasher = Sha256::new();
 
         loop {
             let mut field = match file_data.next_field().await {
@@ -176,6 +228,7 @@ impl FileDelegate {
                 while buffer.len() >= Self::BLOCK_SIZE {
                     let slice = buffer.slice(..Self::BLOCK_SIZE); // Prendre le bloc de taille fixe
 
+                    hasher.update(&slice);
                     block_set.insert(block_num, slice.to_vec());
                     block_num += 1;
                     total_size += slice.len();
@@ -201,6 +254,7 @@ impl FileDelegate {
             // Si le buffer contient encore des données (moins que BLOCK_SIZE), les écrire
             if !buffer.is_empty() {
                 // file.write_all(&buffer).await.unwrap();
+                hasher.update(&buffer);
                 block_set.insert(block_num, buffer.to_vec());
                 block_num += 1;
                 total_size += buffer.len();
@@ -286,20 +340,27 @@ impl FileDelegate {
         //     block_num += 1;
         // }
 
-        Ok((total_size, block_num))
+        Ok((total_size, block_num, Self::format_checksum(hasher)))
     }
 
-    /// Download the parts into a body stream
-    async fn download_from_parts(
-        parts: IndexedParts,
-        media: &Mime,
-    ) -> Result<(HeaderMap, Body), (axum::http::StatusCode, String)> {
-        // Créer le stream à partir de IndexedParts
-        let parts_stream = PartsStream::new(parts);
+    /// Hexadecimal form of the SHA-256, as stored in file_reference.checksum
+    fn format_checksum(hasher: Sha256) -> String {
+        format!("{:x}", hasher.finalize())
+    }
 
-        // Créer un flux compatible avec Body
-        //let body_stream = tokio_util::io::StreamReader::new(parts_stream);
-        let body = Body::from_stream(parts_stream);
+    /// Wrap the stream of clear parts into the body of the download reply
+    /// A partial reply (206) is built when a [range] is given
+    fn download_from_stream<S>(
+        stream: S,
+        media: &Mime,
+        etag: &str,
+        file_size: Option<i64>,
+        range: Option<ByteRange>,
+    ) -> DownloadReply
+    where
+        S: Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static,
+    {
+        let body = Body::from_stream(stream);
 
         // Ajouter les en-têtes HTTP
         let mut headers = HeaderMap::new();
@@ -308,120 +369,206 @@ impl FileDelegate {
             header::CONTENT_DISPOSITION,
             HeaderValue::from_str("inline; filename=\"file_from_parts\"").unwrap(),
         );
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        headers.insert(header::ETAG, HeaderValue::from_str(etag).unwrap());
+
+        let status = match (range, file_size) {
+            (Some(range), Some(file_size)) => {
+                let content_range = format!("bytes {}-{}/{}", range.start, range.end, file_size);
+                headers.insert(header::CONTENT_RANGE, HeaderValue::from_str(&content_range).unwrap());
+                headers.insert(header::CONTENT_LENGTH, HeaderValue::from(range.len()));
+                StatusCode::PARTIAL_CONTENT
+            }
+            (_, Some(file_size)) => {
+                headers.insert(header::CONTENT_LENGTH, HeaderValue::from(file_size));
+                StatusCode::OK
+            }
+            (_, None) => StatusCode::OK,
+        };
+
+        Ok((status, headers, body))
+    }
+
+    /// Empty reply (416) for a range outside of the file
+    fn range_not_satisfiable_reply(file_size: i64) -> DownloadReply {
+        let mut headers = HeaderMap::new();
+        let content_range = format!("bytes */{}", file_size);
+        headers.insert(header::CONTENT_RANGE, HeaderValue::from_str(&content_range).unwrap());
+        Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers, Body::empty()))
+    }
+
+    /// Parse a single range of the Range header, ex : "bytes=0-499", "bytes=500-", "bytes=-500"
+    /// Invalid or multiple ranges are ignored and the whole file is sent
+    fn parse_range_header(range: &str, file_size: u64) -> RangeRequest {
+        let Some(range_set) = range.trim().strip_prefix("bytes=") else {
+            return RangeRequest::Full;
+        };
+        if range_set.contains(',') {
+            return RangeRequest::Full;
+        }
+        let Some((first, last)) = range_set.trim().split_once('-') else {
+            return RangeRequest::Full;
+        };
+
+        let (start, end) = match (first.trim(), last.trim()) {
+            ("", "") => return RangeRequest::Full,
+            // Suffix range : the last bytes of the file
+            ("", suffix) => match suffix.parse::<u64>() {
+                Ok(0) => return RangeRequest::Unsatisfiable,
+                Ok(suffix) => (file_size.saturating_sub(suffix), file_size.saturating_sub(1)),
+                Err(_) => return RangeRequest::Full,
+            },
+            (first, "") => match first.parse::<u64>() {
+                Ok(start) => (start, file_size.saturating_sub(1)),
+                Err(_) => return RangeRequest::Full,
+            },
+            (first, last) => match (first.parse::<u64>(), last.parse::<u64>()) {
+                (Ok(start), Ok(end)) if start <= end => (start, min(end, file_size.saturating_sub(1))),
+                _ => return RangeRequest::Full,
+            },
+        };
+
+        if start >= file_size {
+            return RangeRequest::Unsatisfiable;
+        }
+        RangeRequest::Partial(ByteRange { start, end })
+    }
 
-        // Retourner le corps et les en-têtes
-        Ok((headers, body))
+    /// The range is applied only if the If-Range validator matches the (strong) entity tag of the file
+    fn is_if_range_valid(if_range: &Option<String>, etag: &str) -> bool {
+        match if_range {
+            None => true,
+            Some(validator) => validator.trim() == etag,
+        }
     }
 
-    /// Get all the encrypted parts of the file
-    /// ( "application/pdf", {0 : "...", 1: "...", ...} )
-    async fn search_incoming_blocks(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<SQLDataSet> {
+    /// Stream the blocks received for the file, in base64, read by windows of UPLOAD_WINDOW_SIZE blocks
+    /// so the memory used depends on the window size, not on the file size
+    /// [ (0, "..."), (1, "..."), ... ]
+    fn stream_incoming_blocks(
+        &self,
+        file_ref: &str,
+        customer_code: &str,
+    ) -> impl Stream<Item = anyhow::Result<(u32, String)>> + Send + 'static {
         log_info!("Search the incoming blocks for the file, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
 
+        let local_self = self.clone();
+        let file_ref = file_ref.to_owned();
+        let customer_code = customer_code.to_owned();
+
+        Self::stream_part_windows(0, None, Self::UPLOAD_WINDOW_SIZE, move |first_part, window_size| {
+            let local_self = local_self.clone();
+            let file_ref = file_ref.clone();
+            let customer_code = customer_code.clone();
+            async move { local_self.search_incoming_block_window(&file_ref, &customer_code, first_part, window_size).await }
+        })
+    }
+
+    /// Get a window of at most [window_size] blocks received for the file, starting at block number [first_part]
+    async fn search_incoming_block_window(
+        &self,
+        file_ref: &str,
+        customer_code: &str,
+        first_part: u32,
+        window_size: u32,
+    ) -> anyhow::Result<Vec<(u32, String)>> {
         let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
         let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
 
         let sql_str = r"
             SELECT
-                fu.file_ref,
                 fu.part_number,
                 fu.part_data
             FROM  fs_{customer_code}.file_uploads fu
             WHERE
-                fu.file_ref = :p_file_ref
-            ORDER BY fu.file_ref, fu.part_number";
+                fu.file_ref = :p_file_ref AND
+                fu.part_number >= :p_first_part
+            ORDER BY fu.part_number";
 
         let sql_query = sql_str.replace("{customer_code}", customer_code);
         let mut params = HashMap::new();
         params.insert("p_file_ref".to_string(), CellValue::from_raw_string(file_ref.to_string()));
+        params.insert("p_first_part".to_string(), CellValue::from_raw_int_32(first_part as i32));
 
-        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(window_size), params };
 
-        let dataset =
+        let mut dataset =
             query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
         trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
-        log_info!("😎 Found incoming blocks for the file, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
-
-        Ok(dataset)
-    }
-
-    async fn write_part(
-        &self,
-        file_id: i64,
-        block_number: u32,
-        enc_data: &str,
-        customer_code: &str,
-    ) -> anyhow::Result<()> {
-        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
-        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
-
-        let sql_query = format!(
-            r"
-                    INSERT INTO fs_{}.file_parts (file_reference_id, part_number, part_data)
-                    VALUES (:p_file_reference_id, :p_part_number, :p_part_data)",
-            customer_code
-        );
-
-        let sequence_name = format!("fs_{}.file_parts_id_seq", customer_code);
-
-        let mut params = HashMap::new();
-        params.insert("p_file_reference_id".to_string(), CellValue::from_raw_int(file_id));
-        params.insert("p_part_number".to_string(), CellValue::from_raw_int_32(block_number as i32));
-        params.insert("p_part_data".to_string(), CellValue::from_raw_str(enc_data));
-
-        let sql_insert = SQLChangeAsync { sql_query, params, sequence_name };
 
-        let _file_part_id =
-            sql_insert.insert(&mut trans).await.map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
-        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
-        log_info!("Encrypted block inserted as a part, block_num=[{}], follower=[{}]", block_number, &self.follower);
-        Ok(())
+        let mut blocks = Vec::with_capacity(dataset.len());
+        while dataset.next() {
+            let block_number = dataset.get_int_32("part_number").ok_or(anyhow!("Wrong part_number col"))? as u32;
+            let part_data = dataset.get_string("part_data").ok_or(anyhow!("Wrong part_data col"))?;
+            blocks.push((block_number, part_data));
+        }
+        Ok(blocks)
     }
 
     ///
+    /// Encrypt the blocks received and store them as the parts of the file
+    /// Return the size of the encrypted file
     ///
+    /// The blocks are encrypted on the blocking pool, at most "fs.encrypt.thread_count" at the same time,
+    /// and the encrypted blocks are stored by groups of ENCRYPT_GROUP_SIZE parts
     ///
-    async fn serial_encrypt(
+    async fn parallel_encrypt(
         &self,
         file_id: i64,
         file_ref: &str,
         block_count: u32,
         customer_code: &str,
         customer_key: &str,
-    ) -> anyhow::Result<()> {
-        // Query the blocks from file_upload table
-
-        let mut dataset = self.search_incoming_blocks(file_ref, customer_code).await.map_err(tr_fwd!())?;
-        let mut row_index: u32 = 0;
-
-        // Loop the blocks
-        while dataset.next() {
-            let block_number = dataset.get_int_32("part_number").ok_or(anyhow!("Wrong part_number col"))? as u32;
-            let part_data = dataset.get_string("part_data").ok_or(anyhow!("Wrong part_data col"))?;
-
-            // | Encrypt the data
-            let raw_value = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(part_data).map_err(tr_fwd!())?;
-
-            let encrypted_block = DkEncrypt::new(CC20)
-                .encrypt_vec(&raw_value, &customer_key)
-                .map_err(err_fwd!("Cannot encrypt the data block, follower=[{}]", &self.follower))?;
+    ) -> anyhow::Result<u64> {
+        let thread_count = encrypt_thread_count();
+        log_info!(
+            "Encrypt the blocks, block_count=[{}], thread_count=[{}], follower=[{}]",
+            block_count,
+            thread_count,
+            &self.follower
+        );
 
-            // | Store the data in the file_parts
+        // The blocks are read from the file_uploads table while the previous ones are encrypted
+        let mut encrypted_blocks = pin!(self
+            .stream_incoming_blocks(file_ref, customer_code)
+            .map_ok(|(block_number, part_data)| {
+                let customer_key = customer_key.to_owned();
+                async move {
+                    let encrypted_block =
+                        task::spawn_blocking(move || Self::encrypt_block(&part_data, &customer_key)).await??;
+                    Ok::<_, anyhow::Error>((block_number, encrypted_block))
+                }
+            })
+            .try_buffered(thread_count)
+            .try_chunks(Self::ENCRYPT_GROUP_SIZE));
 
-            let enc_data = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&encrypted_block);
+        let mut encrypted_file_size: u64 = 0;
+        while let Some(group) = encrypted_blocks
+            .try_next()
+            .await
+            .map_err(|e| anyhow!("Cannot encrypt the data block, e=[{}], follower=[{}]", e.1, &self.follower))?
+        {
+            // | Store the group of data in the block store
+            block_store()?
+                .write_parts(customer_code, file_id, &group)
+                .await
+                .map_err(err_fwd!("Cannot store the parts, follower=[{}]", &self.follower))?;
             log_info!(
-                "Encrypted the row number=[{}/{}], enc_parts=[{}], follower=[{}]",
-                row_index,
-                block_count - 1,
-                &enc_data[..10],
+                "Encrypted blocks stored as parts, block_num=[{:?}], follower=[{}]",
+                group.iter().map(|(block_number, _)| *block_number).collect::<Vec<_>>(),
                 &self.follower
             );
-            let _ = self.write_part(file_id, block_number, &enc_data, customer_code).await?;
 
-            row_index += 1;
+            encrypted_file_size += group.iter().map(|(_, encrypted_block)| encrypted_block.len() as u64).sum::<u64>();
         }
 
-        Ok(())
+        Ok(encrypted_file_size)
+    }
+
+    /// Decode the block received, in base64, and encrypt it with the customer key
+    fn encrypt_block(part_data: &str, customer_key: &str) -> anyhow::Result<Vec<u8>> {
+        let raw_value = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(part_data).map_err(tr_fwd!())?;
+        DkEncrypt::new(CC20).encrypt_vec(&raw_value, customer_key).map_err(tr_fwd!())
     }
 
     async fn process_file_blocks(
@@ -432,13 +579,81 @@ impl FileDelegate {
         block_count: u32,
         customer_code: &str,
         customer_key: &str,
+        checksum: Option<String>,
     ) -> anyhow::Result<()> {
         log_info!("Process the blocks for file ref = [{}], follower=[{}]", &file_ref, &self.follower);
-        // Read the file parts from the file_uploads table, encrypt the blocks and store the encrypted part into file_parts
-        let _ = self.serial_encrypt(file_id, file_ref, block_count, customer_code, customer_key).await?;
 
-        // Parse the file (Tika)
-        let _r = self.serial_parse_content(file_id, &file_ref, block_count, customer_code).await?;
+        self.update_processing_status(file_id, ProcessingStatus::Encrypting, None, customer_code)
+            .await
+            .map_err(tr_fwd!())?;
+
+        let checksum = match checksum {
+            Some(checksum) => checksum,
+            None => self.compute_checksum(file_ref, customer_code).await.map_err(tr_fwd!())?,
+        };
+
+        // An identical file may already be stored for the customer, in this case its parts are shared
+        let parts_reference_id =
+            self.find_identical_file(file_id, &checksum, customer_code).await.map_err(tr_fwd!())?;
+        match parts_reference_id {
+            Some(parts_reference_id) => {
+                log_info!(
+                    "Identical content found, share the parts, file_ref=[{}], parts_reference_id=[{}], follower=[{}]",
+                    file_ref,
+                    parts_reference_id,
+                    &self.follower
+                );
+                self.share_parts(file_id, parts_reference_id, &checksum, customer_code).await.map_err(tr_fwd!())?;
+            }
+            None => {
+                // Read the file parts from the file_uploads table, encrypt the blocks and store the encrypted part into file_parts
+                let encrypted_file_size = self
+                    .parallel_encrypt(file_id, file_ref, block_count, customer_code, customer_key)
+                    .await
+                    .map_err(|e| anyhow!("Cannot encrypt the file, {}", e))?;
+                self.update_checksum(file_id, &checksum, encrypted_file_size, customer_code)
+                    .await
+                    .map_err(tr_fwd!())?;
+            }
+        }
+
+        // Parse the file (Tika), the file is kept even if the parsing fails
+        self.update_processing_status(file_id, ProcessingStatus::Parsing, None, customer_code)
+            .await
+            .map_err(tr_fwd!())?;
+        // The clear content is read once, for the parsing and the preview
+        let content = self.read_incoming_content(file_ref, customer_code).await.map_err(tr_fwd!())?;
+        let (media_type, parsing_failure) = self
+            .serial_parse_content(file_id, file_ref, &content, block_count, customer_code)
+            .await
+            .map_err(|e| anyhow!("Cannot parse the file, {}", e))?;
+
+        // Build the preview, the file stays usable without it.
+        // A file sharing its parts shows the preview of the owner of the parts
+        let is_preview_generated = match parts_reference_id {
+            Some(parts_reference_id) => self.has_preview(parts_reference_id, customer_code).await.map_err(tr_fwd!())?,
+            None => self
+                .generate_preview(file_id, file_ref, content, &media_type, customer_code, customer_key)
+                .await
+                .map_err(err_fwd!(
+                    "⛔ Cannot generate the preview, file_ref=[{}], follower=[{}]",
+                    file_ref,
+                    &self.follower
+                ))
+                .unwrap_or(false),
+        };
+        if !is_preview_generated {
+            self.update_preview_flag(file_id, false, customer_code).await.map_err(tr_fwd!())?;
+        }
+
+        let status = match parsing_failure {
+            None => ProcessingStatus::Indexed,
+            Some(_) => ProcessingStatus::Stored,
+        };
+        self.update_processing_status(file_id, status, parsing_failure.as_deref(), customer_code)
+            .await
+            .map_err(tr_fwd!())?;
+
         log_info!(
             "😎 Successful process file for file_ref=[{}], file_id=[{}], follower=[{}]",
             file_ref,
@@ -457,7 +672,7 @@ impl FileDelegate {
     ///     This phase will maintain the session open as long as necessary
     ///  2. Processing Phase : Process the blocks for the file_ref, to encrypt, parse and so on.
     ///     2.b Clean all the data in the upload table for the file_ref.
-    ///         Clean the data for the (customer/user), older than 4 days.
+    ///         The data older than 4 days (fs.cleanup.retention_days) is cleaned by the cleanup job.
     ///
     pub async fn upload2(
         &mut self,
@@ -484,6 +699,11 @@ impl FileDelegate {
             return WebType::from_api_error(&INTERNAL_TECHNICAL_ERROR);
         };
 
+        // Check the quotas of the customer, before any block is written
+        if let Err(api_error) = self.check_customer_quota(customer_code, *content_length, None).await {
+            return WebType::from_api_error(api_error);
+        }
+
         // Create an entry in file_reference
         let Ok((file_id, file_ref)) = self
             .create_file_reference(customer_code, content_length)
@@ -511,12 +731,20 @@ impl FileDelegate {
 
         let item_info = String::from_utf8_lossy(&item_info_decoded);
 
-        let Ok((total_size, block_count)) = self
+        let Ok((total_size, block_count, checksum)) = self
             .read_and_write_incoming_data(&item_info, &file_ref, file_data, &entry_session)
             .await
             .map_err(err_fwd!("💣 Cannot write parts, follower=[{}]", &self.follower))
         else {
             // The stream is managed by the routine above, so no need to empty it here.
+            let _ = self
+                .update_processing_status(
+                    file_id,
+                    ProcessingStatus::Failed,
+                    Some("Cannot receive the content of the file"),
+                    customer_code,
+                )
+                .await;
             return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
         };
 
@@ -527,7 +755,16 @@ impl FileDelegate {
         );
 
         // Phase 2 : Run a thread to perform all the other operations (encrypt, tika parse, ...)
-        self.thread_processing_block(&item_info, file_id, &file_ref, customer_code, &customer_key, block_count).await;
+        self.thread_processing_block(
+            &item_info,
+            file_id,
+            &file_ref,
+            customer_code,
+            &customer_key,
+            block_count,
+            Some(checksum),
+        )
+        .await;
 
         // Return the file_reference
 
@@ -539,839 +776,2311 @@ impl FileDelegate {
         )
     }
 
-    async fn thread_processing_block(
+    /// Check that the customer can store a new file of [incoming_size] bytes
+    /// [incoming_file_id] is the reference of the new file when it is already created
+    async fn check_customer_quota(
         &self,
-        item_info_str: &str,
-        file_id: i64,
-        file_ref: &str,
         customer_code: &str,
-        customer_key: &str,
-        block_count: u32,
-    ) {
-        let local_self = self.clone();
-        let local_item_info_str = String::from(item_info_str);
-        let local_file_ref = String::from(file_ref);
-        let local_customer_code = String::from(customer_code);
-        let local_customer_key = String::from(customer_key);
-        let parallel_process = tokio::spawn(async move {
-            log_info!(
-                "Blocks processing is flying away for file_ref=[{}], follower=[{}]",
-                &local_file_ref,
-                &local_self.follower
-            );
-
-            let status = local_self
-                .process_file_blocks(
-                    file_id,
-                    &local_file_ref,
-                    &local_item_info_str,
-                    block_count,
-                    &local_customer_code,
-                    &local_customer_key,
-                )
-                .await;
-            if status.is_err() {
-                log_error!(
-                    "💣 The file processing failed. Enter the rollback process, file_ref=[{}], follower=[{}]",
-                    &local_file_ref,
-                    &local_self.follower
-                );
-                // Clean the tables : file_parts (file_id) + file_metadata (file_id)
-                let _ = local_self.delete_from_target_table("file_parts", file_id, &local_customer_code).await;
-                let _ = local_self.delete_from_target_table("file_metadata", file_id, &local_customer_code).await;
-                // Change the status of file_reference (file_id) : put all the values to "0" (size + total_part)
-                let _ = local_self.update_file_reference(file_id, 0, 0, "text", &local_customer_code).await;
-                // Call the document server to delete the text indexing
-                if let Ok(document_server) =
-                    Self::find_document_server_client().map_err(err_fwd!("Cannot find the document server"))
-                {
-                    let _ = document_server
-                        .delete_text_indexing(&local_file_ref, &local_self.follower.token_type.value())
-                        .await;
-                }
+        incoming_size: Option<u64>,
+        incoming_file_id: Option<i64>,
+    ) -> Result<(), &'static ApiError<'static>> {
+        match check_quota(customer_code, incoming_size, incoming_file_id, &self.follower).await {
+            Ok(None) => Ok(()),
+            Ok(Some(reason)) => {
+                log_warn!("⛔ The quota of the customer is exceeded, {}, follower=[{}]", reason, &self.follower);
+                Err(&*QUOTA_EXCEEDED)
             }
+            Err(e) => {
+                log_error!("💣 Cannot check the quotas of the customer, e=[{}], follower=[{}]", e, &self.follower);
+                Err(&*INTERNAL_TECHNICAL_ERROR)
+            }
+        }
+    }
 
-            log_info!(
-                "End of the block processing. Ready to delete upload parts, file_ref=[{}], follower=[{}]",
-                &local_file_ref,
-                &local_self.follower
-            );
-
-            // Clean file_uploads (file_ref)
-            let _ = local_self.delete_from_file_uploads(&local_file_ref, &local_customer_code).await;
-        });
+    /// Number of blocks for a file of [file_size] bytes
+    fn expected_block_count(file_size: u64) -> u32 {
+        file_size.div_ceil(Self::BLOCK_SIZE as u64) as u32
+    }
 
-        let _ = parallel_process.map_err(|e| {
-            log_error!("💣 Error in the thread processing block, error=[{}], follower=[{}]", e, &self.follower);
-            e
-        });
+    /// A block must have the size of BLOCK_SIZE, except the last one of the file
+    /// When the file size is unknown, the size of the last block is checked at the end of the session
+    fn is_valid_block(block_num: u32, block_size: usize, file_size: Option<i64>) -> bool {
+        if block_size == 0 || block_size > Self::BLOCK_SIZE {
+            return false;
+        }
+        let Some(file_size) = file_size else {
+            return true;
+        };
+        let block_count = Self::expected_block_count(file_size as u64);
+        if block_num >= block_count {
+            return false;
+        }
+        let expected_size = if block_num == block_count - 1 {
+            file_size as usize - (block_count as usize - 1) * Self::BLOCK_SIZE
+        } else {
+            Self::BLOCK_SIZE
+        };
+        block_size == expected_size
     }
 
-    // Windows only
-    // fn empty_datastream(&self, reader: &mut dyn Read) {
-    //     // BUG https://github.com/SergioBenitez/Rocket/issues/892
-    //     log_warn!("⛔ Running on Windows, need to read the datastream");
-    //     let _r = io::copy(reader, &mut io::sink());
-    // }
+    /// Check that all the blocks of the file have been received
+    /// [received_blocks] are the (block number, size) ordered by block number
+    /// Return the total size of the file and its number of blocks
+    fn check_complete_upload(received_blocks: &[(u32, i64)], block_count: Option<u32>) -> anyhow::Result<(usize, u32)> {
+        let mut total_size: usize = 0;
+        for (index, (block_num, size)) in received_blocks.iter().enumerate() {
+            if *block_num != index as u32 {
+                return Err(anyhow!("Missing block, block number=[{}]", index));
+            }
+            let is_last = index == received_blocks.len() - 1;
+            if !is_last && *size as usize != Self::BLOCK_SIZE {
+                return Err(anyhow!("Wrong block size, block number=[{}], size=[{}]", block_num, size));
+            }
+            total_size += *size as usize;
+        }
+
+        let received_count = received_blocks.len() as u32;
+        match block_count {
+            Some(block_count) if block_count != received_count => {
+                Err(anyhow!("Wrong number of blocks, expected=[{}], received=[{}]", block_count, received_count))
+            }
+            _ => Ok((total_size, received_count)),
+        }
+    }
 
     ///
-    /// Run a thread to process the block and store it in the DB
+    /// REF_TAG : FILE_UPLOAD_SESSION
     ///
-    // fn _parallel_crypto_and_store_block(
-    //     &self,
-    //     file_id: i64,
-    //     block_set: &HashMap<u32, Vec<u8>>,
-    //     customer_code: &str,
-    //     customer_key: &str,
-    // ) -> JoinHandle<anyhow::Result<()>> {
-    //     let s_customer_code = customer_code.to_owned();
-    //     let s_customer_key = customer_key.to_owned();
-    //     let local_block_set = block_set
-    //         .into_iter()
-    //         .map(|(key, value)| (*key, (*value).to_owned()))
-    //         .collect();
-    //
-    //     let local_self = self.clone();
-    //
-    //     let th = thread::spawn(move || {
-    //         local_self._crypto_and_store_block(
-    //             file_id,
-    //             local_block_set,
-    //             s_customer_code,
-    //             s_customer_key,
-    //         )
-    //     });
-    //
-    //     th
-    // }
+    /// 🌟 Create an upload session, to send the file block by block
+    ///
+    /// The upload session is an alternative to upload2 for large files :
+    ///  1. Create the session, it gives the file reference and the block size.
+    ///  2. Put the numbered blocks, in any order. A block can be sent again.
+    ///     If the connection drops, ask for the session to know the blocks already received and resume.
+    ///  3. Finalize the session, to run the processing phase of upload2 (encrypt, parse and so on).
+    ///
+    pub async fn create_upload_session(&mut self, request: &CreateUploadSessionRequest) -> WebType<UploadSessionReply> {
+        log_info!(
+            "🚀 Start create_upload_session api, item_info=[{}], follower=[{}]",
+            &request.item_info,
+            &self.follower
+        );
 
-    async fn serial_parse_content(
-        &self,
-        file_id: i64,
-        file_ref: &str,
-        block_count: u32,
-        customer_code: &str,
-    ) -> anyhow::Result<()> {
-        // Build the file in memory
-        let mut mem_file: Vec<u8> = vec![];
-        let mut dataset =
-            self.search_incoming_blocks(/*&mut trans,*/ file_ref, customer_code).await.map_err(tr_fwd!())?;
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
 
-        // Loop the blocks
-        while dataset.next() {
-            let part_data = dataset.get_string("part_data").ok_or(anyhow!("Wrong part_data col"))?;
+        let customer_code = entry_session.customer_code.as_str();
 
-            // | Encrypt the data
-            let raw_value = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(part_data).map_err(tr_fwd!())?;
+        if request.item_info.is_empty() || request.item_info.len() > 50 {
+            log_error!("💣 Wrong item info, item_info=[{}], follower=[{}]", &request.item_info, &self.follower);
+            return WebType::from_api_error(&UPLOAD_WRONG_ITEM_INFO);
+        }
 
-            mem_file.extend(&raw_value);
+        if let Err(api_error) = self.check_customer_quota(customer_code, request.file_size, None).await {
+            return WebType::from_api_error(api_error);
         }
 
-        let total_size = mem_file.len();
-        // Read the metadata and the raw text of the file
-        let media_type = self.analyse_entire_content(&file_ref, mem_file, &customer_code).await.map_err(tr_fwd!())?;
-        // Update the file_reference table : checksum, original_file_size, total_part, media_type
-        let _ = self
-            .update_file_reference(file_id, total_size, block_count, &media_type, customer_code)
+        // Create an entry in file_reference
+        let Ok((_file_id, file_ref)) = self
+            .create_file_reference(customer_code, &request.file_size)
             .await
-            .map_err(tr_fwd!())?;
-        Ok(())
-    }
+            .map_err(err_fwd!("💣 Cannot create an entry in the file reference table, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
 
-    fn min_max<T>(map: &HashMap<u32, T>) -> (u32, u32) {
-        let mut min: u32 = u32::MAX;
-        let mut max: u32 = u32::MIN;
+        let block_count = request.file_size.map(Self::expected_block_count);
 
-        for (index, _) in map {
-            if *index >= max {
-                max = *index;
-            }
-            if *index <= min {
-                min = *index;
-            }
+        if self
+            .insert_upload_session(&file_ref, &request.item_info, &request.file_size, block_count, &entry_session)
+            .await
+            .map_err(err_fwd!("💣 Cannot create the upload session, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
         }
-        (min, max)
+
+        log_info!("😎 Created upload session, file_ref=[{}], follower=[{}]", &file_ref, &self.follower);
+        log_info!("🏁 End create_upload_session api, follower=[{}]", &self.follower);
+
+        WebType::from_item(
+            StatusCode::OK.as_u16(),
+            UploadSessionReply { file_ref, block_size: Self::BLOCK_SIZE as u32, block_count, received_blocks: vec![] },
+        )
     }
 
-    /// Store the encrypted file's blocks in the database.
-    async fn store_group_block(
-        &self,
-        item_info_str: &str,
-        file_ref: &str,
-        block_set: &HashMap<u32, Vec<u8>>,
-        entry_session: &EntrySession,
-    ) -> anyhow::Result<()> {
-        // Open the transaction
-        let block_range = Self::min_max(&block_set);
+    /// 🌟 Get the upload session, with the blocks already received, to resume an interrupted upload
+    pub async fn upload_session_status(&mut self, file_ref: &str) -> WebType<UploadSessionReply> {
+        log_info!("🚀 Start upload_session_status api, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
 
-        log_info!("Block range processing, block range=[{:?}], follower=[{}]", &block_range, &self.follower);
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
 
-        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
-        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+        let customer_code = entry_session.customer_code.as_str();
 
-        for (block_num, block) in block_set {
-            log_debug!("Block processing... : block_num=[{}], follower=[{}]", block_num, &self.follower);
+        let upload_session = match self.search_upload_session(file_ref, &entry_session).await {
+            Ok(Some(upload_session)) => upload_session,
+            Ok(None) => return WebType::from_api_error(&UPLOAD_SESSION_NOT_FOUND),
+            Err(e) => {
+                log_error!("💣 Cannot read the upload session, e=[{}], follower=[{}]", e, &self.follower);
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
+        };
 
-            let original_part_size = block.len();
-            // Store in the DB
-            let data = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&block);
-            let sql_query = format!(
-                r"
-            INSERT INTO fs_{}.file_uploads (session_id, start_time_gmt,
-                                      user_id, item_info, file_ref, part_number, original_part_size, part_data)
-            VALUES (:p_session_id, :p_start_time_gmt,
-                    :p_user_id, :p_item_info, :p_file_ref, :p_part_number, :p_original_part_size, :p_part_data)",
-                &entry_session.customer_code
-            );
+        let Ok(received_blocks) = self.search_received_blocks(file_ref, customer_code).await.map_err(tr_fwd!()) else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
 
-            let mut params = HashMap::new();
+        log_info!("🏁 End upload_session_status api, follower=[{}]", &self.follower);
 
-            params.insert("p_session_id".to_string(), CellValue::from_raw_str(&self.follower.token_type.value()));
-            params.insert("p_start_time_gmt".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
-            params.insert("p_user_id".to_string(), CellValue::from_raw_int(entry_session.user_id));
-            params.insert("p_item_info".to_string(), CellValue::from_raw_str(item_info_str));
-            params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
-            params.insert("p_part_number".to_string(), CellValue::from_raw_int_32(*block_num as i32));
-            params.insert("p_original_part_size".to_string(), CellValue::from_raw_int(original_part_size as i64));
-            params.insert("p_part_data".to_string(), CellValue::from_raw_string(data));
+        WebType::from_item(
+            StatusCode::OK.as_u16(),
+            UploadSessionReply {
+                file_ref: file_ref.to_string(),
+                block_size: Self::BLOCK_SIZE as u32,
+                block_count: upload_session.block_count,
+                received_blocks: received_blocks.into_iter().map(|(block_num, _)| block_num).collect(),
+            },
+        )
+    }
 
-            let sql_insert = SQLChangeAsync { sql_query, params, sequence_name: "".to_uppercase() };
+    /// 🌟 Store one block of the file in the upload session
+    pub async fn upload_block(&mut self, file_ref: &str, block_num: u32, block: Bytes) -> WebType<UploadBlockReply> {
+        log_info!(
+            "🚀 Start upload_block api, file_ref=[{}], block_num=[{}], size=[{}], follower=[{}]",
+            file_ref,
+            block_num,
+            block.len(),
+            &self.follower
+        );
 
-            let _ = sql_insert
-                .insert_no_pk(&mut trans)
-                .await
-                .map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
-            log_debug!("...Block inserted, block_num=[{}], follower=[{}]", block_num, &self.follower);
-        }
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
 
-        // End of the 'pink' multiple transaction
-        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
-        log_info!("😎 Committed. Block inserted, block_range=[{:?}], follower=[{}]", &block_range, &self.follower);
+        let upload_session = match self.search_upload_session(file_ref, &entry_session).await {
+            Ok(Some(upload_session)) => upload_session,
+            Ok(None) => return WebType::from_api_error(&UPLOAD_SESSION_NOT_FOUND),
+            Err(e) => {
+                log_error!("💣 Cannot read the upload session, e=[{}], follower=[{}]", e, &self.follower);
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
+        };
 
-        // Slow down the process for 2 seconds
-        // tokio::time::sleep(Duration::from_secs(5)).await;
+        if !Self::is_valid_block(block_num, block.len(), upload_session.file_size) {
+            log_error!(
+                "💣 Wrong block, block_num=[{}], size=[{}], file_size=[{:?}], follower=[{}]",
+                block_num,
+                block.len(),
+                upload_session.file_size,
+                &self.follower
+            );
+            return WebType::from_api_error(&UPLOAD_WRONG_BLOCK);
+        }
 
-        Ok(())
-    }
+        let size = block.len();
+        let block_set = HashMap::from([(block_num, block.to_vec())]);
+        if self
+            .store_group_block(&upload_session.item_info, file_ref, &block_set, &entry_session)
+            .await
+            .map_err(err_fwd!("💣 Cannot store the block, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
 
-    //
-    // fn _crypto_and_store_block(
-    //     &self,
-    //     file_id: i64,
-    //     block_set: HashMap<u32, Vec<u8>>,
-    //     customer_code: String,
-    //     customer_key: String,
-    // ) -> anyhow::Result<()> {
-    //     // Open the transaction
-    //     let block_range = Self::min_max(&block_set);
-    //
-    //     log_info!(
-    //         "Block range processing, block range=[{:?}], follower=[{}]",
-    //         &block_range,
-    //         &self.follower
-    //     );
-    //
-    //     let mut r_cnx = SQLConnection2::from_pool().await;
-    //     let mut trans = open_transaction(&mut r_cnx).map_err(err_fwd!(
-    //         "Open transaction error, block_range=[{:?}], follower=[{}]",
-    //         &block_range,
-    //         &self.follower
-    //     ))?;
-    //
-    //     for (block_num, block) in block_set {
-    //         log_debug!(
-    //             "Block processing... : block_num=[{}], follower=[{}]",
-    //             block_num,
-    //             &self.follower
-    //         );
-    //
-    //         // Encrypt the block
-    //         let encrypted_block =
-    //             DkEncrypt::new(CC20).encrypt_vec(&block, &customer_key).map_err(err_fwd!(
-    //                 "Cannot encrypt the data block, follower=[{}]",
-    //                 &self.follower
-    //             ))?;
-    //
-    //         // and store in the DB
-    //
-    //         let data = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(encrypted_block);
-    //         // let data = encrypted_block.to_base64(URL_SAFE);
-    //
-    //         let sql_query = format!(
-    //             r"
-    //                 INSERT INTO fs_{}.file_parts (file_reference_id, part_number, part_data)
-    //                 VALUES (:p_file_reference_id, :p_part_number, :p_part_data)",
-    //             customer_code
-    //         );
-    //
-    //         let sequence_name = format!("fs_{}.file_parts_id_seq", customer_code);
-    //
-    //         let mut params = HashMap::new();
-    //         params.insert(
-    //             "p_file_reference_id".to_string(),
-    //             CellValue::from_raw_int(file_id),
-    //         );
-    //         params.insert(
-    //             "p_part_number".to_string(),
-    //             CellValue::from_raw_int_32(block_num as i32),
-    //         );
-    //         params.insert("p_part_data".to_string(), CellValue::from_raw_string(data));
-    //
-    //         let sql_insert = SQLChange {
-    //             sql_query,
-    //             params,
-    //             sequence_name,
-    //         };
-    //
-    //         let _file_part_id = sql_insert
-    //             .insert(&mut trans)
-    //             .map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
-    //
-    //         log_debug!(
-    //             "...Block inserted, block_num=[{}], follower=[{}]",
-    //             block_num,
-    //             &self.follower
-    //         );
-    //     }
-    //
-    //     trans
-    //         .commit()
-    //         .map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
-    //
-    //     log_info!(
-    //         "😎 Committed. Block inserted, block_range=[{:?}], follower=[{}]",
-    //         &block_range,
-    //         &self.follower
-    //     );
-    //
-    //     Ok(())
-    // }
+        log_info!("🏁 End upload_block api, follower=[{}]", &self.follower);
 
-    fn find_document_server_client() -> anyhow::Result<DocumentServerClientAsync> {
-        let document_server_host = get_prop_value(DOCUMENT_SERVER_HOSTNAME_PROPERTY)?;
-        let document_server_port = get_prop_value(DOCUMENT_SERVER_PORT_PROPERTY)?.parse::<u16>()?;
-        Ok(DocumentServerClientAsync::new(&document_server_host, document_server_port))
+        WebType::from_item(
+            StatusCode::OK.as_u16(),
+            UploadBlockReply { file_ref: file_ref.to_string(), block_num, size },
+        )
     }
 
-    /// Call the tika server to parse the file and get the text data
-    /// Insert the metadata
-    /// Call the document server to fulltext parse the text data
-    /// return the media type
-    async fn analyse_entire_content(
-        &self,
-        file_ref: &str,
-        mem_file: Vec<u8>,
-        customer_code: &str,
-    ) -> anyhow::Result<String> {
-        log_info!("Parsing file content ... ,file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+    /// 🌟 Close the upload session once all the blocks are received and process the file
+    pub async fn finalize_upload_session(&mut self, file_ref: &str) -> WebType<UploadReply> {
+        log_info!("🚀 Start finalize_upload_session api, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
 
-        let tika_server_host = get_prop_value(TIKA_SERVER_HOSTNAME_PROPERTY)?;
-        let tika_server_port = get_prop_value(TIKA_SERVER_PORT_PROPERTY)?.parse::<u16>()?;
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
 
-        // Get the raw text from the original file
-        let tsc = TikaServerClientAsync::new(&tika_server_host, tika_server_port);
-        let raw_json = tsc.parse_data_json(&mem_file).await.map_err(err_fwd!("Cannot parse the original file"))?;
-        let x_tika_content = raw_json[TIKA_CONTENT_META].as_str().ok_or(anyhow!("Bad tika content"))?;
-        let content_type = raw_json[CONTENT_TYPE_META].as_str().ok_or(anyhow!("Bad content type"))?;
+        let customer_code = entry_session.customer_code.as_str();
 
-        let metadata = match raw_json.as_object() {
-            Some(obj) => obj.clone(),
-            None => {
-                return Err(anyhow!("Bad metadata"));
+        let upload_session = match self.search_upload_session(file_ref, &entry_session).await {
+            Ok(Some(upload_session)) => upload_session,
+            Ok(None) => return WebType::from_api_error(&UPLOAD_SESSION_NOT_FOUND),
+            Err(e) => {
+                log_error!("💣 Cannot read the upload session, e=[{}], follower=[{}]", e, &self.follower);
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
             }
         };
 
+        let Ok(received_blocks) = self.search_received_blocks(file_ref, customer_code).await.map_err(tr_fwd!()) else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let Ok((total_size, block_count)) = Self::check_complete_upload(&received_blocks, upload_session.block_count)
+            .map_err(err_fwd!("💣 The upload is not complete, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&UPLOAD_INCOMPLETE);
+        };
+
+        // The size of the file may be unknown at the creation of the session, the quotas are checked on the blocks received
+        if let Err(api_error) =
+            self.check_customer_quota(customer_code, Some(total_size as u64), Some(upload_session.file_id)).await
+        {
+            return WebType::from_api_error(api_error);
+        }
+
+        let Ok(customer_key) = fetch_customer_key(customer_code, &self.follower)
+            .await
+            .map_err(err_fwd!("💣 Cannot get the customer key, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_TECHNICAL_ERROR);
+        };
+
+        // No more block can be sent once the session is closed
+        if self
+            .delete_upload_session(file_ref, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot close the upload session, follower=[{}]", &self.follower))
+            .is_err()
+        {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        }
+
         log_info!(
-            "Parsing done for file_ref=[{}], content size=[{}], content type=[{}], follower=[{}]",
+            "😎 Upload complete. About to process blocks, file_ref=[{}], follower=[{}]",
             file_ref,
-            x_tika_content.len(),
-            &content_type,
             &self.follower
         );
 
-        let _ = self.insert_metadata(&customer_code, file_ref, &metadata).await?;
+        self.thread_processing_block(
+            &upload_session.item_info,
+            upload_session.file_id,
+            file_ref,
+            customer_code,
+            &customer_key,
+            block_count,
+            None,
+        )
+        .await;
 
-        // TODO TikaParsing can contain all the metadata, so keep them and return then instead of getting only the content-type.
-        log_info!("Metadata done for file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+        log_info!("🏁 End finalize_upload_session api, follower=[{}]", &self.follower);
 
-        let document_server =
-            Self::find_document_server_client().map_err(err_fwd!("Cannot find the document server"))?;
+        WebType::from_item(
+            StatusCode::OK.as_u16(),
+            UploadReply { file_ref: file_ref.to_string(), size: total_size, block_count },
+        )
+    }
 
-        // TODO we must also pass the  self.follower.x_request_id + handle the file name
-        let wr_reply = document_server
-            .fulltext_indexing(&x_tika_content, "no_filename_for_now", file_ref, &self.follower.token_type.value())
-            .await;
-        match wr_reply {
-            Ok(reply) => {
-                log_info!(
-                    "Fulltext indexing done, number of text parts=[{}], follower=[{}]",
-                    reply.part_count,
-                    &self.follower
+    async fn insert_upload_session(
+        &self,
+        file_ref: &str,
+        item_info: &str,
+        file_size: &Option<u64>,
+        block_count: Option<u32>,
+        entry_session: &EntrySession,
+    ) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"INSERT INTO fs_{}.file_upload_session
+            ( file_ref, user_id, item_info, file_size, block_count, start_time_gmt )
+            VALUES ( :p_file_ref, :p_user_id, :p_item_info, :p_file_size, :p_block_count, :p_start_time_gmt )",
+            &entry_session.customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+        params.insert("p_user_id".to_string(), CellValue::from_raw_int(entry_session.user_id));
+        params.insert("p_item_info".to_string(), CellValue::from_raw_str(item_info));
+        params.insert("p_file_size".to_string(), CellValue::Int(file_size.map(|size| size as i64)));
+        params.insert("p_block_count".to_string(), CellValue::Int32(block_count.map(|count| count as i32)));
+        params.insert("p_start_time_gmt".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
+
+        let sql_insert = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+
+        sql_insert
+            .insert_no_pk(&mut trans)
+            .await
+            .map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        Ok(())
+    }
+
+    /// Find the upload session of the user for the file reference
+    async fn search_upload_session(
+        &self,
+        file_ref: &str,
+        entry_session: &EntrySession,
+    ) -> anyhow::Result<Option<UploadSession>> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_str = r"
+            SELECT fr.id AS file_id,
+                us.item_info,
+                us.file_size,
+                us.block_count
+            FROM fs_{customer_code}.file_upload_session us, fs_{customer_code}.file_reference fr
+            WHERE
+                fr.file_ref = us.file_ref AND
+                us.file_ref = :p_file_ref AND
+                us.user_id = :p_user_id";
+
+        let sql_query = sql_str.replace("{customer_code}", &entry_session.customer_code);
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+        params.insert("p_user_id".to_string(), CellValue::from_raw_int(entry_session.user_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+
+        if !dataset.next() {
+            return Ok(None);
+        }
+
+        let file_id = dataset.get_int("file_id").ok_or(anyhow!("Wrong file_id col"))?;
+        let item_info = dataset.get_string("item_info").ok_or(anyhow!("Wrong item_info col"))?;
+        let file_size = dataset.get_int("file_size");
+        let block_count = dataset.get_int_32("block_count").map(|count| count as u32);
+
+        Ok(Some(UploadSession { file_id, item_info, file_size, block_count }))
+    }
+
+    /// ( <block_number>, <original_part_size> ) of the blocks received for the file, ordered by block number
+    async fn search_received_blocks(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<Vec<(u32, i64)>> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"SELECT part_number, original_part_size
+            FROM fs_{}.file_uploads
+            WHERE file_ref = :p_file_ref
+            ORDER BY part_number",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+
+        let mut blocks = Vec::with_capacity(dataset.len());
+        while dataset.next() {
+            let part_number = dataset.get_int_32("part_number").ok_or(anyhow!("Wrong part_number col"))?;
+            let size = dataset.get_int("original_part_size").unwrap_or(0);
+            blocks.push((part_number as u32, size));
+        }
+
+        Ok(blocks)
+    }
+
+    async fn delete_upload_session(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_delete = format!("DELETE FROM fs_{}.file_upload_session WHERE file_ref = :p_file_ref", &customer_code);
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+
+        let query = SQLChangeAsync { sql_query: sql_delete, params, sequence_name: "".to_string() };
+
+        query.delete(&mut trans).await.map_err(err_fwd!(
+            "💣 Query failed, [{}], follower=[{}]",
+            &query.sql_query,
+            &self.follower
+        ))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        Ok(())
+    }
+
+    async fn thread_processing_block(
+        &self,
+        item_info_str: &str,
+        file_id: i64,
+        file_ref: &str,
+        customer_code: &str,
+        customer_key: &str,
+        block_count: u32,
+        checksum: Option<String>,
+    ) {
+        let local_self = self.clone();
+        let local_item_info_str = String::from(item_info_str);
+        let local_file_ref = String::from(file_ref);
+        let local_customer_code = String::from(customer_code);
+        let local_customer_key = String::from(customer_key);
+        let parallel_process = tokio::spawn(async move {
+            log_info!(
+                "Blocks processing is flying away for file_ref=[{}], follower=[{}]",
+                &local_file_ref,
+                &local_self.follower
+            );
+
+            let status = local_self
+                .process_file_blocks(
+                    file_id,
+                    &local_file_ref,
+                    &local_item_info_str,
+                    block_count,
+                    &local_customer_code,
+                    &local_customer_key,
+                    checksum,
+                )
+                .await;
+            if let Err(e) = status {
+                log_error!(
+                    "💣 The file processing failed. Enter the rollback process, file_ref=[{}], follower=[{}]",
+                    &local_file_ref,
+                    &local_self.follower
                 );
-                self.set_file_reference_fulltext_indicator(file_ref, customer_code).await.map_err(err_fwd!(
-                    "Cannot set the file reference to fulltext parsed indicator, follower=[{}]",
-                    &self.follower
-                ))?;
+                // Give back the parts, written so far or shared with an identical file
+                let _ = local_self.release_parts(file_id, &local_customer_code).await;
+                // Clean the table : file_metadata (file_id)
+                let _ = local_self.delete_from_target_table("file_metadata", file_id, &local_customer_code).await;
+                // Change the status of file_reference (file_id) : put all the values to "0" (size + total_part)
+                let _ = local_self.update_file_reference(file_id, 0, 0, "text", &local_customer_code).await;
+                // Keep the reason of the failure for the client
+                let _ = local_self
+                    .update_processing_status(
+                        file_id,
+                        ProcessingStatus::Failed,
+                        Some(&Self::failure_reason(&e)),
+                        &local_customer_code,
+                    )
+                    .await;
+                // Call the document server to delete the text indexing
+                if let Ok(document_server) =
+                    Self::find_document_server_client().map_err(err_fwd!("Cannot find the document server"))
+                {
+                    let _ = document_server
+                        .delete_text_indexing(&local_file_ref, &local_self.follower.token_type.value())
+                        .await;
+                }
             }
+
+            log_info!(
+                "End of the block processing. Ready to delete upload parts, file_ref=[{}], follower=[{}]",
+                &local_file_ref,
+                &local_self.follower
+            );
+
+            // Clean file_uploads (file_ref)
+            let _ = local_self.delete_from_file_uploads(&local_file_ref, &local_customer_code).await;
+        });
+
+        let _ = parallel_process.map_err(|e| {
+            log_error!("💣 Error in the thread processing block, error=[{}], follower=[{}]", e, &self.follower);
+            e
+        });
+    }
+
+    // Windows only
+    // fn empty_datastream(&self, reader: &mut dyn Read) {
+    //     // BUG https://github.com/SergioBenitez/Rocket/issues/892
+    //     log_warn!("⛔ Running on Windows, need to read the datastream");
+    //     let _r = io::copy(reader, &mut io::sink());
+    // }
+
+    ///
+    /// Parse the file from its clear content, return its media type and the reason of the parsing failure if any
+    ///
+    async fn serial_parse_content(
+        &self,
+        file_id: i64,
+        file_ref: &str,
+        content: &Vec<u8>,
+        block_count: u32,
+        customer_code: &str,
+    ) -> anyhow::Result<(String, Option<String>)> {
+        let total_size = content.len();
+        // Read the metadata and the raw text of the file
+        let (media_type, parsing_failure) =
+            self.parse_and_index(file_id, file_ref, content, customer_code).await.map_err(tr_fwd!())?;
+        // Update the file_reference table : checksum, original_file_size, total_part, media_type
+        let _ = self
+            .update_file_reference(file_id, total_size, block_count, &media_type, customer_code)
+            .await
+            .map_err(tr_fwd!())?;
+        Ok((media_type, parsing_failure))
+    }
+
+    ///
+    /// Parse the content (Tika) and index its text (document server).
+    /// When the parsing fails, Tika or the document server being down for instance, the metadata are removed,
+    /// the file is marked as not fulltext parsed and gets an unknown media type, so it can be reindexed later.
+    /// Return the media type and the reason of the parsing failure if any
+    ///
+    async fn parse_and_index(
+        &self,
+        file_id: i64,
+        file_ref: &str,
+        content: &Vec<u8>,
+        customer_code: &str,
+    ) -> anyhow::Result<(String, Option<String>)> {
+        match self.analyse_entire_content(file_ref, content, customer_code).await {
+            Ok(media_type) => Ok((media_type, None)),
             Err(e) => {
-                log_error!("Error while sending the raw text to the fulltext indexing, file_ref=[{}], reply=[{:?}], follower=[{}], ",  file_ref, e, &self.follower);
-                return Err(anyhow::anyhow!(e.message));
+                log_warn!(
+                    "⛔ The parsing failed, the file is kept to be reindexed, file_ref=[{}], e=[{}], follower=[{}]",
+                    file_ref,
+                    e,
+                    &self.follower
+                );
+                self.delete_from_target_table("file_metadata", file_id, customer_code).await.map_err(tr_fwd!())?;
+                self.set_file_reference_fulltext_indicator(file_ref, false, customer_code).await.map_err(tr_fwd!())?;
+                Ok((UNKNOWN_MEDIA_TYPE.to_string(), Some(Self::failure_reason(&e))))
             }
         }
+    }
 
-        log_info!("... End of parse file content processing, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
-        Ok(content_type.to_owned())
+    /// Clear content of the file, from the blocks received
+    async fn read_incoming_content(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<Vec<u8>> {
+        let mut mem_file: Vec<u8> = vec![];
+        let mut blocks = pin!(self.stream_incoming_blocks(file_ref, customer_code));
+
+        // Loop the blocks
+        while let Some((_, part_data)) = blocks.try_next().await.map_err(tr_fwd!())? {
+            let raw_value = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(part_data).map_err(tr_fwd!())?;
+            mem_file.extend(&raw_value);
+        }
+        Ok(mem_file)
     }
 
-    async fn insert_metadata(
+    ///
+    /// Build the preview of the file when a renderer accepts its media type,
+    /// and store it encrypted, as a file reference of its own linked to the file in the preview table.
+    /// Return false if there is no renderer for the file
+    ///
+    async fn generate_preview(
         &self,
-        customer_code: &str,
+        file_id: i64,
         file_ref: &str,
-        metadata: &Map<String, Value>,
+        content: Vec<u8>,
+        media_type: &str,
+        customer_code: &str,
+        customer_key: &str,
+    ) -> anyhow::Result<bool> {
+        // "text/plain; charset=UTF-8" -> "text/plain"
+        let essence = media_type.split(';').next().unwrap_or_default().trim();
+        let Some(renderer) = find_preview_renderer(essence) else {
+            log_info!("No preview for the media type=[{}], follower=[{}]", essence, &self.follower);
+            return Ok(false);
+        };
+
+        let preview_data = task::spawn_blocking(move || renderer.render(&content)).await??;
+
+        let (preview_id, preview_ref) =
+            self.create_file_reference(customer_code, &Some(preview_data.len() as u64)).await.map_err(tr_fwd!())?;
+
+        // A preview not linked to the file is marked as failed, so the cleanup removes it
+        if let Err(e) =
+            self.store_preview(file_id, preview_id, &preview_ref, &preview_data, customer_code, customer_key).await
+        {
+            let _ = self
+                .update_processing_status(
+                    preview_id,
+                    ProcessingStatus::Failed,
+                    Some(&Self::failure_reason(&e)),
+                    customer_code,
+                )
+                .await;
+            return Err(e);
+        }
+
+        log_info!(
+            "😎 Preview generated, file_ref=[{}], preview_ref=[{}], follower=[{}]",
+            file_ref,
+            &preview_ref,
+            &self.follower
+        );
+        Ok(true)
+    }
+
+    /// Encrypt and write the parts of the preview [preview_id], then link it to the file [file_id]
+    async fn store_preview(
+        &self,
+        file_id: i64,
+        preview_id: i64,
+        preview_ref: &str,
+        preview_data: &[u8],
+        customer_code: &str,
+        customer_key: &str,
+    ) -> anyhow::Result<()> {
+        let mut block_count: u32 = 0;
+        let mut encrypted_size: u64 = 0;
+        for block in preview_data.chunks(Self::BLOCK_SIZE) {
+            let encrypted_block = DkEncrypt::new(CC20)
+                .encrypt_vec(&block.to_vec(), customer_key)
+                .map_err(err_fwd!("Cannot encrypt the preview block, follower=[{}]", &self.follower))?;
+            block_store()?.write_part(customer_code, preview_id, block_count, &encrypted_block).await?;
+            encrypted_size += encrypted_block.len() as u64;
+            block_count += 1;
+        }
+
+        // The preview can be verified as any stored file
+        let mut hasher = Sha256::new();
+        hasher.update(preview_data);
+        let stored_preview = StoredPreview {
+            preview_id,
+            preview_ref,
+            checksum: Self::format_checksum(hasher),
+            original_file_size: preview_data.len() as u64,
+            encrypted_file_size: encrypted_size,
+            block_count,
+        };
+        self.link_preview(file_id, &stored_preview, customer_code).await.map_err(tr_fwd!())
+    }
+
+    ///
+    /// In one transaction, complete the file reference of the stored preview,
+    /// link the preview to the file and mark the file as previewed
+    ///
+    async fn link_preview(
+        &self,
+        file_id: i64,
+        stored_preview: &StoredPreview<'_>,
+        customer_code: &str,
     ) -> anyhow::Result<()> {
         let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
         let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
 
-        let sql_query = format!(
-            r"INSERT INTO fs_{0}.file_metadata ( file_reference_id, meta_key,  value )
-        VALUES ((SELECT id FROM fs_{0}.file_reference WHERE file_ref = :p_file_ref), :p_meta_key, :p_value)",
-            customer_code
-        );
-        let sequence_name = format!("fs_{}.file_metadata_id_seq", customer_code);
+        let sql_query = format!(
+            r"UPDATE fs_{}.file_reference
+                SET checksum = :p_checksum,
+                    original_file_size = :p_original_file_size,
+                    encrypted_file_size = :p_encrypted_file_size,
+                    total_part = :p_total_part,
+                    mime_type = :p_mime_type,
+                    is_encrypted = true,
+                    processing_status = :p_processing_status,
+                    status_gmt = :p_status_gmt
+                WHERE id = :p_preview_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_checksum".to_string(), CellValue::from_raw_str(&stored_preview.checksum));
+        params.insert(
+            "p_original_file_size".to_string(),
+            CellValue::from_raw_int(stored_preview.original_file_size as i64),
+        );
+        params.insert(
+            "p_encrypted_file_size".to_string(),
+            CellValue::from_raw_int(stored_preview.encrypted_file_size as i64),
+        );
+        params.insert("p_total_part".to_string(), CellValue::from_raw_int_32(stored_preview.block_count as i32));
+        params.insert("p_mime_type".to_string(), CellValue::from_raw_str(PREVIEW_MEDIA_TYPE));
+        params.insert("p_processing_status".to_string(), CellValue::from_raw_str(ProcessingStatus::Indexed.as_str()));
+        params.insert("p_status_gmt".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
+        params.insert("p_preview_id".to_string(), CellValue::from_raw_int(stored_preview.preview_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        let sql_query = format!(
+            r"INSERT INTO fs_{}.preview (file_reference_id, file_identifier, sort_order)
+            VALUES (:p_file_id, :p_preview_ref, 0)",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+        params.insert("p_preview_ref".to_string(), CellValue::from_raw_str(stored_preview.preview_ref));
+
+        let sql_insert =
+            SQLChangeAsync { sql_query, params, sequence_name: format!("fs_{}.preview_id_seq", customer_code) };
+        let _ =
+            sql_insert.insert(&mut trans).await.map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
+
+        let sql_query = format!(
+            r"UPDATE fs_{}.file_reference SET is_preview_generated = true WHERE id = :p_file_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+        Ok(())
+    }
+
+    /// True if a preview is linked to the file
+    async fn has_preview(&self, file_id: i64, customer_code: &str) -> anyhow::Result<bool> {
+        let sql_query = format!(
+            r"SELECT EXISTS (SELECT 1 FROM fs_{}.preview p WHERE p.file_reference_id = :p_file_id) AS has_preview",
+            customer_code
+        );
+
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let mut params = HashMap::new();
+        params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        if !dataset.next() {
+            return Ok(false);
+        }
+        dataset.get_bool("has_preview").ok_or(anyhow!("Wrong has_preview col"))
+    }
+
+    async fn update_preview_flag(
+        &self,
+        file_id: i64,
+        is_preview_generated: bool,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"UPDATE fs_{}.file_reference SET is_preview_generated = :p_is_preview_generated WHERE id = :p_file_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_is_preview_generated".to_string(), CellValue::from_raw_bool(is_preview_generated));
+        params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+        Ok(())
+    }
+
+    fn min_max<T>(map: &HashMap<u32, T>) -> (u32, u32) {
+        let mut min: u32 = u32::MAX;
+        let mut max: u32 = u32::MIN;
+
+        for (index, _) in map {
+            if *index >= max {
+                max = *index;
+            }
+            if *index <= min {
+                min = *index;
+            }
+        }
+        (min, max)
+    }
+
+    /// Store the encrypted file's blocks in the database.
+    /// A block sent again (resumed upload) replaces the previous one.
+    async fn store_group_block(
+        &self,
+        item_info_str: &str,
+        file_ref: &str,
+        block_set: &HashMap<u32, Vec<u8>>,
+        entry_session: &EntrySession,
+    ) -> anyhow::Result<()> {
+        // Open the transaction
+        let block_range = Self::min_max(&block_set);
+
+        log_info!("Block range processing, block range=[{:?}], follower=[{}]", &block_range, &self.follower);
+
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        for (block_num, block) in block_set {
+            log_debug!("Block processing... : block_num=[{}], follower=[{}]", block_num, &self.follower);
+
+            let original_part_size = block.len();
+            // Store in the DB
+            let data = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&block);
+            let sql_query = format!(
+                r"
+            INSERT INTO fs_{}.file_uploads (session_id, start_time_gmt,
+                                      user_id, item_info, file_ref, part_number, original_part_size, part_data)
+            VALUES (:p_session_id, :p_start_time_gmt,
+                    :p_user_id, :p_item_info, :p_file_ref, :p_part_number, :p_original_part_size, :p_part_data)
+            ON CONFLICT (file_ref, part_number) DO UPDATE
+                SET session_id = EXCLUDED.session_id,
+                    start_time_gmt = EXCLUDED.start_time_gmt,
+                    original_part_size = EXCLUDED.original_part_size,
+                    part_data = EXCLUDED.part_data",
+                &entry_session.customer_code
+            );
+
+            let mut params = HashMap::new();
+
+            params.insert("p_session_id".to_string(), CellValue::from_raw_str(&self.follower.token_type.value()));
+            params.insert("p_start_time_gmt".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
+            params.insert("p_user_id".to_string(), CellValue::from_raw_int(entry_session.user_id));
+            params.insert("p_item_info".to_string(), CellValue::from_raw_str(item_info_str));
+            params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+            params.insert("p_part_number".to_string(), CellValue::from_raw_int_32(*block_num as i32));
+            params.insert("p_original_part_size".to_string(), CellValue::from_raw_int(original_part_size as i64));
+            params.insert("p_part_data".to_string(), CellValue::from_raw_string(data));
+
+            let sql_insert = SQLChangeAsync { sql_query, params, sequence_name: "".to_uppercase() };
+
+            let _ = sql_insert
+                .insert_no_pk(&mut trans)
+                .await
+                .map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
+            log_debug!("...Block inserted, block_num=[{}], follower=[{}]", block_num, &self.follower);
+        }
+
+        // End of the 'pink' multiple transaction
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+        log_info!("😎 Committed. Block inserted, block_range=[{:?}], follower=[{}]", &block_range, &self.follower);
+
+        // Slow down the process for 2 seconds
+        // tokio::time::sleep(Duration::from_secs(5)).await;
+
+        Ok(())
+    }
+
+    //
+    // fn _crypto_and_store_block(
+    //     &self,
+    //     file_id: i64,
+    //     block_set: HashMap<u32, Vec<u8>>,
+    //     customer_code: String,
+    //     customer_key: String,
+    // ) -> anyhow::Result<()> {
+    //     // Open the transaction
+    //     let block_range = Self::min_max(&block_set);
+    //
+    //     log_info!(
+    //         "Block range processing, block range=[{:?}], follower=[{}]",
+    //         &block_range,
+    //         &self.follower
+    //     );
+    //
+    //     let mut r_cnx = SQLConnection2::from_pool().await;
+    //     let mut trans = open_transaction(&mut r_cnx).map_err(err_fwd!(
+    //         "Open transaction error, block_range=[{:?}], follower=[{}]",
+    //         &block_range,
+    //         &self.follower
+    //     ))?;
+    //
+    //     for (block_num, block) in block_set {
+    //         log_debug!(
+    //             "Block processing... : block_num=[{}], follower=[{}]",
+    //             block_num,
+    //             &self.follower
+    //         );
+    //
+    //         // Encrypt the block
+    //         let encrypted_block =
+    //             DkEncrypt::new(CC20).encrypt_vec(&block, &customer_key).map_err(err_fwd!(
+    //                 "Cannot encrypt the data block, follower=[{}]",
+    //                 &self.follower
+    //             ))?;
+    //
+    //         // and store in the DB
+    //
+    //         let data = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(encrypted_block);
+    //         // let data = encrypted_block.to_base64(URL_SAFE);
+    //
+    //         let sql_query = format!(
+    //             r"
+    //                 INSERT INTO fs_{}.file_parts (file_reference_id, part_number, part_data)
+    //                 VALUES (:p_file_reference_id, :p_part_number, :p_part_data)",
+    //             customer_code
+    //         );
+    //
+    //         let sequence_name = format!("fs_{}.file_parts_id_seq", customer_code);
+    //
+    //         let mut params = HashMap::new();
+    //         params.insert(
+    //             "p_file_reference_id".to_string(),
+    //             CellValue::from_raw_int(file_id),
+    //         );
+    //         params.insert(
+    //             "p_part_number".to_string(),
+    //             CellValue::from_raw_int_32(block_num as i32),
+    //         );
+    //         params.insert("p_part_data".to_string(), CellValue::from_raw_string(data));
+    //
+    //         let sql_insert = SQLChange {
+    //             sql_query,
+    //             params,
+    //             sequence_name,
+    //         };
+    //
+    //         let _file_part_id = sql_insert
+    //             .insert(&mut trans)
+    //             .map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
+    //
+    //         log_debug!(
+    //             "...Block inserted, block_num=[{}], follower=[{}]",
+    //             block_num,
+    //             &self.follower
+    //         );
+    //     }
+    //
+    //     trans
+    //         .commit()
+    //         .map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+    //
+    //     log_info!(
+    //         "😎 Committed. Block inserted, block_range=[{:?}], follower=[{}]",
+    //         &block_range,
+    //         &self.follower
+    //     );
+    //
+    //     Ok(())
+    // }
+
+    fn find_document_server_client() -> anyhow::Result<DocumentServerClientAsync> {
+        let document_server_host = get_prop_value(DOCUMENT_SERVER_HOSTNAME_PROPERTY)?;
+        let document_server_port = get_prop_value(DOCUMENT_SERVER_PORT_PROPERTY)?.parse::<u16>()?;
+        Ok(DocumentServerClientAsync::new(&document_server_host, document_server_port))
+    }
+
+    /// Call the tika server to parse the file and get the text data
+    /// Insert the metadata
+    /// Call the document server to fulltext parse the text data
+    /// Project the metadata onto the tags of the items of the file
+    /// return the media type
+    async fn analyse_entire_content(
+        &self,
+        file_ref: &str,
+        mem_file: &Vec<u8>,
+        customer_code: &str,
+    ) -> anyhow::Result<String> {
+        log_info!("Parsing file content ... ,file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+
+        let tika_server_host = get_prop_value(TIKA_SERVER_HOSTNAME_PROPERTY)?;
+        let tika_server_port = get_prop_value(TIKA_SERVER_PORT_PROPERTY)?.parse::<u16>()?;
+
+        // Get the raw text from the original file
+        let tsc = TikaServerClientAsync::new(&tika_server_host, tika_server_port);
+        let raw_json = tsc.parse_data_json(mem_file).await.map_err(err_fwd!("Cannot parse the original file"))?;
+        let x_tika_content = raw_json[TIKA_CONTENT_META].as_str().ok_or(anyhow!("Bad tika content"))?;
+        let content_type = raw_json[CONTENT_TYPE_META].as_str().ok_or(anyhow!("Bad content type"))?;
+
+        let metadata = match raw_json.as_object() {
+            Some(obj) => obj.clone(),
+            None => {
+                return Err(anyhow!("Bad metadata"));
+            }
+        };
+
+        log_info!(
+            "Parsing done for file_ref=[{}], content size=[{}], content type=[{}], follower=[{}]",
+            file_ref,
+            x_tika_content.len(),
+            &content_type,
+            &self.follower
+        );
+
+        let list_of_metadata = extract_metadata(&metadata);
+        self.insert_metadata(customer_code, file_ref, &list_of_metadata).await?;
+
+        log_info!(
+            "Metadata done for file_ref=[{}], count=[{}], follower=[{}]",
+            file_ref,
+            list_of_metadata.len(),
+            &self.follower
+        );
+
+        let document_server =
+            Self::find_document_server_client().map_err(err_fwd!("Cannot find the document server"))?;
+
+        // TODO we must also pass the  self.follower.x_request_id + handle the file name
+        let wr_reply = document_server
+            .fulltext_indexing(&x_tika_content, "no_filename_for_now", file_ref, &self.follower.token_type.value())
+            .await;
+        match wr_reply {
+            Ok(reply) => {
+                log_info!(
+                    "Fulltext indexing done, number of text parts=[{}], follower=[{}]",
+                    reply.part_count,
+                    &self.follower
+                );
+                self.set_file_reference_fulltext_indicator(file_ref, true, customer_code).await.map_err(err_fwd!(
+                    "Cannot set the file reference to fulltext parsed indicator, follower=[{}]",
+                    &self.follower
+                ))?;
+            }
+            Err(e) => {
+                log_error!("Error while sending the raw text to the fulltext indexing, file_ref=[{}], reply=[{:?}], follower=[{}], ",  file_ref, e, &self.follower);
+                return Err(anyhow::anyhow!(e.message));
+            }
+        }
+
+        self.project_metadata_on_tags(file_ref, &list_of_metadata).await;
+
+        log_info!("... End of parse file content processing, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+        Ok(content_type.to_owned())
+    }
+
+    ///
+    /// Set the metadata selected by the "fs.metadata.tags" property as tags of the items of the file.
+    /// The items linked to the file later get their tags on the reindexing of the file.
+    /// A failure does not stop the processing of the file, it's only logged
+    ///
+    async fn project_metadata_on_tags(&self, file_ref: &str, list_of_metadata: &[FileMetadata]) {
+        let Ok(projection) = get_prop_value(METADATA_TAGS_PROPERTY) else {
+            return;
+        };
+        let properties = project_on_tags(list_of_metadata, &parse_tag_projection(&projection));
+        if properties.is_empty() {
+            return;
+        }
+
+        let document_server = match Self::find_document_server_client() {
+            Ok(document_server) => document_server,
+            Err(e) => {
+                log_warn!("⛔ Cannot find the document server, e=[{}], follower=[{}]", e, &self.follower);
+                return;
+            }
+        };
+
+        let request = AddItemTagRequest { properties };
+        match document_server.update_file_item_tags(file_ref, &request, &self.follower.token_type.value()).await {
+            Ok(_) => {
+                log_info!(
+                    "😎 Projected the metadata onto the item tags, file_ref=[{}], tag count=[{}], follower=[{}]",
+                    file_ref,
+                    request.properties.len(),
+                    &self.follower
+                );
+            }
+            Err(e) => {
+                log_warn!(
+                    "⛔ Cannot project the metadata onto the item tags, file_ref=[{}], e=[{}], follower=[{}]",
+                    file_ref,
+                    e.message,
+                    &self.follower
+                );
+            }
+        }
+    }
+
+    ///
+    /// Insert the metadata of the file, by batches of METADATA_BATCH_SIZE rows.
+    /// The typed values are also stored in their own column, to be searchable
+    ///
+    async fn insert_metadata(
+        &self,
+        customer_code: &str,
+        file_ref: &str,
+        list_of_metadata: &[FileMetadata],
+    ) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let list_of_metadata: Vec<&FileMetadata> = list_of_metadata
+            .iter()
+            .filter(|metadata| {
+                let is_valid = metadata.key.chars().count() <= META_KEY_MAX_LENGTH;
+                if !is_valid {
+                    log_warn!(
+                        "⛔ The metadata key is too long, it's ignored, file_ref=[{}], key=[{}], follower=[{}]",
+                        file_ref,
+                        &metadata.key,
+                        &self.follower
+                    );
+                }
+                is_valid
+            })
+            .collect();
+
+        for batch in list_of_metadata.chunks(METADATA_BATCH_SIZE) {
+            let mut params: HashMap<String, CellValue> = HashMap::new();
+            params.insert("p_file_ref".to_owned(), CellValue::from_raw_str(file_ref));
+
+            // Fixed width suffixes, so a parameter name is never the prefix of another one
+            let mut rows = Vec::with_capacity(batch.len());
+            for (index, metadata) in batch.iter().enumerate() {
+                let (value_type, value_integer, value_datetime) = match &metadata.value {
+                    EnumTagValue::Integer(i) => (TagType::Int, *i, None),
+                    EnumTagValue::DateTime(Some(dt)) => (
+                        TagType::DateTime,
+                        None,
+                        Some(SystemTime::from(DateTime::parse_from_rfc3339(dt).map_err(tr_fwd!())?)),
+                    ),
+                    _ => (TagType::Text, None, None),
+                };
+                params.insert(format!("p_key_{:04}", index), CellValue::from_raw_str(&metadata.key));
+                params.insert(format!("p_value_{:04}", index), CellValue::from_raw_string(metadata.value.to_string()));
+                params.insert(format!("p_type_{:04}", index), CellValue::from_raw_str(value_type.as_str()));
+                params.insert(format!("p_int_{:04}", index), CellValue::Int(value_integer));
+                params.insert(format!("p_dt_{:04}", index), CellValue::SystemTime(value_datetime));
+                rows.push(format!(
+                    "(:p_key_{0:04}, :p_value_{0:04}, :p_type_{0:04}, CAST(:p_int_{0:04} AS int8), CAST(:p_dt_{0:04} AS timestamp))",
+                    index
+                ));
+            }
+
+            let sql_query = format!(
+                r"INSERT INTO fs_{0}.file_metadata ( file_reference_id, meta_key, value, value_type, value_integer, value_datetime )
+                SELECT fr.id, v.meta_key, v.value, v.value_type, v.value_integer, v.value_datetime
+                FROM fs_{0}.file_reference fr,
+                    ( VALUES {1} ) AS v ( meta_key, value, value_type, value_integer, value_datetime )
+                WHERE fr.file_ref = :p_file_ref",
+                customer_code,
+                rows.join(", ")
+            );
+
+            let sql_insert = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+            sql_insert.insert_no_pk(&mut trans).await.map_err(err_fwd!(
+                "💣 Cannot insert the metadata, file_ref=[{}], follower=[{}]",
+                file_ref,
+                &self.follower
+            ))?;
+        }
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        log_info!(
+            "Success inserting the metadata, file_ref=[{}], count=[{}], follower=[{}]",
+            file_ref,
+            list_of_metadata.len(),
+            &self.follower
+        );
+        Ok(())
+    }
+
+    //
+    async fn set_file_reference_fulltext_indicator(
+        &self,
+        file_ref: &str,
+        is_fulltext_parsed: bool,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"UPDATE fs_{}.file_reference
+                SET is_fulltext_parsed = :p_is_fulltext_parsed
+                WHERE file_ref = :p_file_ref ",
+            customer_code
+        );
+
+        let sequence_name = format!("fs_{}.file_reference_id_seq", customer_code);
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_string(file_ref.to_string()));
+        params.insert("p_is_fulltext_parsed".to_string(), CellValue::from_raw_bool(is_fulltext_parsed));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name };
+
+        let _ =
+            sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        log_info!(
+            "😎 Committed. Successfully set the full text indicator, file_ref=[{:?}], is_fulltext_parsed=[{}], follower=[{}]",
+            file_ref,
+            is_fulltext_parsed,
+            &self.follower
+        );
+
+        Ok(())
+    }
+
+    fn web_type_error<T>() -> impl Fn(&ApiError<'static>) -> WebType<T>
+    where
+        T: DeserializeOwned,
+    {
+        |e| {
+            log_error!("💣 Error after try {:?}", e);
+            WebType::from_api_error(e)
+        }
+    }
+
+    ///
+    /// 🌟 Get the information about the composition of a file [file_ref]
+    ///
+    pub async fn file_info(&mut self, file_ref: &str) -> WebType<Option<GetFileInfoReply>> {
+        log_info!("🚀 Start file_info api, follower=[{}]", &self.follower);
+        // Check if the token is valid
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+
+        let customer_code = entry_session.customer_code.as_str();
+
+        let mut files = try_or_return!(
+            self.fetch_files_information(file_ref, &FileListQuery::default(), customer_code).await,
+            Self::web_type_error()
+        );
+
+        let web_type = if !files.list_of_files.is_empty() {
+            let item = files.list_of_files.remove(0);
+            WebType::from_item(StatusCode::OK.as_u16(), Some(item))
+        } else {
+            WebType::from_item(StatusCode::OK.as_u16(), None)
+        };
+
+        log_info!("🏁 End file_info api, follower=[{}]", &self.follower);
+        web_type
+    }
+
+    /// 🌟 Find the files in the system
+    ///
+    /// The [match_expression] is a glob pattern on the file_ref, * matches any sequence of chars and ? a single one.
+    /// The [list_query] filters, sorts and pages the files
+    pub async fn file_list(
+        &mut self,
+        match_expression: &str,
+        list_query: &FileListQuery,
+    ) -> WebType<ListOfFileInfoReply> {
+        log_info!(
+            "🚀 Start file_list api, match_expression=[{}], list_query=[{:?}], follower=[{}]",
+            match_expression,
+            list_query,
+            &self.follower
+        );
+
+        let entry_session = try_or_return!(valid_sid_get_session(&self.session_token, &mut self.follower).await, |e| {
+            WebType::from_api_error(e)
+        });
+
+        log_info!(
+            "😎 We read the session information, customer_code=[{}], user_id=[{}], follower=[{}]",
+            &entry_session.customer_code,
+            &entry_session.user_id,
+            &self.follower
+        );
+
+        let r_files = self.fetch_files_information(match_expression, list_query, &entry_session.customer_code).await;
+
+        log_info!("🏁 End file_list api, follower=[{}]", &self.follower);
+
+        match r_files {
+            Ok(files) => WebType::from_item(StatusCode::OK.as_u16(), files),
+            Err(e) => WebType::from_api_error(e),
+        }
+    }
+
+    fn is_valid_pattern(s: &str) -> bool {
+        s.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '*' || c == '?')
+    }
+
+    /// SQL LIKE form of a glob pattern, the LIKE wildcards of the pattern are escaped
+    fn glob_to_like(pattern: &str) -> String {
+        let mut sql_pattern = String::with_capacity(pattern.len());
+        for c in pattern.chars() {
+            match c {
+                '*' => sql_pattern.push('%'),
+                '?' => sql_pattern.push('_'),
+                '%' | '_' | '\\' => {
+                    sql_pattern.push('\\');
+                    sql_pattern.push(c);
+                }
+                _ => sql_pattern.push(c),
+            }
+        }
+        sql_pattern
+    }
+
+    /// ORDER BY clause of the file listing, None if the sort criteria are unknown
+    fn file_list_order_by(list_query: &FileListQuery) -> Option<String> {
+        let direction = match list_query.sort_order.as_deref() {
+            None | Some("asc") => "ASC",
+            Some("desc") => "DESC",
+            Some(_) => return None,
+        };
+        let column = match list_query.sort_by.as_deref() {
+            None | Some("file_ref") => return Some(format!("fr.file_ref {}", direction)),
+            Some("size") => "fr.original_file_size",
+            Some("date") => "fr.created_gmt",
+            Some(_) => return None,
+        };
+        // The file_ref keeps the same order from one page to the other
+        Some(format!("{0} {1} NULLS LAST, fr.file_ref {1}", column, direction))
+    }
+
+    /// WHERE clause of the file listing, with its parameters
+    fn file_list_conditions(
+        customer_code: &str,
+        sql_pattern: &str,
+        list_query: &FileListQuery,
+    ) -> (String, HashMap<String, CellValue>) {
+        // The previews are file references of their own, they are not listed
+        let preview_filter =
+            format!("NOT EXISTS (SELECT 1 FROM fs_{}.preview p WHERE p.file_identifier = fr.file_ref)", customer_code);
+        let mut conditions = vec!["fr.file_ref LIKE :p_file_reference", &preview_filter];
+        let mut params = HashMap::new();
+        params.insert("p_file_reference".to_string(), CellValue::from_raw_str(sql_pattern));
+
+        if let Some(media_type) = &list_query.media_type {
+            conditions.push("fr.mime_type LIKE :p_media_type");
+            params.insert("p_media_type".to_string(), CellValue::from_raw_string(Self::glob_to_like(media_type)));
+        }
+        if let Some(is_encrypted) = list_query.is_encrypted {
+            conditions.push("fr.is_encrypted = :p_is_encrypted");
+            params.insert("p_is_encrypted".to_string(), CellValue::from_raw_bool(is_encrypted));
+        }
+        if let Some(is_fulltext_parsed) = list_query.is_fulltext_parsed {
+            conditions.push("COALESCE(fr.is_fulltext_parsed, false) = :p_is_fulltext_parsed");
+            params.insert("p_is_fulltext_parsed".to_string(), CellValue::from_raw_bool(is_fulltext_parsed));
+        }
+
+        (conditions.join(" AND "), params)
+    }
+
+    /// Page of the files matching the criteria, with the total number of matching files
+    async fn query_file_reference(
+        customer_code: &str,
+        sql_pattern: &str,
+        list_query: &FileListQuery,
+        order_by: &str,
+        follower: &Follower,
+    ) -> anyhow::Result<(SQLDataSet, i64)> {
+        let (conditions, params) = Self::file_list_conditions(customer_code, sql_pattern, list_query);
+
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_count =
+            format!(r"SELECT COUNT(*) total_count FROM fs_{0}.file_reference fr WHERE {1}", customer_code, &conditions);
+        let query = SQLQueryBlockAsync { sql_query: sql_count, start: 0, length: None, params: params.clone() };
+        let mut count_set = query.execute(&mut trans).await?;
+        let total_count = if count_set.next() { count_set.get_int("total_count").unwrap_or(0) } else { 0 };
+
+        let sql_query = format!(
+            r"SELECT
+                    fr.file_ref,
+                    fr.mime_type,
+                    fr.checksum,
+                    fr.total_part,
+                    fr.original_file_size,
+                    fr.encrypted_file_size,
+                    fr.is_encrypted,
+                    fr.is_fulltext_parsed,
+                    fr.is_preview_generated,
+                    fr.processing_status,
+                    fr.failure_reason,
+                    fr.created_gmt
+                FROM  fs_{0}.file_reference fr
+                WHERE {1}
+                ORDER BY {2}",
+            customer_code, &conditions, order_by
+        );
+
+        let page_size = list_query.page_size;
+        let start = list_query.start_page.unwrap_or(0) * page_size.unwrap_or(0);
+        let query = SQLQueryBlockAsync { sql_query, start, length: page_size, params };
+        let dataset = query.execute(&mut trans).await?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &follower))?;
+        Ok((dataset, total_count))
+    }
+
+    /// Inner function
+    async fn build_file_reference_block_info(data_set: &mut SQLDataSet) -> anyhow::Result<GetFileInfoReply> {
+        let file_ref = data_set.get_string("file_ref").ok_or(anyhow!("Wrong file_ref"))?;
+        let media_type = data_set.get_string("mime_type");
+        let checksum = data_set.get_string("checksum");
+        let original_file_size = data_set.get_int("original_file_size");
+        let encrypted_file_size = data_set.get_int("encrypted_file_size");
+        let total_part = data_set.get_int_32("total_part");
+        // let total_part = None;
+        let is_encrypted = data_set.get_bool("is_encrypted").ok_or(anyhow!("Wrong is_encrypted col"))?;
+        let is_fulltext_parsed = data_set.get_bool("is_fulltext_parsed");
+        let is_preview_generated = data_set.get_bool("is_preview_generated");
+        let processing_status =
+            data_set.get_string("processing_status").ok_or(anyhow!("Wrong processing_status col"))?;
+        let failure_reason = data_set.get_string("failure_reason");
+        let created = data_set.get_timestamp_as_datetime("created_gmt").ok_or(anyhow!("Wrong created_gmt col"))?;
+
+        Ok(GetFileInfoReply {
+            file_ref,
+            media_type,
+            checksum,
+            original_file_size,
+            encrypted_file_size,
+            block_count: total_part,
+            is_encrypted,
+            is_fulltext_parsed,
+            is_preview_generated,
+            processing_status,
+            failure_reason,
+            created: date_time_to_iso(&created),
+        })
+    }
+
+    /// Query the information related to the existing files whose the reference matches the given pattern
+    async fn fetch_files_information(
+        &self,
+        pattern: &str,
+        list_query: &FileListQuery,
+        customer_code: &str,
+    ) -> Result<ListOfFileInfoReply, &ApiError<'static>> {
+        if !Self::is_valid_pattern(&pattern) {
+            return Err(&FILE_INFO_NOT_FOUND);
+        }
+
+        let Some(order_by) = Self::file_list_order_by(list_query) else {
+            log_warn!("⛔ Unknown sort criteria, list_query=[{:?}], follower=[{}]", list_query, &self.follower);
+            return Err(&INVALID_REQUEST);
+        };
+
+        let sql_pattern = Self::glob_to_like(pattern);
+
+        let Ok((mut data_set, total_count)) =
+            Self::query_file_reference(customer_code, &sql_pattern, list_query, &order_by, &self.follower)
+                .await
+                .map_err(err_fwd!("💣 Cannot read the files, follower=[{}]", &self.follower))
+        else {
+            return Err(&INTERNAL_DATABASE_ERROR);
+        };
+
+        let mut files = ListOfFileInfoReply { list_of_files: vec![], total_count };
+
+        while data_set.next() {
+            match Self::build_file_reference_block_info(&mut data_set).await {
+                Ok(block_info) => files.list_of_files.push(block_info),
+                Err(e) => {
+                    log_error!("💣 Error while building file info, e=[{}], follower=[{}]", e, &self.follower);
+                    return Err(&INTERNAL_DATABASE_ERROR);
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// 🌟 Get the information about the files being loaded
+    ///
+    /// Get all the upload information. Only the session id is required to identify the (customer id/user id)
+    ///
+    /// All the current uploads will be extracted for the current user and a then a list of information will be returned
+    ///
+    /// * start_date_time :
+    /// * item_info :  Is a non unique string to make link with the item element during the initial phase of upload (ex: the file name)
+    /// * file_reference :
+    /// * session_number :
+    pub async fn file_loading(&mut self) -> WebType<ListOfUploadInfoReply> {
+        log_info!("🚀 Start file_loading api, follower=[{}]", &self.follower);
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+
+        log_info!(
+            "😎 We read the session information, customer_code=[{}], user_id=[{}], follower=[{}]",
+            &entry_session.customer_code,
+            &entry_session.user_id,
+            &self.follower
+        );
+
+        let sql_query = format!(
+            r"SELECT current_uploads.file_ref,
+                            current_uploads.session_id,
+                            current_uploads.start_time,
+                            current_uploads.item_info,
+                            current_uploads.count_uploaded,
+                            (SELECT total_part FROM fs_{0}.file_reference
+                                               WHERE file_ref = current_uploads.file_ref
+                            ) total_part,
+                            (SELECT processing_status FROM fs_{0}.file_reference
+                                               WHERE file_ref = current_uploads.file_ref
+                            ) processing_status,
+                            (SELECT count(*)
+                                FROM  fs_{0}.file_parts
+                                WHERE file_reference_id = (SELECT COALESCE(parts_reference_id, id) FROM fs_{0}.file_reference
+                                                                    WHERE file_ref = current_uploads.file_ref)
+                                ) count_encrypted
+                        FROM
+                            (SELECT file_ref, session_id,
+                                     MIN(start_time_gmt) start_time,
+                                     user_id, item_info ,
+                                     COUNT(*) count_uploaded
+                             FROM fs_{0}.file_uploads
+                             WHERE user_id = :p_user_id
+                             GROUP BY file_ref, session_id, user_id, item_info ) current_uploads",
+            entry_session.customer_code
+        );
+
+        /// Inner function
+        async fn execute_query(
+            entry_session: &EntrySession,
+            sql_query: String,
+            follower: &Follower,
+        ) -> anyhow::Result<SQLDataSet> {
+            let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+            let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+            let mut params = HashMap::new();
+            params.insert("p_user_id".to_string(), CellValue::from_raw_int(entry_session.user_id));
+
+            let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+
+            let dataset = query.execute(&mut trans).await?;
+            trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", follower))?;
+
+            Ok(dataset)
+        }
+
+        let Ok(mut data_set) = execute_query(&entry_session, sql_query, &self.follower).await else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        // Inner function
+        async fn build_loading_info_item(data_set: &mut SQLDataSet) -> anyhow::Result<UploadInfoReply> {
+            let file_reference = data_set.get_string("file_ref").ok_or(anyhow!("Wrong file_ref col"))?;
+            let session_number = data_set.get_string("session_id").ok_or(anyhow!("Wrong session_id col"))?;
+            let item_info = data_set.get_string("item_info").ok_or(anyhow!("Wrong item_info col"))?;
+            let start_date_time =
+                data_set.get_timestamp_as_datetime("start_time").ok_or(anyhow!("Wrong start_time col"))?;
+            let total_part = data_set.get_int_32("total_part").ok_or(anyhow!("Wrong total_part col"))?;
+            let encrypted_count = data_set.get_int("count_encrypted").ok_or(anyhow!("Wrong count_encrypted col"))?;
+            let uploaded_count = data_set.get_int("count_uploaded").ok_or(anyhow!("Wrong count_uploaded col"))?;
+            let processing_status =
+                data_set.get_string("processing_status").ok_or(anyhow!("Wrong processing_status col"))?;
+
+            let limit = min(session_number.len() - 2, 22);
+            Ok(UploadInfoReply {
+                start_date_time,
+                item_info,
+                file_reference,
+                session_number: format!("{}...", session_number[..limit].to_owned()),
+                encrypted_count,
+                uploaded_count,
+                total_part: total_part as i64,
+                processing_status,
+            })
+        }
+
+        let mut list_of_upload_info: Vec<UploadInfoReply> = vec![];
+        while data_set.next() {
+            let Ok(loading_info_item) = build_loading_info_item(&mut data_set)
+                .await
+                .map_err(err_fwd!("Build loading info item failed, follower=[{}]", &self.follower))
+            else {
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            };
+            list_of_upload_info.push(loading_info_item);
+        }
+
+        log_info!("😎 Successfully read the loading info, follower=[{}]", &self.follower);
+
+        let upload_info = ListOfUploadInfoReply { list_of_upload_info };
+
+        log_info!("🏁 End file_loading api, follower=[{}]", &self.follower);
+        WebType::from_item(StatusCode::OK.as_u16(), upload_info)
+    }
+
+    ///
+    /// 🌟 Get the information about the loading status of the [file_ref]
+    ///
+    pub async fn file_stats(&mut self, file_ref: &str) -> WebType<GetFileInfoShortReply> {
+        log_info!("🚀 Start file_stats api, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+
+        // Check if the token is valid
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+
+        // TODO instead of constant 1, check if the document is fulltext parsed
+        // The blocks in file_parts are encrypted, so the number of bloccks is the same as the number of encrypted blocks
+        let sql_query = format!(
+            r" SELECT
+                fr.mime_type, fr.checksum, fr.original_file_size, fr.total_part, 1 fulltext,
+                fr.processing_status, fr.failure_reason,
+                CASE WHEN fr.is_preview_generated THEN 1 ELSE 0 END preview,
+                (SELECT count(*)
+                FROM  fs_{0}.file_parts
+                WHERE file_reference_id = COALESCE(fr.parts_reference_id, fr.id)
+                ) parts_count,
+
+                (SELECT  count(*) from fs_{0}.file_uploads WHERE file_ref = :p_file_ref) count_uploaded
+            FROM fs_{0}.file_reference fr
+            WHERE file_ref = :p_file_ref",
+            customer_code
+        );
+
+        /// Inner function
+        async fn local_execute_query(
+            sql_query: &str,
+            file_ref: &str,
+            follower: &Follower,
+        ) -> anyhow::Result<SQLDataSet> {
+            let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+            let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+            let mut params = HashMap::new();
+            params.insert("p_file_ref".to_string(), CellValue::from_raw_string(file_ref.to_string()));
+
+            let query = SQLQueryBlockAsync { sql_query: sql_query.to_string(), start: 0, length: None, params };
+
+            let dataset = query.execute(&mut trans).await?;
+            trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &follower))?;
+
+            Ok(dataset)
+        }
+
+        let Ok(mut data_set) = local_execute_query(&sql_query, &file_ref, &self.follower).await else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        // Inner function
+        async fn build_file_info(data_set: &mut SQLDataSet, file_ref: &str) -> anyhow::Result<GetFileInfoShortReply> {
+            let _mime_type = data_set.get_string("mime_type").unwrap_or("".to_string()); // optional
+            let _checksum = data_set.get_string("checksum").unwrap_or("".to_string()); // optional
+            let original_file_size = data_set.get_int("original_file_size")/*.ok_or(anyhow!("Wrong original_file_size col"))?*/;
+            let total_part = data_set.get_int_32("total_part").ok_or(anyhow!("Wrong total_part col"))?;
+
+            let parts_count = data_set.get_int("parts_count").ok_or(anyhow!("Wrong count_encrypted col"))?;
+            let uploaded_count = data_set.get_int("count_uploaded").ok_or(anyhow!("Wrong count_uploaded col"))?;
+            let fulltext_indexed_count = data_set.get_int_32("fulltext").ok_or(anyhow!("Wrong fulltext col"))?;
+            let preview_generated_count = data_set.get_int_32("preview").ok_or(anyhow!("Wrong preview col"))?;
+            let processing_status =
+                data_set.get_string("processing_status").ok_or(anyhow!("Wrong processing_status col"))?;
+            let failure_reason = data_set.get_string("failure_reason");
+
+            Ok(GetFileInfoShortReply {
+                file_ref: file_ref.to_string(),
+                block_count: total_part as u32,
+                original_file_size: original_file_size.unwrap_or(0i64) as u64,
+                encrypted_count: parts_count,
+                uploaded_count,
+                fulltext_indexed_count: fulltext_indexed_count as i64,
+                preview_generated_count: preview_generated_count as i64,
+                processing_status,
+                failure_reason,
+            })
+        }
+
+        let wt_stats = if data_set.next() {
+            let Ok(stats) = build_file_info(&mut data_set, file_ref)
+                .await
+                .map_err(err_fwd!("Build file info failed, follower=[{}]", &self.follower))
+            else {
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            };
+
+            log_info!("😎 Successfully read the file stats, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+            WebType::from_item(StatusCode::OK.as_u16(), stats)
+        } else {
+            log_info!("⛔ Cannot find the file stats, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+            WebType::from_api_error(&INTERNAL_TECHNICAL_ERROR)
+        };
+
+        log_info!("🏁 End file_stats api, follower=[{}]", &self.follower);
+        wt_stats
+    }
+
+    ///
+    /// 🌟 Get the processing status of the [file_ref], with the reason of the failure if any
+    ///
+    pub async fn file_status(&mut self, file_ref: &str) -> WebType<FileStatusReply> {
+        log_info!("🚀 Start file_status api, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+
+        let wt_status = match self.search_file_status(file_ref, customer_code).await {
+            Ok(Some(file_status)) => {
+                log_info!(
+                    "😎 Successfully read the file status, file_ref=[{}], status=[{}], follower=[{}]",
+                    file_ref,
+                    &file_status.status,
+                    &self.follower
+                );
+                WebType::from_item(StatusCode::OK.as_u16(), file_status)
+            }
+            Ok(None) => {
+                log_warn!("⛔ Cannot find the file status, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+                WebType::from_api_error(&FILE_INFO_NOT_FOUND)
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the file status, e=[{}], follower=[{}]", e, &self.follower);
+                WebType::from_api_error(&INTERNAL_DATABASE_ERROR)
+            }
+        };
+
+        log_info!("🏁 End file_status api, follower=[{}]", &self.follower);
+        wt_status
+    }
+
+    async fn search_file_status(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<Option<FileStatusReply>> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"SELECT processing_status, failure_reason, status_gmt
+                FROM fs_{}.file_reference
+                WHERE file_ref = :p_file_ref",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        if !dataset.next() {
+            return Ok(None);
+        }
+
+        let status = dataset.get_string("processing_status").ok_or(anyhow!("Wrong processing_status col"))?;
+        let failure_reason = dataset.get_string("failure_reason");
+        let status_date_time =
+            dataset.get_timestamp_as_datetime("status_gmt").ok_or(anyhow!("Wrong status_gmt col"))?;
 
-        // TODO Could be done with a specific batch insert sql routine that will build a big insert statement!
-        for (key, value) in metadata.iter() {
-            if key != TIKA_CONTENT_META && value.to_string().len() < 200 {
-                let mut params: HashMap<String, CellValue> = HashMap::new();
-                params.insert("p_file_ref".to_owned(), CellValue::from_raw_str(file_ref));
-                params.insert("p_meta_key".to_owned(), CellValue::from_raw_str(key));
-                params.insert("p_value".to_owned(), CellValue::from_raw_string(value.to_string()));
+        Ok(Some(FileStatusReply { file_ref: file_ref.to_string(), status, failure_reason, status_date_time }))
+    }
 
-                let sql_insert =
-                    SQLChangeAsync { sql_query: sql_query.clone(), params, sequence_name: sequence_name.clone() };
-                let meta_id = sql_insert.insert(&mut trans).await.map_err(err_fwd!(
-                    "💣 Cannot insert the metadata, file_ref=[{}], key=[{}], value=[{}], follower=[{}]",
-                    file_ref,
-                    key,
-                    value.to_string(),
-                    &self.follower
-                ))?;
+    ///
+    /// 🌟 Get the metadata of the [file_ref], the ones found by Tika and the normalized ones
+    ///
+    pub async fn file_metadata(&mut self, file_ref: &str) -> WebType<FileMetadataReply> {
+        log_info!("🚀 Start file_metadata api, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
 
+        let wt_metadata = match self.search_file_metadata(file_ref, customer_code).await {
+            Ok(Some(list_of_metadata)) => {
                 log_info!(
-                    "Success inserting meta meta_id=[{}], file_ref=[{}], key=[{}], value=[{}], follower=[{}]",
-                    meta_id,
+                    "😎 Successfully read the file metadata, file_ref=[{}], count=[{}], follower=[{}]",
                     file_ref,
-                    key,
-                    value.to_string(),
+                    list_of_metadata.len(),
                     &self.follower
                 );
+                WebType::from_item(
+                    StatusCode::OK.as_u16(),
+                    FileMetadataReply { file_ref: file_ref.to_string(), list_of_metadata },
+                )
             }
-        }
-
-        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+            Ok(None) => {
+                log_warn!("⛔ Cannot find the file, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+                WebType::from_api_error(&FILE_INFO_NOT_FOUND)
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the file metadata, e=[{}], follower=[{}]", e, &self.follower);
+                WebType::from_api_error(&INTERNAL_DATABASE_ERROR)
+            }
+        };
 
-        Ok(())
+        log_info!("🏁 End file_metadata api, follower=[{}]", &self.follower);
+        wt_metadata
     }
 
-    //
-    async fn set_file_reference_fulltext_indicator(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<()> {
+    /// Metadata of the file, None if the file does not exist
+    async fn search_file_metadata(
+        &self,
+        file_ref: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<Option<Vec<MetadataElement>>> {
         let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
         let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
 
         let sql_query = format!(
-            r"UPDATE fs_{}.file_reference
-                SET is_fulltext_parsed = true
-                WHERE file_ref = :p_file_ref ",
+            r"SELECT fm.meta_key, fm.value, fm.value_type
+                FROM fs_{0}.file_reference fr
+                    LEFT OUTER JOIN fs_{0}.file_metadata fm ON fm.file_reference_id = fr.id
+                WHERE fr.file_ref = :p_file_ref
+                ORDER BY fm.meta_key",
             customer_code
         );
 
-        let sequence_name = format!("fs_{}.file_reference_id_seq", customer_code);
-
         let mut params = HashMap::new();
-        params.insert("p_file_ref".to_string(), CellValue::from_raw_string(file_ref.to_string()));
-
-        let sql_update = SQLChangeAsync { sql_query, params, sequence_name };
-
-        let _ =
-            sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
 
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
         trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
 
-        log_info!(
-            "😎 Committed. Successfully set the full text indicator, file_ref=[{:?}], follower=[{}]",
-            file_ref,
-            &self.follower
-        );
-
-        Ok(())
-    }
+        if dataset.len() == 0 {
+            return Ok(None);
+        }
 
-    fn web_type_error<T>() -> impl Fn(&ApiError<'static>) -> WebType<T>
-    where
-        T: DeserializeOwned,
-    {
-        |e| {
-            log_error!("💣 Error after try {:?}", e);
-            WebType::from_api_error(e)
+        let mut list_of_metadata = vec![];
+        while dataset.next() {
+            // No metadata for the file
+            let Some(key) = dataset.get_string("meta_key") else {
+                continue;
+            };
+            let value = dataset.get_string("value").unwrap_or_default();
+            let value_type = dataset.get_string("value_type").ok_or(anyhow!("Wrong value_type col"))?;
+            let value = EnumTagValue::from_string(&value, &value_type).map_err(|e| anyhow!(e))?;
+            list_of_metadata.push(MetadataElement { key, value });
         }
+        Ok(Some(list_of_metadata))
     }
 
     ///
-    /// 🌟 Get the information about the composition of a file [file_ref]
+    /// 🌟 Delete the file [file_ref] with its metadata and its previews.
+    /// Its parts are removed, or kept for the other files which share them
     ///
-    pub async fn file_info(&mut self, file_ref: &str) -> WebType<Option<GetFileInfoReply>> {
-        log_info!("🚀 Start file_info api, follower=[{}]", &self.follower);
-        // Check if the token is valid
+    pub async fn delete_file(&mut self, file_ref: &str) -> WebType<SimpleMessage> {
+        log_info!("🚀 Start delete_file api, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
 
         let entry_session = try_or_return!(
             valid_sid_get_session(&self.session_token, &mut self.follower).await,
             Self::web_type_error()
         );
-
         let customer_code = entry_session.customer_code.as_str();
 
-        let mut files =
-            try_or_return!(self.fetch_files_information(file_ref, &customer_code).await, Self::web_type_error());
-
-        let web_type = if !files.list_of_files.is_empty() {
-            let item = files.list_of_files.remove(0);
-            WebType::from_item(StatusCode::OK.as_u16(), Some(item))
-        } else {
-            WebType::from_item(StatusCode::OK.as_u16(), None)
+        let file_id = match self.search_file_to_delete(file_ref, customer_code).await {
+            Ok(Some(file_id)) => file_id,
+            Ok(None) => {
+                log_warn!("⛔ Cannot find the file, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+                return WebType::from_api_error(&FILE_INFO_NOT_FOUND);
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the file, e=[{}], follower=[{}]", e, &self.follower);
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
         };
 
-        log_info!("🏁 End file_info api, follower=[{}]", &self.follower);
-        web_type
-    }
-
-    /// 🌟 Find the files in the system
-    pub async fn file_list(&mut self, match_expression: &str) -> WebType<ListOfFileInfoReply> {
-        log_info!("🚀 Start file_list api, follower=[{}]", &self.follower);
-
-        let entry_session = try_or_return!(valid_sid_get_session(&self.session_token, &mut self.follower).await, |e| {
-            WebType::from_api_error(e)
-        });
+        let removed_file = match remove_file(customer_code, file_id).await {
+            Ok(removed_file) => removed_file,
+            Err(e) => {
+                log_error!(
+                    "💣 Cannot delete the file, file_id=[{}], e=[{}], follower=[{}]",
+                    file_id,
+                    e,
+                    &self.follower
+                );
+                return WebType::from_api_error(&INTERNAL_TECHNICAL_ERROR);
+            }
+        };
 
         log_info!(
-            "😎 We read the session information, customer_code=[{}], user_id=[{}], follower=[{}]",
-            &entry_session.customer_code,
-            &entry_session.user_id,
+            "😎 Deleted the file, file_ref=[{}], preview count=[{}], part count=[{}], follower=[{}]",
+            file_ref,
+            removed_file.file_count - 1,
+            removed_file.part_count,
             &self.follower
         );
-
-        let r_files = self.fetch_files_information(&match_expression, &entry_session.customer_code).await;
-
-        log_info!("🏁 End file_list api, follower=[{}]", &self.follower);
-
-        match r_files {
-            Ok(files) => WebType::from_item(StatusCode::OK.as_u16(), files),
-            Err(e) => WebType::from_api_error(e),
-        }
-    }
-
-    fn is_valid_pattern(s: &str) -> bool {
-        s.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '*')
+        log_info!("🏁 End delete_file api, follower=[{}]", &self.follower);
+        WebType::from_item(StatusCode::OK.as_u16(), SimpleMessage { message: "Ok".to_string() })
     }
 
-    ///
-    async fn query_file_reference(
-        customer_code: &str,
-        sql_pattern: &str,
-        follower: &Follower,
-    ) -> anyhow::Result<SQLDataSet> {
-        let sql_query = format!(
-            r"SELECT
-                    fr.file_ref,
-                    fr.mime_type,
-                    fr.checksum,
-                    fr.total_part,
-                    fr.original_file_size,
-                    fr.encrypted_file_size,
-                    fr.is_encrypted,
-                    fr.is_fulltext_parsed,
-                    fr.is_preview_generated
-                FROM  fs_{0}.file_reference fr
-                WHERE
-                    fr.file_ref like :p_file_reference",
-            customer_code
-        );
-
+    /// Id of the file reference [file_ref]
+    async fn search_file_to_delete(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<Option<i64>> {
         let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
         let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
-        let mut params = HashMap::new();
-        params.insert("p_file_reference".to_string(), CellValue::from_raw_str(sql_pattern));
 
-        let query = SQLQueryBlockAsync { sql_query: sql_query.to_string(), start: 0, length: None, params };
-        let dataset = query.execute(&mut trans).await?;
-        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &follower))?;
-        Ok(dataset)
-    }
+        let sql_query =
+            format!("SELECT fr.id FROM fs_{}.file_reference fr WHERE fr.file_ref = :p_file_ref", customer_code);
 
-    /// Inner function
-    async fn build_file_reference_block_info(data_set: &mut SQLDataSet) -> anyhow::Result<GetFileInfoReply> {
-        let file_ref = data_set.get_string("file_ref").ok_or(anyhow!("Wrong file_ref"))?;
-        let media_type = data_set.get_string("mime_type");
-        let checksum = data_set.get_string("checksum");
-        let original_file_size = data_set.get_int("original_file_size");
-        let encrypted_file_size = data_set.get_int("encrypted_file_size");
-        let total_part = data_set.get_int_32("total_part");
-        // let total_part = None;
-        let is_encrypted = data_set.get_bool("is_encrypted").ok_or(anyhow!("Wrong is_encrypted col"))?;
-        let is_fulltext_parsed = data_set.get_bool("is_fulltext_parsed");
-        let is_preview_generated = data_set.get_bool("is_preview_generated");
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
 
-        Ok(GetFileInfoReply {
-            file_ref,
-            media_type,
-            checksum,
-            original_file_size,
-            encrypted_file_size,
-            block_count: total_part,
-            is_encrypted,
-            is_fulltext_parsed,
-            is_preview_generated,
-        })
-    }
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
 
-    /// Query the information related to the existing files whose the reference matches the given pattern
-    async fn fetch_files_information(
-        &self,
-        pattern: &str,
-        customer_code: &str,
-    ) -> Result<ListOfFileInfoReply, &ApiError<'static>> {
-        if !Self::is_valid_pattern(&pattern) {
-            return Err(&FILE_INFO_NOT_FOUND);
+        if !dataset.next() {
+            return Ok(None);
         }
+        Ok(Some(dataset.get_int("id").ok_or(anyhow!("Wrong id col"))?))
+    }
 
-        let sql_pattern = pattern.replace('*', "%");
+    ///
+    /// 🌟 File versions of the item [item_id], with the size and the checksum of their files.
+    /// The versions are kept by the document server, the files by the file server
+    ///
+    pub async fn item_versions(&mut self, item_id: i64) -> WebType<ItemVersionReply> {
+        log_info!("🚀 Start item_versions api, item_id=[{}], follower=[{}]", item_id, &self.follower);
 
-        let Ok(mut data_set) = Self::query_file_reference(&customer_code, &sql_pattern, &self.follower).await else {
-            return Err(&INTERNAL_DATABASE_ERROR);
-        };
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
 
-        let mut files = ListOfFileInfoReply { list_of_files: vec![] };
+        let mut reply = match self.fetch_item_versions(item_id).await {
+            Ok(reply) => reply,
+            Err(e) => return WebType::from_api_error(&e),
+        };
 
-        while data_set.next() {
-            match Self::build_file_reference_block_info(&mut data_set).await {
-                Ok(block_info) => files.list_of_files.push(block_info),
+        for version in reply.list_of_versions.iter_mut() {
+            match self.search_version_file(&version.file_ref, customer_code).await {
+                Ok(Some((file_size, checksum))) => {
+                    version.file_size = file_size;
+                    version.checksum = checksum;
+                }
+                Ok(None) => {
+                    log_warn!(
+                        "⛔ The file of the version is not found, file_ref=[{}], follower=[{}]",
+                        &version.file_ref,
+                        &self.follower
+                    );
+                }
                 Err(e) => {
-                    log_error!("💣 Error while building file info, e=[{}], follower=[{}]", e, &self.follower);
-                    return Err(&INTERNAL_DATABASE_ERROR);
+                    log_error!("💣 Cannot read the file of the version, e=[{}], follower=[{}]", e, &self.follower);
+                    return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
                 }
             }
         }
-        Ok(files)
+
+        log_info!("🏁 End item_versions api, follower=[{}]", &self.follower);
+        WebType::from_item(StatusCode::OK.as_u16(), reply)
     }
 
-    /// 🌟 Get the information about the files being loaded
     ///
-    /// Get all the upload information. Only the session id is required to identify the (customer id/user id)
-    ///
-    /// All the current uploads will be extracted for the current user and a then a list of information will be returned
+    /// 🌟 Download the file of the version [version_number] of the item [item_id]
     ///
-    /// * start_date_time :
-    /// * item_info :  Is a non unique string to make link with the item element during the initial phase of upload (ex: the file name)
-    /// * file_reference :
-    /// * session_number :
-    pub async fn file_loading(&mut self) -> WebType<ListOfUploadInfoReply> {
-        log_info!("🚀 Start file_loading api, follower=[{}]", &self.follower);
+    pub async fn download_version(
+        &mut self,
+        item_id: i64,
+        version_number: u32,
+        range: &Option<String>,
+        if_range: &Option<String>,
+    ) -> DownloadReply {
+        log_info!(
+            "🚀 Start download_version api, item_id=[{}], version_number=[{}], follower=[{}]",
+            item_id,
+            version_number,
+            &self.follower
+        );
 
-        let entry_session = try_or_return!(
+        let _ = try_or_return!(
             valid_sid_get_session(&self.session_token, &mut self.follower).await,
-            Self::web_type_error()
+            Self::download_reply_error()
         );
 
-        log_info!(
-            "😎 We read the session information, customer_code=[{}], user_id=[{}], follower=[{}]",
-            &entry_session.customer_code,
-            &entry_session.user_id,
-            &self.follower
-        );
+        let reply = match self.fetch_item_versions(item_id).await {
+            Ok(reply) => reply,
+            Err(e) => return DownloadReply::from_api_error(&e),
+        };
+
+        let Some(version) = reply.list_of_versions.into_iter().find(|version| version.version_number == version_number)
+        else {
+            log_warn!("⛔ Version not found, version_number=[{}], follower=[{}]", version_number, &self.follower);
+            return DownloadReply::from_api_error(&ITEM_VERSION_NOT_FOUND);
+        };
+
+        log_info!("🏁 End download_version api, follower=[{}]", &self.follower);
+        self.download(&version.file_ref, range, if_range).await
+    }
+
+    async fn fetch_item_versions(&self, item_id: i64) -> Result<ItemVersionReply, ApiError<'static>> {
+        let document_server = Self::find_document_server_client().map_err(|e| {
+            log_error!("💣 Cannot find the document server, e=[{}], follower=[{}]", e, &self.follower);
+            INTERNAL_TECHNICAL_ERROR.clone()
+        })?;
+
+        // For now the token is the sid itself
+        document_server.item_versions(item_id, &self.follower.token_type.value()).await.inspect_err(|e| {
+            log_error!("💣 Document Server failed with status [{}], follower=[{}]", e.message, &self.follower);
+        })
+    }
+
+    /// Original size and checksum of the file, None if the file does not exist
+    async fn search_version_file(
+        &self,
+        file_ref: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<Option<(Option<u64>, Option<String>)>> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
 
         let sql_query = format!(
-            r"SELECT current_uploads.file_ref,
-                            current_uploads.session_id,
-                            current_uploads.start_time,
-                            current_uploads.item_info,
-                            current_uploads.count_uploaded,
-                            (SELECT total_part FROM fs_{0}.file_reference
-                                               WHERE file_ref = current_uploads.file_ref
-                            ) total_part,
-                            (SELECT count(*)
-                                FROM  fs_{0}.file_parts
-                                WHERE file_reference_id = (SELECT id FROM fs_{0}.file_reference
-                                                                    WHERE file_ref = current_uploads.file_ref)
-                                ) count_encrypted
-                        FROM
-                            (SELECT file_ref, session_id,
-                                     MIN(start_time_gmt) start_time,
-                                     user_id, item_info ,
-                                     COUNT(*) count_uploaded
-                             FROM fs_{0}.file_uploads
-                             WHERE user_id = :p_user_id
-                             GROUP BY file_ref, session_id, user_id, item_info ) current_uploads",
-            entry_session.customer_code
+            r"SELECT original_file_size, checksum FROM fs_{}.file_reference WHERE file_ref = :p_file_ref",
+            customer_code
         );
 
-        /// Inner function
-        async fn execute_query(
-            entry_session: &EntrySession,
-            sql_query: String,
-            follower: &Follower,
-        ) -> anyhow::Result<SQLDataSet> {
-            let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
-            let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        if !dataset.next() {
+            return Ok(None);
+        }
+
+        let file_size = dataset.get_int("original_file_size").map(|size| size as u64);
+        Ok(Some((file_size, dataset.get_string("checksum"))))
+    }
 
-            let mut params = HashMap::new();
-            params.insert("p_user_id".to_string(), CellValue::from_raw_int(entry_session.user_id));
+    ///
+    /// 🌟 Decrypt the stored file [file_ref], parse it and index its text again, whatever its previous parsing
+    ///
+    pub async fn reindex_file(&mut self, file_ref: &str) -> WebType<ReindexReply> {
+        log_info!("🚀 Start reindex_file api, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
 
-            let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+        let web_type = match self.reindex(file_ref, false).await {
+            Ok(reply) if reply.list_of_files.is_empty() => WebType::from_api_error(&FILE_INFO_NOT_FOUND),
+            Ok(reply) => WebType::from_item(StatusCode::OK.as_u16(), reply),
+            Err(e) => WebType::from_api_error(e),
+        };
 
-            let dataset = query.execute(&mut trans).await?;
-            trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", follower))?;
+        log_info!("🏁 End reindex_file api, follower=[{}]", &self.follower);
+        web_type
+    }
 
-            Ok(dataset)
+    ///
+    /// 🌟 Reindex the stored files matching the [pattern] whose parsing failed
+    ///
+    pub async fn reindex_files(&mut self, pattern: &str) -> WebType<ReindexReply> {
+        log_info!("🚀 Start reindex_files api, pattern=[{}], follower=[{}]", pattern, &self.follower);
+
+        let web_type = match self.reindex(pattern, true).await {
+            Ok(reply) => WebType::from_item(StatusCode::OK.as_u16(), reply),
+            Err(e) => WebType::from_api_error(e),
+        };
+
+        log_info!("🏁 End reindex_files api, follower=[{}]", &self.follower);
+        web_type
+    }
+
+    async fn reindex(
+        &mut self,
+        pattern: &str,
+        only_not_parsed: bool,
+    ) -> Result<ReindexReply, &'static ApiError<'static>> {
+        let entry_session = valid_sid_get_session(&self.session_token, &mut self.follower).await?;
+        let customer_code = entry_session.customer_code.as_str();
+
+        if !Self::is_valid_pattern(pattern) {
+            return Err(&FILE_INFO_NOT_FOUND);
         }
 
-        let Ok(mut data_set) = execute_query(&entry_session, sql_query, &self.follower).await else {
-            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        let Ok(customer_key) = fetch_customer_key(customer_code, &self.follower)
+            .await
+            .map_err(err_fwd!("💣 Cannot get the customer key, follower=[{}]", &self.follower))
+        else {
+            return Err(&INTERNAL_TECHNICAL_ERROR);
         };
 
-        // Inner function
-        async fn build_loading_info_item(data_set: &mut SQLDataSet) -> anyhow::Result<UploadInfoReply> {
-            let file_reference = data_set.get_string("file_ref").ok_or(anyhow!("Wrong file_ref col"))?;
-            let session_number = data_set.get_string("session_id").ok_or(anyhow!("Wrong session_id col"))?;
-            let item_info = data_set.get_string("item_info").ok_or(anyhow!("Wrong item_info col"))?;
-            let start_date_time =
-                data_set.get_timestamp_as_datetime("start_time").ok_or(anyhow!("Wrong start_time col"))?;
-            let total_part = data_set.get_int_32("total_part").ok_or(anyhow!("Wrong total_part col"))?;
-            let encrypted_count = data_set.get_int("count_encrypted").ok_or(anyhow!("Wrong count_encrypted col"))?;
-            let uploaded_count = data_set.get_int("count_uploaded").ok_or(anyhow!("Wrong count_uploaded col"))?;
+        let Ok(files) = self
+            .search_files_to_reindex(&Self::glob_to_like(pattern), only_not_parsed, customer_code)
+            .await
+            .map_err(err_fwd!("💣 Cannot find the files to reindex, follower=[{}]", &self.follower))
+        else {
+            return Err(&INTERNAL_DATABASE_ERROR);
+        };
 
-            let limit = min(session_number.len() - 2, 22);
-            Ok(UploadInfoReply {
-                start_date_time,
-                item_info,
-                file_reference,
-                session_number: format!("{}...", session_number[..limit].to_owned()),
-                encrypted_count,
-                uploaded_count,
-                total_part: total_part as i64,
-            })
+        let mut list_of_files = Vec::with_capacity(files.len());
+        for file in files {
+            // A file in error must not prevent the reindexing of the others
+            let _ = self.reindex_one_file(&file, customer_code, &customer_key).await.map_err(err_fwd!(
+                "💣 Cannot reindex the file, file_ref=[{}], follower=[{}]",
+                &file.file_ref,
+                &self.follower
+            ));
+
+            match self.search_file_status(&file.file_ref, customer_code).await {
+                Ok(Some(file_status)) => list_of_files.push(file_status),
+                _ => return Err(&INTERNAL_DATABASE_ERROR),
+            }
         }
 
-        let mut list_of_upload_info: Vec<UploadInfoReply> = vec![];
-        while data_set.next() {
-            let Ok(loading_info_item) = build_loading_info_item(&mut data_set)
-                .await
-                .map_err(err_fwd!("Build loading info item failed, follower=[{}]", &self.follower))
-            else {
-                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
-            };
-            list_of_upload_info.push(loading_info_item);
+        log_info!("😎 Reindexed the files, count=[{}], follower=[{}]", list_of_files.len(), &self.follower);
+        Ok(ReindexReply { list_of_files })
+    }
+
+    /// Decrypt the parts of the file, parse and index its content again, and update its processing status
+    async fn reindex_one_file(
+        &self,
+        file: &FileToReindex,
+        customer_code: &str,
+        customer_key: &str,
+    ) -> anyhow::Result<()> {
+        let content = self
+            .read_stored_content(file.parts_id, file.total_part, customer_code, customer_key)
+            .await
+            .map_err(tr_fwd!())?;
+        let total_size = content.len();
+
+        // Remove the results of the previous parsing
+        self.delete_from_target_table("file_metadata", file.id, customer_code).await.map_err(tr_fwd!())?;
+        let document_server = Self::find_document_server_client().map_err(tr_fwd!())?;
+        if let Err(e) = document_server.delete_text_indexing(&file.file_ref, &self.follower.token_type.value()).await {
+            log_warn!(
+                "⛔ Cannot delete the previous text indexing, file_ref=[{}], e=[{}], follower=[{}]",
+                &file.file_ref,
+                e.message,
+                &self.follower
+            );
         }
 
-        log_info!("😎 Successfully read the loading info, follower=[{}]", &self.follower);
+        self.update_processing_status(file.id, ProcessingStatus::Parsing, None, customer_code)
+            .await
+            .map_err(tr_fwd!())?;
 
-        let upload_info = ListOfUploadInfoReply { list_of_upload_info };
+        let parsing = self.parse_and_index(file.id, &file.file_ref, &content, customer_code).await;
+        let (status, failure_reason) = match &parsing {
+            Ok((media_type, None)) => {
+                self.update_file_reference(file.id, total_size, file.total_part, media_type, customer_code)
+                    .await
+                    .map_err(tr_fwd!())?;
+                (ProcessingStatus::Indexed, None)
+            }
+            Ok((_, Some(failure_reason))) => (ProcessingStatus::Stored, Some(failure_reason.clone())),
+            Err(e) => (ProcessingStatus::Stored, Some(Self::failure_reason(e))),
+        };
 
-        log_info!("🏁 End file_loading api, follower=[{}]", &self.follower);
-        WebType::from_item(StatusCode::OK.as_u16(), upload_info)
+        self.update_processing_status(file.id, status, failure_reason.as_deref(), customer_code)
+            .await
+            .map_err(tr_fwd!())?;
+
+        log_info!(
+            "😎 Reindexed the file, file_ref=[{}], status=[{}], follower=[{}]",
+            &file.file_ref,
+            status,
+            &self.follower
+        );
+        parsing.map(|_| ())
     }
 
-    ///
-    /// 🌟 Get the information about the loading status of the [file_ref]
-    ///
-    pub async fn file_stats(&mut self, file_ref: &str) -> WebType<GetFileInfoShortReply> {
-        log_info!("🚀 Start file_stats api, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+    /// Clear content of a stored file, from its encrypted parts
+    async fn read_stored_content(
+        &self,
+        parts_id: i64,
+        total_part: u32,
+        customer_code: &str,
+        customer_key: &str,
+    ) -> anyhow::Result<Vec<u8>> {
+        let parts = self.search_part_window(parts_id, customer_code, 0, total_part).await.map_err(tr_fwd!())?;
+        if parts.len() != total_part as usize {
+            return Err(anyhow!("Missing parts, expected=[{}], found=[{}]", total_part, parts.len()));
+        }
 
-        // Check if the token is valid
-        let entry_session = try_or_return!(
-            valid_sid_get_session(&self.session_token, &mut self.follower).await,
-            Self::web_type_error()
-        );
-        let customer_code = entry_session.customer_code.as_str();
+        let mut content: Vec<u8> = vec![];
+        for (part_number, enc_content) in parts {
+            content.extend(Self::decrypt_part(part_number, enc_content, customer_key)?);
+        }
+        Ok(content)
+    }
 
-        // TODO instead of constant 1, check if the document is fulltext parsed and previewed
-        // The blocks in file_parts are encrypted, so the number of bloccks is the same as the number of encrypted blocks
-        let sql_query = format!(
-            r" SELECT
-                fr.mime_type, fr.checksum, fr.original_file_size, fr.total_part, 1 fulltext,  1 preview,
-                (SELECT count(*)
-                FROM  fs_{0}.file_parts
-                WHERE file_reference_id = (SELECT id FROM fs_{0}.file_reference WHERE file_ref = :p_file_ref)
-                ) parts_count,
+    /// Stored files whose reference matches the [sql_pattern], the previews excluded
+    async fn search_files_to_reindex(
+        &self,
+        sql_pattern: &str,
+        only_not_parsed: bool,
+        customer_code: &str,
+    ) -> anyhow::Result<Vec<FileToReindex>> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
 
-                (SELECT  count(*) from fs_{0}.file_uploads WHERE file_ref = :p_file_ref) count_uploaded
-            FROM fs_{0}.file_reference fr
-            WHERE file_ref = :p_file_ref",
-            customer_code
+        let not_parsed_condition = if only_not_parsed { "AND fr.is_fulltext_parsed = false" } else { "" };
+        let sql_query = format!(
+            r"SELECT fr.id, fr.file_ref, COALESCE(fr.parts_reference_id, fr.id) parts_id, fr.total_part
+                FROM fs_{0}.file_reference fr
+                WHERE
+                    fr.file_ref LIKE :p_file_ref AND
+                    fr.processing_status IN ('indexed', 'stored') AND
+                    NOT EXISTS (SELECT 1 FROM fs_{0}.preview p WHERE p.file_identifier = fr.file_ref)
+                    {1}
+                ORDER BY fr.id",
+            customer_code, not_parsed_condition
         );
 
-        /// Inner function
-        async fn local_execute_query(
-            sql_query: &str,
-            file_ref: &str,
-            follower: &Follower,
-        ) -> anyhow::Result<SQLDataSet> {
-            let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
-            let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(sql_pattern));
 
-            let mut params = HashMap::new();
-            params.insert("p_file_ref".to_string(), CellValue::from_raw_string(file_ref.to_string()));
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
 
-            let query = SQLQueryBlockAsync { sql_query: sql_query.to_string(), start: 0, length: None, params };
+        let mut files = Vec::with_capacity(dataset.len());
+        while dataset.next() {
+            files.push(FileToReindex {
+                id: dataset.get_int("id").ok_or(anyhow!("Wrong id col"))?,
+                file_ref: dataset.get_string("file_ref").ok_or(anyhow!("Wrong file_ref col"))?,
+                parts_id: dataset.get_int("parts_id").ok_or(anyhow!("Wrong parts_id col"))?,
+                total_part: dataset.get_int_32("total_part").ok_or(anyhow!("Wrong total_part col"))? as u32,
+            });
+        }
+        Ok(files)
+    }
 
-            let dataset = query.execute(&mut trans).await?;
-            trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &follower))?;
+    /// 🌟 Download the preview of a file, a jpeg image
+    pub async fn preview(&mut self, file_ref: &str) -> DownloadReply {
+        log_info!("🚀 Start preview api, file_ref = [{}], follower=[{}]", file_ref, &self.follower);
 
-            Ok(dataset)
-        }
+        // Check if the token is valid
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::download_reply_error()
+        );
 
-        let Ok(mut data_set) = local_execute_query(&sql_query, &file_ref, &self.follower).await else {
-            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        let preview_ref = match self.search_preview_ref(file_ref, &entry_session.customer_code).await {
+            Ok(Some(preview_ref)) => preview_ref,
+            Ok(None) => {
+                log_warn!("⛔ No preview for the file, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+                return DownloadReply::from_api_error(&PREVIEW_NOT_FOUND);
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the preview, e=[{}], follower=[{}]", e, &self.follower);
+                return DownloadReply::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
         };
 
-        // Inner function
-        async fn build_file_info(data_set: &mut SQLDataSet, file_ref: &str) -> anyhow::Result<GetFileInfoShortReply> {
-            let _mime_type = data_set.get_string("mime_type").unwrap_or("".to_string()); // optional
-            let _checksum = data_set.get_string("checksum").unwrap_or("".to_string()); // optional
-            let original_file_size = data_set.get_int("original_file_size")/*.ok_or(anyhow!("Wrong original_file_size col"))?*/;
-            let total_part = data_set.get_int_32("total_part").ok_or(anyhow!("Wrong total_part col"))?;
+        log_info!("🏁 End preview api, preview_ref=[{}], follower=[{}]", &preview_ref, &self.follower);
+        self.download(&preview_ref, &None, &None).await
+    }
 
-            let parts_count = data_set.get_int("parts_count").ok_or(anyhow!("Wrong count_encrypted col"))?;
-            let uploaded_count = data_set.get_int("count_uploaded").ok_or(anyhow!("Wrong count_uploaded col"))?;
-            let fulltext_indexed_count = data_set.get_int_32("fulltext").ok_or(anyhow!("Wrong fulltext col"))?;
-            let preview_generated_count = data_set.get_int_32("preview").ok_or(anyhow!("Wrong preview col"))?;
+    /// File reference of the first preview of the file, the preview of the owner of its parts if they are shared
+    async fn search_preview_ref(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<Option<String>> {
+        let sql_query = format!(
+            r"SELECT p.file_identifier
+            FROM fs_{0}.preview p
+            INNER JOIN fs_{0}.file_reference fr ON COALESCE(fr.parts_reference_id, fr.id) = p.file_reference_id
+            WHERE fr.file_ref = :p_file_ref
+            ORDER BY p.sort_order",
+            customer_code
+        );
 
-            Ok(GetFileInfoShortReply {
-                file_ref: file_ref.to_string(),
-                block_count: total_part as u32,
-                original_file_size: original_file_size.unwrap_or(0i64) as u64,
-                encrypted_count: parts_count,
-                uploaded_count,
-                fulltext_indexed_count: fulltext_indexed_count as i64,
-                preview_generated_count: preview_generated_count as i64,
-            })
-        }
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
 
-        let wt_stats = if data_set.next() {
-            let Ok(stats) = build_file_info(&mut data_set, file_ref)
-                .await
-                .map_err(err_fwd!("Build file info failed, follower=[{}]", &self.follower))
-            else {
-                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
-            };
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
 
-            log_info!("😎 Successfully read the file stats, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
-            WebType::from_item(StatusCode::OK.as_u16(), stats)
-        } else {
-            log_info!("⛔ Cannot find the file stats, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
-            WebType::from_api_error(&INTERNAL_TECHNICAL_ERROR)
-        };
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
 
-        log_info!("🏁 End file_stats api, follower=[{}]", &self.follower);
-        wt_stats
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        if !dataset.next() {
+            return Ok(None);
+        }
+        Ok(Some(dataset.get_string("file_identifier").ok_or(anyhow!("Wrong file_identifier col"))?))
     }
 
     fn download_reply_error() -> impl Fn(&ApiError<'static>) -> DownloadReply {
@@ -1382,7 +3091,18 @@ impl FileDelegate {
     }
 
     /// 🌟 Download the binary content of a file
-    pub async fn download(&mut self, file_ref: &str) -> DownloadReply {
+    ///
+    /// The encrypted parts are read by windows of DOWNLOAD_WINDOW_SIZE parts and only DECRYPT_AHEAD parts
+    /// are decrypted in advance, so the memory used depends on the window size, not on the file size.
+    ///
+    /// With a [range] (Range header), only the parts holding the requested bytes are read and a 206 is sent.
+    /// The [if_range] (If-Range header) must match the ETag of the file for the range to be applied.
+    pub async fn download(
+        &mut self,
+        file_ref: &str,
+        range: &Option<String>,
+        if_range: &Option<String>,
+    ) -> DownloadReply {
         log_info!("🚀 Start download api, file_ref = [{}], follower=[{}]", file_ref, &self.follower);
 
         // Check if the token is valid
@@ -1394,88 +3114,86 @@ impl FileDelegate {
         let customer_code = entry_session.customer_code.as_str();
         log_info!("Found session and customer code=[{}], follower=[{}]", &customer_code, &self.follower);
 
-        // Search the document's parts from the database
-
-        let Ok((media_type, enc_parts)) = self.search_parts(file_ref, customer_code).await.map_err(tr_fwd!()) else {
-            log_error!("");
-            return DownloadReply::from_api_error(&INTERNAL_DATABASE_ERROR);
+        // Search the document's header from the database
+        let file_header = match self.search_file_header(file_ref, customer_code).await {
+            Ok(Some(file_header)) => file_header,
+            Ok(None) => {
+                log_error!("💣 The file is not found, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+                return DownloadReply::from_api_error(&FILE_INFO_NOT_FOUND);
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the file header, e=[{}], follower=[{}]", e, &self.follower);
+                return DownloadReply::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
         };
 
-        log_info!(
-            "😎 Found the encrypted parts, number of parts=[{}], follower=[{}]",
-            &enc_parts.len(),
-            &self.follower
-        );
+        if !file_header.is_encrypted {
+            log_error!("💣 The file is not processed yet, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+            return DownloadReply::from_api_error(&FILE_INFO_NOT_FOUND);
+        }
 
-        let Ok(media) = media_type.parse::<Mime>().map_err(tr_fwd!()) else {
+        let Ok(media) = file_header.media_type.parse::<Mime>().map_err(tr_fwd!()) else {
             return DownloadReply::from_api_error(&INTERNAL_TECHNICAL_ERROR);
         };
 
         log_info!("😎 Found correct media type=[{}], follower=[{}]", &media, &self.follower);
 
-        // Get the customer key
-        let Ok(customer_key) = fetch_customer_key(customer_code, &self.follower)
-            .await
-            .map_err(err_fwd!("💣 Cannot get the customer key, follower=[{}]", &self.follower))
-        else {
-            return DownloadReply::from_api_error(&INTERNAL_TECHNICAL_ERROR);
-        };
-
-        // Parallel decrypt of slides of parts [Parts, Q+(1*)]
+        // The content of a file reference never changes, so the reference is a strong validator
+        let etag = format!("\"{}\"", file_ref);
 
-        let Ok(clear_parts) = self.parallel_decrypt(enc_parts, &customer_key).await else {
-            log_error!(
-                "💣 Cannot decrypt the parts for the file, file reference=[{}], follower=[{}]",
-                &file_ref,
-                &self.follower
-            );
-            return DownloadReply::from_api_error(&INTERNAL_TECHNICAL_ERROR);
+        let range_request = match (range, file_header.file_size) {
+            (Some(range), Some(file_size)) if Self::is_if_range_valid(if_range, &etag) => {
+                Self::parse_range_header(range, file_size as u64)
+            }
+            _ => RangeRequest::Full,
         };
 
-        // Output : Get a file array of P parts
-
-        log_info!("😎 Decrypt done, number of parts=[{}], follower=[{}]", &clear_parts.len(), &self.follower);
+        let byte_range = match range_request {
+            RangeRequest::Partial(byte_range) => {
+                log_info!(
+                    "😎 Partial download, range=[{}-{}], follower=[{}]",
+                    byte_range.start,
+                    byte_range.end,
+                    &self.follower
+                );
+                Some(byte_range)
+            }
+            RangeRequest::Full => None,
+            RangeRequest::Unsatisfiable => {
+                log_warn!("⛔ Range not satisfiable, range=[{:?}], follower=[{}]", range, &self.follower);
+                return Self::range_not_satisfiable_reply(file_header.file_size.unwrap_or(0));
+            }
+        };
 
-        // Merge all the parts in one big file
-        let Ok(stream) = Self::download_from_parts(clear_parts, &media).await else {
-            log_error!(
-                "💣 Cannot merge the parts for the file, file reference=[{}], follower=[{}]",
-                &file_ref,
-                &self.follower
-            );
+        // Get the customer key
+        let Ok(customer_key) = fetch_customer_key(customer_code, &self.follower)
+            .await
+            .map_err(err_fwd!("💣 Cannot get the customer key, follower=[{}]", &self.follower))
+        else {
             return DownloadReply::from_api_error(&INTERNAL_TECHNICAL_ERROR);
         };
 
-        log_info!("😎 Merged all the parts, follower=[{}]", &self.follower);
-        log_info!("🏁 End download api, follower=[{}]", &self.follower);
+        // The parts are fetched and decrypted while the body is sent
+        let parts_range = byte_range.or_else(|| match file_header.file_size {
+            Some(file_size) if file_size > 0 => Some(ByteRange { start: 0, end: file_size as u64 - 1 }),
+            _ => None,
+        });
+        let clear_parts = self.stream_clear_parts(file_header.parts_id, customer_code, &customer_key, parts_range);
 
-        Ok(stream)
+        log_info!("🏁 End download api, follower=[{}]", &self.follower);
+        Self::download_from_stream(clear_parts, &media, &etag, file_header.file_size, byte_range)
     }
 
-    /// Get all the encrypted parts of the file
-    /// ( "application/pdf", {0 : "...", 1: "...", ...} )
-    async fn search_parts(
-        &self,
-        file_ref: &str,
-        customer_code: &str,
-    ) -> anyhow::Result<(String, HashMap<u32, String>)> {
-        log_info!("Search the parts for the file, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
-
-        let sql_str = r"
-            SELECT fp.id,
-                fr.file_ref,
-                fr.mime_type,
-                fr.is_encrypted,
-                fp.part_number,
-                length(fp.part_data) as part_data_length,
-                fp.part_data
-            FROM  fs_{customer_code}.file_reference fr, fs_{customer_code}.file_parts fp
-            WHERE
-                fp.file_reference_id = fr.id AND
-                fr.file_ref = :p_file_ref
-            ORDER BY fr.file_ref, fp.part_number";
+    /// Read the information of the file reference needed to start the download
+    async fn search_file_header(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<Option<FileHeader>> {
+        log_info!("Search the header of the file, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
 
-        let sql_query = sql_str.replace("{customer_code}", customer_code);
+        let sql_query = format!(
+            r"SELECT mime_type, original_file_size, is_encrypted, COALESCE(parts_reference_id, id) parts_id
+            FROM fs_{}.file_reference
+            WHERE file_ref = :p_file_ref",
+            customer_code
+        );
 
         let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
         let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
@@ -1483,43 +3201,149 @@ impl FileDelegate {
         let mut params = HashMap::new();
         params.insert("p_file_ref".to_string(), CellValue::from_raw_string(file_ref.to_string()));
 
-        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
 
         let mut dataset =
             query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
 
-        let mut parts: HashMap<u32, String> = HashMap::new();
-        let mut media_type = String::new();
-        while dataset.next() {
-            let part_info_len = dataset.get_int_32("part_data_length").ok_or(anyhow!("Wrong part_data_length col"))?;
-            let part_info = Self::read_part(&mut dataset)
-                .map_err(err_fwd!("Cannot read part data, follower=[{}]", &self.follower))?;
-            media_type = part_info.0; // always the same media type for each row
-            parts.insert(part_info.1, part_info.2);
+        if !dataset.next() {
+            return Ok(None);
         }
 
-        log_info!(
-            "😎 Found parts for the file, file_ref=[{}], n_parts=[{}], follower=[{}]",
-            file_ref,
-            parts.len(),
-            &self.follower
+        let media_type = dataset.get_string("mime_type").unwrap_or_default();
+        let file_size = dataset.get_int("original_file_size");
+        let is_encrypted = dataset.get_bool("is_encrypted").ok_or(anyhow!("Wrong is_encrypted col"))?;
+        let parts_id = dataset.get_int("parts_id").ok_or(anyhow!("Wrong parts_id col"))?;
+
+        Ok(Some(FileHeader { media_type, file_size, is_encrypted, parts_id }))
+    }
+
+    /// Stream the clear content of the file, part after part
+    ///
+    /// Windows of encrypted parts are read one after the other and each part is decrypted
+    /// on the blocking pool, with at most DECRYPT_AHEAD parts decrypted ahead of the one being sent.
+    /// With a [range], only the parts covering the range are read and the bytes outside are dropped,
+    /// otherwise all the parts are sent.
+    fn stream_clear_parts(
+        &self,
+        parts_id: i64,
+        customer_code: &str,
+        customer_key: &str,
+        range: Option<ByteRange>,
+    ) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static {
+        let local_self = self.clone();
+        let customer_code = customer_code.to_owned();
+        let customer_key = customer_key.to_owned();
+        let follower = self.follower.clone();
+
+        let start_part = range.map(|r| r.first_part()).unwrap_or(0);
+        let last_part = range.map(|r| r.last_part());
+
+        let enc_parts = Self::stream_part_windows(
+            start_part,
+            last_part,
+            Self::DOWNLOAD_WINDOW_SIZE,
+            move |first_part, window_size| {
+                let local_self = local_self.clone();
+                let customer_code = customer_code.clone();
+                async move { local_self.search_part_window(parts_id, &customer_code, first_part, window_size).await }
+            },
         );
 
-        Ok((media_type, parts))
+        enc_parts
+            .map_ok(move |(part_number, enc_part)| {
+                let customer_key = customer_key.clone();
+                async move {
+                    let clear_part =
+                        task::spawn_blocking(move || Self::decrypt_part(part_number, enc_part, &customer_key))
+                            .await??;
+                    Ok::<_, anyhow::Error>((part_number, clear_part))
+                }
+            })
+            .try_buffered(Self::DECRYPT_AHEAD)
+            .map_ok(move |(part_number, clear_part)| match range {
+                Some(range) => range.slice_part(part_number, clear_part),
+                None => Bytes::from(clear_part),
+            })
+            .map_err(move |e| {
+                log_error!("💣 Download interrupted, e=[{}], follower=[{}]", e, &follower);
+                std::io::Error::other(e.to_string())
+            })
+    }
+
+    /// Stream the parts from [start_part] to [last_part], or to the end of the file without [last_part].
+    /// The parts are read by windows of at most [window_size] parts with [search_window](first_part, window_size)
+    fn stream_part_windows<T, F, Fut>(
+        start_part: u32,
+        last_part: Option<u32>,
+        window_size: u32,
+        search_window: F,
+    ) -> impl Stream<Item = anyhow::Result<(u32, T)>> + Send + 'static
+    where
+        T: Send + 'static,
+        F: Fn(u32, u32) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = anyhow::Result<Vec<(u32, T)>>> + Send + 'static,
+    {
+        stream::try_unfold(Some(start_part), move |first_part| {
+            let search_window = search_window.clone();
+            async move {
+                let Some(first_part) = first_part else {
+                    return Ok(None);
+                };
+                let window_size = match last_part {
+                    Some(last_part) => min(window_size, last_part - first_part + 1),
+                    None => window_size,
+                };
+                let window = search_window(first_part, window_size).await?;
+                let next_part = first_part + window.len() as u32;
+
+                let next_part = match last_part {
+                    // All the parts of the range must exist, the length of the reply is already sent
+                    Some(last_part) if window.len() < window_size as usize => {
+                        return Err(anyhow!("Missing part, part number=[{}], last part=[{}]", next_part, last_part));
+                    }
+                    Some(last_part) if next_part > last_part => None,
+                    Some(_) => Some(next_part),
+                    // A short window means the last part has been read
+                    None if window.len() < window_size as usize => None,
+                    None => Some(next_part),
+                };
+                Ok(Some((stream::iter(window.into_iter().map(Ok)), next_part)))
+            }
+        })
+        .try_flatten()
     }
 
-    /// ( <mdeia_type>, <part_number>, <data> )
-    fn read_part(data_set: &mut SQLDataSet) -> anyhow::Result<(String, u32, String)> {
-        let media_type = data_set.get_string("mime_type").ok_or(anyhow!("Wrong mime_type col"))?;
-        let is_encrypted = data_set.get_bool("is_encrypted").ok_or(anyhow!("Wrong is_encrypted col"))?;
-        let part_number = data_set.get_int_32("part_number").ok_or(anyhow!("Wrong part_number col"))?;
-        let part_data = data_set.get_string("part_data").ok_or(anyhow!("Wrong part_data col"))?;
+    /// Get a window of at most [window_size] encrypted parts of the file, starting at part number [first_part]
+    /// [ (first_part, [...]), (first_part + 1, [...]), ... ]
+    async fn search_part_window(
+        &self,
+        parts_id: i64,
+        customer_code: &str,
+        first_part: u32,
+        window_size: u32,
+    ) -> anyhow::Result<Vec<(u32, Vec<u8>)>> {
+        log_debug!(
+            "Search the parts for the file, parts_id=[{}], first_part=[{}], follower=[{}]",
+            parts_id,
+            first_part,
+            &self.follower
+        );
 
-        if !is_encrypted {
-            return Err(anyhow!("Part is not encrypted, part number=[{}]", part_number));
+        let parts = block_store()?
+            .search_parts(customer_code, parts_id, first_part, window_size)
+            .await
+            .map_err(err_fwd!("Cannot read the parts, follower=[{}]", &self.follower))?;
+
+        // The parts are numbered from 0 without any gap
+        for (index, (part_number, _)) in parts.iter().enumerate() {
+            let expected_part = first_part + index as u32;
+            if *part_number != expected_part {
+                return Err(anyhow!("Missing part, part number=[{}], parts_id=[{}]", expected_part, parts_id));
+            }
         }
 
-        Ok((media_type, part_number as u32, part_data))
+        Ok(parts)
     }
 
     //
@@ -1545,29 +3369,6 @@ impl FileDelegate {
     //     Ok(bytes)
     // }
 
-    // N = Number of threads = Number of Cores - 1;
-    // 5 cores , 20 parts => 4 decrypt by core
-    // 5 cores, 22 parts => 5 5 4 4 4
-    // 22 eucl 5 = 4,2 => 2 (number of extra decrypts)
-    // P eucl N = [Q,R]  Q is the number of decrypts by thread and R is the number of thread with 1 extra decrypt.
-    fn compute_pool_size(number_of_threads: u32, number_of_parts: u32) -> Vec<u32> {
-        let mut pool_size = vec![];
-        let q = number_of_parts / number_of_threads;
-        let mut r = number_of_parts % number_of_threads;
-
-        // dbg!(number_of_parts, number_of_threads, q,r);
-        for _ in 0..number_of_threads {
-            let extra = if r > 0 {
-                r -= 1;
-                1
-            } else {
-                0
-            };
-            pool_size.push(q + extra);
-        }
-        pool_size
-    }
-
     /// Decypher the file parts in parallel
     // fn parallel_decrypt(
     //     &self,
@@ -1647,106 +3448,11 @@ impl FileDelegate {
     //     Ok(clear_slide_parts)
     // }
 
-    /// Decypher the file parts in parallel
-    pub async fn parallel_decrypt(
-        &self,
-        enc_parts: HashMap<u32, String>,
-        customer_key: &str,
-    ) -> anyhow::Result<IndexedParts> {
-        let mut task_handles = vec![];
-        let n_threads = std::cmp::max(1, num_cpus::get() - 1);
-
-        log_debug!("Number of threads=[{}], follower=[{}]", n_threads, &self.follower);
-
-        let number_of_parts = enc_parts.len();
-        let pool_size = Self::compute_pool_size(n_threads as u32, number_of_parts as u32);
-
-        let mut offset: u32 = 0;
-        for pool_index in 0..n_threads {
-            if pool_size[pool_index] != 0 {
-                log_info!(
-                    "Prepare the pool number [{}] of size [{}] (parts) : [{} -> {}], follower=[{}]",
-                    pool_index,
-                    pool_size[pool_index],
-                    offset,
-                    offset + pool_size[pool_index] - 1,
-                    &self.follower
-                );
-
-                let mut enc_slides = HashMap::new();
-                for index in offset..offset + pool_size[pool_index] {
-                    let v = base64::engine::general_purpose::URL_SAFE_NO_PAD
-                        .decode(enc_parts.get(&index).ok_or(anyhow!("Wrong index"))?)?;
-
-                    enc_slides.insert(index, v);
-                }
-
-                offset += pool_size[pool_index];
-
-                let s_customer_key = customer_key.to_owned();
-                let local_self = self.clone();
-
-                // Utiliser tokio::spawn pour créer une tâche asynchrone
-                let task_handle = task::spawn(async move {
-                    local_self.decrypt_slide_of_parts(pool_index as u32, enc_slides, s_customer_key)
-                });
-
-                task_handles.push(task_handle);
-            }
-        }
-
-        let mut clear_slide_parts: IndexedParts = HashMap::new();
-
-        // Wait for all task to be completed
-        let results = future::join_all(task_handles).await;
-
-        for result in results {
-            match result {
-                Ok(Ok(clear_parts)) => {
-                    for (key, value) in clear_parts {
-                        clear_slide_parts.insert(key, value);
-                    }
-                }
-                Ok(Err(e)) => {
-                    log_error!("Decryption error for pool [{:?}], follower=[{}]", e, &self.follower,);
-                }
-                Err(e) => {
-                    log_error!("Task join error [{:?}], follower=[{}]", e, &self.follower);
-                }
-            }
-        }
-
-        Ok(clear_slide_parts)
-    }
-
-    /// Decypher a few parts
-    fn decrypt_slide_of_parts(
-        &self,
-        pool_index: u32,
-        enc_slides: IndexedParts,
-        customer_key: String,
-    ) -> anyhow::Result<IndexedParts> {
-        let mut clear_slides: HashMap<u32, Vec<u8>> = HashMap::new();
-
-        log_info!(
-            "Decrypt, pool_index=[{}], number of parts=[{}], follower=[{}]",
-            pool_index,
-            enc_slides.len(),
-            &self.follower
-        );
-
-        for (index, enc_content) in enc_slides {
-            let clear_content = DkEncrypt::new(CC20).decrypt_vec(&enc_content, &customer_key).map_err(err_fwd!(
-                "Cannot decrypt the part, pool_index=[{}], follower=[{}]",
-                pool_index,
-                &self.follower
-            ))?;
-
-            clear_slides.insert(index, clear_content);
-        }
-
-        log_info!("😎 Decrypted, pool_index=[{}], follower=[{}]", pool_index, &self.follower);
-        Ok(clear_slides)
+    /// Decypher one part of the file
+    fn decrypt_part(part_number: u32, enc_content: Vec<u8>, customer_key: &str) -> anyhow::Result<Vec<u8>> {
+        DkEncrypt::new(CC20)
+            .decrypt_vec(&enc_content, customer_key)
+            .map_err(err_fwd!("Cannot decrypt the part, part number=[{}]", part_number))
     }
 
     async fn create_file_reference(
@@ -1837,6 +3543,196 @@ impl FileDelegate {
         Ok(())
     }
 
+    /// Compute the checksum of the clear content from the blocks received so far (block by block upload)
+    async fn compute_checksum(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<String> {
+        let mut blocks = pin!(self.stream_incoming_blocks(file_ref, customer_code));
+        let mut hasher = Sha256::new();
+        while let Some((_, part_data)) = blocks.try_next().await.map_err(tr_fwd!())? {
+            let raw_value = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(part_data).map_err(tr_fwd!())?;
+            hasher.update(&raw_value);
+        }
+        Ok(Self::format_checksum(hasher))
+    }
+
+    /// Find a file of the customer with the same content, already encrypted and owning its parts
+    async fn find_identical_file(
+        &self,
+        file_id: i64,
+        checksum: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<Option<i64>> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"SELECT fr.id
+                FROM fs_{}.file_reference fr
+                WHERE
+                    fr.checksum = :p_checksum AND
+                    fr.is_encrypted = true AND
+                    fr.processing_status IN ('indexed', 'stored') AND
+                    fr.parts_reference_id IS NULL AND
+                    fr.id <> :p_file_id
+                ORDER BY fr.id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_checksum".to_string(), CellValue::from_raw_str(checksum));
+        params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params };
+
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        if dataset.next() {
+            let id = dataset.get_int("id").ok_or(anyhow!("Wrong id col"))?;
+            Ok(Some(id))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Point the file reference to the parts of an identical file and count one more reference on them
+    async fn share_parts(
+        &self,
+        file_id: i64,
+        parts_reference_id: i64,
+        checksum: &str,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"UPDATE fs_{0}.file_reference
+                SET parts_reference_id = :p_parts_reference_id,
+                    checksum = :p_checksum,
+                    encrypted_file_size = (SELECT encrypted_file_size FROM fs_{0}.file_reference WHERE id = :p_parts_reference_id)
+                WHERE id = :p_file_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_parts_reference_id".to_string(), CellValue::from_raw_int(parts_reference_id));
+        params.insert("p_checksum".to_string(), CellValue::from_raw_str(checksum));
+        params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        let sql_query = format!(
+            r"UPDATE fs_{}.file_reference
+                SET ref_count = ref_count + 1
+                WHERE id = :p_parts_reference_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_parts_reference_id".to_string(), CellValue::from_raw_int(parts_reference_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        Ok(())
+    }
+
+    /// Give up the parts of the file reference, see [release_file_parts]
+    async fn release_parts(&self, file_id: i64, customer_code: &str) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let part_keys = release_file_parts(&mut trans, customer_code, file_id)
+            .await
+            .map_err(err_fwd!("💣 Cannot release the parts, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        purge_parts(customer_code, &part_keys).await;
+        Ok(())
+    }
+
+    /// Record the checksum of the clear content and the size of the encrypted parts, checked by the integrity verification
+    async fn update_checksum(
+        &self,
+        file_id: i64,
+        checksum: &str,
+        encrypted_file_size: u64,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"UPDATE fs_{}.file_reference
+                SET checksum = :p_checksum,
+                    encrypted_file_size = :p_encrypted_file_size
+                WHERE id = :p_file_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_checksum".to_string(), CellValue::from_raw_str(checksum));
+        params.insert("p_encrypted_file_size".to_string(), CellValue::from_raw_int(encrypted_file_size as i64));
+        params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        Ok(())
+    }
+
+    /// Move the file reference to the next step of its processing, the [failure_reason] is kept for a failure only
+    async fn update_processing_status(
+        &self,
+        file_id: i64,
+        status: ProcessingStatus,
+        failure_reason: Option<&str>,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"UPDATE fs_{}.file_reference
+                SET processing_status = :p_processing_status,
+                    failure_reason = :p_failure_reason,
+                    status_gmt = :p_status_gmt
+                WHERE id = :p_file_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_processing_status".to_string(), CellValue::from_raw_str(status.as_str()));
+        params.insert("p_failure_reason".to_string(), CellValue::String(failure_reason.map(str::to_string)));
+        params.insert("p_status_gmt".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
+        params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        log_info!(
+            "Processing status changed, file_id=[{}], status=[{}], follower=[{}]",
+            file_id,
+            status,
+            &self.follower
+        );
+        Ok(())
+    }
+
+    /// Message of the error, cut to fit in the failure_reason column
+    fn failure_reason(e: &anyhow::Error) -> String {
+        e.to_string().chars().take(FAILURE_REASON_MAX_LENGTH).collect()
+    }
+
     async fn delete_from_target_table(
         &self,
         target_table: &str,
@@ -1897,9 +3793,20 @@ impl FileDelegate {
 //
 #[cfg(test)]
 mod file_server_tests {
+    use std::cmp::min;
     use std::path::Path;
     use std::process::exit;
-    use std::sync::Once;
+    use std::sync::{Arc, Mutex, Once};
+
+    use anyhow::anyhow;
+    use dkdto::web_types::FileListQuery;
+    use futures::executor::block_on;
+    use futures::TryStreamExt;
+    use sha2::{Digest, Sha256};
+
+    use crate::file_delegate::{
+        thread_count_or_default, ByteRange, FileDelegate, RangeRequest, FAILURE_REASON_MAX_LENGTH,
+    };
 
     static INIT: Once = Once::new();
 
@@ -1919,6 +3826,227 @@ mod file_server_tests {
         });
     }
 
+    #[test]
+    fn parse_range_header_test() {
+        const FILE_SIZE: u64 = 3_000_000;
+        let partial = |start, end| RangeRequest::Partial(ByteRange { start, end });
+
+        assert_eq!(partial(0, 499), FileDelegate::parse_range_header("bytes=0-499", FILE_SIZE));
+        assert_eq!(partial(1_048_576, 2_999_999), FileDelegate::parse_range_header("bytes=1048576-", FILE_SIZE));
+        assert_eq!(partial(2_999_500, 2_999_999), FileDelegate::parse_range_header("bytes=-500", FILE_SIZE));
+        assert_eq!(partial(0, 2_999_999), FileDelegate::parse_range_header("bytes=-5000000", FILE_SIZE));
+        assert_eq!(partial(100, 2_999_999), FileDelegate::parse_range_header("bytes=100-9000000", FILE_SIZE));
+
+        assert_eq!(RangeRequest::Unsatisfiable, FileDelegate::parse_range_header("bytes=3000000-", FILE_SIZE));
+        assert_eq!(RangeRequest::Unsatisfiable, FileDelegate::parse_range_header("bytes=-0", FILE_SIZE));
+        assert_eq!(RangeRequest::Unsatisfiable, FileDelegate::parse_range_header("bytes=0-10", 0));
+
+        assert_eq!(RangeRequest::Full, FileDelegate::parse_range_header("bytes=500-100", FILE_SIZE));
+        assert_eq!(RangeRequest::Full, FileDelegate::parse_range_header("bytes=0-10,20-30", FILE_SIZE));
+        assert_eq!(RangeRequest::Full, FileDelegate::parse_range_header("items=0-10", FILE_SIZE));
+        assert_eq!(RangeRequest::Full, FileDelegate::parse_range_header("bytes=a-b", FILE_SIZE));
+        assert_eq!(RangeRequest::Full, FileDelegate::parse_range_header("bytes=-", FILE_SIZE));
+    }
+
+    #[test]
+    fn byte_range_parts_test() {
+        const BLOCK_SIZE: u64 = FileDelegate::BLOCK_SIZE as u64;
+        let range = ByteRange { start: BLOCK_SIZE - 10, end: 2 * BLOCK_SIZE + 4 };
+
+        assert_eq!(BLOCK_SIZE + 15, range.len());
+        assert_eq!(0, range.first_part());
+        assert_eq!(2, range.last_part());
+
+        let part = vec![7u8; BLOCK_SIZE as usize];
+        assert_eq!(10, range.slice_part(0, part.clone()).len());
+        assert_eq!(BLOCK_SIZE as usize, range.slice_part(1, part.clone()).len());
+        assert_eq!(5, range.slice_part(2, part).len());
+
+        // The last part of a file is shorter than a block
+        let range = ByteRange { start: 10, end: 19 };
+        assert_eq!(&[10u8, 11, 12, 13, 14, 15, 16, 17, 18, 19][..], &range.slice_part(0, (0..30).collect())[..]);
+    }
+
+    /// Read the parts of a file of [part_count] parts by windows of 4 parts.
+    /// Return the part numbers and the (first_part, window_size) of each window read
+    fn read_part_windows(
+        part_count: u32,
+        start_part: u32,
+        last_part: Option<u32>,
+    ) -> (anyhow::Result<Vec<u32>>, Vec<(u32, u32)>) {
+        let windows = Arc::new(Mutex::new(vec![]));
+        let searched_windows = windows.clone();
+        let search_window = move |first_part: u32, window_size: u32| {
+            searched_windows.lock().unwrap().push((first_part, window_size));
+            let parts: Vec<(u32, Vec<u8>)> =
+                (first_part..min(first_part + window_size, part_count)).map(|n| (n, vec![n as u8])).collect();
+            async move { Ok(parts) }
+        };
+
+        let part_numbers = block_on(
+            FileDelegate::stream_part_windows(start_part, last_part, 4, search_window)
+                .map_ok(|(part_number, _)| part_number)
+                .try_collect::<Vec<_>>(),
+        );
+        let windows = windows.lock().unwrap().clone();
+        (part_numbers, windows)
+    }
+
+    #[test]
+    fn stream_part_windows_boundary_test() {
+        // The parts fill the windows exactly, an empty window ends the file
+        let (part_numbers, windows) = read_part_windows(8, 0, None);
+        assert_eq!((0..8).collect::<Vec<_>>(), part_numbers.unwrap());
+        assert_eq!(vec![(0, 4), (4, 4), (8, 4)], windows);
+
+        // A range ending on a window boundary does not read further
+        let (part_numbers, windows) = read_part_windows(20, 2, Some(9));
+        assert_eq!((2..=9).collect::<Vec<_>>(), part_numbers.unwrap());
+        assert_eq!(vec![(2, 4), (6, 4)], windows);
+    }
+
+    #[test]
+    fn stream_part_windows_partial_test() {
+        // The last window is partial
+        let (part_numbers, windows) = read_part_windows(10, 0, None);
+        assert_eq!((0..10).collect::<Vec<_>>(), part_numbers.unwrap());
+        assert_eq!(vec![(0, 4), (4, 4), (8, 4)], windows);
+
+        // The last window of a range is shortened to the range
+        let (part_numbers, windows) = read_part_windows(20, 3, Some(8));
+        assert_eq!((3..=8).collect::<Vec<_>>(), part_numbers.unwrap());
+        assert_eq!(vec![(3, 4), (7, 2)], windows);
+
+        // A range after the end of the file is missing parts
+        let (part_numbers, _) = read_part_windows(5, 0, Some(7));
+        assert!(part_numbers.is_err());
+    }
+
+    #[test]
+    fn stream_part_windows_single_part_test() {
+        let (part_numbers, windows) = read_part_windows(1, 0, None);
+        assert_eq!(vec![0], part_numbers.unwrap());
+        assert_eq!(vec![(0, 4)], windows);
+
+        let (part_numbers, windows) = read_part_windows(1, 0, Some(0));
+        assert_eq!(vec![0], part_numbers.unwrap());
+        assert_eq!(vec![(0, 1)], windows);
+    }
+
+    #[test]
+    fn upload_block_test() {
+        const BLOCK_SIZE: usize = FileDelegate::BLOCK_SIZE;
+        let file_size = Some(2 * BLOCK_SIZE as i64 + 100);
+
+        assert_eq!(0, FileDelegate::expected_block_count(0));
+        assert_eq!(1, FileDelegate::expected_block_count(BLOCK_SIZE as u64));
+        assert_eq!(3, FileDelegate::expected_block_count(2 * BLOCK_SIZE as u64 + 100));
+
+        assert!(FileDelegate::is_valid_block(0, BLOCK_SIZE, file_size));
+        assert!(FileDelegate::is_valid_block(2, 100, file_size));
+        assert!(!FileDelegate::is_valid_block(1, 100, file_size));
+        assert!(!FileDelegate::is_valid_block(2, BLOCK_SIZE, file_size));
+        assert!(!FileDelegate::is_valid_block(3, 100, file_size));
+        assert!(!FileDelegate::is_valid_block(0, 0, file_size));
+
+        // Without the file size, only the maximum size of the block is checked
+        assert!(FileDelegate::is_valid_block(8, 100, None));
+        assert!(!FileDelegate::is_valid_block(8, BLOCK_SIZE + 1, None));
+    }
+
+    #[test]
+    fn check_complete_upload_test() {
+        const BLOCK_SIZE: i64 = FileDelegate::BLOCK_SIZE as i64;
+        let blocks = vec![(0, BLOCK_SIZE), (1, BLOCK_SIZE), (2, 100)];
+
+        assert_eq!((2 * BLOCK_SIZE as usize + 100, 3), FileDelegate::check_complete_upload(&blocks, Some(3)).unwrap());
+        assert_eq!((2 * BLOCK_SIZE as usize + 100, 3), FileDelegate::check_complete_upload(&blocks, None).unwrap());
+        assert!(FileDelegate::check_complete_upload(&blocks, Some(4)).is_err());
+
+        // Missing block
+        assert!(FileDelegate::check_complete_upload(&[(0, BLOCK_SIZE), (2, 100)], None).is_err());
+        // Short block before the last one
+        assert!(FileDelegate::check_complete_upload(&[(0, 100), (1, 100)], None).is_err());
+
+        assert_eq!((0, 0), FileDelegate::check_complete_upload(&[], Some(0)).unwrap());
+    }
+
+    #[test]
+    fn checksum_test() {
+        // The checksum computed block by block is the one of the entire content
+        let mut hasher = Sha256::new();
+        hasher.update(b"ab");
+        hasher.update(b"c");
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            FileDelegate::format_checksum(hasher)
+        );
+        assert_eq!(64, FileDelegate::format_checksum(Sha256::new()).len());
+    }
+
+    #[test]
+    fn failure_reason_test() {
+        let e = anyhow!("Cannot parse the file, {}", "Tika is down");
+        assert_eq!("Cannot parse the file, Tika is down", FileDelegate::failure_reason(&e));
+
+        // Cut on the characters, not the bytes
+        let e = anyhow!("é".repeat(FAILURE_REASON_MAX_LENGTH + 10));
+        assert_eq!(FAILURE_REASON_MAX_LENGTH, FileDelegate::failure_reason(&e).chars().count());
+    }
+
+    #[test]
+    fn glob_to_like_test() {
+        assert_eq!("0f37%", FileDelegate::glob_to_like("0f37*"));
+        assert_eq!("%-98e_-%", FileDelegate::glob_to_like("*-98e?-*"));
+        assert_eq!("image/%", FileDelegate::glob_to_like("image/*"));
+        // The LIKE wildcards are taken literally
+        assert_eq!("a\\%b\\_c\\\\", FileDelegate::glob_to_like("a%b_c\\"));
+
+        assert!(FileDelegate::is_valid_pattern("0f37?b54-*"));
+        assert!(!FileDelegate::is_valid_pattern("0f37%"));
+        assert!(!FileDelegate::is_valid_pattern("0f37' OR 1=1"));
+    }
+
+    #[test]
+    fn thread_count_or_default_test() {
+        assert_eq!(3, thread_count_or_default(None, 3));
+        assert_eq!(6, thread_count_or_default(Some(" 6 "), 3));
+        assert_eq!(3, thread_count_or_default(Some("many"), 3));
+        // At least one block is encrypted at a time
+        assert_eq!(1, thread_count_or_default(Some("0"), 3));
+        assert_eq!(1, thread_count_or_default(None, 0));
+    }
+
+    #[test]
+    fn file_list_order_by_test() {
+        let query = |sort_by: Option<&str>, sort_order: Option<&str>| FileListQuery {
+            sort_by: sort_by.map(str::to_string),
+            sort_order: sort_order.map(str::to_string),
+            ..Default::default()
+        };
+
+        assert_eq!(Some("fr.file_ref ASC".to_string()), FileDelegate::file_list_order_by(&query(None, None)));
+        assert_eq!(
+            Some("fr.original_file_size DESC NULLS LAST, fr.file_ref DESC".to_string()),
+            FileDelegate::file_list_order_by(&query(Some("size"), Some("desc")))
+        );
+        assert_eq!(
+            Some("fr.created_gmt ASC NULLS LAST, fr.file_ref ASC".to_string()),
+            FileDelegate::file_list_order_by(&query(Some("date"), None))
+        );
+        assert_eq!(None, FileDelegate::file_list_order_by(&query(Some("name"), None)));
+        assert_eq!(None, FileDelegate::file_list_order_by(&query(None, Some("up"))));
+    }
+
+    #[test]
+    fn if_range_test() {
+        let etag = "\"0f373b54-5dbb-4c75-98e7-98fd141593dc\"";
+        assert!(FileDelegate::is_if_range_valid(&None, etag));
+        assert!(FileDelegate::is_if_range_valid(&Some(etag.to_string()), etag));
+        assert!(!FileDelegate::is_if_range_valid(&Some("\"another_file\"".to_string()), etag));
+        assert!(!FileDelegate::is_if_range_valid(&Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()), etag));
+    }
+
     // #[test]
     // fn test_1() {
     //     init_log();
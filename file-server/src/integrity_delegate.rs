@@ -0,0 +1,700 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::anyhow;
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use log::*;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+
+use commons_error::*;
+use commons_pg::sql_transaction::{CellValue, SQLDataSet};
+use commons_pg::sql_transaction_async::{SQLChangeAsync, SQLConnectionAsync, SQLQueryBlockAsync};
+use commons_services::key_lib::fetch_customer_key;
+use commons_services::session_lib::valid_sid_get_session;
+use commons_services::token_lib::SessionToken;
+use commons_services::try_or_return;
+use commons_services::x_request_id::{Follower, XRequestID};
+use dkcrypto::dk_crypto::CypherMode::CC20;
+use dkcrypto::dk_crypto::DkEncrypt;
+use dkdto::api_error::ApiError;
+use dkdto::error_codes::{
+    FILE_INFO_NOT_FOUND, FILE_NOT_STORED, INTERNAL_DATABASE_ERROR, INTERNAL_TECHNICAL_ERROR, SCRUB_ALREADY_RUNNING,
+    SCRUB_NOT_FOUND,
+};
+use dkdto::web_types::{FileIntegrityReply, ScrubReply, WebType, WebTypeBuilder};
+use doka_cli::request_client::TokenType;
+
+use crate::block_store::block_store;
+
+/// Number of parts read at once from the block store
+const PARTS_WINDOW: u32 = 10;
+/// Size of the integrity_issue column
+const INTEGRITY_ISSUE_MAX_LENGTH: usize = 500;
+const ISSUE_SEPARATOR: &str = "; ";
+const SCRUB_STATUS_RUNNING: &str = "running";
+const SCRUB_STATUS_DONE: &str = "done";
+
+/// Customer codes whose scrub is running
+static RUNNING_SCRUBS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// File reference to verify, the parts are the ones of the [parts_id] file when they are shared
+struct StoredFile {
+    id: i64,
+    file_ref: String,
+    is_stored: bool,
+    parts_id: i64,
+    total_part: u32,
+    checksum: Option<String>,
+    original_file_size: Option<i64>,
+    encrypted_file_size: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct IntegrityDelegate {
+    pub session_token: SessionToken,
+    pub follower: Follower,
+}
+
+impl IntegrityDelegate {
+    pub fn new(session_token: SessionToken, x_request_id: XRequestID) -> Self {
+        Self {
+            session_token,
+            follower: Follower { x_request_id: x_request_id.new_if_null(), token_type: TokenType::None },
+        }
+    }
+
+    fn web_type_error<T>() -> impl Fn(&ApiError<'static>) -> WebType<T>
+    where
+        T: DeserializeOwned,
+    {
+        |e| {
+            log_error!("💣 Error after try {:?}", e);
+            WebType::from_api_error(e)
+        }
+    }
+
+    ///
+    /// 🌟 Decrypt all the parts of the file [file_ref], and check the part numbers, the checksum and the sizes
+    ///
+    pub async fn verify(&mut self, file_ref: &str) -> WebType<FileIntegrityReply> {
+        log_info!("🚀 Start verify api, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+
+        let file = match self.search_file(file_ref, customer_code).await {
+            Ok(Some(file)) => file,
+            Ok(None) => {
+                log_warn!("⛔ Cannot find the file, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+                return WebType::from_api_error(&FILE_INFO_NOT_FOUND);
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the file, e=[{}], follower=[{}]", e, &self.follower);
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
+        };
+
+        if !file.is_stored {
+            log_warn!("⛔ The file is not stored yet, file_ref=[{}], follower=[{}]", file_ref, &self.follower);
+            return WebType::from_api_error(&FILE_NOT_STORED);
+        }
+
+        let Ok(customer_key) = fetch_customer_key(customer_code, &self.follower)
+            .await
+            .map_err(err_fwd!("💣 Cannot get the customer key, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_TECHNICAL_ERROR);
+        };
+
+        let Ok(reply) = self
+            .verify_file(&file, customer_code, &customer_key)
+            .await
+            .map_err(err_fwd!("💣 Cannot record the verification, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+        };
+
+        log_info!("🏁 End verify api, is_valid=[{}], follower=[{}]", reply.is_valid, &self.follower);
+        WebType::from_item(StatusCode::OK.as_u16(), reply)
+    }
+
+    ///
+    /// 🌟 Start the verification of all the stored files of the customer, in the background.
+    /// The progress and the corrupted files are read with the scrub report
+    ///
+    pub async fn start_scrub(&mut self) -> WebType<ScrubReply> {
+        log_info!("🚀 Start start_scrub api, follower=[{}]", &self.follower);
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.clone();
+
+        let Ok(customer_key) = fetch_customer_key(&customer_code, &self.follower)
+            .await
+            .map_err(err_fwd!("💣 Cannot get the customer key, follower=[{}]", &self.follower))
+        else {
+            return WebType::from_api_error(&INTERNAL_TECHNICAL_ERROR);
+        };
+
+        if !acquire_scrub(&customer_code) {
+            log_warn!(
+                "⛔ The scrub is already running, customer_code=[{}], follower=[{}]",
+                &customer_code,
+                &self.follower
+            );
+            return WebType::from_api_error(&SCRUB_ALREADY_RUNNING);
+        }
+
+        let start_date_time = Utc::now();
+        let run_id = match self.insert_scrub_run(start_date_time, &customer_code).await {
+            Ok(run_id) => run_id,
+            Err(e) => {
+                log_error!("💣 Cannot create the scrub run, e=[{}], follower=[{}]", e, &self.follower);
+                release_scrub(&customer_code);
+                return WebType::from_api_error(&INTERNAL_DATABASE_ERROR);
+            }
+        };
+
+        let delegate = self.clone();
+        let scrub_customer_code = customer_code.clone();
+        tokio::spawn(async move {
+            let _ = delegate.run_scrub(run_id, &scrub_customer_code, &customer_key).await.map_err(err_fwd!(
+                "💣 Scrub failed, customer_code=[{}], follower=[{}]",
+                &scrub_customer_code,
+                &delegate.follower
+            ));
+            let _ = delegate
+                .end_scrub_run(run_id, &scrub_customer_code)
+                .await
+                .map_err(err_fwd!("💣 Cannot end the scrub run, follower=[{}]", &delegate.follower));
+            release_scrub(&scrub_customer_code);
+        });
+
+        log_info!("🏁 End start_scrub api, run_id=[{}], follower=[{}]", run_id, &self.follower);
+        WebType::from_item(
+            StatusCode::OK.as_u16(),
+            ScrubReply {
+                status: SCRUB_STATUS_RUNNING.to_string(),
+                start_date_time,
+                end_date_time: None,
+                file_count: 0,
+                corrupted_count: 0,
+                list_of_corrupted_files: vec![],
+            },
+        )
+    }
+
+    ///
+    /// 🌟 Progress of the last scrub of the customer, with all the files found corrupted
+    ///
+    pub async fn scrub_report(&mut self) -> WebType<ScrubReply> {
+        log_info!("🚀 Start scrub_report api, follower=[{}]", &self.follower);
+
+        let entry_session = try_or_return!(
+            valid_sid_get_session(&self.session_token, &mut self.follower).await,
+            Self::web_type_error()
+        );
+        let customer_code = entry_session.customer_code.as_str();
+
+        let wt_report = match self.search_scrub_report(customer_code).await {
+            Ok(Some(reply)) => {
+                log_info!(
+                    "😎 Read the scrub report, status=[{}], corrupted files=[{}], follower=[{}]",
+                    &reply.status,
+                    reply.list_of_corrupted_files.len(),
+                    &self.follower
+                );
+                WebType::from_item(StatusCode::OK.as_u16(), reply)
+            }
+            Ok(None) => {
+                log_warn!("⛔ No scrub for the customer, follower=[{}]", &self.follower);
+                WebType::from_api_error(&SCRUB_NOT_FOUND)
+            }
+            Err(e) => {
+                log_error!("💣 Cannot read the scrub report, e=[{}], follower=[{}]", e, &self.follower);
+                WebType::from_api_error(&INTERNAL_DATABASE_ERROR)
+            }
+        };
+
+        log_info!("🏁 End scrub_report api, follower=[{}]", &self.follower);
+        wt_report
+    }
+
+    /// Verify all the stored files of the customer, the progress is recorded after each file
+    async fn run_scrub(&self, run_id: i64, customer_code: &str, customer_key: &str) -> anyhow::Result<()> {
+        let files = self.search_stored_files(customer_code).await.map_err(tr_fwd!())?;
+        log_info!(
+            "Start the scrub, customer_code=[{}], file count=[{}], follower=[{}]",
+            customer_code,
+            files.len(),
+            &self.follower
+        );
+
+        let mut file_count: u64 = 0;
+        let mut corrupted_count: u64 = 0;
+        for file in &files {
+            let reply = self.verify_file(file, customer_code, customer_key).await.map_err(tr_fwd!())?;
+            file_count += 1;
+            if !reply.is_valid {
+                corrupted_count += 1;
+            }
+            self.update_scrub_run(run_id, file_count, corrupted_count, customer_code).await.map_err(tr_fwd!())?;
+        }
+
+        log_info!(
+            "😎 Scrub done, customer_code=[{}], files=[{}], corrupted files=[{}], follower=[{}]",
+            customer_code,
+            file_count,
+            corrupted_count,
+            &self.follower
+        );
+        Ok(())
+    }
+
+    /// Check the parts of the file and record the result on the file reference
+    async fn verify_file(
+        &self,
+        file: &StoredFile,
+        customer_code: &str,
+        customer_key: &str,
+    ) -> anyhow::Result<FileIntegrityReply> {
+        let mut parts_check = PartsCheck::new(file.total_part, customer_key);
+
+        let mut first_part: u32 = 0;
+        loop {
+            let parts = match block_store()?.search_parts(customer_code, file.parts_id, first_part, PARTS_WINDOW).await
+            {
+                Ok(parts) => parts,
+                Err(e) => {
+                    parts_check
+                        .issues
+                        .push(format!("Cannot read the parts, first part number=[{}], e=[{}]", first_part, e));
+                    break;
+                }
+            };
+            let window_size = parts.len() as u32;
+            let Some(next_part) = parts.last().map(|(part_number, _)| part_number + 1) else {
+                break;
+            };
+            for (part_number, enc_content) in parts {
+                parts_check.add_part(part_number, enc_content);
+            }
+            if window_size < PARTS_WINDOW {
+                break;
+            }
+            first_part = next_part;
+        }
+
+        let issues = parts_check.finish(file.checksum.as_deref(), file.original_file_size, file.encrypted_file_size);
+        if !issues.is_empty() {
+            log_warn!(
+                "⛔ The file is corrupted, file_ref=[{}], issues=[{}], follower=[{}]",
+                &file.file_ref,
+                issues.join(ISSUE_SEPARATOR),
+                &self.follower
+            );
+        }
+
+        let verified_date_time = Utc::now();
+        self.update_verification(file.id, &issues, verified_date_time, customer_code).await.map_err(tr_fwd!())?;
+
+        Ok(FileIntegrityReply {
+            file_ref: file.file_ref.clone(),
+            is_valid: issues.is_empty(),
+            issues,
+            verified_date_time,
+        })
+    }
+
+    async fn search_file(&self, file_ref: &str, customer_code: &str) -> anyhow::Result<Option<StoredFile>> {
+        let sql_query = format!(
+            r"SELECT fr.id, fr.file_ref, COALESCE(fr.parts_reference_id, fr.id) parts_id, fr.total_part,
+                    fr.checksum, fr.original_file_size, fr.encrypted_file_size,
+                    fr.is_encrypted AND fr.processing_status IN ('indexed', 'stored') is_stored
+                FROM fs_{}.file_reference fr
+                WHERE fr.file_ref = :p_file_ref",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_file_ref".to_string(), CellValue::from_raw_str(file_ref));
+
+        let mut files = self.query_stored_files(sql_query, params).await.map_err(tr_fwd!())?;
+        Ok(files.pop())
+    }
+
+    /// All the files whose parts are stored, the previews included
+    async fn search_stored_files(&self, customer_code: &str) -> anyhow::Result<Vec<StoredFile>> {
+        let sql_query = format!(
+            r"SELECT fr.id, fr.file_ref, COALESCE(fr.parts_reference_id, fr.id) parts_id, fr.total_part,
+                    fr.checksum, fr.original_file_size, fr.encrypted_file_size, true is_stored
+                FROM fs_{}.file_reference fr
+                WHERE fr.is_encrypted = true AND fr.processing_status IN ('indexed', 'stored')
+                ORDER BY fr.id",
+            customer_code
+        );
+
+        self.query_stored_files(sql_query, HashMap::new()).await
+    }
+
+    async fn query_stored_files(
+        &self,
+        sql_query: String,
+        params: HashMap<String, CellValue>,
+    ) -> anyhow::Result<Vec<StoredFile>> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+        let mut dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        let mut files = Vec::with_capacity(dataset.len());
+        while dataset.next() {
+            files.push(StoredFile {
+                id: dataset.get_int("id").ok_or(anyhow!("Wrong id col"))?,
+                file_ref: dataset.get_string("file_ref").ok_or(anyhow!("Wrong file_ref col"))?,
+                is_stored: dataset.get_bool("is_stored").unwrap_or(false),
+                parts_id: dataset.get_int("parts_id").ok_or(anyhow!("Wrong parts_id col"))?,
+                total_part: dataset.get_int_32("total_part").unwrap_or(0) as u32,
+                checksum: dataset.get_string("checksum"),
+                original_file_size: dataset.get_int("original_file_size"),
+                encrypted_file_size: dataset.get_int("encrypted_file_size"),
+            });
+        }
+        Ok(files)
+    }
+
+    /// Record the date of the verification, with the issues found, if any
+    async fn update_verification(
+        &self,
+        file_id: i64,
+        issues: &[String],
+        verified_date_time: DateTime<Utc>,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"UPDATE fs_{}.file_reference
+                SET verified_gmt = :p_verified_gmt,
+                    integrity_issue = :p_integrity_issue
+                WHERE id = :p_file_id",
+            customer_code
+        );
+
+        let integrity_issue = (!issues.is_empty())
+            .then(|| issues.join(ISSUE_SEPARATOR).chars().take(INTEGRITY_ISSUE_MAX_LENGTH).collect::<String>());
+
+        let mut params = HashMap::new();
+        params
+            .insert("p_verified_gmt".to_string(), CellValue::from_raw_systemtime(SystemTime::from(verified_date_time)));
+        params.insert("p_integrity_issue".to_string(), CellValue::String(integrity_issue));
+        params.insert("p_file_id".to_string(), CellValue::from_raw_int(file_id));
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+        Ok(())
+    }
+
+    async fn insert_scrub_run(&self, start_date_time: DateTime<Utc>, customer_code: &str) -> anyhow::Result<i64> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(r"INSERT INTO fs_{}.scrub_run (start_gmt) VALUES (:p_start_gmt)", customer_code);
+        let sequence_name = format!("fs_{}.scrub_run_id_seq", customer_code);
+
+        let mut params = HashMap::new();
+        params.insert("p_start_gmt".to_string(), CellValue::from_raw_systemtime(SystemTime::from(start_date_time)));
+
+        let sql_insert = SQLChangeAsync { sql_query, params, sequence_name };
+        let run_id =
+            sql_insert.insert(&mut trans).await.map_err(err_fwd!("Insertion failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+        Ok(run_id)
+    }
+
+    async fn update_scrub_run(
+        &self,
+        run_id: i64,
+        file_count: u64,
+        corrupted_count: u64,
+        customer_code: &str,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r"UPDATE fs_{}.scrub_run
+                SET file_count = :p_file_count,
+                    corrupted_count = :p_corrupted_count
+                WHERE id = :p_run_id",
+            customer_code
+        );
+
+        let mut params = HashMap::new();
+        params.insert("p_file_count".to_string(), CellValue::from_raw_int(file_count as i64));
+        params.insert("p_corrupted_count".to_string(), CellValue::from_raw_int(corrupted_count as i64));
+        params.insert("p_run_id".to_string(), CellValue::from_raw_int(run_id));
+
+        self.change_scrub_run(sql_query, params).await
+    }
+
+    async fn end_scrub_run(&self, run_id: i64, customer_code: &str) -> anyhow::Result<()> {
+        let sql_query = format!(r"UPDATE fs_{}.scrub_run SET end_gmt = :p_end_gmt WHERE id = :p_run_id", customer_code);
+
+        let mut params = HashMap::new();
+        params.insert("p_end_gmt".to_string(), CellValue::from_raw_systemtime(SystemTime::now()));
+        params.insert("p_run_id".to_string(), CellValue::from_raw_int(run_id));
+
+        self.change_scrub_run(sql_query, params).await
+    }
+
+    async fn change_scrub_run(&self, sql_query: String, params: HashMap<String, CellValue>) -> anyhow::Result<()> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_update = SQLChangeAsync { sql_query, params, sequence_name: "".to_string() };
+        sql_update.update(&mut trans).await.map_err(err_fwd!("Update failed, follower=[{}]", &self.follower))?;
+
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+        Ok(())
+    }
+
+    /// The last scrub run of the customer, with all the files found corrupted
+    async fn search_scrub_report(&self, customer_code: &str) -> anyhow::Result<Option<ScrubReply>> {
+        let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+        let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+        let sql_query = format!(
+            r"SELECT start_gmt, end_gmt, file_count, corrupted_count
+                FROM fs_{}.scrub_run
+                ORDER BY id DESC",
+            customer_code
+        );
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: Some(1), params: HashMap::new() };
+        let mut run_dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+
+        if !run_dataset.next() {
+            trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+            return Ok(None);
+        }
+
+        let sql_query = format!(
+            r"SELECT file_ref, integrity_issue, verified_gmt
+                FROM fs_{}.file_reference
+                WHERE integrity_issue IS NOT NULL
+                ORDER BY file_ref",
+            customer_code
+        );
+        let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params: HashMap::new() };
+        let mut file_dataset =
+            query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", &self.follower))?;
+        trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", &self.follower))?;
+
+        let list_of_corrupted_files = Self::build_corrupted_files(&mut file_dataset)?;
+        let end_date_time = run_dataset.get_timestamp_as_datetime("end_gmt");
+        let status = if end_date_time.is_some() { SCRUB_STATUS_DONE } else { SCRUB_STATUS_RUNNING };
+
+        Ok(Some(ScrubReply {
+            status: status.to_string(),
+            start_date_time: run_dataset
+                .get_timestamp_as_datetime("start_gmt")
+                .ok_or(anyhow!("Wrong start_gmt col"))?,
+            end_date_time,
+            file_count: run_dataset.get_int("file_count").ok_or(anyhow!("Wrong file_count col"))? as u64,
+            corrupted_count: run_dataset.get_int("corrupted_count").ok_or(anyhow!("Wrong corrupted_count col"))? as u64,
+            list_of_corrupted_files,
+        }))
+    }
+
+    fn build_corrupted_files(dataset: &mut SQLDataSet) -> anyhow::Result<Vec<FileIntegrityReply>> {
+        let mut corrupted_files = Vec::with_capacity(dataset.len());
+        while dataset.next() {
+            let integrity_issue = dataset.get_string("integrity_issue").unwrap_or_default();
+            corrupted_files.push(FileIntegrityReply {
+                file_ref: dataset.get_string("file_ref").ok_or(anyhow!("Wrong file_ref col"))?,
+                is_valid: false,
+                issues: integrity_issue.split(ISSUE_SEPARATOR).map(|issue| issue.to_string()).collect(),
+                verified_date_time: dataset
+                    .get_timestamp_as_datetime("verified_gmt")
+                    .ok_or(anyhow!("Wrong verified_gmt col"))?,
+            });
+        }
+        Ok(corrupted_files)
+    }
+}
+
+/// False if a scrub is already running for the customer
+fn acquire_scrub(customer_code: &str) -> bool {
+    let mut running_scrubs = RUNNING_SCRUBS.lock().unwrap_or_else(|e| e.into_inner());
+    if running_scrubs.iter().any(|code| code == customer_code) {
+        return false;
+    }
+    running_scrubs.push(customer_code.to_string());
+    true
+}
+
+fn release_scrub(customer_code: &str) {
+    RUNNING_SCRUBS.lock().unwrap_or_else(|e| e.into_inner()).retain(|code| code != customer_code);
+}
+
+///
+/// Check of the parts of a file, given in the order of their part number :
+/// each part must be present once and decipherable, and the clear content must match the recorded checksum and sizes
+///
+struct PartsCheck<'a> {
+    customer_key: &'a str,
+    total_part: u32,
+    next_part: u32,
+    last_part: Option<u32>,
+    hasher: Sha256,
+    clear_size: u64,
+    encrypted_size: u64,
+    issues: Vec<String>,
+}
+
+impl<'a> PartsCheck<'a> {
+    fn new(total_part: u32, customer_key: &'a str) -> Self {
+        Self {
+            customer_key,
+            total_part,
+            next_part: 0,
+            last_part: None,
+            hasher: Sha256::new(),
+            clear_size: 0,
+            encrypted_size: 0,
+            issues: vec![],
+        }
+    }
+
+    fn add_part(&mut self, part_number: u32, enc_content: Vec<u8>) {
+        if self.last_part == Some(part_number) {
+            self.issues.push(format!("Duplicate part, part number=[{}]", part_number));
+            return;
+        }
+        self.last_part = Some(part_number);
+
+        if part_number > self.next_part {
+            self.push_missing_parts(part_number.min(self.total_part));
+        }
+        self.next_part = part_number + 1;
+
+        if part_number >= self.total_part {
+            self.issues.push(format!("Unexpected part, part number=[{}]", part_number));
+        }
+
+        self.encrypted_size += enc_content.len() as u64;
+        match DkEncrypt::new(CC20).decrypt_vec(&enc_content, self.customer_key) {
+            Ok(clear_content) => {
+                self.clear_size += clear_content.len() as u64;
+                self.hasher.update(&clear_content);
+            }
+            Err(_) => self.issues.push(format!("Cannot decrypt the part, part number=[{}]", part_number)),
+        }
+    }
+
+    /// Missing parts between the next expected part and the [end_part] excluded
+    fn push_missing_parts(&mut self, end_part: u32) {
+        match end_part.saturating_sub(self.next_part) {
+            0 => {}
+            1 => self.issues.push(format!("Missing part, part number=[{}]", self.next_part)),
+            _ => self.issues.push(format!("Missing parts, part numbers=[{}..{}]", self.next_part, end_part - 1)),
+        }
+    }
+
+    /// All the issues found, the checksum and the sizes are checked only when they are recorded
+    fn finish(
+        mut self,
+        checksum: Option<&str>,
+        original_file_size: Option<i64>,
+        encrypted_file_size: Option<i64>,
+    ) -> Vec<String> {
+        self.push_missing_parts(self.total_part);
+
+        let computed_checksum = format!("{:x}", self.hasher.finalize_reset());
+        if let Some(checksum) = checksum {
+            if checksum != computed_checksum {
+                self.issues.push(format!("Wrong checksum, expected=[{}], found=[{}]", checksum, computed_checksum));
+            }
+        }
+        if let Some(size) = original_file_size {
+            if size as u64 != self.clear_size {
+                self.issues.push(format!("Wrong file size, expected=[{}], found=[{}]", size, self.clear_size));
+            }
+        }
+        if let Some(size) = encrypted_file_size {
+            if size as u64 != self.encrypted_size {
+                self.issues
+                    .push(format!("Wrong encrypted file size, expected=[{}], found=[{}]", size, self.encrypted_size));
+            }
+        }
+        self.issues
+    }
+}
+
+#[cfg(test)]
+mod integrity_tests {
+    use dkcrypto::dk_crypto::CypherMode::CC20;
+    use dkcrypto::dk_crypto::DkEncrypt;
+    use sha2::{Digest, Sha256};
+
+    use crate::integrity_delegate::PartsCheck;
+
+    const KEY: &str = "fqYVyce-Nh0HwpPQ7ZGZLog5s7PBLnwFMAW2OMnNPUs";
+
+    fn encrypted_parts(clear_parts: &[&[u8]]) -> Vec<Vec<u8>> {
+        clear_parts.iter().map(|part| DkEncrypt::new(CC20).encrypt_vec(&part.to_vec(), KEY).unwrap()).collect()
+    }
+
+    #[test]
+    fn parts_check_test() {
+        let clear_parts: [&[u8]; 3] = [b"first part, ", b"second part, ", b"last part"];
+        let checksum = format!("{:x}", Sha256::digest(clear_parts.concat()));
+        let parts = encrypted_parts(&clear_parts);
+        let clear_size = clear_parts.concat().len() as i64;
+        let encrypted_size = parts.iter().map(|part| part.len() as i64).sum::<i64>();
+
+        // All the parts are valid
+        let mut parts_check = PartsCheck::new(3, KEY);
+        for (part_number, part) in parts.iter().enumerate() {
+            parts_check.add_part(part_number as u32, part.clone());
+        }
+        assert!(parts_check.finish(Some(&checksum), Some(clear_size), Some(encrypted_size)).is_empty());
+
+        // Missing, duplicate and corrupted parts
+        let mut parts_check = PartsCheck::new(3, KEY);
+        parts_check.add_part(1, parts[1].clone());
+        parts_check.add_part(1, parts[1].clone());
+        parts_check.add_part(3, b"corrupted".to_vec());
+        let issues = parts_check.finish(Some(&checksum), Some(clear_size), None);
+        assert_eq!(
+            vec![
+                "Missing part, part number=[0]",
+                "Duplicate part, part number=[1]",
+                "Missing part, part number=[2]",
+                "Unexpected part, part number=[3]",
+                "Cannot decrypt the part, part number=[3]",
+            ],
+            issues[..5]
+        );
+        assert!(issues[5].starts_with("Wrong checksum"));
+        assert!(issues[6].starts_with("Wrong file size"));
+        assert_eq!(7, issues.len());
+
+        // No part at all
+        let issues = PartsCheck::new(12, KEY).finish(None, None, None);
+        assert_eq!(vec!["Missing parts, part numbers=[0..11]"], issues);
+    }
+}
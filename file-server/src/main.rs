@@ -1,29 +1,43 @@
 use std::net::SocketAddr;
 use std::process::exit;
 
-use axum::extract::{DefaultBodyLimit, Multipart, Path};
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Multipart, Path, Query};
 use axum::http::Method;
-use axum::routing::{get, post};
-use axum::Router;
+use axum::routing::{delete, get, post, put};
+use axum::{Json, Router};
 use log::*;
+use serde_derive::{Deserialize, Serialize};
 use tower_http::cors::{Any, CorsLayer};
 
 use commons_error::*;
 use commons_pg::sql_transaction_async::init_db_pool_async;
 use commons_services::read_cek_and_store;
-use commons_services::token_lib::SessionToken;
+use commons_services::token_lib::{SecurityToken, SessionToken};
 use commons_services::x_request_id::XRequestID;
 use common_config::conf_reader::{read_config, read_env};
 use common_config::properties::{get_prop_pg_connect_string, get_prop_value, set_prop_values};
 use common_config::property_name::{LOG_CONFIG_FILE_PROPERTY, SERVER_PORT_PROPERTY};
 use dkdto::web_types::{
-    DownloadReply, GetFileInfoReply, GetFileInfoShortReply, ListOfFileInfoReply, ListOfUploadInfoReply, UploadReply,
+    CleanupReply, CreateUploadSessionRequest, DownloadReply, FileIntegrityReply, FileListQuery, FileMetadataReply,
+    FileStatusReply, GetFileInfoReply, GetFileInfoShortReply, ItemVersionReply, ListOfFileInfoReply,
+    ListOfUploadInfoReply, ReindexReply, ScrubReply, SimpleMessage, UploadBlockReply, UploadReply, UploadSessionReply,
     WebType,
 };
 
+use crate::block_store::init_block_store;
+use crate::cleanup_delegate::{start_cleanup_scheduler, CleanupDelegate};
 use crate::file_delegate::FileDelegate;
+use crate::integrity_delegate::IntegrityDelegate;
+use crate::preview::init_preview_renderers;
 
+mod block_store;
+mod cleanup_delegate;
 mod file_delegate;
+mod integrity_delegate;
+mod metadata;
+mod preview;
+mod quota;
 
 ///
 /// 🌟  Upload the binary content of a file v2
@@ -46,6 +60,55 @@ pub async fn upload(
     delegate.upload2(&item_info, &content_length, &mut file_data).await
 }
 
+///
+/// 🌟 Create a session to upload a file block by block
+///
+// #[post("/upload_session")]
+pub async fn create_upload_session(
+    session_token: SessionToken,
+    Json(request): Json<CreateUploadSessionRequest>,
+) -> WebType<UploadSessionReply> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.create_upload_session(&request).await
+}
+
+///
+/// 🌟 Get the blocks already received for the upload session [file_ref]
+///
+// #[get("/upload_session/<file_ref>")]
+pub async fn upload_session_status(
+    session_token: SessionToken,
+    Path(file_ref): Path<String>,
+) -> WebType<UploadSessionReply> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.upload_session_status(&file_ref).await
+}
+
+///
+/// 🌟 Send the block number [block_num] of the upload session [file_ref]
+///
+// #[put("/upload_session/<file_ref>/block/<block_num>", data = "<block>")]
+pub async fn upload_block(
+    session_token: SessionToken,
+    Path((file_ref, block_num)): Path<(String, u32)>,
+    block: Bytes,
+) -> WebType<UploadBlockReply> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.upload_block(&file_ref, block_num, block).await
+}
+
+///
+/// 🌟 Close the upload session [file_ref] and process the file
+///
+// #[post("/upload_session/<file_ref>/finalize")]
+pub async fn finalize_upload_session(
+    session_token: SessionToken,
+    Path(file_ref): Path<String>,
+) -> WebType<UploadReply> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.finalize_upload_session(&file_ref).await
+}
+
 ///
 /// 🌟 Get the information about the files being loaded
 ///
@@ -70,21 +133,150 @@ pub async fn file_stats(session_token: SessionToken, Path(file_ref): Path<String
     delegate.file_stats(&file_ref).await
 }
 
+///
+/// 🌟 Get the processing status of a file [file_ref], with the reason of its failure
+///
+// #[get("/status/<file_ref>")]
+pub async fn file_status(session_token: SessionToken, Path(file_ref): Path<String>) -> WebType<FileStatusReply> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.file_status(&file_ref).await
+}
+
+///
+/// 🌟 Get the metadata of a file [file_ref], the ones found by Tika and the normalized ones
+///
+// #[get("/metadata/<file_ref>")]
+pub async fn file_metadata(session_token: SessionToken, Path(file_ref): Path<String>) -> WebType<FileMetadataReply> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.file_metadata(&file_ref).await
+}
+
+///
+/// 🌟 Delete a file [file_ref], with its parts, its metadata and its previews
+///
+// #[delete("/file/<file_ref>")]
+pub async fn delete_file(session_token: SessionToken, Path(file_ref): Path<String>) -> WebType<SimpleMessage> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.delete_file(&file_ref).await
+}
+
+/// 🌟 Get the file versions of an item, with the size and the checksum of their files
+// #[get("/versions/<item_id>")]
+pub async fn item_versions(session_token: SessionToken, Path(item_id): Path<i64>) -> WebType<ItemVersionReply> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.item_versions(item_id).await
+}
+
+/// 🌟 Download the file of a version of an item
+// #[get("/versions/<item_id>/<version_number>/download")]
+pub async fn download_version(
+    headers: axum::http::HeaderMap,
+    session_token: SessionToken,
+    Path((item_id, version_number)): Path<(i64, u32)>,
+) -> DownloadReply {
+    let range = headers.get(axum::http::header::RANGE).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let if_range = headers.get(axum::http::header::IF_RANGE).and_then(|value| value.to_str().ok()).map(str::to_string);
+
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.download_version(item_id, version_number, &range, &if_range).await
+}
+
 /// 🌟 Get the information about the composition of files [pattern of file_ref]
-// #[get("/list/<pattern>")]
-pub async fn file_list(session_token: SessionToken, Path(pattern): Path<String>) -> WebType<ListOfFileInfoReply> {
+/// The files can be filtered, sorted and paged
+// #[get("/list/<pattern>?<start_page>&<page_size>&<sort_by>&<sort_order>&<media_type>&<is_encrypted>&<is_fulltext_parsed>")]
+pub async fn file_list(
+    session_token: SessionToken,
+    Path(pattern): Path<String>,
+    Query(list_query): Query<FileListQuery>,
+) -> WebType<ListOfFileInfoReply> {
     let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
-    delegate.file_list(&pattern).await
+    delegate.file_list(&pattern, &list_query).await
 }
 
 ///
 /// 🌟  Download the binary content of a file
+///     The Range and If-Range headers allow to download only a part of the file (206 Partial Content)
 ///
 // #[get("/download/<file_ref>")]
-pub async fn download(session_token: SessionToken, Path(file_ref): Path<String>) -> DownloadReply {
+pub async fn download(
+    headers: axum::http::HeaderMap,
+    session_token: SessionToken,
+    Path(file_ref): Path<String>,
+) -> DownloadReply {
     // let session_token = SessionToken { 0: "9ARks93f49KdpZ3sPnPYpSRZUOk9shmbQVZKn9If6RQmwi25yGtCN3vCis4JnYxGO46Hf07hDEZc9LFPRW5ncPFCeO-14VyW-Hdq-Q".to_string() };
+    let range = headers.get(axum::http::header::RANGE).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let if_range = headers.get(axum::http::header::IF_RANGE).and_then(|value| value.to_str().ok()).map(str::to_string);
+
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.download(&file_ref, &range, &if_range).await
+}
+
+///
+/// 🌟  Download the preview of a file (jpeg thumbnail)
+///
+// #[get("/preview/<file_ref>")]
+pub async fn preview(session_token: SessionToken, Path(file_ref): Path<String>) -> DownloadReply {
     let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
-    delegate.download(&file_ref).await
+    delegate.preview(&file_ref).await
+}
+
+///
+/// 🌟 Parse and index again a stored file [file_ref]
+///
+// #[post("/reindex/<file_ref>")]
+pub async fn reindex_file(session_token: SessionToken, Path(file_ref): Path<String>) -> WebType<ReindexReply> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.reindex_file(&file_ref).await
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReindexQuery {
+    pub pattern: String,
+}
+
+///
+/// 🌟 Parse and index again the stored files matching the pattern, whose parsing failed
+///
+// #[post("/reindex?<pattern>")]
+pub async fn reindex_files(Query(reindex): Query<ReindexQuery>, session_token: SessionToken) -> WebType<ReindexReply> {
+    let mut delegate = FileDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.reindex_files(&reindex.pattern).await
+}
+
+///
+/// 🌟 Remove the stale uploads and the failed files of all the customers, with a security token
+///
+// #[post("/cleanup")]
+pub async fn cleanup(security_token: SecurityToken) -> WebType<CleanupReply> {
+    let mut delegate = CleanupDelegate::new(security_token, XRequestID::from_value(None));
+    delegate.cleanup().await
+}
+
+///
+/// 🌟 Check the stored parts of the file [file_ref] against its checksum and sizes
+///
+// #[get("/verify/<file_ref>")]
+pub async fn verify(session_token: SessionToken, Path(file_ref): Path<String>) -> WebType<FileIntegrityReply> {
+    let mut delegate = IntegrityDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.verify(&file_ref).await
+}
+
+///
+/// 🌟 Start the integrity check of all the stored files, in the background
+///
+// #[post("/scrub")]
+pub async fn start_scrub(session_token: SessionToken) -> WebType<ScrubReply> {
+    let mut delegate = IntegrityDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.start_scrub().await
+}
+
+///
+/// 🌟 Report of the last integrity check of the files
+///
+// #[get("/scrub")]
+pub async fn scrub_report(session_token: SessionToken) -> WebType<ScrubReply> {
+    let mut delegate = IntegrityDelegate::new(session_token, XRequestID::from_value(None));
+    delegate.scrub_report().await
 }
 
 #[derive(Debug)]
@@ -143,6 +335,21 @@ async fn main() {
 
     let _ = init_db_pool_async(&connect_string, db_pool_size).await;
 
+    // Init the storage of the file parts
+    if let Err(e) = init_block_store().map_err(err_fwd!("Cannot init the block store")) {
+        log_error!("{:?}", e);
+        exit(-65);
+    }
+
+    // Init the preview renderers
+    if let Err(e) = init_preview_renderers().map_err(err_fwd!("Cannot init the preview renderers")) {
+        log_error!("{:?}", e);
+        exit(-66);
+    }
+
+    // Maintenance of the uploads
+    start_cleanup_scheduler();
+
     log_info!("🚀 Start {} on port {}", PROGRAM_NAME, port);
 
     let cors = CorsLayer::new()
@@ -154,12 +361,27 @@ async fn main() {
     let base_url = format!("/{}", PROJECT_CODE);
     let key_routes = Router::new()
         .route("/upload2/:item_info", post(upload))
+        .route("/upload_session", post(create_upload_session))
+        .route("/upload_session/:file_ref", get(upload_session_status))
+        .route("/upload_session/:file_ref/block/:block_num", put(upload_block))
+        .route("/upload_session/:file_ref/finalize", post(finalize_upload_session))
         .route("/loading", get(file_loading))
         .route("/info/:file_ref", get(file_info))
         .route("/stats/:file_ref", get(file_stats))
         .route("/list/:pattern", get(file_list))
         // .route("/raw_download/:file_ref", get(raw_download))
         .route("/download/:file_ref", get(download))
+        .route("/versions/:item_id", get(item_versions))
+        .route("/versions/:item_id/:version_number/download", get(download_version))
+        .route("/status/:file_ref", get(file_status))
+        .route("/metadata/:file_ref", get(file_metadata))
+        .route("/file/:file_ref", delete(delete_file))
+        .route("/preview/:file_ref", get(preview))
+        .route("/reindex", post(reindex_files))
+        .route("/reindex/:file_ref", post(reindex_file))
+        .route("/cleanup", post(cleanup))
+        .route("/verify/:file_ref", get(verify))
+        .route("/scrub", post(start_scrub).get(scrub_report))
         .layer(cors)
         .layer(DefaultBodyLimit::max(usize::MAX));
 
@@ -0,0 +1,218 @@
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value};
+
+use dkdto::web_types::{AddTagValue, EnumTagValue};
+
+/// Tika key of the text content, it is indexed by the document server, not stored as a metadata
+pub(crate) const TIKA_CONTENT_META: &str = "X-TIKA:content";
+
+/// Prefix of the keys of the normalized metadata
+const NORMALIZED_PREFIX: &str = "doka:";
+/// Max length of a text tag value on the document server
+const TAG_TEXT_MAX_LENGTH: usize = 2000;
+
+///
+/// Well-known metadata, normalized from the Tika keys which depend on the format of the file.
+/// The first key found in the Tika metadata gives the value
+///
+const NORMALIZED_KEYS: [(&str, MetadataType, &[&str]); 6] = [
+    ("author", MetadataType::Text, &["dc:creator", "meta:author", "Author", "creator", "pdf:docinfo:creator"]),
+    ("title", MetadataType::Text, &["dc:title", "title", "pdf:docinfo:title"]),
+    ("page_count", MetadataType::Integer, &["xmpTPg:NPages", "meta:page-count", "Page-Count"]),
+    (
+        "creation_date",
+        MetadataType::DateTime,
+        &["dcterms:created", "meta:creation-date", "Creation-Date", "pdf:docinfo:created", "exif:DateTimeOriginal"],
+    ),
+    ("width", MetadataType::Integer, &["tiff:ImageWidth", "Image Width", "width"]),
+    ("height", MetadataType::Integer, &["tiff:ImageLength", "Image Height", "height"]),
+];
+
+#[derive(Clone, Copy)]
+enum MetadataType {
+    Text,
+    Integer,
+    DateTime,
+}
+
+/// Metadata of a file, as stored in the file_metadata table
+#[derive(Debug, Clone)]
+pub(crate) struct FileMetadata {
+    pub key: String,
+    pub value: EnumTagValue,
+}
+
+///
+/// All the metadata found by Tika, as text, followed by the normalized ones, typed.
+/// Multiple values are joined with a comma
+///
+pub(crate) fn extract_metadata(tika_metadata: &Map<String, Value>) -> Vec<FileMetadata> {
+    let mut list_of_metadata: Vec<FileMetadata> = tika_metadata
+        .iter()
+        .filter(|(key, _)| key.as_str() != TIKA_CONTENT_META)
+        .map(|(key, value)| FileMetadata { key: key.clone(), value: EnumTagValue::Text(Some(raw_text(value))) })
+        .collect();
+
+    for (name, metadata_type, tika_keys) in NORMALIZED_KEYS.iter() {
+        let normalized_value = tika_keys
+            .iter()
+            .filter_map(|tika_key| tika_metadata.get(*tika_key).map(first_text))
+            .find_map(|text| normalize(&text, *metadata_type));
+        if let Some(value) = normalized_value {
+            list_of_metadata.push(FileMetadata { key: format!("{}{}", NORMALIZED_PREFIX, name), value });
+        }
+    }
+
+    list_of_metadata
+}
+
+///
+/// Read the projection of the metadata onto the item tags,
+/// ex : "doka:author=author, doka:page_count=pages" gives [("doka:author", "author"), ("doka:page_count", "pages")]
+///
+pub(crate) fn parse_tag_projection(projection: &str) -> Vec<(String, String)> {
+    projection
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, tag_name)| (key.trim().to_string(), tag_name.trim().to_string()))
+        .filter(|(key, tag_name)| !key.is_empty() && !tag_name.is_empty())
+        .collect()
+}
+
+/// Tag values for the metadata of the [projection] found in the [list_of_metadata]
+pub(crate) fn project_on_tags(list_of_metadata: &[FileMetadata], projection: &[(String, String)]) -> Vec<AddTagValue> {
+    projection
+        .iter()
+        .filter_map(|(key, tag_name)| {
+            let metadata = list_of_metadata.iter().find(|metadata| &metadata.key == key)?;
+            let value = match &metadata.value {
+                EnumTagValue::Text(Some(text)) => EnumTagValue::Text(Some(truncate(text, TAG_TEXT_MAX_LENGTH))),
+                value => value.clone(),
+            };
+            Some(AddTagValue { tag_id: None, tag_name: Some(tag_name.clone()), value })
+        })
+        .collect()
+}
+
+/// Text of a Tika value, without the quotes of the json strings
+fn raw_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(values) => values.iter().map(raw_text).collect::<Vec<_>>().join(", "),
+        value => value.to_string(),
+    }
+}
+
+/// Text of the first value of a Tika value
+fn first_text(value: &Value) -> String {
+    match value {
+        Value::Array(values) => values.first().map(raw_text).unwrap_or_default(),
+        value => raw_text(value),
+    }
+}
+
+fn normalize(text: &str, metadata_type: MetadataType) -> Option<EnumTagValue> {
+    let text = text.trim();
+    match metadata_type {
+        MetadataType::Text => (!text.is_empty()).then(|| EnumTagValue::Text(Some(text.to_string()))),
+        MetadataType::Integer => parse_integer(text).map(|i| EnumTagValue::Integer(Some(i))),
+        MetadataType::DateTime => parse_date_time(text)
+            .map(|date_time| EnumTagValue::DateTime(Some(date_time.to_rfc3339_opts(SecondsFormat::Secs, true)))),
+    }
+}
+
+/// "1024 pixels" -> 1024
+fn parse_integer(text: &str) -> Option<i64> {
+    let digits: String = text.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Rfc3339 date time, or the local date time of the Exif data, taken as UTC
+fn parse_date_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|date_time| date_time.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S").map(|date_time| date_time.and_utc()))
+        .ok()
+}
+
+fn truncate(text: &str, max_length: usize) -> String {
+    match text.char_indices().nth(max_length) {
+        Some((index, _)) => text[..index].to_string(),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod metadata_tests {
+    use dkdto::web_types::EnumTagValue;
+    use serde_json::json;
+
+    use crate::metadata::{extract_metadata, parse_tag_projection, project_on_tags, FileMetadata};
+
+    fn find<'a>(list_of_metadata: &'a [FileMetadata], key: &str) -> Option<&'a EnumTagValue> {
+        list_of_metadata.iter().find(|metadata| metadata.key == key).map(|metadata| &metadata.value)
+    }
+
+    #[test]
+    fn extract_metadata_test() {
+        let long_title = "t".repeat(300);
+        let tika_json = json!({
+            "X-TIKA:content": "The text content",
+            "Content-Type": "application/pdf",
+            "dc:creator": ["Denis", "Doka"],
+            "pdf:docinfo:title": long_title,
+            "xmpTPg:NPages": "12",
+            "dcterms:created": "2021-03-23T10:04:10+02:00",
+            "tiff:ImageWidth": "1024 pixels",
+        });
+        let list_of_metadata = extract_metadata(tika_json.as_object().unwrap());
+
+        assert!(find(&list_of_metadata, "X-TIKA:content").is_none());
+        assert!(
+            matches!(find(&list_of_metadata, "Content-Type"), Some(EnumTagValue::Text(Some(t))) if t == "application/pdf")
+        );
+        assert!(
+            matches!(find(&list_of_metadata, "dc:creator"), Some(EnumTagValue::Text(Some(t))) if t == "Denis, Doka")
+        );
+
+        assert!(matches!(find(&list_of_metadata, "doka:author"), Some(EnumTagValue::Text(Some(t))) if t == "Denis"));
+        assert!(matches!(find(&list_of_metadata, "doka:title"), Some(EnumTagValue::Text(Some(t))) if t.len() == 300));
+        assert!(matches!(find(&list_of_metadata, "doka:page_count"), Some(EnumTagValue::Integer(Some(12)))));
+        assert!(matches!(find(&list_of_metadata, "doka:width"), Some(EnumTagValue::Integer(Some(1024)))));
+        assert!(find(&list_of_metadata, "doka:height").is_none());
+        assert!(
+            matches!(find(&list_of_metadata, "doka:creation_date"), Some(EnumTagValue::DateTime(Some(t))) if t == "2021-03-23T08:04:10Z")
+        );
+
+        let exif_json = json!({ "exif:DateTimeOriginal": "2019-07-14T15:20:00" });
+        let list_of_metadata = extract_metadata(exif_json.as_object().unwrap());
+        assert!(
+            matches!(find(&list_of_metadata, "doka:creation_date"), Some(EnumTagValue::DateTime(Some(t))) if t == "2019-07-14T15:20:00Z")
+        );
+    }
+
+    #[test]
+    fn project_on_tags_test() {
+        let projection = parse_tag_projection("doka:author=author, doka:page_count = pages,bad_pair,=empty");
+        assert_eq!(
+            vec![
+                ("doka:author".to_string(), "author".to_string()),
+                ("doka:page_count".to_string(), "pages".to_string())
+            ],
+            projection
+        );
+
+        let tika_json = json!({ "meta:author": "Denis", "Page-Count": "3", "dc:title": "é".repeat(2500) });
+        let list_of_metadata = extract_metadata(tika_json.as_object().unwrap());
+        let mut projection = projection;
+        projection.push(("doka:title".to_string(), "title".to_string()));
+        projection.push(("doka:width".to_string(), "width".to_string()));
+
+        let tags = project_on_tags(&list_of_metadata, &projection);
+        assert_eq!(3, tags.len());
+        assert_eq!(Some("author".to_string()), tags[0].tag_name);
+        assert!(matches!(&tags[0].value, EnumTagValue::Text(Some(t)) if t == "Denis"));
+        assert!(matches!(tags[1].value, EnumTagValue::Integer(Some(3))));
+        assert!(matches!(&tags[2].value, EnumTagValue::Text(Some(t)) if t.chars().count() == 2000));
+    }
+}
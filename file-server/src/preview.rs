@@ -0,0 +1,139 @@
+use std::io::Cursor;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use image::imageops::FilterType;
+use image::ImageFormat;
+use log::*;
+
+use common_config::properties::get_prop_value;
+use common_config::property_name::PDF_RENDER_COMMAND_PROPERTY;
+use commons_error::*;
+
+/// Media type of all the previews
+pub(crate) const PREVIEW_MEDIA_TYPE: &str = "image/jpeg";
+/// Size of the box the thumbnail must fit in
+const THUMBNAIL_SIZE: u32 = 256;
+
+static PREVIEW_RENDERERS: OnceLock<Vec<Box<dyn PreviewRenderer>>> = OnceLock::new();
+
+///
+/// Build the image of the preview of a file, from its clear content
+///
+pub(crate) trait PreviewRenderer: Send + Sync {
+    /// True if the renderer can build a preview for the [media_type]
+    fn accept(&self, media_type: &str) -> bool;
+
+    /// Jpeg image of the preview
+    fn render(&self, content: &[u8]) -> anyhow::Result<Vec<u8>>;
+}
+
+///
+/// Register the renderers : the image thumbnails are always generated,
+/// the PDF renders only when the "fs.preview.pdf_command" property is defined
+///
+pub(crate) fn init_preview_renderers() -> anyhow::Result<()> {
+    let mut renderers: Vec<Box<dyn PreviewRenderer>> = vec![Box::new(ImageThumbnailRenderer)];
+
+    if let Ok(command_line) = get_prop_value(PDF_RENDER_COMMAND_PROPERTY) {
+        log_info!("PDF preview renderer in use, command=[{}]", &command_line);
+        renderers.push(Box::new(PdfCommandRenderer { command_line }));
+    }
+
+    PREVIEW_RENDERERS.set(renderers).map_err(|_| anyhow!("The preview renderers are already initialized"))
+}
+
+/// The first renderer able to build a preview for the [media_type]
+pub(crate) fn find_preview_renderer(media_type: &str) -> Option<&'static dyn PreviewRenderer> {
+    PREVIEW_RENDERERS.get()?.iter().find(|renderer| renderer.accept(media_type)).map(|renderer| renderer.as_ref())
+}
+
+/// Reduce the image to fit in the thumbnail box and encode it in jpeg
+fn to_thumbnail(image_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let image = image::load_from_memory(image_data).map_err(tr_fwd!())?;
+    let thumbnail = if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+        image.resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Lanczos3)
+    } else {
+        image
+    };
+    let mut thumbnail_data = Vec::new();
+    // Jpeg has no alpha channel
+    thumbnail.to_rgb8().write_to(&mut Cursor::new(&mut thumbnail_data), ImageFormat::Jpeg).map_err(tr_fwd!())?;
+    Ok(thumbnail_data)
+}
+
+struct ImageThumbnailRenderer;
+
+impl PreviewRenderer for ImageThumbnailRenderer {
+    fn accept(&self, media_type: &str) -> bool {
+        matches!(media_type, "image/jpeg" | "image/png" | "image/gif" | "image/bmp" | "image/tiff" | "image/webp")
+    }
+
+    fn render(&self, content: &[u8]) -> anyhow::Result<Vec<u8>> {
+        to_thumbnail(content)
+    }
+}
+
+///
+/// Render the first page of a PDF with an external command,
+/// {input} is replaced by the path of the PDF and the image is read from the standard output,
+/// for instance : pdftoppm -f 1 -l 1 -singlefile -png -scale-to 512 {input}
+///
+struct PdfCommandRenderer {
+    command_line: String,
+}
+
+impl PreviewRenderer for PdfCommandRenderer {
+    fn accept(&self, media_type: &str) -> bool {
+        media_type == "application/pdf"
+    }
+
+    fn render(&self, content: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let input_path = std::env::temp_dir().join(format!("doka-preview-{}.pdf", rs_uuid::iso::uuid_v4()));
+        std::fs::write(&input_path, content).map_err(tr_fwd!())?;
+
+        let input = input_path.to_string_lossy();
+        let args: Vec<String> =
+            self.command_line.split_whitespace().map(|arg| arg.replace("{input}", &input)).collect();
+        let output = match args.split_first() {
+            Some((program, args)) => Command::new(program).args(args).output().map_err(|e| anyhow!(e)),
+            None => Err(anyhow!("Empty PDF render command")),
+        };
+        let _ = std::fs::remove_file(&input_path);
+
+        let output = output.map_err(tr_fwd!())?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "PDF render command failed, status=[{}], stderr=[{}]",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        to_thumbnail(&output.stdout)
+    }
+}
+
+#[cfg(test)]
+mod preview_tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, RgbaImage};
+
+    use crate::preview::{ImageThumbnailRenderer, PreviewRenderer, THUMBNAIL_SIZE};
+
+    #[test]
+    fn image_thumbnail_test() {
+        let renderer = ImageThumbnailRenderer;
+        assert!(renderer.accept("image/png"));
+        assert!(!renderer.accept("application/pdf"));
+
+        let mut png_data = Vec::new();
+        RgbaImage::new(1024, 512).write_to(&mut Cursor::new(&mut png_data), ImageFormat::Png).unwrap();
+
+        let thumbnail = image::load_from_memory(&renderer.render(&png_data).unwrap()).unwrap();
+        assert_eq!((THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2), (thumbnail.width(), thumbnail.height()));
+
+        assert!(renderer.render(b"not an image").is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use log::*;
+
+use common_config::properties::get_prop_value;
+use common_config::property_name::{ADMIN_SERVER_HOSTNAME_PROPERTY, ADMIN_SERVER_PORT_PROPERTY};
+use commons_error::*;
+use commons_pg::sql_transaction::CellValue;
+use commons_pg::sql_transaction_async::{SQLConnectionAsync, SQLQueryBlockAsync};
+use commons_services::x_request_id::Follower;
+use dkdto::web_types::CustomerQuotaReply;
+use doka_cli::async_request_client::AdminServerClientAsync;
+
+/// Storage used by a customer, the previews are not uploaded files so they are not counted
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct StorageUsage {
+    pub file_count: u64,
+    pub original_size: u64,
+}
+
+///
+/// Check the quotas of the customer before storing a new file of [incoming_size] bytes.
+/// The file reference of the new file [incoming_file_id], if already created, is not part of the usage.
+/// Return the reason of the overrun, if any
+///
+pub(crate) async fn check_quota(
+    customer_code: &str,
+    incoming_size: Option<u64>,
+    incoming_file_id: Option<i64>,
+    follower: &Follower,
+) -> anyhow::Result<Option<String>> {
+    let quota = fetch_customer_quota(customer_code, follower).await.map_err(tr_fwd!())?;
+    if quota.storage_quota.is_none() && quota.file_count_quota.is_none() {
+        return Ok(None);
+    }
+
+    let usage = search_storage_usage(customer_code, incoming_file_id, follower).await.map_err(tr_fwd!())?;
+    log_info!(
+        "Storage usage, customer_code=[{}], usage=[{:?}], quota=[{:?}], follower=[{}]",
+        customer_code,
+        &usage,
+        &quota,
+        follower
+    );
+
+    Ok(exceeded_quota(&quota, &usage, incoming_size))
+}
+
+///
+/// Reason why a new file of [incoming_size] bytes would exceed the quotas, if any.
+/// When the size is unknown, the file is accepted as long as some storage is left
+///
+pub(crate) fn exceeded_quota(
+    quota: &CustomerQuotaReply,
+    usage: &StorageUsage,
+    incoming_size: Option<u64>,
+) -> Option<String> {
+    if let Some(file_count_quota) = quota.file_count_quota {
+        if usage.file_count >= file_count_quota {
+            return Some(format!("file count=[{}], file count quota=[{}]", usage.file_count, file_count_quota));
+        }
+    }
+
+    if let Some(storage_quota) = quota.storage_quota {
+        let new_size = usage.original_size + incoming_size.unwrap_or(0);
+        let is_full = match incoming_size {
+            Some(_) => new_size > storage_quota,
+            None => new_size >= storage_quota,
+        };
+        if is_full {
+            return Some(format!(
+                "storage size=[{}], incoming size=[{:?}], storage quota=[{}]",
+                usage.original_size, incoming_size, storage_quota
+            ));
+        }
+    }
+
+    None
+}
+
+/// The quotas are defined on the customer, in the admin server
+async fn fetch_customer_quota(customer_code: &str, follower: &Follower) -> anyhow::Result<CustomerQuotaReply> {
+    let as_host = get_prop_value(ADMIN_SERVER_HOSTNAME_PROPERTY).map_err(tr_fwd!())?;
+    let as_port: u16 = get_prop_value(ADMIN_SERVER_PORT_PROPERTY)?.parse().map_err(tr_fwd!())?;
+    let asc = AdminServerClientAsync::new(&as_host, as_port);
+
+    // For now the token is the sid itself
+    asc.customer_quota(customer_code, &follower.token_type.value()).await.map_err(|e| {
+        log_error!("Admin Server failed with status [{}], follower=[{}]", e.message, follower);
+        anyhow!("{} - {}", e.http_error_code, e.message)
+    })
+}
+
+async fn search_storage_usage(
+    customer_code: &str,
+    excluded_file_id: Option<i64>,
+    follower: &Follower,
+) -> anyhow::Result<StorageUsage> {
+    let mut cnx = SQLConnectionAsync::from_pool().await.map_err(tr_fwd!())?;
+    let mut trans = cnx.begin().await.map_err(tr_fwd!())?;
+
+    let sql_query = format!(
+        r"SELECT COUNT(*) file_count, COALESCE(SUM(fr.original_file_size), 0)::int8 original_size
+            FROM fs_{0}.file_reference fr
+            WHERE NOT EXISTS (SELECT 1 FROM fs_{0}.preview p WHERE p.file_identifier = fr.file_ref)
+            AND fr.id <> COALESCE(CAST(:p_excluded_file_id AS int8), -1)",
+        customer_code
+    );
+
+    let mut params = HashMap::new();
+    params.insert("p_excluded_file_id".to_string(), CellValue::Int(excluded_file_id));
+
+    let query = SQLQueryBlockAsync { sql_query, start: 0, length: None, params };
+    let mut dataset = query.execute(&mut trans).await.map_err(err_fwd!("💣 Query failed, follower=[{}]", follower))?;
+    trans.commit().await.map_err(err_fwd!("💣 Commit failed, follower=[{}]", follower))?;
+
+    if !dataset.next() {
+        return Ok(StorageUsage::default());
+    }
+
+    Ok(StorageUsage {
+        file_count: dataset.get_int("file_count").ok_or(anyhow!("Wrong file_count col"))? as u64,
+        original_size: dataset.get_int("original_size").ok_or(anyhow!("Wrong original_size col"))? as u64,
+    })
+}
+
+#[cfg(test)]
+mod quota_tests {
+    use dkdto::web_types::CustomerQuotaReply;
+
+    use crate::quota::{exceeded_quota, StorageUsage};
+
+    fn quota(storage_quota: Option<u64>, file_count_quota: Option<u64>) -> CustomerQuotaReply {
+        CustomerQuotaReply { customer_code: "f1248fab".to_string(), storage_quota, file_count_quota }
+    }
+
+    #[test]
+    fn exceeded_quota_test() {
+        let usage = StorageUsage { file_count: 10, original_size: 1_000 };
+
+        assert!(exceeded_quota(&quota(None, None), &usage, Some(1_000_000)).is_none());
+
+        // Storage quota
+        assert!(exceeded_quota(&quota(Some(2_000), None), &usage, Some(1_000)).is_none());
+        assert!(exceeded_quota(&quota(Some(2_000), None), &usage, Some(1_001)).is_some());
+        assert!(exceeded_quota(&quota(Some(2_000), None), &usage, None).is_none());
+        assert!(exceeded_quota(&quota(Some(1_000), None), &usage, None).is_some());
+
+        // File count quota
+        assert!(exceeded_quota(&quota(None, Some(11)), &usage, Some(1)).is_none());
+        assert!(exceeded_quota(&quota(None, Some(10)), &usage, Some(1)).is_some());
+        assert!(exceeded_quota(&quota(Some(2_000), Some(10)), &usage, None).is_some());
+    }
+}